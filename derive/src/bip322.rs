@@ -0,0 +1,390 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generic message signing (BIP-322) for P2WPKH and P2TR addresses, and legacy
+//! `signmessage` signatures for P2PKH addresses.
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use bc::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use bc::secp256k1::{Message, SecretKey, SECP256K1};
+use bc::{
+    Bip340Sig, CompressedPk, ConsensusDecode, ConsensusEncode, LegacyPk, LegacySig, LockTime,
+    Outpoint, PubkeyHash, Sats, ScriptPubkey, SeqNo, SigScript, SighashType, TapNodeHash, Tx, TxIn,
+    TxOut, TxVer, Txid, VarInt, VarIntArray, Vout, WPubkeyHash, Witness, XOnlyPk,
+};
+use bitcoin_hashes::{sha256d, Hash};
+use commit_verify::{DigestExt, Sha256};
+
+use crate::{Address, AddressPayload, InternalPk, SighashCache};
+
+const TAG_MESSAGE: &[u8] = b"BIP0322-signed-message";
+const LEGACY_MESSAGE_PREFIX: &[u8] = b"\x18Bitcoin Signed Message:\n";
+
+/// Message signature formats.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+pub enum MessageSigFormat {
+    /// Legacy `signmessage` compact signature with public key recovery data,
+    /// supported only by P2PKH addresses.
+    #[display("legacy")]
+    Legacy,
+
+    /// BIP-322 simple format: the witness stack of the signing transaction.
+    #[display("simple")]
+    Simple,
+
+    /// BIP-322 full format: the whole signing transaction.
+    #[display("full")]
+    Full,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MessageSignError {
+    /// {0} addresses are not supported by {1} message signature format.
+    UnsupportedAddress(crate::AddressType, MessageSigFormat),
+
+    /// the private key doesn't match the address.
+    KeyMismatch,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MessageVerifyError {
+    /// message signature is not a valid Base64 string.
+    Base64,
+
+    /// message signature can't be decoded in any of the known formats.
+    InvalidEncoding,
+
+    /// signing transaction doesn't match BIP-322 requirements.
+    InvalidTransaction,
+
+    /// message signatures for {0} addresses are not supported.
+    UnsupportedAddress(crate::AddressType),
+
+    /// message signature doesn't match the address.
+    InvalidSignature,
+}
+
+/// Computes BIP-322 tagged hash of a message.
+pub fn message_hash(msg: &[u8]) -> [u8; 32] {
+    let mut engine = Sha256::from_tag(TAG_MESSAGE);
+    engine.input_raw(msg);
+    engine.finish()
+}
+
+/// Computes message digest signed by legacy `signmessage` signatures.
+pub fn legacy_message_hash(msg: &[u8]) -> [u8; 32] {
+    let mut data = LEGACY_MESSAGE_PREFIX.to_vec();
+    VarInt::new(msg.len() as u64).consensus_encode(&mut data).ok();
+    data.extend(msg);
+    sha256d::Hash::hash(&data).to_byte_array()
+}
+
+/// Constructs BIP-322 virtual `to_spend` transaction for a message and an
+/// address script.
+pub fn to_spend(script_pubkey: &ScriptPubkey, msg: &[u8]) -> Tx {
+    let mut sig_script = vec![0x00, 0x20];
+    sig_script.extend(message_hash(msg));
+    Tx {
+        version: TxVer::from_consensus_i32(0),
+        inputs: VarIntArray::from_iter_unsafe([TxIn {
+            prev_output: Outpoint::new(Txid::from([0u8; 32]), Vout::from_u32(0xFFFF_FFFF)),
+            sig_script: SigScript::from_unsafe(sig_script),
+            sequence: SeqNo::from_consensus_u32(0),
+            witness: Witness::new(),
+        }]),
+        outputs: VarIntArray::from_iter_unsafe([TxOut::new(script_pubkey.clone(), Sats::ZERO)]),
+        lock_time: LockTime::ZERO,
+    }
+}
+
+/// Constructs BIP-322 virtual `to_sign` transaction spending `to_spend`
+/// transaction with a given witness.
+pub fn to_sign(to_spend: &Tx, witness: Witness) -> Tx {
+    Tx {
+        version: TxVer::from_consensus_i32(0),
+        inputs: VarIntArray::from_iter_unsafe([TxIn {
+            prev_output: Outpoint::new(to_spend.txid(), Vout::from_u32(0)),
+            sig_script: SigScript::new(),
+            sequence: SeqNo::from_consensus_u32(0),
+            witness,
+        }]),
+        outputs: VarIntArray::from_iter_unsafe([TxOut::new(
+            ScriptPubkey::from_unsafe(vec![0x6a]),
+            Sats::ZERO,
+        )]),
+        lock_time: LockTime::ZERO,
+    }
+}
+
+fn sighash_cache(to_spend: &Tx, to_sign: Tx) -> SighashCache {
+    SighashCache::new(to_sign, vec![to_spend.outputs[0].clone()])
+        .expect("single input with a single prevout")
+}
+
+/// Signs message with a private key controlling the address, returning Base64
+/// encoded signature.
+pub fn sign_message(
+    address: &Address,
+    msg: &[u8],
+    key: &SecretKey,
+    format: MessageSigFormat,
+) -> Result<String, MessageSignError> {
+    let unsupported = || MessageSignError::UnsupportedAddress(address.address_type(), format);
+    let pk = key.public_key(SECP256K1);
+
+    if format == MessageSigFormat::Legacy {
+        let AddressPayload::Pkh(hash) = address.payload else {
+            return Err(unsupported());
+        };
+        let compressed = match hash {
+            hash if hash == PubkeyHash::from(CompressedPk::from(pk)) => true,
+            hash if hash == PubkeyHash::from(LegacyPk::uncompressed(pk)) => false,
+            _ => return Err(MessageSignError::KeyMismatch),
+        };
+        let msg = Message::from_digest(legacy_message_hash(msg));
+        let (recid, sig) = SECP256K1.sign_ecdsa_recoverable(&msg, key).serialize_compact();
+        let mut data = Vec::with_capacity(65);
+        data.push(27 + recid.to_i32() as u8 + if compressed { 4 } else { 0 });
+        data.extend(sig);
+        return Ok(BASE64_STANDARD.encode(data));
+    }
+
+    let to_spend = to_spend(&address.script_pubkey(), msg);
+    let witness = match address.payload {
+        AddressPayload::Wpkh(hash) => {
+            let pk = CompressedPk::from(pk);
+            if hash != WPubkeyHash::from(pk) {
+                return Err(MessageSignError::KeyMismatch);
+            }
+            let sig = sighash_cache(&to_spend, to_sign(&to_spend, Witness::new()))
+                .sign_wpkh(0, SighashType::all(), key)
+                .expect("single input");
+            Witness::from_consensus_stack([sig.to_vec(), pk.to_byte_array().to_vec()])
+        }
+        AddressPayload::Tr(output_pk) => {
+            let internal_pk = InternalPk::from_unchecked(XOnlyPk::from(pk));
+            if internal_pk.to_output_pk(None::<TapNodeHash>).0 != output_pk {
+                return Err(MessageSignError::KeyMismatch);
+            }
+            let sig = sighash_cache(&to_spend, to_sign(&to_spend, Witness::new()))
+                .sign_tap_key(0, None, key, None)
+                .expect("single input");
+            Witness::from_consensus_stack([sig.to_vec()])
+        }
+        _ => return Err(unsupported()),
+    };
+
+    let data = match format {
+        MessageSigFormat::Legacy => unreachable!(),
+        MessageSigFormat::Simple => witness.consensus_serialize(),
+        MessageSigFormat::Full => to_sign(&to_spend, witness).consensus_serialize(),
+    };
+    Ok(BASE64_STANDARD.encode(data))
+}
+
+/// Verifies Base64-encoded message signature in any of the supported formats,
+/// returning the detected format.
+pub fn verify_message(
+    address: &Address,
+    msg: &[u8],
+    signature: &str,
+) -> Result<MessageSigFormat, MessageVerifyError> {
+    let data = BASE64_STANDARD.decode(signature).map_err(|_| MessageVerifyError::Base64)?;
+
+    if let AddressPayload::Pkh(hash) = address.payload {
+        verify_legacy(hash, msg, &data)?;
+        return Ok(MessageSigFormat::Legacy);
+    }
+
+    let to_spend = to_spend(&address.script_pubkey(), msg);
+    let (format, to_sign) = if let Ok(witness) = Witness::consensus_deserialize(&data) {
+        (MessageSigFormat::Simple, to_sign(&to_spend, witness))
+    } else if let Ok(tx) = Tx::consensus_deserialize(&data) {
+        let expected = to_sign(&to_spend, Witness::new());
+        if tx.inputs.len() != 1 || tx.outputs != expected.outputs {
+            return Err(MessageVerifyError::InvalidTransaction);
+        }
+        if tx.inputs[0].prev_output != expected.inputs[0].prev_output {
+            return Err(MessageVerifyError::InvalidSignature);
+        }
+        (MessageSigFormat::Full, tx)
+    } else {
+        return Err(MessageVerifyError::InvalidEncoding);
+    };
+
+    let witness = to_sign.inputs[0].witness.elements().collect::<Vec<_>>();
+    let cache = sighash_cache(&to_spend, to_sign.clone());
+    match (address.payload, witness.as_slice()) {
+        (AddressPayload::Wpkh(hash), [sig, pk]) => {
+            let sig =
+                LegacySig::from_bytes(sig).map_err(|_| MessageVerifyError::InvalidSignature)?;
+            let pk =
+                CompressedPk::from_bytes(pk).map_err(|_| MessageVerifyError::InvalidSignature)?;
+            if hash != WPubkeyHash::from(pk) {
+                return Err(MessageVerifyError::InvalidSignature);
+            }
+            let script_code = ScriptPubkey::p2pkh(PubkeyHash::from(pk));
+            let sighash = cache
+                .segwit_sighash(0, script_code.as_script_bytes(), sig.sighash_type)
+                .map_err(|_| MessageVerifyError::InvalidSignature)?;
+            let mut ecdsa = sig.sig;
+            ecdsa.normalize_s();
+            SECP256K1
+                .verify_ecdsa(&sighash.into(), &ecdsa, &pk)
+                .map_err(|_| MessageVerifyError::InvalidSignature)?;
+        }
+        (AddressPayload::Tr(output_pk), [sig]) => {
+            let sig =
+                Bip340Sig::from_bytes(sig).map_err(|_| MessageVerifyError::InvalidSignature)?;
+            let sighash = cache
+                .tap_sighash_key(0, sig.sighash_type)
+                .map_err(|_| MessageVerifyError::InvalidSignature)?;
+            SECP256K1
+                .verify_schnorr(&sig.sig, &sighash.into(), &output_pk)
+                .map_err(|_| MessageVerifyError::InvalidSignature)?;
+        }
+        (AddressPayload::Wpkh(_) | AddressPayload::Tr(_), _) => {
+            return Err(MessageVerifyError::InvalidSignature)
+        }
+        _ => return Err(MessageVerifyError::UnsupportedAddress(address.address_type())),
+    }
+    Ok(format)
+}
+
+fn verify_legacy(hash: PubkeyHash, msg: &[u8], data: &[u8]) -> Result<(), MessageVerifyError> {
+    let (header, sig) = data.split_first().ok_or(MessageVerifyError::InvalidEncoding)?;
+    if sig.len() != 64 || !(27..35).contains(header) {
+        return Err(MessageVerifyError::InvalidEncoding);
+    }
+    let compressed = *header >= 31;
+    let recid = RecoveryId::from_i32(((header - 27) % 4) as i32)
+        .map_err(|_| MessageVerifyError::InvalidEncoding)?;
+    let sig = RecoverableSignature::from_compact(sig, recid)
+        .map_err(|_| MessageVerifyError::InvalidEncoding)?;
+    let msg = Message::from_digest(legacy_message_hash(msg));
+    let pk =
+        SECP256K1.recover_ecdsa(&msg, &sig).map_err(|_| MessageVerifyError::InvalidSignature)?;
+    let pk = match compressed {
+        true => LegacyPk::compressed(pk),
+        false => LegacyPk::uncompressed(pk),
+    };
+    if PubkeyHash::from(pk) != hash {
+        return Err(MessageVerifyError::InvalidSignature);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use amplify::hex::ToHex;
+
+    use super::*;
+    use crate::{AddressNetwork, PrivateKey};
+
+    const WIF: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+    const P2WPKH: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+
+    fn key() -> SecretKey { PrivateKey::from_str(WIF).unwrap().to_private_ecdsa() }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(
+            message_hash(b"").to_hex(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            message_hash(b"Hello World").to_hex(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+    }
+
+    #[test]
+    fn p2wpkh() {
+        let address = Address::from_str(P2WPKH).unwrap();
+        for (msg, sig) in [
+            (
+                &b""[..],
+                "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+            ),
+            (
+                &b"Hello World"[..],
+                "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+            ),
+        ] {
+            assert_eq!(verify_message(&address, msg, sig), Ok(MessageSigFormat::Simple));
+        }
+        assert_eq!(
+            verify_message(&address, b"Hello World", "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="),
+            Err(MessageVerifyError::InvalidSignature)
+        );
+
+        for format in [MessageSigFormat::Simple, MessageSigFormat::Full] {
+            let sig = sign_message(&address, b"Hello World", &key(), format).unwrap();
+            assert_eq!(verify_message(&address, b"Hello World", &sig), Ok(format));
+        }
+    }
+
+    #[test]
+    fn p2tr() {
+        let pk = InternalPk::from_unchecked(XOnlyPk::from(key().public_key(SECP256K1)));
+        let address = Address::new(
+            AddressPayload::Tr(pk.to_output_pk(None::<TapNodeHash>).0),
+            AddressNetwork::Mainnet,
+        );
+        let sig = "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==";
+        assert_eq!(verify_message(&address, b"Hello World", sig), Ok(MessageSigFormat::Simple));
+
+        for format in [MessageSigFormat::Simple, MessageSigFormat::Full] {
+            let sig = sign_message(&address, b"msg", &key(), format).unwrap();
+            assert_eq!(verify_message(&address, b"msg", &sig), Ok(format));
+            assert_eq!(
+                verify_message(&address, b"other", &sig),
+                Err(MessageVerifyError::InvalidSignature)
+            );
+        }
+    }
+
+    #[test]
+    fn legacy() {
+        let pk = LegacyPk::compressed(key().public_key(SECP256K1));
+        let address =
+            Address::new(AddressPayload::Pkh(PubkeyHash::from(pk)), AddressNetwork::Mainnet);
+        let sig = sign_message(&address, b"Hello World", &key(), MessageSigFormat::Legacy).unwrap();
+        assert_eq!(verify_message(&address, b"Hello World", &sig), Ok(MessageSigFormat::Legacy));
+        assert_eq!(
+            verify_message(&address, b"Hello", &sig),
+            Err(MessageVerifyError::InvalidSignature)
+        );
+        assert_eq!(
+            sign_message(&address, b"", &key(), MessageSigFormat::Simple),
+            Err(MessageSignError::UnsupportedAddress(
+                crate::AddressType::P2pkh,
+                MessageSigFormat::Simple
+            ))
+        );
+    }
+}
@@ -0,0 +1,387 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Version 1 reusable payment codes (BIP-47).
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use bc::secp256k1::{PublicKey, Scalar, SecretKey, SECP256K1};
+use bc::{
+    CompressedPk, ConsensusEncode, InvalidPubkey, Outpoint, PubkeyHash, Sats, ScriptPubkey, Tx,
+    TxIn, TxOut,
+};
+use bitcoin_hashes::{sha256, sha512, Hash, HashEngine, Hmac, HmacEngine};
+
+use crate::{
+    base58, DerivationIndex, HardenedIndex, Idx, NormalIndex, Xpriv, Xpub, XpubCore, XpubMeta,
+};
+
+/// Base58 prefix byte of payment codes, producing `PM8T` strings.
+pub const PAYMENT_CODE_PREFIX: u8 = 0x47;
+/// BIP-43 purpose used by the payment code derivation `m/47'/coin_type'/account'`.
+pub const BIP47_PURPOSE: HardenedIndex = HardenedIndex::hardened(47);
+
+const PAYMENT_CODE_VERSION: u8 = 0x01;
+const PAYMENT_CODE_LEN: usize = 80;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PaymentCodeDecodeError {
+    /// wrong length of payment code data ({0}).
+    WrongLength(usize),
+
+    /// unsupported payment code version {0:#04x}.
+    UnsupportedVersion(u8),
+
+    /// payment code contains {0}
+    #[from]
+    #[from(bc::secp256k1::Error)]
+    InvalidPubkey(InvalidPubkey<33>),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+pub enum PaymentCodeParseError {
+    /// wrong Base58 encoding of payment code data - {0}
+    #[display(doc_comments)]
+    #[from]
+    Base58(base58::Error),
+
+    /// payment code has an invalid prefix {0:#04x}.
+    #[display(doc_comments)]
+    InvalidPrefix(u8),
+
+    #[display(inner)]
+    #[from]
+    Decode(PaymentCodeDecodeError),
+}
+
+/// Version 1 payment code, consisting of the public key and chain code of the
+/// account key at `m/47'/coin_type'/account'`.
+///
+/// Bitmessage notification fields are not supported and are serialized as
+/// zeros.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PaymentCode {
+    pub features: u8,
+    pub core: XpubCore,
+}
+
+impl From<Xpub> for PaymentCode {
+    fn from(xpub: Xpub) -> Self { PaymentCode::with_account_xpub(&xpub) }
+}
+
+impl PaymentCode {
+    /// Constructs payment code from the account-level extended public key.
+    pub fn with_account_xpub(xpub: &Xpub) -> Self {
+        PaymentCode {
+            features: 0,
+            core: xpub.core(),
+        }
+    }
+
+    /// Constructs payment code from the account-level extended private key.
+    pub fn with_account_xpriv(xpriv: &Xpriv) -> Self { Self::with_account_xpub(&xpriv.to_xpub()) }
+
+    /// Returns public key of the payment code with a given derivation index.
+    pub fn derive_pk(&self, index: NormalIndex) -> CompressedPk {
+        let meta = XpubMeta {
+            depth: 3,
+            parent_fp: default!(),
+            child_number: DerivationIndex::ZERO,
+        };
+        Xpub::with(false, meta, self.core).ckd_pub(index).to_compr_pub()
+    }
+
+    /// Returns public key which receives notification transactions.
+    pub fn notification_pk(&self) -> CompressedPk { self.derive_pk(NormalIndex::ZERO) }
+
+    /// Returns P2PKH `scriptPubkey` of the notification address.
+    pub fn notification_script(&self) -> ScriptPubkey {
+        ScriptPubkey::p2pkh(PubkeyHash::from(self.notification_pk()))
+    }
+
+    pub fn decode(data: impl AsRef<[u8]>) -> Result<Self, PaymentCodeDecodeError> {
+        let data = data.as_ref();
+        if data.len() != PAYMENT_CODE_LEN {
+            return Err(PaymentCodeDecodeError::WrongLength(data.len()));
+        }
+        if data[0] != PAYMENT_CODE_VERSION {
+            return Err(PaymentCodeDecodeError::UnsupportedVersion(data[0]));
+        }
+        let public_key = CompressedPk::from_bytes(&data[2..35])?;
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&data[35..67]);
+        Ok(PaymentCode {
+            features: data[1],
+            core: XpubCore {
+                public_key,
+                chain_code: chain_code.into(),
+            },
+        })
+    }
+
+    pub fn encode(&self) -> [u8; PAYMENT_CODE_LEN] {
+        let mut data = [0u8; PAYMENT_CODE_LEN];
+        data[0] = PAYMENT_CODE_VERSION;
+        data[1] = self.features;
+        data[2..35].copy_from_slice(&self.core.public_key.serialize());
+        data[35..67].copy_from_slice(self.core.chain_code.as_ref());
+        data
+    }
+
+    /// Constructs outputs of a notification transaction from the owner of this
+    /// payment code to the `recipient`: a dust output to the recipient
+    /// notification address and an `OP_RETURN` output with the blinded payment
+    /// code.
+    ///
+    /// The payment code is blinded with the private key of the designated input
+    /// and the outpoint spent by it; the designated input must expose its
+    /// public key (P2PKH or P2WPKH) and be the first such input in the
+    /// transaction.
+    pub fn notification_txouts(
+        &self,
+        recipient: &PaymentCode,
+        designated_key: &SecretKey,
+        designated_outpoint: Outpoint,
+        amount: Sats,
+    ) -> [TxOut; 2] {
+        let mut payload = self.encode();
+        let secret = shared_secret(designated_key, &recipient.notification_pk());
+        blind(&mut payload, designated_outpoint, &secret);
+        [
+            TxOut::new(recipient.notification_script(), amount),
+            TxOut::new(ScriptPubkey::op_return(&payload), Sats::ZERO),
+        ]
+    }
+
+    /// Detects notification transaction sent to the payment code of the
+    /// `account` and returns payment code of the sender.
+    ///
+    /// Returns `None` if the transaction doesn't pay to the notification
+    /// address or has no valid blinded payment code.
+    pub fn detect_notification(account: &Xpriv, tx: &Tx) -> Option<PaymentCode> {
+        let notification_key = account.ckd_priv(NormalIndex::ZERO);
+        let notification_script =
+            ScriptPubkey::p2pkh(PubkeyHash::from(notification_key.to_compr_pub()));
+        if !tx.outputs.iter().any(|txout| txout.script_pubkey == notification_script) {
+            return None;
+        }
+        let (txin, designated_pk) =
+            tx.inputs.iter().find_map(|txin| input_pubkey(txin).map(|pk| (txin, pk)))?;
+
+        tx.outputs.iter().find_map(|txout| {
+            let script = txout.script_pubkey.as_slice();
+            // OP_RETURN OP_PUSHDATA1 80 <payload>
+            if script.len() != PAYMENT_CODE_LEN + 3 || script[..3] != [0x6a, 0x4c, 0x50] {
+                return None;
+            }
+            let mut payload = [0u8; PAYMENT_CODE_LEN];
+            payload.copy_from_slice(&script[3..]);
+            let mut sk = notification_key.to_private_ecdsa();
+            let secret = shared_secret(&sk, &designated_pk);
+            sk.non_secure_erase();
+            blind(&mut payload, txin.prev_output, &secret);
+            PaymentCode::decode(payload).ok()
+        })
+    }
+
+    /// Returns public key of the `index`-th payment from the owner of the
+    /// `sender` account to this payment code.
+    pub fn send_pk(&self, sender: &Xpriv, index: NormalIndex) -> CompressedPk {
+        let pk = self.derive_pk(index);
+        let mut sk = sender.ckd_priv(NormalIndex::ZERO).to_private_ecdsa();
+        let secret = shared_secret(&sk, &pk);
+        sk.non_secure_erase();
+        pk.add_exp_tweak(SECP256K1, &secret_tweak(secret)).expect("negligible probability").into()
+    }
+
+    /// Returns private key for the `index`-th payment received by the owner of
+    /// the `receiver` account from the owner of this payment code.
+    pub fn receive_key(&self, receiver: &Xpriv, index: NormalIndex) -> SecretKey {
+        let mut sk = receiver.ckd_priv(index).to_private_ecdsa();
+        let secret = shared_secret(&sk, &self.notification_pk());
+        let key = sk.add_tweak(&secret_tweak(secret)).expect("negligible probability");
+        sk.non_secure_erase();
+        key
+    }
+}
+
+/// Returns X coordinate of the ECDH shared point.
+fn shared_secret(sk: &SecretKey, pk: &PublicKey) -> [u8; 32] {
+    let point = pk.mul_tweak(SECP256K1, &Scalar::from(*sk)).expect("negligible probability");
+    let mut x = [0u8; 32];
+    x.copy_from_slice(&point.serialize()[1..]);
+    x
+}
+
+fn secret_tweak(secret: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(sha256::Hash::hash(&secret).to_byte_array())
+        .expect("negligible probability")
+}
+
+/// Blinds or unblinds public key X coordinate and chain code of a serialized
+/// payment code.
+fn blind(payload: &mut [u8; PAYMENT_CODE_LEN], outpoint: Outpoint, secret: &[u8; 32]) {
+    let mut engine = HmacEngine::<sha512::Hash>::new(&outpoint.consensus_serialize());
+    engine.input(secret);
+    let mask = Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+    for (byte, mask) in payload[3..67].iter_mut().zip(mask) {
+        *byte ^= mask;
+    }
+}
+
+/// Extracts compressed public key exposed by a P2PKH or P2WPKH input.
+fn input_pubkey(txin: &TxIn) -> Option<CompressedPk> {
+    if let Some(pk) = txin.witness.iter().last() {
+        return CompressedPk::from_bytes(pk).ok();
+    }
+    let script = txin.sig_script.as_slice();
+    let mut last = None;
+    let mut pos = 0;
+    while pos < script.len() {
+        let (len, skip) = match script[pos] {
+            op @ 0x01..=0x4b => (op as usize, 1),
+            0x4c => (*script.get(pos + 1)? as usize, 2),
+            0x4d => {
+                (u16::from_le_bytes([*script.get(pos + 1)?, *script.get(pos + 2)?]) as usize, 3)
+            }
+            _ => return None,
+        };
+        last = Some(script.get(pos + skip..pos + skip + len)?);
+        pos += skip + len;
+    }
+    CompressedPk::from_bytes(last?).ok()
+}
+
+impl Display for PaymentCode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut data = Vec::with_capacity(PAYMENT_CODE_LEN + 1);
+        data.push(PAYMENT_CODE_PREFIX);
+        data.extend(self.encode());
+        base58::encode_check_to_fmt(f, &data)
+    }
+}
+
+impl FromStr for PaymentCode {
+    type Err = PaymentCodeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = base58::decode_check(s)?;
+        match data.split_first() {
+            Some((&PAYMENT_CODE_PREFIX, payload)) => Ok(PaymentCode::decode(payload)?),
+            Some((&prefix, _)) => Err(PaymentCodeParseError::InvalidPrefix(prefix)),
+            None => Err(PaymentCodeDecodeError::WrongLength(0).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bc::{LockTime, SeqNo, SigScript, TxVer, Txid, VarIntArray, Vout, Witness};
+
+    use super::*;
+    use crate::{Address, AddressNetwork, AddressPayload, Language, Mnemonic};
+
+    const ALICE_MNEMONIC: &str =
+        "response seminar brave tip suit recall often sound stick owner lottery motion";
+    const BOB_MNEMONIC: &str =
+        "reward upper indicate eight swift arch injury crystal super wrestle already dentist";
+    const ALICE_CODE: &str = "PM8TJTLJbPRGxSbc8EJi42Wrr6QbNSaSSVJ5Y3E4pbCYiTHUskHg13935Ubb7q8tx9GVbh2UuRnBc3WSyJHhUrw8KhprKnn9eDznYGieTzFcwQRya4GA";
+    const BOB_CODE: &str = "PM8TJS2JxQ5ztXUpBBRnpTbcUXbUHy2T1abfrb3KkAAtMEGNbey4oumH7Hc578WgQJhPjBxteQ5GHHToTYHE3A1w6p7tU6KSoFmWBVbFGjKPisZDbP97";
+
+    fn account(mnemonic: &str) -> Xpriv {
+        let seed = Mnemonic::parse_in(Language::English, mnemonic).unwrap().to_seed("");
+        Xpriv::from_seed(false, &seed).derive_priv([
+            BIP47_PURPOSE,
+            HardenedIndex::hardened(0),
+            HardenedIndex::hardened(0),
+        ])
+    }
+
+    #[test]
+    fn payment_codes() {
+        let alice = PaymentCode::with_account_xpriv(&account(ALICE_MNEMONIC));
+        let bob = PaymentCode::with_account_xpriv(&account(BOB_MNEMONIC));
+        assert_eq!(alice.to_string(), ALICE_CODE);
+        assert_eq!(bob.to_string(), BOB_CODE);
+        assert_eq!(PaymentCode::from_str(ALICE_CODE).unwrap(), alice);
+        assert_eq!(PaymentCode::from_str(BOB_CODE).unwrap(), bob);
+    }
+
+    #[test]
+    fn payment_keys() {
+        let alice_account = account(ALICE_MNEMONIC);
+        let bob_account = account(BOB_MNEMONIC);
+        let alice = PaymentCode::with_account_xpriv(&alice_account);
+        let bob = PaymentCode::from_str(BOB_CODE).unwrap();
+
+        for index in 0..4u16 {
+            let index = NormalIndex::from(index);
+            let pk = bob.send_pk(&alice_account, index);
+            let sk = alice.receive_key(&bob_account, index);
+            assert_eq!(pk, CompressedPk::from(sk.public_key(SECP256K1)));
+        }
+        let addr = |pk: CompressedPk| {
+            Address::new(AddressPayload::Pkh(PubkeyHash::from(pk)), AddressNetwork::Mainnet)
+                .to_string()
+        };
+        assert_eq!(
+            addr(bob.send_pk(&alice_account, NormalIndex::ZERO)),
+            "141fi7TY3h936vRUKh1qfUZr8rSBuYbVBK"
+        );
+        assert_eq!(
+            addr(bob.send_pk(&alice_account, NormalIndex::ONE)),
+            "12u3Uued2fuko2nY4SoSFGCoGLCBUGPkk6"
+        );
+        assert_eq!(addr(alice.notification_pk()), "1JDdmqFLhpzcUwPeinhJbUPw4Co3aWLyzW");
+        assert_eq!(addr(bob.notification_pk()), "1ChvUUvht2hUQufHBXF8NgLhW8SwE2ecGV");
+    }
+
+    #[test]
+    fn notification() {
+        let alice = PaymentCode::from_str(ALICE_CODE).unwrap();
+        let bob_account = account(BOB_MNEMONIC);
+        let bob = PaymentCode::with_account_xpriv(&bob_account);
+
+        let designated_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let outpoint = Outpoint::new(Txid::from([0x42; 32]), Vout::from_u32(1));
+        let txouts = alice.notification_txouts(&bob, &designated_key, outpoint, Sats(546));
+        assert_eq!(txouts[0].script_pubkey, bob.notification_script());
+        assert_ne!(&txouts[1].script_pubkey[3..], &alice.encode()[..]);
+
+        let pk = CompressedPk::from(designated_key.public_key(SECP256K1));
+        let tx = Tx {
+            version: TxVer::V2,
+            inputs: VarIntArray::from_iter_unsafe([TxIn {
+                prev_output: outpoint,
+                sig_script: SigScript::empty(),
+                sequence: SeqNo::from_consensus_u32(0),
+                witness: Witness::from_consensus_stack([vec![0x30; 71], pk.serialize().to_vec()]),
+            }]),
+            outputs: VarIntArray::from_iter_unsafe(txouts),
+            lock_time: LockTime::ZERO,
+        };
+        assert_eq!(PaymentCode::detect_notification(&bob_account, &tx), Some(alice));
+        assert_eq!(PaymentCode::detect_notification(&account(ALICE_MNEMONIC), &tx), None);
+    }
+}
@@ -23,6 +23,9 @@
 use std::cmp::Ordering;
 use std::collections::BTreeSet;
 use std::num::ParseIntError;
+#[cfg(feature = "rayon")]
+use std::ops::Range;
+use std::ops::{Bound, RangeBounds};
 use std::str::FromStr;
 
 use bc::{
@@ -31,13 +34,20 @@ use bc::{
 };
 use indexmap::IndexMap;
 use invoice::AddressError;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 use crate::{
     Address, AddressNetwork, AddressParseError, ControlBlockFactory, DerivationIndex, Idx, IdxBase,
-    IndexParseError, NormalIndex, TapTree, XpubDerivable, XpubSpec,
+    IndexParseError, NormalIndex, TapTree, XpubDerivable, XpubSpec, HARDENED_INDEX_BOUNDARY,
 };
 
 #[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug, Display, From)]
+#[cfg_attr(
+    feature = "strict_encoding",
+    derive(StrictType, StrictEncode, StrictDecode),
+    strict_type(lib = crate::LIB_NAME_BP_DERIVE)
+)]
 #[wrapper(FromStr)]
 #[display(inner)]
 pub struct Keychain(u8);
@@ -57,6 +67,14 @@ impl Keychain {
     pub const INNER: Self = Keychain(1);
 
     pub const fn with(idx: u8) -> Self { Keychain(idx) }
+
+    /// Detects whether the keychain is the BIP-44 external chain, used for receiving payments.
+    #[inline]
+    pub const fn is_external(self) -> bool { self.0 == Self::OUTER.0 }
+
+    /// Detects whether the keychain is the BIP-44 internal chain, used for change outputs.
+    #[inline]
+    pub const fn is_internal(self) -> bool { self.0 == Self::INNER.0 }
 }
 
 impl IdxBase for Keychain {
@@ -71,6 +89,11 @@ impl IdxBase for Keychain {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display)]
+#[cfg_attr(
+    feature = "strict_encoding",
+    derive(StrictType, StrictDumb, StrictEncode, StrictDecode),
+    strict_type(lib = crate::LIB_NAME_BP_DERIVE)
+)]
 #[display("&{keychain}/{index}")]
 pub struct Terminal {
     pub keychain: Keychain,
@@ -85,6 +108,10 @@ impl Terminal {
         }
     }
     pub fn change(index: NormalIndex) -> Self { Self::new(1, index) }
+
+    /// Detects whether the terminal belongs to the internal (change) keychain.
+    #[inline]
+    pub fn is_change(&self) -> bool { self.keychain.is_internal() }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
@@ -213,6 +240,21 @@ impl DerivedScript {
         }
     }
 
+    /// Detects whether the script is spent with a witness, either as a native segwit output or
+    /// nested into P2SH.
+    pub fn is_segwit(&self) -> bool {
+        match self {
+            DerivedScript::Bare(script_pubkey) => script_pubkey.is_witness_program(),
+            DerivedScript::Bip13(redeem_script) => {
+                ScriptPubkey::from_unsafe(redeem_script.to_vec()).is_witness_program()
+            }
+            DerivedScript::Segwit(_)
+            | DerivedScript::Nested(_)
+            | DerivedScript::TaprootKeyOnly(_)
+            | DerivedScript::TaprootScript(_, _) => true,
+        }
+    }
+
     pub fn to_redeem_script(&self) -> Option<RedeemScript> {
         match self {
             DerivedScript::Bare(_) => None,
@@ -336,23 +378,48 @@ pub trait Derive<D> {
 
     fn derive(&self, keychain: impl Into<Keychain>, index: impl Into<NormalIndex>) -> D;
 
+    /// Returns lazy iterator deriving values for the `keychain` and all indexes in the `range`.
+    /// To derive a specific number of values use an open range with [`Iterator::take`].
     fn derive_batch(
         &self,
         keychain: impl Into<Keychain>,
-        from: impl Into<NormalIndex>,
-        max_count: u8,
-    ) -> Vec<D> {
-        let mut index = from.into();
-        let mut count = 0u8;
-        let mut batch = Vec::with_capacity(max_count as usize);
+        range: impl RangeBounds<NormalIndex>,
+    ) -> impl Iterator<Item = D> {
         let keychain = keychain.into();
-        loop {
-            batch.push(self.derive(keychain, index));
-            count += 1;
-            if index.checked_inc_assign().is_none() || count >= max_count {
-                return batch;
-            }
-        }
+        let start = match range.start_bound() {
+            Bound::Included(index) => index.child_number(),
+            Bound::Excluded(index) => index.child_number() + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(index) => index.child_number() + 1,
+            Bound::Excluded(index) => index.child_number(),
+            Bound::Unbounded => HARDENED_INDEX_BOUNDARY,
+        };
+        (start..end).map(move |child_number| {
+            let index = NormalIndex::try_from_child_number(child_number)
+                .expect("range is within normal index bounds");
+            self.derive(keychain, index)
+        })
+    }
+
+    /// Derives values for the `keychain` and all indexes in the `range` using multiple threads.
+    /// The returned values are ordered by their index.
+    #[cfg(feature = "rayon")]
+    fn derive_batch_par(&self, keychain: impl Into<Keychain>, range: Range<NormalIndex>) -> Vec<D>
+    where
+        Self: Sync,
+        D: Send,
+    {
+        let keychain = keychain.into();
+        (range.start.child_number()..range.end.child_number())
+            .into_par_iter()
+            .map(|child_number| {
+                let index = NormalIndex::try_from_child_number(child_number)
+                    .expect("range is within normal index bounds");
+                self.derive(keychain, index)
+            })
+            .collect()
     }
 }
 
@@ -380,22 +447,119 @@ pub trait DeriveScripts: Derive<DerivedScript> {
         Address::with(&spk, network)
     }
 
+    /// Derives addresses for the `keychain` and all indexes in the `range`, which must be
+    /// bounded.
     fn derive_address_batch(
         &self,
         network: AddressNetwork,
         keychain: impl Into<Keychain>,
-        from: impl Into<NormalIndex>,
-        max_count: u8,
+        range: impl RangeBounds<NormalIndex>,
     ) -> Result<Vec<Address>, AddressError> {
-        self.derive_batch(keychain, from, max_count)
-            .iter()
-            .map(DerivedScript::to_script_pubkey)
+        self.derive_batch(keychain, range)
+            .map(|script| script.to_script_pubkey())
             .map(|spk| Address::with(&spk, network))
             .collect()
     }
+
+    /// Returns lazy iterator over addresses of the `keychain`, starting from index zero. The
+    /// addresses are derived only once they are requested, and skipping addresses with
+    /// [`Iterator::nth`] doesn't derive the skipped ones.
+    ///
+    /// The iterator ends once the index space is exhausted. It is empty if the derived scripts
+    /// have no address representation.
+    fn addresses(
+        &self,
+        network: AddressNetwork,
+        keychain: impl Into<Keychain>,
+    ) -> AddrIter<'_, Self>
+    where
+        Self: Sized,
+    {
+        AddrIter {
+            descr: self,
+            network,
+            keychain: keychain.into(),
+            next: Some(NormalIndex::ZERO),
+        }
+    }
+
+    /// Finds the terminal at which the script pubkey is derived, scanning all keychains for the
+    /// indexes below the `horizon`, which is usually the first index not yet used by a wallet
+    /// plus its gap limit.
+    fn terminal_of(&self, script_pubkey: &ScriptPubkey, horizon: NormalIndex) -> Option<Terminal> {
+        self.keychains().into_iter().find_map(|keychain| {
+            let pos = self
+                .derive_batch(keychain, ..horizon)
+                .position(|script| script.to_script_pubkey() == *script_pubkey)?;
+            let index = NormalIndex::try_from_child_number(pos as u32).expect("below horizon");
+            Some(Terminal::new(keychain, index))
+        })
+    }
+
+    /// Detects whether the address belongs to the descriptor, i.e. whether its script pubkey is
+    /// derived at some terminal below the `horizon` index (see [`DeriveScripts::terminal_of`]).
+    /// The address network is not checked.
+    fn is_mine(&self, address: &Address, horizon: NormalIndex) -> bool {
+        self.terminal_of(&address.script_pubkey(), horizon).is_some()
+    }
+
+    /// Derives scripts for the `keychain` and all indexes in the `range` using multiple threads,
+    /// returning those script pubkeys which are accepted by `filter` (for instance, present in
+    /// the chain data), ordered by their index.
+    #[cfg(feature = "rayon")]
+    fn scan_par(
+        &self,
+        keychain: impl Into<Keychain>,
+        range: Range<NormalIndex>,
+        filter: impl Fn(&ScriptPubkey) -> bool + Sync,
+    ) -> Vec<(Terminal, ScriptPubkey)>
+    where
+        Self: Sync,
+    {
+        let keychain = keychain.into();
+        (range.start.child_number()..range.end.child_number())
+            .into_par_iter()
+            .filter_map(|child_number| {
+                let index = NormalIndex::try_from_child_number(child_number)
+                    .expect("range is within normal index bounds");
+                let spk = self.derive(keychain, index).to_script_pubkey();
+                filter(&spk).then(|| (Terminal::new(keychain, index), spk))
+            })
+            .collect()
+    }
 }
 impl<T: Derive<DerivedScript>> DeriveScripts for T {}
 
+/// Lazy iterator over addresses derived for a single keychain, created with
+/// [`DeriveScripts::addresses`].
+#[derive(Clone, Debug)]
+pub struct AddrIter<'descr, D: DeriveScripts> {
+    descr: &'descr D,
+    network: AddressNetwork,
+    keychain: Keychain,
+    next: Option<NormalIndex>,
+}
+
+impl<'descr, D: DeriveScripts> Iterator for AddrIter<'descr, D> {
+    type Item = DerivedAddr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next?;
+        let Ok(addr) = self.descr.derive_address(self.network, self.keychain, index) else {
+            self.next = None;
+            return None;
+        };
+        self.next = index.checked_inc();
+        Some(DerivedAddr::new(addr, self.keychain, index))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.next =
+            self.next.zip(u32::try_from(n).ok()).and_then(|(index, n)| index.checked_add(n));
+        self.next()
+    }
+}
+
 impl DeriveKey<LegacyPk> for XpubDerivable {
     fn xpub_spec(&self) -> &XpubSpec { self.spec() }
 }
@@ -416,7 +580,7 @@ impl Derive<LegacyPk> for XpubDerivable {
     fn keychains(&self) -> BTreeSet<Keychain> { self.keychains.to_set() }
 
     fn derive(&self, keychain: impl Into<Keychain>, index: impl Into<NormalIndex>) -> LegacyPk {
-        self.xpub().derive_pub([keychain.into().into(), index.into()]).to_legacy_pub()
+        self.keychain_xpub(keychain.into()).ckd_pub(index.into()).to_legacy_pub()
     }
 }
 
@@ -428,7 +592,7 @@ impl Derive<CompressedPk> for XpubDerivable {
     fn keychains(&self) -> BTreeSet<Keychain> { self.keychains.to_set() }
 
     fn derive(&self, keychain: impl Into<Keychain>, index: impl Into<NormalIndex>) -> CompressedPk {
-        self.xpub().derive_pub([keychain.into().into(), index.into()]).to_compr_pub()
+        self.keychain_xpub(keychain.into()).ckd_pub(index.into()).to_compr_pub()
     }
 }
 
@@ -440,7 +604,7 @@ impl Derive<XOnlyPk> for XpubDerivable {
     fn keychains(&self) -> BTreeSet<Keychain> { self.keychains.to_set() }
 
     fn derive(&self, keychain: impl Into<Keychain>, index: impl Into<NormalIndex>) -> XOnlyPk {
-        self.xpub().derive_pub([keychain.into().into(), index.into()]).to_xonly_pub()
+        self.keychain_xpub(keychain.into()).ckd_pub(index.into()).to_xonly_pub()
     }
 }
 
@@ -0,0 +1,200 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Electrum native (version 2 and above) seed phrases.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::mem;
+use std::str::FromStr;
+
+use bitcoin_hashes::{sha512, Hash, HashEngine, Hmac, HmacEngine};
+
+use crate::mnemonic::{pbkdf2_sha512, salt};
+use crate::xpub::erase;
+use crate::{HardenedIndex, KeyApplication, Seed, Xpriv, XprivAccount};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ElectrumSeedError {
+    /// the phrase is not an Electrum seed, or it is a two-factor authentication seed, which is
+    /// not supported.
+    UnknownVersion,
+}
+
+/// Type of an Electrum seed, defined by its version prefix.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum ElectrumSeedType {
+    /// Legacy P2PKH wallet seed (version prefix `01`).
+    Standard,
+
+    /// Native P2WPKH wallet seed (version prefix `100`).
+    Segwit,
+}
+
+impl ElectrumSeedType {
+    /// Hex version prefix of the seed type.
+    pub const fn version_prefix(self) -> &'static str {
+        match self {
+            ElectrumSeedType::Standard => "01",
+            ElectrumSeedType::Segwit => "100",
+        }
+    }
+
+    /// Application of the keys derived from the seed.
+    pub const fn key_application(self) -> KeyApplication {
+        match self {
+            ElectrumSeedType::Standard => KeyApplication::Legacy,
+            ElectrumSeedType::Segwit => KeyApplication::Wpkh,
+        }
+    }
+
+    /// Derivation path of the wallet account key relative to the seed master key.
+    pub fn account_path(self) -> Vec<HardenedIndex> {
+        match self {
+            ElectrumSeedType::Standard => vec![],
+            ElectrumSeedType::Segwit => vec![HardenedIndex::hardened(0)],
+        }
+    }
+}
+
+/// Electrum seed phrase.
+///
+/// Unlike BIP-39, Electrum seeds carry no checksum over the wordlist; instead the HMAC of the
+/// normalized phrase commits to the seed type. The phrase is normalized by lowercasing it and
+/// collapsing whitespace; non-ASCII phrases must be already converted into the Unicode NFKD
+/// form with the combining characters removed.
+#[derive(Clone, Eq, PartialEq)]
+pub struct ElectrumSeed {
+    phrase: String,
+    seed_type: ElectrumSeedType,
+}
+
+impl ElectrumSeed {
+    fn normalize(s: &str) -> String {
+        s.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Parses Electrum seed phrase, detecting its type.
+    pub fn parse(s: &str) -> Result<Self, ElectrumSeedError> {
+        let phrase = Self::normalize(s);
+
+        let mut engine = HmacEngine::<sha512::Hash>::new(b"Seed version");
+        engine.input(phrase.as_bytes());
+        let version = Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+        let seed_type = match (version[0], version[1] >> 4) {
+            (0x01, _) => ElectrumSeedType::Standard,
+            (0x10, 0x0) => ElectrumSeedType::Segwit,
+            _ => return Err(ElectrumSeedError::UnknownVersion),
+        };
+
+        Ok(ElectrumSeed { phrase, seed_type })
+    }
+
+    /// Type of the seed.
+    pub fn seed_type(&self) -> ElectrumSeedType { self.seed_type }
+
+    /// Derives the BIP-32 seed from the phrase and a passphrase (which may be empty).
+    ///
+    /// The passphrase is normalized in the same way as the phrase itself.
+    pub fn to_seed(&self, passphrase: &str) -> Seed {
+        let mut passphrase = Self::normalize(passphrase).into_bytes();
+        let mut salt = salt("electrum", &passphrase);
+        let mut seed = Seed([0u8; 64]);
+        pbkdf2_sha512(self.phrase.as_bytes(), &salt, &mut seed.0);
+        passphrase.iter_mut().chain(&mut salt).for_each(erase);
+        seed
+    }
+
+    /// Derives the wallet account key, which Electrum uses as the root of the receiving and
+    /// change keychains.
+    pub fn to_account(&self, passphrase: &str, testnet: bool) -> XprivAccount {
+        let master = Xpriv::from_seed(testnet, &self.to_seed(passphrase));
+        XprivAccount::new_master(master).derive(self.seed_type.account_path())
+    }
+}
+
+impl Drop for ElectrumSeed {
+    fn drop(&mut self) {
+        let mut phrase = mem::take(&mut self.phrase).into_bytes();
+        phrase.iter_mut().for_each(erase)
+    }
+}
+
+impl Debug for ElectrumSeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ElectrumSeed").field("seed_type", &self.seed_type).finish_non_exhaustive()
+    }
+}
+
+impl Display for ElectrumSeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str(&self.phrase) }
+}
+
+impl FromStr for ElectrumSeed {
+    type Err = ElectrumSeedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::parse(s) }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::ToHex;
+
+    use super::*;
+
+    // Test vector from Electrum
+    const PHRASE: &str =
+        "wild father tree among universe such mobile favorite target dynamic credit identify";
+
+    #[test]
+    fn segwit() {
+        let seed = ElectrumSeed::from_str(PHRASE).unwrap();
+        assert_eq!(seed.seed_type(), ElectrumSeedType::Segwit);
+        assert_eq!(
+            seed.to_seed("").as_ref().to_hex(),
+            "aac2a6302e48577ab4b46f23dbae0774e2e62c796f797d0a1b5faeb528301e3064342dafb79069e7c4c6b8c38ae11d7a973bec0d4f70626f8cc5184a8d0b0756"
+        );
+        assert_eq!(
+            seed.to_seed("Did you ever hear the tragedy of Darth Plagueis the Wise?").as_ref().to_hex(),
+            "4aa29f2aeb0127efb55138ab9e7be83b36750358751906f86c662b21a1ea1370f949e6d1a12fa56d3d93cadda93038c76ac8118597364e46f5156fde6183c82f"
+        );
+        assert_eq!(seed.to_account("", false).origin().derivation().to_string(), "/0h");
+    }
+
+    #[test]
+    fn normalization() {
+        let seed = ElectrumSeed::from_str(&format!("  {}\n", PHRASE.to_uppercase())).unwrap();
+        assert_eq!(seed.to_string(), PHRASE);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            ElectrumSeed::from_str(
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                 abandon about"
+            ),
+            Err(ElectrumSeedError::UnknownVersion)
+        );
+    }
+}
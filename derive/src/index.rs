@@ -52,6 +52,9 @@ pub enum IndexParseError {
 
     /// expected hardened index value instead of the provided unhardened {0}
     HardenedRequired(String),
+
+    /// invalid index range '{0}'
+    InvalidRange(String),
 }
 
 /// Trait defining basic index functionality without mathematics operations.
@@ -247,6 +250,11 @@ fn checked_sub_assign(index: &mut u32, sub: impl Into<u32>) -> Option<u32> {
 /// Index for unhardened children derivation; ensures that the inner value
 /// is always < 2^31
 #[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Default, Display, From)]
+#[cfg_attr(
+    feature = "strict_encoding",
+    derive(StrictType, StrictEncode, StrictDecode),
+    strict_type(lib = crate::LIB_NAME_BP_DERIVE)
+)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -350,6 +358,11 @@ impl FromStr for NormalIndex {
 /// Index for hardened children derivation; ensures that the index always >=
 /// 2^31.
 #[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default, Display, From)]
+#[cfg_attr(
+    feature = "strict_encoding",
+    derive(StrictType, StrictEncode, StrictDecode),
+    strict_type(lib = crate::LIB_NAME_BP_DERIVE)
+)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -360,7 +373,7 @@ pub struct HardenedIndex(
     /// The inner index value; always reduced by [`HARDENED_INDEX_BOUNDARY`]
     #[from(u8)]
     #[from(u16)]
-    pub(crate) u32,
+    pub(super) u32,
 );
 
 impl PartialEq<u8> for HardenedIndex {
@@ -459,6 +472,11 @@ impl FromStr for HardenedIndex {
 }
 
 #[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, From)]
+#[cfg_attr(
+    feature = "strict_encoding",
+    derive(StrictType, StrictDumb, StrictEncode, StrictDecode),
+    strict_type(lib = crate::LIB_NAME_BP_DERIVE, tags = order, dumb = Self::Normal(strict_dumb!()))
+)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -558,7 +576,7 @@ impl FromStr for DerivationIndex {
     type Err = IndexParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.strip_suffix(['h', 'H', '*']) {
+        match s.strip_suffix(['h', 'H', '\'']) {
             Some(_) => HardenedIndex::from_str(s).map(Self::Hardened),
             None => NormalIndex::from_str(s).map(Self::Normal),
         }
@@ -0,0 +1,239 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Single private keys in wallet import format (WIF).
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use bc::secp256k1::SECP256K1;
+use bc::{secp256k1, CompressedPk, LegacyPk};
+
+use crate::base58;
+
+pub const WIF_MAINNET_PREFIX: u8 = 0x80;
+pub const WIF_TESTNET_PREFIX: u8 = 0xEF;
+
+/// Trailing byte of WIF data marking that the key is used with a compressed public key.
+const WIF_COMPRESSED_FLAG: u8 = 0x01;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum WifDecodeError {
+    /// wrong length of WIF private key data ({0}).
+    WrongLength(usize),
+
+    /// unknown WIF private key network prefix {0:#04x}.
+    UnknownNetwork(u8),
+
+    /// invalid WIF private key compression flag {0:#04x}.
+    InvalidCompressionFlag(u8),
+
+    /// WIF private key contains invalid secret key data.
+    #[from(bc::secp256k1::Error)]
+    InvalidSecretKey,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+pub enum WifParseError {
+    /// wrong Base58 encoding of WIF private key data - {0}
+    #[display(doc_comments)]
+    #[from]
+    Base58(base58::Error),
+
+    #[display(inner)]
+    #[from]
+    Decode(WifDecodeError),
+}
+
+/// Single private key together with the information required for its wallet import format
+/// (WIF) encoding: network and whether the key is used with a compressed public key.
+///
+/// The secret key is erased from the memory when the value is dropped.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PrivateKey {
+    testnet: bool,
+    compressed: bool,
+    secret: secp256k1::SecretKey,
+}
+
+impl PrivateKey {
+    /// Constructs private key used with a compressed public key.
+    pub fn compressed(testnet: bool, secret: secp256k1::SecretKey) -> Self {
+        PrivateKey {
+            testnet,
+            compressed: true,
+            secret,
+        }
+    }
+
+    /// Constructs private key used with an uncompressed public key.
+    pub fn uncompressed(testnet: bool, secret: secp256k1::SecretKey) -> Self {
+        PrivateKey {
+            testnet,
+            compressed: false,
+            secret,
+        }
+    }
+
+    /// Whether the key is used on a test network.
+    pub fn is_testnet(&self) -> bool { self.testnet }
+
+    /// Whether the key is used with a compressed public key.
+    pub fn is_compressed(&self) -> bool { self.compressed }
+
+    /// Returns secret key for ECDSA signing.
+    pub fn to_private_ecdsa(&self) -> secp256k1::SecretKey { self.secret }
+
+    /// Constructs public key matching the private key, in compressed or uncompressed form
+    /// depending on the key compression flag.
+    pub fn to_legacy_pk(&self) -> LegacyPk {
+        let pubkey = self.secret.public_key(SECP256K1);
+        match self.compressed {
+            true => LegacyPk::compressed(pubkey),
+            false => LegacyPk::uncompressed(pubkey),
+        }
+    }
+
+    /// Constructs compressed public key matching the private key.
+    ///
+    /// Returns `None` if the key is used with an uncompressed public key, since the compressed
+    /// key would produce different scripts and addresses.
+    pub fn to_compr_pk(&self) -> Option<CompressedPk> {
+        self.compressed.then(|| CompressedPk::from(self.secret.public_key(SECP256K1)))
+    }
+
+    pub fn decode(data: impl AsRef<[u8]>) -> Result<PrivateKey, WifDecodeError> {
+        let data = data.as_ref();
+        let compressed = match data.len() {
+            33 => false,
+            34 if data[33] == WIF_COMPRESSED_FLAG => true,
+            34 => return Err(WifDecodeError::InvalidCompressionFlag(data[33])),
+            len => return Err(WifDecodeError::WrongLength(len)),
+        };
+        let testnet = match data[0] {
+            WIF_MAINNET_PREFIX => false,
+            WIF_TESTNET_PREFIX => true,
+            unknown => return Err(WifDecodeError::UnknownNetwork(unknown)),
+        };
+        let secret = secp256k1::SecretKey::from_slice(&data[1..33])?;
+        Ok(PrivateKey {
+            testnet,
+            compressed,
+            secret,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(34);
+        ret.push(match self.testnet {
+            false => WIF_MAINNET_PREFIX,
+            true => WIF_TESTNET_PREFIX,
+        });
+        ret.extend(self.secret.secret_bytes());
+        if self.compressed {
+            ret.push(WIF_COMPRESSED_FLAG);
+        }
+        ret
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) { self.secret.non_secure_erase() }
+}
+
+impl Display for PrivateKey {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        base58::encode_check_to_fmt(f, &self.encode())
+    }
+}
+
+impl FromStr for PrivateKey {
+    type Err = WifParseError;
+
+    fn from_str(inp: &str) -> Result<PrivateKey, WifParseError> {
+        let data = base58::decode_check(inp)?;
+        Ok(PrivateKey::decode(data)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::{FromHex, ToHex};
+
+    use super::*;
+
+    const SECRET: &str = "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d";
+
+    #[test]
+    fn wif() {
+        let secret =
+            secp256k1::SecretKey::from_slice(&Vec::<u8>::from_hex(SECRET).unwrap()).unwrap();
+        for (wif, key) in [
+            (
+                "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ",
+                PrivateKey::uncompressed(false, secret),
+            ),
+            (
+                "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617",
+                PrivateKey::compressed(false, secret),
+            ),
+        ] {
+            assert_eq!(key.to_string(), wif);
+            assert_eq!(PrivateKey::from_str(wif).unwrap(), key);
+        }
+
+        let testnet = PrivateKey::compressed(true, secret);
+        assert_eq!(PrivateKey::from_str(&testnet.to_string()).unwrap(), testnet);
+    }
+
+    #[test]
+    fn pubkeys() {
+        let key =
+            PrivateKey::from_str("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ").unwrap();
+        assert_eq!(
+            key.to_legacy_pk().to_vec().to_hex(),
+            "04d0de0aaeaefad02b8bdc8a01a1b8b11c696bd3d66a2c5f10780d95b7df42645cd85228a6fb29940e858e7e55842ae2bd115d1ed7cc0e82d934e929c97648cb0a"
+        );
+        assert_eq!(key.to_compr_pk(), None);
+
+        let key =
+            PrivateKey::from_str("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617").unwrap();
+        assert_eq!(
+            key.to_compr_pk().unwrap().to_byte_array().to_hex(),
+            "02d0de0aaeaefad02b8bdc8a01a1b8b11c696bd3d66a2c5f10780d95b7df42645c"
+        );
+        assert_eq!(key.to_legacy_pk(), LegacyPk::from(key.to_compr_pk().unwrap()));
+    }
+
+    #[test]
+    fn invalid() {
+        let mut data = PrivateKey::from_str("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617")
+            .unwrap()
+            .encode();
+        data[33] = 2;
+        assert_eq!(PrivateKey::decode(&data), Err(WifDecodeError::InvalidCompressionFlag(2)));
+        data[0] = 0x6F;
+        assert_eq!(PrivateKey::decode(&data[..33]), Err(WifDecodeError::UnknownNetwork(0x6F)));
+        assert_eq!(PrivateKey::decode(&data[..32]), Err(WifDecodeError::WrongLength(32)));
+    }
+}
@@ -22,32 +22,85 @@
 
 #[macro_use]
 extern crate amplify;
+#[cfg(feature = "strict_encoding")]
+#[macro_use]
+extern crate strict_encoding;
 #[cfg(feature = "serde")]
 #[macro_use]
 extern crate serde_crate as serde;
 
+mod bip322;
+mod bip47;
+mod electrum;
 mod index;
+mod key;
+mod mnemonic;
+mod musig;
 mod path;
 mod xpub;
 mod derive;
+mod sighash;
+mod silent;
+mod slip132;
+mod slip39;
 pub mod taptree;
+mod taptweak;
+mod template;
+mod txstats;
 
 pub use bc::*;
+pub use bip322::{
+    legacy_message_hash, message_hash, sign_message, to_sign, to_spend, verify_message,
+    MessageSigFormat, MessageSignError, MessageVerifyError,
+};
+pub use bip47::{
+    PaymentCode, PaymentCodeDecodeError, PaymentCodeParseError, BIP47_PURPOSE, PAYMENT_CODE_PREFIX,
+};
 pub use derive::{
-    Derive, DeriveCompr, DeriveKey, DeriveScripts, DeriveSet, DeriveXOnly, DerivedAddr,
-    DerivedAddrParseError, DerivedScript, Keychain, Terminal, TerminalParseError,
+    AddrIter, Derive, DeriveCompr, DeriveKey, DeriveLegacy, DeriveScripts, DeriveSet, DeriveXOnly,
+    DerivedAddr, DerivedAddrParseError, DerivedScript, Keychain, Terminal, TerminalParseError,
 };
+pub use electrum::{ElectrumSeed, ElectrumSeedError, ElectrumSeedType};
 pub use index::{
     DerivationIndex, HardenedIndex, Idx, IdxBase, IndexError, IndexParseError, NormalIndex,
     HARDENED_INDEX_BOUNDARY,
 };
 pub use invoice::*;
-pub use path::{DerivationParseError, DerivationPath, DerivationSeg, SegParseError};
+pub use key::{PrivateKey, WifDecodeError, WifParseError, WIF_MAINNET_PREFIX, WIF_TESTNET_PREFIX};
+pub use mnemonic::{Language, Mnemonic, MnemonicError, Seed, BIP39_WORDLIST_LEN};
+pub use musig::{
+    musig_sort_keys, AggNonce, KeyAggContext, MuSigError, MuSigSession, PartialSig, PubNonce,
+    SecNonce,
+};
+pub use path::{
+    DerivationParseError, DerivationPath, DerivationScheme, DerivationSeg, SegParseError,
+};
+pub use sighash::{Sighash, SighashCache, SighashError};
+pub use silent::{
+    SilentAddress, SilentAddressError, SilentPaymentError, SilentPaymentOutput,
+    SilentPaymentScanner, SilentPaymentSender, SILENT_PAYMENT_HRP_MAINNET,
+    SILENT_PAYMENT_HRP_TESTNET,
+};
+pub use slip132::KeyApplication;
+pub use slip39::{
+    slip39_wordlist, Slip39Error, Slip39Share, SLIP39_MAX_SHARES, SLIP39_WORDLIST_LEN,
+};
 pub use taptree::{
-    ControlBlockFactory, FinalizedTree, InvalidTree, LeafInfo, TapDerivation, TapTree,
+    control_block_merkle_root, serialize_control_block, verify_control_block, ControlBlockFactory,
+    FinalizedTree, InvalidTree, LeafInfo, TapBranch, TapDerivation, TapNode, TapTree,
     TapTreeBuilder, UnfinalizedTree,
 };
+pub use taptweak::{tap_tweak_hash, TapTweak, TweakedPk};
+pub use template::{PathTemplate, RangedIndex, RangedPaths, TemplateSeg};
+pub use txstats::{
+    count_sigops, script_pushes, PrevoutsMismatch, TxStats, DEFAULT_BYTES_PER_SIGOP,
+    MAX_BLOCK_SIGOPS_COST, MAX_STANDARD_TX_SIGOPS_COST, MAX_STANDARD_TX_WEIGHT,
+};
 pub use xpub::{
-    KeyOrigin, OriginParseError, Xpub, XpubDecodeError, XpubDerivable, XpubFp, XpubId, XpubMeta,
-    XpubOrigin, XpubParseError, XpubSpec,
+    ChainCode, DepthOverflow, KeyOrigin, OriginParseError, Xpriv, XprivAccount, XprivBytes,
+    XprivDecodeError, XprivParseError, Xpub, XpubCore, XpubDecodeError, XpubDerivable, XpubFp,
+    XpubId, XpubMeta, XpubOrigin, XpubParseError, XpubSpec,
 };
+
+#[cfg(feature = "strict_encoding")]
+pub const LIB_NAME_BP_DERIVE: &str = "BPDerive";
@@ -0,0 +1,378 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BIP-39 mnemonic codes for generation of deterministic wallet seeds.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use bitcoin_hashes::{sha256, sha512, Hash, HashEngine, Hmac, HmacEngine};
+
+use crate::xpub::erase;
+
+/// Number of words in a BIP-39 wordlist.
+pub const BIP39_WORDLIST_LEN: usize = 2048;
+
+/// Number of PBKDF2 rounds used in the seed derivation.
+const PBKDF2_ROUNDS: usize = 2048;
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MnemonicError {
+    /// invalid entropy length of {0} bits; mnemonic entropy must be from 128 to 256 bits and
+    /// a multiple of 32 bits.
+    EntropyLength(usize),
+
+    /// invalid number of mnemonic words {0}; it must be 12, 15, 18, 21 or 24.
+    WordCount(usize),
+
+    /// word '{0}' is absent in the mnemonic wordlist.
+    UnknownWord(String),
+
+    /// invalid mnemonic checksum.
+    Checksum,
+}
+
+/// Language of a BIP-39 wordlist.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Display)]
+#[display(lowercase)]
+#[non_exhaustive]
+pub enum Language {
+    #[default]
+    English,
+}
+
+impl Language {
+    /// Wordlist for the language, with words ordered by their index.
+    pub fn wordlist(self) -> &'static [&'static str] {
+        static ENGLISH: OnceLock<Vec<&'static str>> = OnceLock::new();
+        match self {
+            Language::English => ENGLISH.get_or_init(|| {
+                let list = include_str!("wordlists/english.txt").lines().collect::<Vec<_>>();
+                debug_assert_eq!(list.len(), BIP39_WORDLIST_LEN);
+                list
+            }),
+        }
+    }
+
+    /// Finds index of a word in the language wordlist.
+    pub fn find_word(self, word: &str) -> Option<u16> {
+        self.wordlist().iter().position(|w| *w == word).map(|pos| pos as u16)
+    }
+}
+
+/// BIP-39 mnemonic code.
+///
+/// A new mnemonic can be generated with [`Mnemonic::generate`] when the crate is compiled with
+/// `rand` feature; otherwise the entropy must be provided by the caller from a cryptographically
+/// secure random source. The entropy is erased from the memory when the mnemonic is dropped.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Mnemonic {
+    language: Language,
+    entropy: Vec<u8>,
+}
+
+impl Mnemonic {
+    /// Constructs mnemonic out of 128 to 256 bits of entropy.
+    pub fn from_entropy(language: Language, entropy: &[u8]) -> Result<Self, MnemonicError> {
+        let bits = entropy.len() * 8;
+        if !(128..=256).contains(&bits) || bits % 32 != 0 {
+            return Err(MnemonicError::EntropyLength(bits));
+        }
+        Ok(Mnemonic {
+            language,
+            entropy: entropy.to_vec(),
+        })
+    }
+
+    /// Generates a new mnemonic with the given number of words using entropy from the
+    /// thread-local cryptographically secure random number generator.
+    #[cfg(feature = "rand")]
+    pub fn generate(language: Language, word_count: usize) -> Result<Self, MnemonicError> {
+        use secp256k1::rand::{thread_rng, RngCore};
+
+        if !(12..=24).contains(&word_count) || word_count % 3 != 0 {
+            return Err(MnemonicError::WordCount(word_count));
+        }
+        let mut entropy = [0u8; 32];
+        let len = word_count * 4 / 3;
+        thread_rng().fill_bytes(&mut entropy[..len]);
+        let mnemonic = Mnemonic::from_entropy(language, &entropy[..len]);
+        erase(&mut entropy);
+        mnemonic
+    }
+
+    /// Parses mnemonic from a whitespace-separated words of the given language, verifying
+    /// its checksum.
+    pub fn parse_in(language: Language, s: &str) -> Result<Self, MnemonicError> {
+        let indexes = s
+            .split_whitespace()
+            .map(|word| {
+                language.find_word(word).ok_or_else(|| MnemonicError::UnknownWord(word.to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let count = indexes.len();
+        if !(12..=24).contains(&count) || count % 3 != 0 {
+            return Err(MnemonicError::WordCount(count));
+        }
+
+        let mut bits = Vec::with_capacity(count * 11);
+        for index in indexes {
+            bits.extend((0..11).rev().map(|bit| (index >> bit) & 1 == 1));
+        }
+        let checksum_len = count / 3;
+        let (entropy_bits, checksum) = bits.split_at(bits.len() - checksum_len);
+        let entropy = entropy_bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8))
+            .collect::<Vec<_>>();
+
+        let mnemonic = Mnemonic { language, entropy };
+        let expected = mnemonic.checksum();
+        if checksum.iter().enumerate().any(|(no, bit)| *bit != ((expected >> (7 - no)) & 1 == 1)) {
+            return Err(MnemonicError::Checksum);
+        }
+        Ok(mnemonic)
+    }
+
+    pub fn language(&self) -> Language { self.language }
+
+    pub fn entropy(&self) -> &[u8] { &self.entropy }
+
+    pub fn word_count(&self) -> usize { self.entropy.len() * 3 / 4 }
+
+    /// First byte of the entropy hash, the leading bits of which are used as the checksum.
+    fn checksum(&self) -> u8 { sha256::Hash::hash(&self.entropy).to_byte_array()[0] }
+
+    /// Iterates over the mnemonic words.
+    pub fn words(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let wordlist = self.language.wordlist();
+        let checksum = self.checksum();
+        let bit = move |pos: usize| -> u16 {
+            match self.entropy.get(pos / 8) {
+                Some(byte) => ((byte >> (7 - pos % 8)) & 1) as u16,
+                None => ((checksum >> (7 - (pos % 8))) & 1) as u16,
+            }
+        };
+        (0..self.word_count()).map(move |no| {
+            let index = (0..11).fold(0u16, |acc, pos| (acc << 1) | bit(no * 11 + pos));
+            wordlist[index as usize]
+        })
+    }
+
+    /// Derives BIP-39 seed from the mnemonic and a passphrase (which may be empty).
+    ///
+    /// The passphrase must be already normalized into the Unicode NFKD form; ASCII-only
+    /// passphrases always satisfy this requirement.
+    pub fn to_seed(&self, passphrase: &str) -> Seed {
+        // Buffers are allocated with the exact capacity, such that no copies of the secrets are
+        // left behind by reallocations
+        let mut phrase = Vec::with_capacity(self.words().map(|word| word.len() + 1).sum());
+        for (no, word) in self.words().enumerate() {
+            if no > 0 {
+                phrase.push(b' ');
+            }
+            phrase.extend_from_slice(word.as_bytes());
+        }
+        let mut salt = salt("mnemonic", passphrase.as_bytes());
+        let mut seed = Seed([0u8; 64]);
+        pbkdf2_sha512(&phrase, &salt, &mut seed.0);
+        phrase.iter_mut().chain(&mut salt).for_each(erase);
+        seed
+    }
+}
+
+/// Constructs PBKDF2 salt out of a prefix and a passphrase, which has to be erased after use.
+pub(crate) fn salt(prefix: &str, passphrase: &[u8]) -> Vec<u8> {
+    let mut salt = Vec::with_capacity(prefix.len() + passphrase.len());
+    salt.extend_from_slice(prefix.as_bytes());
+    salt.extend_from_slice(passphrase);
+    salt
+}
+
+/// PBKDF2-HMAC-SHA512 with a single output block, as used for the mnemonic seed derivation.
+/// The intermediate blocks are erased once the `output` is computed.
+pub(crate) fn pbkdf2_sha512(password: &[u8], salt: &[u8], output: &mut [u8; 64]) {
+    let mut engine = HmacEngine::<sha512::Hash>::new(password);
+    engine.input(salt);
+    engine.input(&1u32.to_be_bytes());
+    let mut u = Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+    output.copy_from_slice(&u);
+    for _ in 1..PBKDF2_ROUNDS {
+        let mut engine = HmacEngine::<sha512::Hash>::new(password);
+        engine.input(&u);
+        u = Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+        output.iter_mut().zip(u).for_each(|(s, u)| *s ^= u);
+    }
+    u.iter_mut().for_each(erase);
+}
+
+impl Drop for Mnemonic {
+    fn drop(&mut self) { self.entropy.iter_mut().for_each(erase) }
+}
+
+impl Debug for Mnemonic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mnemonic")
+            .field("language", &self.language)
+            .field("words", &self.word_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Mnemonic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (no, word) in self.words().enumerate() {
+            if no > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(word)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses mnemonic from English words.
+impl FromStr for Mnemonic {
+    type Err = MnemonicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::parse_in(Language::English, s) }
+}
+
+/// 512-bit seed derived from a BIP-39 mnemonic, used to construct master extended private
+/// key with [`Xpriv::from_seed`]. The seed is erased from the memory when dropped.
+///
+/// [`Xpriv::from_seed`]: crate::Xpriv::from_seed
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Seed(pub(crate) [u8; 64]);
+
+impl Seed {
+    pub fn as_bytes(&self) -> &[u8; 64] { &self.0 }
+}
+
+impl AsRef<[u8]> for Seed {
+    fn as_ref(&self) -> &[u8] { &self.0 }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) { self.0.iter_mut().for_each(erase) }
+}
+
+impl Debug for Seed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str("Seed(..)") }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::{FromHex, ToHex};
+
+    use super::*;
+    use crate::Xpriv;
+
+    // Test vectors from BIP-39 reference implementation, all using "TREZOR" passphrase
+    const VECTORS: [(&str, &str, &str); 4] = [
+        (
+            "00000000000000000000000000000000",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        ),
+        (
+            "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+        ),
+        (
+            "9e885d952ad362caeb4efe34a8e91bd2",
+            "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+            "",
+        ),
+        (
+            "68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+            "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+            "",
+        ),
+    ];
+
+    #[test]
+    fn wordlist() {
+        let list = Language::English.wordlist();
+        assert_eq!(list.len(), BIP39_WORDLIST_LEN);
+        assert_eq!(list[0], "abandon");
+        assert_eq!(list[2047], "zoo");
+        assert_eq!(Language::English.find_word("zoo"), Some(2047));
+        assert_eq!(Language::English.find_word("bitcoin"), None);
+    }
+
+    #[test]
+    fn vectors() {
+        for (entropy, words, seed) in VECTORS {
+            let entropy = Vec::<u8>::from_hex(entropy).unwrap();
+            let mnemonic = Mnemonic::from_entropy(Language::English, &entropy).unwrap();
+            assert_eq!(mnemonic.to_string(), words);
+            assert_eq!(Mnemonic::from_str(words).unwrap(), mnemonic);
+            if !seed.is_empty() {
+                assert_eq!(mnemonic.to_seed("TREZOR").as_ref().to_hex(), seed);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn generate() {
+        for count in [12, 15, 18, 21, 24] {
+            let mnemonic = Mnemonic::generate(Language::English, count).unwrap();
+            assert_eq!(mnemonic.word_count(), count);
+            assert_eq!(mnemonic.words().count(), count);
+            assert_eq!(Mnemonic::from_str(&mnemonic.to_string()).unwrap(), mnemonic);
+            assert_ne!(Mnemonic::generate(Language::English, count).unwrap(), mnemonic);
+        }
+        assert_eq!(Mnemonic::generate(Language::English, 13), Err(MnemonicError::WordCount(13)));
+    }
+
+    #[test]
+    fn master_key() {
+        let mnemonic = Mnemonic::from_str(VECTORS[0].1).unwrap();
+        let xpriv = Xpriv::from_seed(false, &mnemonic.to_seed("TREZOR"));
+        assert_eq!(
+            xpriv.to_string(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            Mnemonic::from_entropy(Language::English, &[0u8; 15]),
+            Err(MnemonicError::EntropyLength(120))
+        );
+        assert_eq!(Mnemonic::from_str("abandon abandon abandon"), Err(MnemonicError::WordCount(3)));
+        assert_eq!(
+            Mnemonic::from_str(&VECTORS[0].1.replace("about", "bitcoin")),
+            Err(MnemonicError::UnknownWord(s!("bitcoin")))
+        );
+        assert_eq!(
+            Mnemonic::from_str(&VECTORS[0].1.replace("about", "abandon")),
+            Err(MnemonicError::Checksum)
+        );
+    }
+}
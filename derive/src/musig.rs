@@ -0,0 +1,746 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! MuSig2 multi-signatures (BIP-327): key aggregation, nonce generation and
+//! aggregation, partial signing and signature aggregation.
+//!
+//! Scalars are represented as `Option<SecretKey>` and points as
+//! `Option<PublicKey>`, where `None` stands for zero and the point at infinity.
+
+use bc::secp256k1::schnorr::Signature;
+use bc::secp256k1::{Parity, PublicKey, Scalar, SecretKey, SECP256K1};
+use bc::{CompressedPk, InvalidPubkey, TapNodeHash, XOnlyPk};
+use commit_verify::{DigestExt, Sha256};
+
+use crate::tap_tweak_hash;
+
+const TAG_KEYAGG_LIST: &[u8] = b"KeyAgg list";
+const TAG_KEYAGG_COEFF: &[u8] = b"KeyAgg coefficient";
+const TAG_AUX: &[u8] = b"MuSig/aux";
+const TAG_NONCE: &[u8] = b"MuSig/nonce";
+const TAG_NONCE_COEFF: &[u8] = b"MuSig/noncecoef";
+const TAG_CHALLENGE: &[u8] = b"BIP0340/challenge";
+
+/// Errors of MuSig2 key aggregation and signing.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum MuSigError {
+    /// no keys are provided for the aggregation.
+    NoKeys,
+
+    /// no public nonces are provided for the aggregation.
+    NoNonces,
+
+    /// aggregated public key is the point at infinity.
+    InfiniteKey,
+
+    /// tweak value exceeds the curve order.
+    InvalidTweak,
+
+    /// invalid public nonce - {0}
+    #[from]
+    #[from(bc::secp256k1::Error)]
+    InvalidNonce(InvalidPubkey<33>),
+
+    /// partial signature value exceeds the curve order.
+    InvalidPartialSig,
+
+    /// the secret key doesn't match the public key of the nonce or is not part of the
+    /// aggregated keys.
+    KeyMismatch,
+}
+
+type Sc = Option<SecretKey>;
+type Pt = Option<PublicKey>;
+
+fn sc_one() -> SecretKey {
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    SecretKey::from_slice(&one).expect("one is a valid scalar")
+}
+
+fn sc_from_bytes(bytes: [u8; 32]) -> Option<Sc> {
+    if bytes == [0u8; 32] {
+        return Some(None);
+    }
+    SecretKey::from_slice(&bytes).ok().map(Some)
+}
+
+fn sc_from_hash(engine: Sha256) -> Sc {
+    sc_from_bytes(engine.finish()).expect("negligible probability")
+}
+
+fn sc_bytes(a: Sc) -> [u8; 32] { a.map(|k| k.secret_bytes()).unwrap_or_default() }
+
+fn sc_add(a: Sc, b: Sc) -> Sc {
+    match (a, b) {
+        (None, x) | (x, None) => x,
+        (Some(a), Some(b)) => a.add_tweak(&Scalar::from(b)).ok(),
+    }
+}
+
+fn sc_mul(a: Sc, b: Sc) -> Sc {
+    Some(a?.mul_tweak(&Scalar::from(b?)).expect("product of non-zero scalars is non-zero"))
+}
+
+fn sc_neg_if(a: Sc, negate: bool) -> Sc {
+    match negate {
+        true => a.map(SecretKey::negate),
+        false => a,
+    }
+}
+
+fn pt_base(k: Sc) -> Pt { k.map(|k| PublicKey::from_secret_key(SECP256K1, &k)) }
+
+fn pt_add(a: Pt, b: Pt) -> Pt {
+    match (a, b) {
+        (None, x) | (x, None) => x,
+        (Some(a), Some(b)) => a.combine(&b).ok(),
+    }
+}
+
+fn pt_mul(p: Pt, k: Sc) -> Pt {
+    Some(p?.mul_tweak(SECP256K1, &Scalar::from(k?)).expect("non-zero scalar"))
+}
+
+fn has_even_y(p: PublicKey) -> bool { p.x_only_public_key().1 == Parity::Even }
+
+fn xbytes(p: PublicKey) -> [u8; 32] { p.x_only_public_key().0.serialize() }
+
+fn cbytes_ext(p: Pt) -> [u8; 33] { p.map(|p| p.serialize()).unwrap_or([0u8; 33]) }
+
+/// Sorts public keys lexicographically, as required by the `KeySort` algorithm of BIP-327.
+pub fn musig_sort_keys(keys: &mut [CompressedPk]) { keys.sort_by_key(CompressedPk::to_byte_array); }
+
+/// Key aggregation context, holding the aggregated public key and the tweaks applied to it.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct KeyAggContext {
+    keys: Vec<CompressedPk>,
+    list_hash: [u8; 32],
+    second_key: Option<CompressedPk>,
+    agg_pk: PublicKey,
+    // `gacc` is always either 1 or -1, thus we store just whether it is negative.
+    gacc_neg: bool,
+    tacc: Sc,
+}
+
+impl KeyAggContext {
+    /// Aggregates keys in the order they are provided.
+    pub fn new(keys: impl IntoIterator<Item = CompressedPk>) -> Result<Self, MuSigError> {
+        let keys = keys.into_iter().collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Err(MuSigError::NoKeys);
+        }
+
+        let mut engine = Sha256::from_tag(TAG_KEYAGG_LIST);
+        for key in &keys {
+            engine.input_raw(&key.to_byte_array());
+        }
+        let list_hash = engine.finish();
+        let second_key = keys.iter().find(|key| **key != keys[0]).copied();
+
+        let mut ctx = KeyAggContext {
+            keys,
+            list_hash,
+            second_key,
+            agg_pk: PublicKey::from_secret_key(SECP256K1, &sc_one()),
+            gacc_neg: false,
+            tacc: None,
+        };
+        let agg_pk = ctx
+            .keys
+            .iter()
+            .fold(None, |acc, key| pt_add(acc, pt_mul(Some(**key), Some(ctx.coefficient(*key)))));
+        ctx.agg_pk = agg_pk.ok_or(MuSigError::InfiniteKey)?;
+        Ok(ctx)
+    }
+
+    /// Aggregates keys after sorting them, such that the aggregated key doesn't depend on the
+    /// order in which the keys are provided.
+    pub fn sorted(keys: impl IntoIterator<Item = CompressedPk>) -> Result<Self, MuSigError> {
+        let mut keys = keys.into_iter().collect::<Vec<_>>();
+        musig_sort_keys(&mut keys);
+        Self::new(keys)
+    }
+
+    /// Keys participating in the aggregation, in the order used by the aggregation.
+    pub fn keys(&self) -> &[CompressedPk] { &self.keys }
+
+    /// Aggregated public key with all tweaks applied.
+    pub fn agg_pk(&self) -> CompressedPk { CompressedPk::from(self.agg_pk) }
+
+    /// X-only aggregated public key with all tweaks applied, which is used in signature
+    /// verification.
+    pub fn agg_xonly_pk(&self) -> XOnlyPk { XOnlyPk::from(self.agg_pk.x_only_public_key().0) }
+
+    fn coefficient(&self, key: CompressedPk) -> SecretKey {
+        if Some(key) == self.second_key {
+            return sc_one();
+        }
+        let mut engine = Sha256::from_tag(TAG_KEYAGG_COEFF);
+        engine.input_raw(&self.list_hash);
+        engine.input_raw(&key.to_byte_array());
+        sc_from_hash(engine).expect("negligible probability")
+    }
+
+    fn apply_tweak(&mut self, tweak: [u8; 32], xonly: bool) -> Result<(), MuSigError> {
+        let negate = xonly && !has_even_y(self.agg_pk);
+        let tweak = sc_from_bytes(tweak).ok_or(MuSigError::InvalidTweak)?;
+        let agg_pk = sc_neg_if(Some(sc_one()), negate);
+        let agg_pk = pt_add(pt_mul(Some(self.agg_pk), agg_pk), pt_base(tweak));
+        self.agg_pk = agg_pk.ok_or(MuSigError::InfiniteKey)?;
+        self.gacc_neg ^= negate;
+        self.tacc = sc_add(tweak, sc_neg_if(self.tacc, negate));
+        Ok(())
+    }
+
+    /// Applies plain (non-x-only) tweak, as used by BIP-32 derivation from the aggregated key.
+    pub fn plain_tweak(&mut self, tweak: [u8; 32]) -> Result<(), MuSigError> {
+        self.apply_tweak(tweak, false)
+    }
+
+    /// Applies x-only tweak to the aggregated key.
+    pub fn xonly_tweak(&mut self, tweak: [u8; 32]) -> Result<(), MuSigError> {
+        self.apply_tweak(tweak, true)
+    }
+
+    /// Applies BIP-341 taproot tweak, making the aggregated key a taproot output key for the
+    /// given script tree merkle root.
+    pub fn taproot_tweak(&mut self, merkle_root: Option<TapNodeHash>) -> Result<(), MuSigError> {
+        self.xonly_tweak(tap_tweak_hash(self.agg_xonly_pk(), merkle_root))
+    }
+}
+
+/// Secret nonce of a signer. It is consumed by signing, preventing nonce reuse, and gets erased
+/// on drop.
+#[derive(Debug)]
+pub struct SecNonce {
+    k1: SecretKey,
+    k2: SecretKey,
+    pk: CompressedPk,
+}
+
+impl Drop for SecNonce {
+    fn drop(&mut self) {
+        self.k1.non_secure_erase();
+        self.k2.non_secure_erase();
+    }
+}
+
+impl SecNonce {
+    /// Generates nonce pair for a signer with public key `pk` (`NonceGen` algorithm).
+    ///
+    /// The `rand` value must be freshly generated uniformly random bytes and must never be
+    /// reused. All other arguments are optional and serve as a defence-in-depth measure against
+    /// bad randomness.
+    pub fn generate(
+        rand: [u8; 32],
+        sk: Option<&SecretKey>,
+        pk: CompressedPk,
+        agg_pk: Option<XOnlyPk>,
+        msg: Option<&[u8]>,
+        extra: &[u8],
+    ) -> (SecNonce, PubNonce) {
+        let mut rand = rand;
+        if let Some(sk) = sk {
+            let mut engine = Sha256::from_tag(TAG_AUX);
+            engine.input_raw(&rand);
+            let aux = engine.finish();
+            for (byte, mask) in rand.iter_mut().zip(sk.secret_bytes().iter().zip(aux)) {
+                *byte = mask.0 ^ mask.1;
+            }
+        }
+
+        let nonce = |index: u8| {
+            let mut engine = Sha256::from_tag(TAG_NONCE);
+            engine.input_raw(&rand);
+            engine.input_raw(&[33]);
+            engine.input_raw(&pk.to_byte_array());
+            match agg_pk {
+                Some(agg_pk) => {
+                    engine.input_raw(&[32]);
+                    engine.input_raw(&agg_pk.to_byte_array());
+                }
+                None => engine.input_raw(&[0]),
+            }
+            match msg {
+                Some(msg) => {
+                    engine.input_raw(&[1]);
+                    engine.input_raw(&(msg.len() as u64).to_be_bytes());
+                    engine.input_raw(msg);
+                }
+                None => engine.input_raw(&[0]),
+            }
+            engine.input_raw(&(extra.len() as u32).to_be_bytes());
+            engine.input_raw(extra);
+            engine.input_raw(&[index]);
+            sc_from_hash(engine).expect("negligible probability")
+        };
+
+        let secnonce = SecNonce {
+            k1: nonce(0),
+            k2: nonce(1),
+            pk,
+        };
+        let pubnonce = PubNonce {
+            r1: PublicKey::from_secret_key(SECP256K1, &secnonce.k1),
+            r2: PublicKey::from_secret_key(SECP256K1, &secnonce.k2),
+        };
+        (secnonce, pubnonce)
+    }
+
+    /// Public key of the signer for which the nonce was generated.
+    pub fn pk(&self) -> CompressedPk { self.pk }
+}
+
+/// Public nonce of a signer, which is shared with other signers.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PubNonce {
+    r1: PublicKey,
+    r2: PublicKey,
+}
+
+impl PubNonce {
+    pub fn from_byte_array(data: [u8; 66]) -> Result<Self, MuSigError> {
+        Ok(PubNonce {
+            r1: PublicKey::from_slice(&data[..33])?,
+            r2: PublicKey::from_slice(&data[33..])?,
+        })
+    }
+
+    pub fn to_byte_array(&self) -> [u8; 66] {
+        let mut data = [0u8; 66];
+        data[..33].copy_from_slice(&self.r1.serialize());
+        data[33..].copy_from_slice(&self.r2.serialize());
+        data
+    }
+}
+
+/// Aggregated public nonce of all signers.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct AggNonce {
+    r1: Pt,
+    r2: Pt,
+}
+
+impl AggNonce {
+    /// Aggregates public nonces of all signers (`NonceAgg` algorithm).
+    pub fn aggregate<'a>(
+        nonces: impl IntoIterator<Item = &'a PubNonce>,
+    ) -> Result<Self, MuSigError> {
+        let mut nonces = nonces.into_iter().peekable();
+        if nonces.peek().is_none() {
+            return Err(MuSigError::NoNonces);
+        }
+        Ok(nonces.fold(AggNonce { r1: None, r2: None }, |acc, nonce| AggNonce {
+            r1: pt_add(acc.r1, Some(nonce.r1)),
+            r2: pt_add(acc.r2, Some(nonce.r2)),
+        }))
+    }
+
+    pub fn from_byte_array(data: [u8; 66]) -> Result<Self, MuSigError> {
+        let point = |data: &[u8]| -> Result<Pt, MuSigError> {
+            if data == [0u8; 33] {
+                return Ok(None);
+            }
+            Ok(Some(PublicKey::from_slice(data)?))
+        };
+        Ok(AggNonce {
+            r1: point(&data[..33])?,
+            r2: point(&data[33..])?,
+        })
+    }
+
+    pub fn to_byte_array(&self) -> [u8; 66] {
+        let mut data = [0u8; 66];
+        data[..33].copy_from_slice(&cbytes_ext(self.r1));
+        data[33..].copy_from_slice(&cbytes_ext(self.r2));
+        data
+    }
+}
+
+/// Partial signature produced by a single signer.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PartialSig(Sc);
+
+impl PartialSig {
+    pub fn from_byte_array(data: [u8; 32]) -> Result<Self, MuSigError> {
+        sc_from_bytes(data).map(PartialSig).ok_or(MuSigError::InvalidPartialSig)
+    }
+
+    pub fn to_byte_array(&self) -> [u8; 32] { sc_bytes(self.0) }
+}
+
+/// Signing session for a specific message, aggregated key and aggregated nonce.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MuSigSession {
+    ctx: KeyAggContext,
+    b: Sc,
+    r: PublicKey,
+    e: Sc,
+}
+
+impl MuSigSession {
+    pub fn new(ctx: KeyAggContext, agg_nonce: &AggNonce, msg: &[u8]) -> Self {
+        let mut engine = Sha256::from_tag(TAG_NONCE_COEFF);
+        engine.input_raw(&agg_nonce.to_byte_array());
+        engine.input_raw(&xbytes(ctx.agg_pk));
+        engine.input_raw(msg);
+        let b = sc_from_hash(engine);
+
+        let r = pt_add(agg_nonce.r1, pt_mul(agg_nonce.r2, b))
+            .unwrap_or_else(|| PublicKey::from_secret_key(SECP256K1, &sc_one()));
+
+        let mut engine = Sha256::from_tag(TAG_CHALLENGE);
+        engine.input_raw(&xbytes(r));
+        engine.input_raw(&xbytes(ctx.agg_pk));
+        engine.input_raw(msg);
+        let e = sc_from_hash(engine);
+
+        MuSigSession { ctx, b, r, e }
+    }
+
+    /// Key aggregation context of the session.
+    pub fn key_agg_ctx(&self) -> &KeyAggContext { &self.ctx }
+
+    // Returns whether the secret key must be negated: this accounts both for the parity of the
+    // aggregated key and for the accumulated negations from the x-only tweaks.
+    fn key_negated(&self) -> bool { !has_even_y(self.ctx.agg_pk) ^ self.ctx.gacc_neg }
+
+    /// Creates partial signature, consuming the secret nonce.
+    pub fn sign(&self, secnonce: SecNonce, sk: &SecretKey) -> Result<PartialSig, MuSigError> {
+        let pk = CompressedPk::from(PublicKey::from_secret_key(SECP256K1, sk));
+        if pk != secnonce.pk || !self.ctx.keys.contains(&pk) {
+            return Err(MuSigError::KeyMismatch);
+        }
+        let r_neg = !has_even_y(self.r);
+        let k1 = sc_neg_if(Some(secnonce.k1), r_neg);
+        let k2 = sc_neg_if(Some(secnonce.k2), r_neg);
+        let a = Some(self.ctx.coefficient(pk));
+        let d = sc_neg_if(Some(*sk), self.key_negated());
+        let s = sc_add(sc_add(k1, sc_mul(self.b, k2)), sc_mul(self.e, sc_mul(a, d)));
+        Ok(PartialSig(s))
+    }
+
+    /// Verifies partial signature of the signer with public key `pk` and public nonce
+    /// `pubnonce`.
+    pub fn verify_partial(&self, psig: PartialSig, pubnonce: &PubNonce, pk: CompressedPk) -> bool {
+        if !self.ctx.keys.contains(&pk) {
+            return false;
+        }
+        let re = pt_add(Some(pubnonce.r1), pt_mul(Some(pubnonce.r2), self.b));
+        let re = match has_even_y(self.r) {
+            true => re,
+            false => re.map(|re| re.negate(SECP256K1)),
+        };
+        let a = Some(self.ctx.coefficient(pk));
+        let ea = sc_neg_if(sc_mul(self.e, a), self.key_negated());
+        pt_base(psig.0) == pt_add(re, pt_mul(Some(*pk), ea))
+    }
+
+    /// Aggregates partial signatures of all signers into a BIP-340 signature
+    /// (`PartialSigAgg` algorithm).
+    pub fn aggregate<'a>(&self, psigs: impl IntoIterator<Item = &'a PartialSig>) -> Signature {
+        let s = psigs.into_iter().fold(None, |acc, psig| sc_add(acc, psig.0));
+        let tweak = sc_neg_if(sc_mul(self.e, self.ctx.tacc), !has_even_y(self.ctx.agg_pk));
+        let s = sc_add(s, tweak);
+        let mut sig = [0u8; 64];
+        sig[..32].copy_from_slice(&xbytes(self.r));
+        sig[32..].copy_from_slice(&sc_bytes(s));
+        Signature::from_slice(&sig).expect("64-byte signature")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use amplify::hex::FromHex;
+    use bc::secp256k1::Message;
+
+    use super::*;
+
+    fn pk(s: &str) -> CompressedPk { CompressedPk::from_str(s).unwrap() }
+
+    #[test]
+    fn key_agg_vectors() {
+        let x = [
+            pk("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            pk("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            pk("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+        ];
+        let expected =
+            |s: &str| XOnlyPk::from_byte_array(<[u8; 32]>::from_hex(s).unwrap()).unwrap();
+
+        let ctx = KeyAggContext::new([x[0], x[1], x[2]]).unwrap();
+        assert_eq!(
+            ctx.agg_xonly_pk(),
+            expected("90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C")
+        );
+        let ctx = KeyAggContext::new([x[2], x[1], x[0]]).unwrap();
+        assert_eq!(
+            ctx.agg_xonly_pk(),
+            expected("6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B")
+        );
+        let ctx = KeyAggContext::new([x[0], x[0], x[0]]).unwrap();
+        assert_eq!(
+            ctx.agg_xonly_pk(),
+            expected("B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935")
+        );
+        let ctx = KeyAggContext::new([x[0], x[0], x[1], x[1]]).unwrap();
+        assert_eq!(
+            ctx.agg_xonly_pk(),
+            expected("69BC22BFA5D106306E48A20679DE1D7389386124D07571D0D872686028C26A3E")
+        );
+
+        assert_eq!(
+            KeyAggContext::sorted([x[2], x[0], x[1]]).unwrap(),
+            KeyAggContext::sorted([x[1], x[2], x[0]]).unwrap()
+        );
+        assert_eq!(KeyAggContext::new([]), Err(MuSigError::NoKeys));
+    }
+
+    fn sign(tweak: impl Fn(&mut KeyAggContext)) {
+        let sks =
+            [[0x11u8; 32], [0x22; 32], [0x33; 32]].map(|sk| SecretKey::from_slice(&sk).unwrap());
+        let pks = sks.map(|sk| CompressedPk::from(sk.public_key(SECP256K1)));
+        let mut ctx = KeyAggContext::sorted(pks).unwrap();
+        tweak(&mut ctx);
+        let msg = [0x5Au8; 32];
+
+        let (secnonces, pubnonces): (Vec<_>, Vec<_>) = sks
+            .iter()
+            .zip(pks)
+            .enumerate()
+            .map(|(no, (sk, pk))| {
+                SecNonce::generate([no as u8; 32], Some(sk), pk, None, Some(&msg), &[])
+            })
+            .unzip();
+        let agg_nonce = AggNonce::aggregate(&pubnonces).unwrap();
+        assert_eq!(AggNonce::from_byte_array(agg_nonce.to_byte_array()).unwrap(), agg_nonce);
+
+        let session = MuSigSession::new(ctx.clone(), &agg_nonce, &msg);
+        let psigs = secnonces
+            .into_iter()
+            .zip(&sks)
+            .map(|(secnonce, sk)| session.sign(secnonce, sk).unwrap())
+            .collect::<Vec<_>>();
+        for ((psig, pubnonce), pk) in psigs.iter().zip(&pubnonces).zip(pks) {
+            assert!(session.verify_partial(*psig, pubnonce, pk));
+            assert!(!session.verify_partial(*psig, &pubnonces[0], pks[1]));
+        }
+
+        let sig = session.aggregate(&psigs);
+        SECP256K1.verify_schnorr(&sig, &Message::from_digest(msg), &ctx.agg_xonly_pk()).unwrap();
+    }
+
+    #[test]
+    fn sign_untweaked() { sign(|_| {}) }
+
+    #[test]
+    fn sign_tweaked() {
+        sign(|ctx| {
+            ctx.plain_tweak([7u8; 32]).unwrap();
+            ctx.xonly_tweak([9u8; 32]).unwrap();
+            ctx.taproot_tweak(None).unwrap();
+        })
+    }
+
+    // Data from BIP-327 `sign_verify_vectors.json` and `tweak_vectors.json`
+    const BIP327_SK: &str = "7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671";
+    const BIP327_SECNONCE: [&str; 2] = [
+        "508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61",
+        "FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F7",
+    ];
+    const BIP327_PUBKEYS: [&str; 3] = [
+        "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+        "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+        "02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661",
+    ];
+    const BIP327_TWEAK_PUBKEYS: [&str; 3] = [
+        BIP327_PUBKEYS[0],
+        BIP327_PUBKEYS[1],
+        "02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+    ];
+    const BIP327_PNONCES: [&str; 4] = [
+        "0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+        "0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+        "032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046",
+        "0237C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0387BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+    ];
+    const BIP327_MSG: &str = "F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF";
+    const BIP327_TWEAKS: [&str; 5] = [
+        "E8F791FF9225A2AF0102AFFF4A9A723D9612A682A25EBE79802B263CDFCD83BB",
+        "AE2EA797CC0FE72AC5B97B97F3C6957D7E4199A167A58EB08BCAFFDA70AC0455",
+        "F52ECBC565B3D8BEA2DFD5B75A4F457E54369809322E4120831626F290FA87E0",
+        "1969AD73CC177FA0B4FCED6DF1F7BF9907E665FDE9BA196A74FED0A3CF5AEF9D",
+        "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+    ];
+
+    fn bip327_sk() -> SecretKey { SecretKey::from_str(BIP327_SK).unwrap() }
+
+    fn bip327_secnonce() -> SecNonce {
+        SecNonce {
+            k1: SecretKey::from_str(BIP327_SECNONCE[0]).unwrap(),
+            k2: SecretKey::from_str(BIP327_SECNONCE[1]).unwrap(),
+            pk: pk(BIP327_PUBKEYS[0]),
+        }
+    }
+
+    fn bip327_pnonce(index: usize) -> PubNonce {
+        let data = Vec::<u8>::from_hex(BIP327_PNONCES[index]).unwrap();
+        PubNonce::from_byte_array(data.try_into().unwrap()).unwrap()
+    }
+
+    fn bip327_psig(s: &str) -> PartialSig {
+        PartialSig::from_byte_array(<[u8; 32]>::from_hex(s).unwrap()).unwrap()
+    }
+
+    fn bip327_session(
+        pubkeys: [&str; 3],
+        key_indices: &[usize],
+        nonce_indices: &[usize],
+        tweaks: &[(usize, bool)],
+        msg: &[u8],
+    ) -> MuSigSession {
+        let mut ctx = KeyAggContext::new(key_indices.iter().map(|i| pk(pubkeys[*i]))).unwrap();
+        for (index, xonly) in tweaks {
+            let tweak = <[u8; 32]>::from_hex(BIP327_TWEAKS[*index]).unwrap();
+            match xonly {
+                true => ctx.xonly_tweak(tweak).unwrap(),
+                false => ctx.plain_tweak(tweak).unwrap(),
+            }
+        }
+        let pnonces = nonce_indices.iter().map(|i| bip327_pnonce(*i)).collect::<Vec<_>>();
+        let agg_nonce = AggNonce::aggregate(&pnonces).unwrap();
+        MuSigSession::new(ctx, &agg_nonce, msg)
+    }
+
+    #[test]
+    fn bip327_sign_verify() {
+        let msg = Vec::<u8>::from_hex(BIP327_MSG).unwrap();
+        #[allow(clippy::type_complexity)]
+        let cases: [(&[usize], &[usize], usize, &str); 4] = [
+            (
+                &[0, 1, 2],
+                &[0, 1, 2],
+                0,
+                "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB",
+            ),
+            (
+                &[1, 0, 2],
+                &[1, 0, 2],
+                1,
+                "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52",
+            ),
+            (
+                &[1, 2, 0],
+                &[1, 2, 0],
+                2,
+                "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900",
+            ),
+            // Both halves of the aggregated nonce are the point at infinity
+            (
+                &[0, 1],
+                &[0, 3],
+                0,
+                "AE386064B26105404798F75DE2EB9AF5EDA5387B064B83D049CB7C5E08879531",
+            ),
+        ];
+        for (key_indices, nonce_indices, signer, expected) in cases {
+            let session = bip327_session(BIP327_PUBKEYS, key_indices, nonce_indices, &[], &msg);
+            let psig = session.sign(bip327_secnonce(), &bip327_sk()).unwrap();
+            assert_eq!(psig, bip327_psig(expected));
+            let pnonce = bip327_pnonce(nonce_indices[signer]);
+            assert!(session.verify_partial(psig, &pnonce, pk(BIP327_PUBKEYS[0])));
+        }
+    }
+
+    #[test]
+    fn bip327_verify_fail() {
+        let msg = Vec::<u8>::from_hex(BIP327_MSG).unwrap();
+        let session = bip327_session(BIP327_PUBKEYS, &[0, 1, 2], &[0, 1, 2], &[], &msg);
+        let signer = pk(BIP327_PUBKEYS[0]);
+        // Negation of the valid signature
+        let psig = bip327_psig("FED54434AD4CFE953FC527DC6A5E5BE8F6234907B7C187559557CE87A0541C46");
+        assert!(!session.verify_partial(psig, &bip327_pnonce(0), signer));
+        // Wrong signer
+        let psig = bip327_psig("012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB");
+        assert!(session.verify_partial(psig, &bip327_pnonce(0), signer));
+        assert!(!session.verify_partial(psig, &bip327_pnonce(1), pk(BIP327_PUBKEYS[1])));
+        // Signature exceeds group size
+        assert_eq!(
+            PartialSig::from_byte_array(
+                <[u8; 32]>::from_hex(
+                    "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141"
+                )
+                .unwrap()
+            ),
+            Err(MuSigError::InvalidPartialSig)
+        );
+    }
+
+    #[test]
+    fn bip327_tweak() {
+        let msg = Vec::<u8>::from_hex(BIP327_MSG).unwrap();
+        #[allow(clippy::type_complexity)]
+        let cases: [(&[(usize, bool)], &str); 5] = [
+            (&[(0, true)], "E28A5C66E61E178C2BA19DB77B6CF9F7E2F0F56C17918CD13135E60CC848FE91"),
+            (&[(0, false)], "38B0767798252F21BF5702C48028B095428320F73A4B14DB1E25DE58543D2D2D"),
+            (
+                &[(0, false), (1, true)],
+                "408A0A21C4A0F5DACAF9646AD6EB6FECD7F7A11F03ED1F48DFFF2185BC2C2408",
+            ),
+            (
+                &[(0, false), (1, false), (2, true), (3, true)],
+                "45ABD206E61E3DF2EC9E264A6FEC8292141A633C28586388235541F9ADE75435",
+            ),
+            (
+                &[(0, true), (1, false), (2, true), (3, false)],
+                "B255FDCAC27B40C7CE7848E2D3B7BF5EA0ED756DA81565AC804CCCA3E1D5D239",
+            ),
+        ];
+        for (tweaks, expected) in cases {
+            let session =
+                bip327_session(BIP327_TWEAK_PUBKEYS, &[1, 2, 0], &[1, 2, 0], tweaks, &msg);
+            let psig = session.sign(bip327_secnonce(), &bip327_sk()).unwrap();
+            assert_eq!(psig, bip327_psig(expected));
+            assert!(session.verify_partial(psig, &bip327_pnonce(0), pk(BIP327_PUBKEYS[0])));
+        }
+
+        let mut ctx = KeyAggContext::new(BIP327_TWEAK_PUBKEYS.map(pk)).unwrap();
+        let tweak = <[u8; 32]>::from_hex(BIP327_TWEAKS[4]).unwrap();
+        assert_eq!(ctx.plain_tweak(tweak), Err(MuSigError::InvalidTweak));
+    }
+
+    #[test]
+    fn key_mismatch() {
+        let sk = SecretKey::from_slice(&[0x11u8; 32]).unwrap();
+        let other = SecretKey::from_slice(&[0x22u8; 32]).unwrap();
+        let pk = CompressedPk::from(sk.public_key(SECP256K1));
+        let ctx = KeyAggContext::new([pk]).unwrap();
+        let (secnonce, pubnonce) = SecNonce::generate([0; 32], None, pk, None, None, &[]);
+        let agg_nonce = AggNonce::aggregate([&pubnonce]).unwrap();
+        let session = MuSigSession::new(ctx, &agg_nonce, b"msg");
+        assert_eq!(session.sign(secnonce, &other), Err(MuSigError::KeyMismatch));
+    }
+}
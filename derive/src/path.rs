@@ -29,7 +29,9 @@ use std::collections::BTreeSet;
 use amplify::confinement;
 use amplify::confinement::Confined;
 
-use crate::{DerivationIndex, Idx, IdxBase, IndexParseError, NormalIndex, Terminal};
+use crate::{
+    DerivationIndex, HardenedIndex, Idx, IdxBase, IndexParseError, Network, NormalIndex, Terminal,
+};
 
 #[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
 #[display(doc_comments)]
@@ -140,6 +142,9 @@ pub enum SegParseError {
     /// derivation segment contains too many variants.
     #[from]
     Confinement(confinement::Error),
+
+    /// derivation segment contains repeated variants.
+    RepeatedVariant,
 }
 
 impl<I: IdxBase> FromStr for DerivationSeg<I>
@@ -152,7 +157,12 @@ where
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let t = s.trim_start_matches('<').trim_end_matches('>');
         if t.len() + 2 == s.len() {
-            let set = t.split(';').map(I::from_str).collect::<Result<BTreeSet<_>, _>>()?;
+            let variants = t.split(';').map(I::from_str).collect::<Result<Vec<_>, _>>()?;
+            let count = variants.len();
+            let set = variants.into_iter().collect::<BTreeSet<_>>();
+            if set.len() != count {
+                return Err(SegParseError::RepeatedVariant);
+            }
             Ok(Self(Confined::try_from_iter(set)?))
         } else {
             Ok(Self(I::from_str(s).map(Confined::with)?))
@@ -160,6 +170,31 @@ where
     }
 }
 
+#[cfg(feature = "serde")]
+mod _serde {
+    use serde_crate::de::Error;
+    use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    impl<I: IdxBase + Display> Serialize for DerivationSeg<I> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+
+    impl<'de, I: IdxBase + FromStr> Deserialize<'de> for DerivationSeg<I>
+    where SegParseError: From<I::Err>
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de> {
+            let s = String::deserialize(deserializer)?;
+            Self::from_str(&s).map_err(D::Error::custom)
+        }
+    }
+}
+
 /// Derivation path that consisting only of single type of segments.
 ///
 /// Useful in specifying concrete derivation from a provided extended public key
@@ -190,13 +225,26 @@ impl<I: Display> Display for DerivationPath<I> {
     }
 }
 
+impl<I: Display> DerivationPath<I> {
+    /// Returns canonical BIP-32 representation of the path, which starts with `m` master key
+    /// prefix and uses `h` notation for the hardened indexes, like `m/86h/0h/0h`.
+    pub fn to_canonical_string(&self) -> String { format!("m{self}") }
+}
+
 impl<I: FromStr> FromStr for DerivationPath<I>
 where IndexParseError: From<<I as FromStr>::Err>
 {
     type Err = DerivationParseError;
 
+    /// Parses derivation path, which may start with `m/` master key prefix (or just `/`) and
+    /// have hardened indexes in both `h` and `'` notation. A single `m` denotes empty path.
     fn from_str(mut s: &str) -> Result<Self, Self::Err> {
-        if s.starts_with('/') {
+        if s == "m" || s == "M" {
+            return Ok(Self(vec![]));
+        }
+        if let Some(rest) = s.strip_prefix("m/").or_else(|| s.strip_prefix("M/")) {
+            s = rest;
+        } else if s.starts_with('/') {
             s = &s[1..];
         }
         let inner = s
@@ -233,6 +281,41 @@ impl<I: Idx> DerivationPath<I> {
     /// Constructs empty derivation path.
     pub fn new() -> Self { Self(vec![]) }
 
+    /// Constructs new path by appending components of `other` path to this one.
+    pub fn join<J: Into<I>>(&self, other: impl IntoIterator<Item = J>) -> Self {
+        let mut path = self.clone();
+        path.extend(other.into_iter().map(J::into));
+        path
+    }
+
+    /// Constructs new path by appending a single child index.
+    pub fn child(&self, index: impl Into<I>) -> Self {
+        let mut path = self.clone();
+        path.push(index.into());
+        path
+    }
+
+    /// Constructs new path by appending keychain and index of the terminal.
+    pub fn with_terminal(&self, terminal: Terminal) -> Self
+    where I: From<NormalIndex> {
+        self.join([NormalIndex::from(terminal.keychain), terminal.index])
+    }
+
+    /// Returns the path without its last component, or `None` if the path is empty.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, parent) = self.split_last()?;
+        Some(Self(parent.to_vec()))
+    }
+
+    /// Checks whether the path starts with all components of the `prefix`.
+    pub fn starts_with(&self, prefix: &[I]) -> bool { self.0.starts_with(prefix) }
+
+    /// Returns the rest of the path after the `prefix`, or `None` if the path doesn't start with
+    /// it.
+    pub fn strip_prefix(&self, prefix: &[I]) -> Option<Self> {
+        self.0.strip_prefix(prefix).map(|rest| Self(rest.to_vec()))
+    }
+
     pub fn terminal(&self) -> Option<Terminal> {
         let mut iter = self.iter().rev();
         let index = iter.next()?;
@@ -249,6 +332,96 @@ impl<I: Idx> DerivationPath<I> {
     }
 }
 
+/// Standard BIP-43 derivation schemes of single-signature wallets, which define the origin of
+/// account-level extended keys as `m/purpose'/coin_type'/account'`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[display(lowercase)]
+pub enum DerivationScheme {
+    /// BIP-44 legacy P2PKH accounts.
+    Bip44,
+    /// BIP-49 P2WPKH-in-P2SH accounts.
+    Bip49,
+    /// BIP-84 P2WPKH accounts.
+    Bip84,
+    /// BIP-86 taproot single-key accounts.
+    Bip86,
+}
+
+impl DerivationScheme {
+    /// BIP-43 purpose of the scheme, which is the first component of the derivation path.
+    pub const fn purpose(self) -> HardenedIndex {
+        match self {
+            DerivationScheme::Bip44 => HardenedIndex::hardened(44),
+            DerivationScheme::Bip49 => HardenedIndex::hardened(49),
+            DerivationScheme::Bip84 => HardenedIndex::hardened(84),
+            DerivationScheme::Bip86 => HardenedIndex::hardened(86),
+        }
+    }
+
+    /// SLIP-44 coin type used in the derivation path: `0'` for mainnet and `1'` for all test
+    /// networks.
+    pub const fn coin_type(network: Network) -> HardenedIndex {
+        match network {
+            Network::Mainnet => HardenedIndex::hardened(0),
+            Network::Testnet3 | Network::Testnet4 | Network::Signet | Network::Regtest => {
+                HardenedIndex::hardened(1)
+            }
+        }
+    }
+
+    /// Constructs full hardened derivation path of the account-level extended key.
+    pub fn account_path(
+        self,
+        network: Network,
+        account: impl Into<HardenedIndex>,
+    ) -> DerivationPath<HardenedIndex> {
+        DerivationPath(vec![self.purpose(), Self::coin_type(network), account.into()])
+    }
+
+    /// Derivation path of a BIP-44 account.
+    pub fn bip44(network: Network, account: u16) -> DerivationPath<HardenedIndex> {
+        DerivationScheme::Bip44.account_path(network, account)
+    }
+
+    /// Derivation path of a BIP-49 account.
+    pub fn bip49(network: Network, account: u16) -> DerivationPath<HardenedIndex> {
+        DerivationScheme::Bip49.account_path(network, account)
+    }
+
+    /// Derivation path of a BIP-84 account.
+    pub fn bip84(network: Network, account: u16) -> DerivationPath<HardenedIndex> {
+        DerivationScheme::Bip84.account_path(network, account)
+    }
+
+    /// Derivation path of a BIP-86 account.
+    pub fn bip86(network: Network, account: u16) -> DerivationPath<HardenedIndex> {
+        DerivationScheme::Bip86.account_path(network, account)
+    }
+
+    /// Detects the scheme of a derivation path, which must start with the hardened purpose,
+    /// coin type (`0'` or `1'`) and account components. Further (keychain and index)
+    /// components are allowed.
+    pub fn detect<I: Idx>(path: &DerivationPath<I>) -> Option<Self> {
+        let [purpose, coin_type, account, ..] = path.as_slice() else {
+            return None;
+        };
+        if !purpose.is_hardened() || !coin_type.is_hardened() || !account.is_hardened() {
+            return None;
+        }
+        if coin_type.child_number() > 1 {
+            return None;
+        }
+        [Self::Bip44, Self::Bip49, Self::Bip84, Self::Bip86]
+            .into_iter()
+            .find(|scheme| scheme.purpose().child_number() == purpose.child_number())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -262,4 +435,85 @@ mod test {
         assert_eq!(path1, path2);
         assert_eq!(path1, path3);
     }
+
+    #[test]
+    fn multipath_seg() {
+        let seg = DerivationSeg::<NormalIndex>::from_str("<0;1>").unwrap();
+        assert_eq!(seg, DerivationSeg::standard());
+        assert_eq!(seg.to_string(), "<0;1>");
+        assert_eq!(DerivationSeg::<NormalIndex>::from_str("<1;0>").unwrap(), seg);
+        assert_eq!(DerivationSeg::<NormalIndex>::from_str("5").unwrap().count(), 1);
+        assert_eq!(
+            DerivationSeg::<NormalIndex>::from_str("<0;1;0>"),
+            Err(SegParseError::RepeatedVariant)
+        );
+        assert!(DerivationSeg::<NormalIndex>::from_str("<0;1;2;3;4;5;6;7;8>").is_err());
+    }
+
+    #[test]
+    fn path_notation() {
+        let canonical = DerivationPath::<DerivationIndex>::from_str("86h/0h/0h/1/5").unwrap();
+        for s in ["86'/0'/0'/1/5", "m/86h/0H/0'/1/5", "M/86h/0h/0h/1/5", "/86'/0'/0'/1/5"] {
+            assert_eq!(DerivationPath::<DerivationIndex>::from_str(s), Ok(canonical.clone()));
+        }
+        assert_eq!(canonical.to_canonical_string(), "m/86h/0h/0h/1/5");
+        assert_eq!(format!("{canonical:#}"), "/86'/0'/0'/1/5");
+
+        let hardened = DerivationPath::<HardenedIndex>::from_str("m/84'/1'/0'").unwrap();
+        assert_eq!(hardened.to_canonical_string(), "m/84h/1h/0h");
+        assert_eq!(DerivationPath::<HardenedIndex>::from_str("m"), Ok(DerivationPath::new()));
+        assert!(DerivationPath::<HardenedIndex>::from_str("m/84'/1").is_err());
+        assert!(DerivationPath::<DerivationIndex>::from_str("m/84x").is_err());
+    }
+
+    #[test]
+    fn path_algebra() {
+        let origin = DerivationScheme::bip86(Network::Mainnet, 0);
+        let full = DerivationPath::<DerivationIndex>::from_str("86h/0h/0h/1/5").unwrap();
+        let origin = origin.into_iter().map(DerivationIndex::from).collect::<DerivationPath>();
+
+        let terminal = Terminal::change(NormalIndex::from(5u8));
+        assert_eq!(origin.with_terminal(terminal), full);
+        assert_eq!(full.terminal(), Some(terminal));
+        assert_eq!(origin.join([NormalIndex::from(1u8), NormalIndex::from(5u8)]), full);
+        assert_eq!(origin.child(NormalIndex::from(1u8)).child(NormalIndex::from(5u8)), full);
+        assert_eq!(full.parent().and_then(|p| p.parent()), Some(origin.clone()));
+        assert_eq!(DerivationPath::<DerivationIndex>::new().parent(), None);
+
+        assert!(full.starts_with(&origin));
+        assert!(!origin.starts_with(&full));
+        assert_eq!(full.strip_prefix(&origin).and_then(|rest| rest.terminal()), Some(terminal));
+        assert_eq!(origin.strip_prefix(&full), None);
+        assert_eq!(full.strip_prefix(&full), Some(DerivationPath::new()));
+    }
+
+    #[test]
+    fn scheme() {
+        let path = DerivationScheme::bip84(Network::Mainnet, 0);
+        assert_eq!(path.to_string(), "/84h/0h/0h");
+        assert_eq!(DerivationScheme::bip86(Network::Signet, 3).to_string(), "/86h/1h/3h");
+        assert_eq!(DerivationScheme::coin_type(Network::Testnet4), HardenedIndex::hardened(1));
+        assert_eq!(DerivationScheme::bip84(Network::Testnet4, 0).to_string(), "/84h/1h/0h");
+        assert_eq!(DerivationScheme::detect(&path), Some(DerivationScheme::Bip84));
+
+        let path = DerivationPath::<DerivationIndex>::from_str("49h/1h/0h/1/5").unwrap();
+        assert_eq!(DerivationScheme::detect(&path), Some(DerivationScheme::Bip49));
+        for path in ["48h/0h/0h/2h", "84h/0h", "84h/0/0h", "84h/60h/0h"] {
+            let path = DerivationPath::<DerivationIndex>::from_str(path).unwrap();
+            assert_eq!(DerivationScheme::detect(&path), None);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn seg_serde() {
+        let seg = DerivationSeg::<NormalIndex>::standard();
+        let json = serde_json::to_string(&seg).unwrap();
+        assert_eq!(json, r#""<0;1>""#);
+        assert_eq!(serde_json::from_str::<DerivationSeg>(&json).unwrap(), seg);
+
+        let terminal = Terminal::new(1, NormalIndex::from(5u8));
+        let json = serde_json::to_string(&terminal).unwrap();
+        assert_eq!(serde_json::from_str::<Terminal>(&json).unwrap(), terminal);
+    }
 }
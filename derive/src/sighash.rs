@@ -0,0 +1,728 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signature hash computation for legacy, BIP-143 (segwit v0) and BIP-341
+//! (taproot) inputs.
+
+use amplify::{Bytes32, Wrapper};
+use bc::secp256k1::{Message, SecretKey, SECP256K1};
+use bc::{
+    Bip340Sig, CompressedPk, ConsensusEncode, LegacySig, PubkeyHash, Sats, ScriptBytes,
+    ScriptPubkey, SeqNo, SigScript, SighashFlag, SighashType, TapLeafHash, TapNodeHash, Tx, TxIn,
+    TxOut, VarInt, VarIntArray, Witness, MIDSTATE_TAPSIGHASH,
+};
+use bitcoin_hashes::{sha256, sha256d, Hash};
+use commit_verify::{DigestExt, Sha256};
+
+use crate::TapTweak;
+
+/// First byte of the taproot witness annex.
+const TAPROOT_ANNEX_PREFIX: u8 = 0x50;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum SighashError {
+    /// input index {index} is out of range for a transaction with {inputs} inputs.
+    InvalidInputIndex { index: usize, inputs: usize },
+
+    /// number of spent outputs ({prevouts}) doesn't match the number of transaction inputs
+    /// ({inputs}).
+    PrevoutsMismatch { prevouts: usize, inputs: usize },
+
+    /// input {0} uses SIGHASH_SINGLE, but the transaction has no output with the same index.
+    NoSingleOutput(usize),
+
+    /// annex of input {0} doesn't start with 0x50 byte.
+    InvalidAnnex(usize),
+}
+
+/// Message digest which is signed by a transaction input signature.
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Index, RangeOps, BorrowSlice, Hex, Display, FromStr)]
+pub struct Sighash(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+impl From<Sighash> for [u8; 32] {
+    fn from(sighash: Sighash) -> Self { sighash.0.into_inner() }
+}
+
+impl From<Sighash> for Message {
+    fn from(sighash: Sighash) -> Self { Message::from_digest(sighash.into()) }
+}
+
+/// Transaction-wide hashes reused by all BIP-143 signature hashes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct SegwitCache {
+    hash_prevouts: [u8; 32],
+    hash_sequences: [u8; 32],
+    hash_outputs: [u8; 32],
+}
+
+/// Transaction-wide hashes reused by all BIP-341 signature hashes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct TaprootCache {
+    sha_prevouts: [u8; 32],
+    sha_amounts: [u8; 32],
+    sha_scriptpubkeys: [u8; 32],
+    sha_sequences: [u8; 32],
+    sha_outputs: [u8; 32],
+}
+
+/// Signature hash computation engine for a transaction, which caches the parts
+/// of the signed message shared between all of its inputs.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SighashCache {
+    tx: Tx,
+    prevouts: Vec<TxOut>,
+    segwit: SegwitCache,
+    taproot: TaprootCache,
+}
+
+impl SighashCache {
+    /// Constructs sighash cache for a transaction and a list of outputs spent by
+    /// each of the transaction inputs (in the same order as the inputs).
+    pub fn new(tx: Tx, prevouts: Vec<TxOut>) -> Result<Self, SighashError> {
+        if tx.inputs.len() != prevouts.len() {
+            return Err(SighashError::PrevoutsMismatch {
+                prevouts: prevouts.len(),
+                inputs: tx.inputs.len(),
+            });
+        }
+
+        let mut outpoints = vec![];
+        let mut sequences = vec![];
+        for input in &tx.inputs {
+            input.prev_output.consensus_encode(&mut outpoints).ok();
+            input.sequence.consensus_encode(&mut sequences).ok();
+        }
+        let mut amounts = vec![];
+        let mut scriptpubkeys = vec![];
+        for prevout in &prevouts {
+            prevout.value.consensus_encode(&mut amounts).ok();
+            prevout.script_pubkey.consensus_encode(&mut scriptpubkeys).ok();
+        }
+        let mut outputs = vec![];
+        for output in &tx.outputs {
+            output.consensus_encode(&mut outputs).ok();
+        }
+
+        let sha_prevouts = sha256::Hash::hash(&outpoints).to_byte_array();
+        let sha_sequences = sha256::Hash::hash(&sequences).to_byte_array();
+        let sha_outputs = sha256::Hash::hash(&outputs).to_byte_array();
+
+        Ok(SighashCache {
+            segwit: SegwitCache {
+                hash_prevouts: sha256::Hash::hash(&sha_prevouts).to_byte_array(),
+                hash_sequences: sha256::Hash::hash(&sha_sequences).to_byte_array(),
+                hash_outputs: sha256::Hash::hash(&sha_outputs).to_byte_array(),
+            },
+            taproot: TaprootCache {
+                sha_prevouts,
+                sha_amounts: sha256::Hash::hash(&amounts).to_byte_array(),
+                sha_scriptpubkeys: sha256::Hash::hash(&scriptpubkeys).to_byte_array(),
+                sha_sequences,
+                sha_outputs,
+            },
+            tx,
+            prevouts,
+        })
+    }
+
+    /// Returns transaction for which the signature hashes are computed.
+    pub fn tx(&self) -> &Tx { &self.tx }
+
+    /// Returns outputs spent by the transaction inputs.
+    pub fn prevouts(&self) -> &[TxOut] { &self.prevouts }
+
+    fn check_input(&self, index: usize) -> Result<&TxIn, SighashError> {
+        self.tx.inputs.get(index).ok_or(SighashError::InvalidInputIndex {
+            index,
+            inputs: self.tx.inputs.len(),
+        })
+    }
+
+    /// Computes signature hash for a pre-segwit input.
+    ///
+    /// The `script_code` must be the script pubkey of the spent output, or its
+    /// redeem script for P2SH outputs. `OP_CODESEPARATOR`s are not processed.
+    ///
+    /// Follows consensus rules for `SIGHASH_SINGLE` inputs with no matching
+    /// output, returning hash `0x01` instead of an error.
+    pub fn legacy_sighash(
+        &self,
+        index: usize,
+        script_code: &ScriptBytes,
+        sighash_type: u32,
+    ) -> Result<Sighash, SighashError> {
+        self.check_input(index)?;
+        let SighashType {
+            flag,
+            anyone_can_pay,
+        } = SighashType::from_consensus_u32(sighash_type);
+
+        if flag == SighashFlag::Single && index >= self.tx.outputs.len() {
+            let mut one = [0u8; 32];
+            one[0] = 1;
+            return Ok(Sighash::from(one));
+        }
+
+        let inputs = self
+            .tx
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|(no, _)| !anyone_can_pay || *no == index)
+            .map(|(no, input)| TxIn {
+                prev_output: input.prev_output,
+                sig_script: if no == index {
+                    SigScript::from_unsafe(script_code.to_vec())
+                } else {
+                    SigScript::new()
+                },
+                sequence: if no != index && flag != SighashFlag::All {
+                    SeqNo::from_consensus_u32(0)
+                } else {
+                    input.sequence
+                },
+                witness: Witness::new(),
+            });
+        let outputs = match flag {
+            SighashFlag::All => self.tx.outputs.iter().cloned().collect(),
+            SighashFlag::None => vec![],
+            SighashFlag::Single => self
+                .tx
+                .outputs
+                .iter()
+                .take(index + 1)
+                .enumerate()
+                .map(|(no, output)| {
+                    if no == index {
+                        output.clone()
+                    } else {
+                        TxOut::new(ScriptPubkey::new(), Sats::from(u64::MAX))
+                    }
+                })
+                .collect(),
+        };
+        let tx = Tx {
+            version: self.tx.version,
+            inputs: VarIntArray::from_iter_unsafe(inputs),
+            outputs: VarIntArray::from_iter_unsafe(outputs),
+            lock_time: self.tx.lock_time,
+        };
+
+        let mut data = tx.consensus_serialize();
+        sighash_type.consensus_encode(&mut data).ok();
+        Ok(Sighash::from(sha256d::Hash::hash(&data).to_byte_array()))
+    }
+
+    /// Computes BIP-143 signature hash for a segwit v0 input.
+    ///
+    /// The `script_code` for P2WPKH inputs must be the P2PKH script pubkey of
+    /// the key hash; for P2WSH inputs it must be the witness script.
+    pub fn segwit_sighash(
+        &self,
+        index: usize,
+        script_code: &ScriptBytes,
+        sighash_type: SighashType,
+    ) -> Result<Sighash, SighashError> {
+        let input = self.check_input(index)?;
+        let SighashType {
+            flag,
+            anyone_can_pay,
+        } = sighash_type;
+        let zero = [0u8; 32];
+
+        let mut data = vec![];
+        self.tx.version.consensus_encode(&mut data).ok();
+        data.extend(if anyone_can_pay { &zero } else { &self.segwit.hash_prevouts });
+        data.extend(if anyone_can_pay || flag != SighashFlag::All {
+            &zero
+        } else {
+            &self.segwit.hash_sequences
+        });
+        input.prev_output.consensus_encode(&mut data).ok();
+        script_code.consensus_encode(&mut data).ok();
+        self.prevouts[index].value.consensus_encode(&mut data).ok();
+        input.sequence.consensus_encode(&mut data).ok();
+        match flag {
+            SighashFlag::All => data.extend(self.segwit.hash_outputs),
+            SighashFlag::Single if index < self.tx.outputs.len() => {
+                let output = self.tx.outputs[index].consensus_serialize();
+                data.extend(sha256d::Hash::hash(&output).to_byte_array());
+            }
+            SighashFlag::Single | SighashFlag::None => data.extend(zero),
+        }
+        self.tx.lock_time.consensus_encode(&mut data).ok();
+        (sighash_type.into_consensus_u8() as u32).consensus_encode(&mut data).ok();
+
+        Ok(Sighash::from(sha256d::Hash::hash(&data).to_byte_array()))
+    }
+
+    /// Computes BIP-341 signature hash for a taproot key path spending.
+    ///
+    /// A missing `sighash_type` denotes `SIGHASH_DEFAULT`.
+    pub fn tap_sighash_key(
+        &self,
+        index: usize,
+        sighash_type: Option<SighashType>,
+    ) -> Result<Sighash, SighashError> {
+        self.tap_sighash(index, None, None, sighash_type)
+    }
+
+    /// Computes BIP-341 signature hash for a taproot script path spending of
+    /// the leaf with the provided hash.
+    ///
+    /// A missing `sighash_type` denotes `SIGHASH_DEFAULT`.
+    pub fn tap_sighash_script(
+        &self,
+        index: usize,
+        leaf_hash: impl Into<TapLeafHash>,
+        sighash_type: Option<SighashType>,
+    ) -> Result<Sighash, SighashError> {
+        self.tap_sighash(index, Some(leaf_hash.into()), None, sighash_type)
+    }
+
+    /// Computes BIP-341 signature hash for a taproot input having an `annex` (including the
+    /// `0x50` prefix byte) in its witness, for a key path spending or, if the `leaf_hash` is
+    /// provided, for a script path spending.
+    ///
+    /// A missing `sighash_type` denotes `SIGHASH_DEFAULT`.
+    pub fn tap_sighash_with_annex(
+        &self,
+        index: usize,
+        leaf_hash: Option<TapLeafHash>,
+        annex: &[u8],
+        sighash_type: Option<SighashType>,
+    ) -> Result<Sighash, SighashError> {
+        if annex.first() != Some(&TAPROOT_ANNEX_PREFIX) {
+            return Err(SighashError::InvalidAnnex(index));
+        }
+        self.tap_sighash(index, leaf_hash, Some(annex), sighash_type)
+    }
+
+    /// Signs a pre-segwit input with a private key, committing to the `script_code` (see
+    /// [`Self::legacy_sighash`]).
+    pub fn sign_legacy(
+        &self,
+        index: usize,
+        script_code: &ScriptBytes,
+        sighash_type: SighashType,
+        sk: &SecretKey,
+    ) -> Result<LegacySig, SighashError> {
+        let sighash = self.legacy_sighash(index, script_code, sighash_type.into_consensus_u32())?;
+        let sig = SECP256K1.sign_ecdsa(&sighash.into(), sk);
+        Ok(LegacySig { sig, sighash_type })
+    }
+
+    /// Signs a segwit v0 input with a private key, committing to the `script_code` (see
+    /// [`Self::segwit_sighash`]).
+    pub fn sign_segwit(
+        &self,
+        index: usize,
+        script_code: &ScriptBytes,
+        sighash_type: SighashType,
+        sk: &SecretKey,
+    ) -> Result<LegacySig, SighashError> {
+        let sighash = self.segwit_sighash(index, script_code, sighash_type)?;
+        let sig = SECP256K1.sign_ecdsa(&sighash.into(), sk);
+        Ok(LegacySig { sig, sighash_type })
+    }
+
+    /// Signs a P2WPKH input (native or nested in P2SH), constructing the script code from the
+    /// public key of the private key.
+    pub fn sign_wpkh(
+        &self,
+        index: usize,
+        sighash_type: SighashType,
+        sk: &SecretKey,
+    ) -> Result<LegacySig, SighashError> {
+        let pk = CompressedPk::from(sk.public_key(SECP256K1));
+        let script_code = ScriptPubkey::p2pkh(PubkeyHash::from(pk));
+        self.sign_segwit(index, script_code.as_script_bytes(), sighash_type, sk)
+    }
+
+    /// Signs a taproot key path spending with an untweaked internal private key, which is
+    /// tweaked with the script tree `merkle_root` (`None` for key-only outputs).
+    ///
+    /// A missing `sighash_type` denotes `SIGHASH_DEFAULT`.
+    pub fn sign_tap_key(
+        &self,
+        index: usize,
+        sighash_type: Option<SighashType>,
+        sk: &SecretKey,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Result<Bip340Sig, SighashError> {
+        let sighash = self.tap_sighash_key(index, sighash_type)?;
+        let keypair = sk.tap_tweak(merkle_root);
+        let sig = SECP256K1.sign_schnorr_no_aux_rand(&sighash.into(), &keypair);
+        Ok(Bip340Sig { sig, sighash_type })
+    }
+
+    /// Signs a taproot script path spending of the leaf with the provided hash. The key is used
+    /// untweaked, as it appears in the leaf script.
+    ///
+    /// A missing `sighash_type` denotes `SIGHASH_DEFAULT`.
+    pub fn sign_tap_script(
+        &self,
+        index: usize,
+        leaf_hash: impl Into<TapLeafHash>,
+        sighash_type: Option<SighashType>,
+        sk: &SecretKey,
+    ) -> Result<Bip340Sig, SighashError> {
+        let sighash = self.tap_sighash_script(index, leaf_hash, sighash_type)?;
+        let keypair = sk.keypair(SECP256K1);
+        let sig = SECP256K1.sign_schnorr_no_aux_rand(&sighash.into(), &keypair);
+        Ok(Bip340Sig { sig, sighash_type })
+    }
+
+    fn tap_sighash(
+        &self,
+        index: usize,
+        leaf_hash: Option<TapLeafHash>,
+        annex: Option<&[u8]>,
+        sighash_type: Option<SighashType>,
+    ) -> Result<Sighash, SighashError> {
+        let input = self.check_input(index)?;
+        let SighashType {
+            flag,
+            anyone_can_pay,
+        } = sighash_type.unwrap_or_default();
+
+        let mut engine = Sha256::from_tag(MIDSTATE_TAPSIGHASH);
+        // epoch
+        engine.input_raw(&[0x00]);
+        engine.input_raw(&[sighash_type.map(SighashType::into_consensus_u8).unwrap_or_default()]);
+        engine.input_raw(&self.tx.version.consensus_serialize());
+        engine.input_raw(&self.tx.lock_time.consensus_serialize());
+        if !anyone_can_pay {
+            engine.input_raw(&self.taproot.sha_prevouts);
+            engine.input_raw(&self.taproot.sha_amounts);
+            engine.input_raw(&self.taproot.sha_scriptpubkeys);
+            engine.input_raw(&self.taproot.sha_sequences);
+        }
+        if flag == SighashFlag::All {
+            engine.input_raw(&self.taproot.sha_outputs);
+        }
+        // spend type: extension flag and annex presence
+        let ext_flag = if leaf_hash.is_some() { 1u8 } else { 0u8 };
+        engine.input_raw(&[ext_flag << 1 | annex.is_some() as u8]);
+        if anyone_can_pay {
+            let prevout = &self.prevouts[index];
+            engine.input_raw(&input.prev_output.consensus_serialize());
+            engine.input_raw(&prevout.value.consensus_serialize());
+            engine.input_raw(&prevout.script_pubkey.consensus_serialize());
+            engine.input_raw(&input.sequence.consensus_serialize());
+        } else {
+            engine.input_raw(&(index as u32).to_le_bytes());
+        }
+        if let Some(annex) = annex {
+            let mut data = VarInt::with(annex.len()).consensus_serialize();
+            data.extend_from_slice(annex);
+            engine.input_raw(&sha256::Hash::hash(&data).to_byte_array());
+        }
+        if flag == SighashFlag::Single {
+            let output = self.tx.outputs.get(index).ok_or(SighashError::NoSingleOutput(index))?;
+            engine.input_raw(&sha256::Hash::hash(&output.consensus_serialize()).to_byte_array());
+        }
+        if let Some(leaf_hash) = leaf_hash {
+            engine.input_raw(leaf_hash.into_inner().as_slice());
+            // key version
+            engine.input_raw(&[0x00]);
+            // no OP_CODESEPARATOR was executed
+            engine.input_raw(&u32::MAX.to_le_bytes());
+        }
+
+        Ok(Sighash::from(engine.finish()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use amplify::hex::FromHex;
+
+    use super::*;
+
+    /// Native P2WPKH example from BIP-143.
+    fn bip143_p2wpkh() -> SighashCache {
+        let tx = Tx::from_str(
+            "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000\
+             00eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a01000000\
+             00ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac90\
+             93510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+        )
+        .unwrap();
+        let prevouts = vec![
+            TxOut::new(
+                ScriptPubkey::from_unsafe(
+                    Vec::from_hex(
+                        "2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac",
+                    )
+                    .unwrap(),
+                ),
+                625_000_000u64,
+            ),
+            TxOut::new(
+                ScriptPubkey::from_unsafe(
+                    Vec::from_hex("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap(),
+                ),
+                600_000_000u64,
+            ),
+        ];
+        SighashCache::new(tx, prevouts).unwrap()
+    }
+
+    #[test]
+    fn bip143_sighash() {
+        let cache = bip143_p2wpkh();
+        assert_eq!(
+            cache.segwit.hash_prevouts,
+            <[u8; 32]>::from_hex(
+                "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            cache.segwit.hash_sequences,
+            <[u8; 32]>::from_hex(
+                "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            cache.segwit.hash_outputs,
+            <[u8; 32]>::from_hex(
+                "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5"
+            )
+            .unwrap()
+        );
+
+        let script_code = ScriptPubkey::from_unsafe(
+            Vec::from_hex("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap(),
+        );
+        let sighash =
+            cache.segwit_sighash(1, script_code.as_script_bytes(), SighashType::all()).unwrap();
+        assert_eq!(
+            sighash,
+            Sighash::from_str("c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn sighash_single_bug() {
+        let mut cache = bip143_p2wpkh();
+        cache.tx.outputs = VarIntArray::from_iter_unsafe(cache.tx.outputs.iter().take(1).cloned());
+        let spk = cache.prevouts[1].script_pubkey.clone();
+
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(cache.legacy_sighash(1, spk.as_script_bytes(), 0x03), Ok(Sighash::from(one)));
+        assert_eq!(
+            cache.tap_sighash_key(1, Some(SighashType::single())),
+            Err(SighashError::NoSingleOutput(1))
+        );
+        assert_eq!(
+            cache.tap_sighash_key(2, None),
+            Err(SighashError::InvalidInputIndex {
+                index: 2,
+                inputs: 2
+            })
+        );
+    }
+
+    #[test]
+    fn prevouts_mismatch() {
+        let cache = bip143_p2wpkh();
+        assert_eq!(
+            SighashCache::new(cache.tx.clone(), vec![]),
+            Err(SighashError::PrevoutsMismatch {
+                prevouts: 0,
+                inputs: 2
+            })
+        );
+    }
+
+    #[test]
+    fn bip143_sign() {
+        let cache = bip143_p2wpkh();
+        let sk = SecretKey::from_slice(
+            &Vec::from_hex("619c335025c7f4012e556c2a58b2506e30b8511b53ade95ea316fd8c3286feb9")
+                .unwrap(),
+        )
+        .unwrap();
+        let sig = cache.sign_wpkh(1, SighashType::all(), &sk).unwrap();
+        assert_eq!(
+            sig.to_vec(),
+            Vec::from_hex(
+                "304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a\
+                 954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            cache.sign_wpkh(2, SighashType::all(), &sk),
+            Err(SighashError::InvalidInputIndex {
+                index: 2,
+                inputs: 2
+            })
+        );
+    }
+
+    /// Transaction and spent outputs of the BIP-341 `keyPathSpending` test vectors.
+    fn bip341_key_path() -> SighashCache {
+        let tx = Tx::from_str(
+            "02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c01000000\
+             0000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd9900000000\
+             00fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a4184200000000\
+             00fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b01000000\
+             00feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c00000000\
+             00feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd05000000000\
+             0000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c9401000000\
+             0000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf00000000\
+             00ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af101000000\
+             00ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac80\
+             7840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b00\
+             65cd1d",
+        )
+        .unwrap();
+        let prevouts = [
+            (
+                "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+                420_000_000u64,
+            ),
+            ("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", 462_000_000),
+            ("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", 294_000_000),
+            ("5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e", 504_000_000),
+            ("512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", 630_000_000),
+            ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378_000_000),
+            ("512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831", 672_000_000),
+            ("5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", 546_000_000),
+            ("512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220", 588_000_000),
+        ]
+        .into_iter()
+        .map(|(spk, value)| {
+            TxOut::new(ScriptPubkey::from_unsafe(Vec::from_hex(spk).unwrap()), value)
+        })
+        .collect();
+        SighashCache::new(tx, prevouts).unwrap()
+    }
+
+    #[test]
+    fn bip341_sighash() {
+        let cache = bip341_key_path();
+        for (hash, expected) in [
+            (
+                cache.taproot.sha_prevouts,
+                "e3b33bb4ef3a52ad1fffb555c0d82828eb22737036eaeb02a235d82b909c4c3f",
+            ),
+            (
+                cache.taproot.sha_amounts,
+                "58a6964a4f5f8f0b642ded0a8a553be7622a719da71d1f5befcefcdee8e0fde6",
+            ),
+            (
+                cache.taproot.sha_scriptpubkeys,
+                "23ad0f61ad2bca5ba6a7693f50fce988e17c3780bf2b1e720cfbb38fbdd52e21",
+            ),
+            (
+                cache.taproot.sha_sequences,
+                "18959c7221ab5ce9e26c3cd67b22c24f8baa54bac281d8e6b05e400e6c3a957e",
+            ),
+            (
+                cache.taproot.sha_outputs,
+                "a2e6dab7c1f0dcd297c8d61647fd17d821541ea69c3cc37dcbad7f90d4eb4bc5",
+            ),
+        ] {
+            assert_eq!(hash, <[u8; 32]>::from_hex(expected).unwrap());
+        }
+
+        for (index, sighash_type, expected) in [
+            (0, 0x03, "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555"),
+            (1, 0x83, "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d"),
+            (3, 0x01, "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669"),
+            (4, 0x00, "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef"),
+            (6, 0x02, "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85"),
+            (7, 0x82, "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10"),
+            (8, 0x81, "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2"),
+        ] {
+            let sighash_type = match sighash_type {
+                0x00 => None,
+                other => Some(SighashType::from_consensus_u32(other)),
+            };
+            assert_eq!(
+                cache.tap_sighash_key(index, sighash_type).unwrap(),
+                Sighash::from_str(expected).unwrap(),
+                "input {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn bip341_sighash_annex() {
+        let cache = bip341_key_path();
+        let annex = [0x50, 0x01, 0x02];
+        assert_eq!(
+            cache.tap_sighash_with_annex(0, None, &annex[1..], None),
+            Err(SighashError::InvalidAnnex(0))
+        );
+
+        // Digests of the BIP-341 key path spending vectors with the annex added, computed with
+        // an independent implementation of the BIP-341 signature message
+        for (index, sighash_type, expected) in [
+            (1, 0x83, "f650e8cf8c1247eb263556a54472f10221485b8ad897e08a1ac0b97a33486898"),
+            (3, 0x01, "f4deab1ef23afbe56a24ad3e744b3aaa127c045350979bdd9449021520244ce5"),
+            (4, 0x00, "94e4490f952d6079e42654a57a2d1be23c6d1602352641d6bd1135baa4fb1b71"),
+        ] {
+            let sighash_type = match sighash_type {
+                0x00 => None,
+                other => Some(SighashType::from_consensus_u32(other)),
+            };
+            let sighash = cache.tap_sighash_with_annex(index, None, &annex, sighash_type).unwrap();
+            assert_eq!(sighash, Sighash::from_str(expected).unwrap(), "input {index}");
+            assert_ne!(sighash, cache.tap_sighash_key(index, sighash_type).unwrap());
+        }
+    }
+
+    #[test]
+    fn tap_sign_verify() {
+        let cache = bip143_p2wpkh();
+        let sk = SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let merkle_root = Some(TapNodeHash::from([7u8; 32]));
+
+        let sig = cache.sign_tap_key(0, None, &sk, merkle_root).unwrap();
+        let output_pk = sk.tap_tweak(merkle_root).x_only_public_key().0;
+        let sighash = cache.tap_sighash_key(0, None).unwrap();
+        assert!(SECP256K1.verify_schnorr(&sig.sig, &sighash.into(), &output_pk).is_ok());
+
+        let leaf_hash = TapLeafHash::from([9u8; 32]);
+        let sig = cache.sign_tap_script(1, leaf_hash, Some(SighashType::all()), &sk).unwrap();
+        assert_eq!(sig.sighash_type, Some(SighashType::all()));
+        let sighash = cache.tap_sighash_script(1, leaf_hash, Some(SighashType::all())).unwrap();
+        let pk = sk.x_only_public_key(SECP256K1).0;
+        assert!(SECP256K1.verify_schnorr(&sig.sig, &sighash.into(), &pk).is_ok());
+    }
+}
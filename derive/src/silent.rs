@@ -0,0 +1,531 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Silent payments (BIP-352): reusable static addresses, from which senders
+//! derive unique taproot outputs using the keys of the spent inputs.
+//!
+//! Labels are not supported yet.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use bc::secp256k1::{Parity, PublicKey, Scalar, SecretKey, SECP256K1};
+use bc::{CompressedPk, ConsensusEncode, InvalidPubkey, Outpoint, OutputPk, XOnlyPk};
+use bech32::{u5, FromBase32, ToBase32, Variant};
+use commit_verify::{DigestExt, Sha256};
+
+const TAG_INPUTS: &[u8] = b"BIP0352/Inputs";
+const TAG_SHARED_SECRET: &[u8] = b"BIP0352/SharedSecret";
+
+pub const SILENT_PAYMENT_HRP_MAINNET: &str = "sp";
+pub const SILENT_PAYMENT_HRP_TESTNET: &str = "tsp";
+
+/// Errors parsing silent payment addresses.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum SilentAddressError {
+    /// wrong Bech32m encoding of silent payment address - {0}
+    #[from]
+    Bech32(bech32::Error),
+
+    /// silent payment address must use Bech32m encoding.
+    InvalidVariant,
+
+    /// unknown silent payment address prefix '{0}'.
+    InvalidHrp(String),
+
+    /// silent payment address has invalid version {0}.
+    InvalidVersion(u8),
+
+    /// wrong length of silent payment address data ({0}).
+    WrongLength(usize),
+
+    /// silent payment address contains {0}
+    #[from]
+    #[from(bc::secp256k1::Error)]
+    InvalidPubkey(InvalidPubkey<33>),
+}
+
+/// Errors deriving silent payment outputs.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum SilentPaymentError {
+    /// no inputs eligible for silent payments are provided.
+    NoInputs,
+
+    /// keys of the inputs sum up to zero.
+    ZeroKeySum,
+}
+
+/// Silent payment address (`sp1...`) consisting of the scan and spend public
+/// keys of the receiver.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SilentAddress {
+    pub testnet: bool,
+    pub scan_pk: CompressedPk,
+    pub spend_pk: CompressedPk,
+}
+
+impl SilentAddress {
+    pub fn new(testnet: bool, scan_pk: CompressedPk, spend_pk: CompressedPk) -> Self {
+        SilentAddress {
+            testnet,
+            scan_pk,
+            spend_pk,
+        }
+    }
+}
+
+impl Display for SilentAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let hrp = match self.testnet {
+            false => SILENT_PAYMENT_HRP_MAINNET,
+            true => SILENT_PAYMENT_HRP_TESTNET,
+        };
+        let mut data = self.scan_pk.serialize().to_vec();
+        data.extend(self.spend_pk.serialize());
+        let mut writer = bech32::Bech32Writer::new(hrp, Variant::Bech32m, f)?;
+        bech32::WriteBase32::write_u5(&mut writer, u5::try_from_u8(0).expect("zero version"))?;
+        data.write_base32(&mut writer)?;
+        writer.finalize()
+    }
+}
+
+impl FromStr for SilentAddress {
+    type Err = SilentAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hrp, data, variant) = bech32::decode(s)?;
+        if variant != Variant::Bech32m {
+            return Err(SilentAddressError::InvalidVariant);
+        }
+        let testnet = match hrp.as_str() {
+            SILENT_PAYMENT_HRP_MAINNET => false,
+            SILENT_PAYMENT_HRP_TESTNET => true,
+            _ => return Err(SilentAddressError::InvalidHrp(hrp)),
+        };
+        let (version, data) = data.split_first().ok_or(SilentAddressError::WrongLength(0))?;
+        let data = Vec::<u8>::from_base32(data)?;
+        // Future versions are required to keep the first 66 bytes compatible with
+        // version 0, and may only append data.
+        match version.to_u8() {
+            0 if data.len() != 66 => return Err(SilentAddressError::WrongLength(data.len())),
+            1..=30 if data.len() < 66 => return Err(SilentAddressError::WrongLength(data.len())),
+            0..=30 => {}
+            ver => return Err(SilentAddressError::InvalidVersion(ver)),
+        }
+        Ok(SilentAddress {
+            testnet,
+            scan_pk: CompressedPk::from_bytes(&data[..33])?,
+            spend_pk: CompressedPk::from_bytes(&data[33..66])?,
+        })
+    }
+}
+
+fn input_hash(
+    outpoints: impl IntoIterator<Item = Outpoint>,
+    key_sum: PublicKey,
+) -> Result<Scalar, SilentPaymentError> {
+    let smallest = outpoints
+        .into_iter()
+        .map(|outpoint| outpoint.consensus_serialize())
+        .min()
+        .ok_or(SilentPaymentError::NoInputs)?;
+    let mut engine = Sha256::from_tag(TAG_INPUTS);
+    engine.input_raw(&smallest);
+    engine.input_raw(&key_sum.serialize());
+    Ok(Scalar::from_be_bytes(engine.finish()).expect("negligible probability"))
+}
+
+fn shared_tweak(shared_secret: PublicKey, k: u32) -> Scalar {
+    let mut engine = Sha256::from_tag(TAG_SHARED_SECRET);
+    engine.input_raw(&shared_secret.serialize());
+    engine.input_raw(&k.to_be_bytes());
+    Scalar::from_be_bytes(engine.finish()).expect("negligible probability")
+}
+
+fn output_key(spend_pk: CompressedPk, tweak: Scalar) -> OutputPk {
+    let pk = spend_pk.add_exp_tweak(SECP256K1, &tweak).expect("negligible probability");
+    OutputPk::from_unchecked(XOnlyPk::from(pk))
+}
+
+/// Sender-side derivation of silent payment outputs from the private keys of
+/// the transaction inputs.
+///
+/// The secret data are erased from the memory when the value is dropped.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SilentPaymentSender {
+    /// Sum of input private keys multiplied by the input hash.
+    secret: SecretKey,
+}
+
+impl SilentPaymentSender {
+    /// Constructs sender from the outpoints of all transaction inputs and the
+    /// private keys of the inputs eligible for silent payments.
+    ///
+    /// Each key is accompanied with a flag whether it is used for a taproot
+    /// (x-only) spending.
+    pub fn new(
+        outpoints: impl IntoIterator<Item = Outpoint>,
+        input_keys: impl IntoIterator<Item = (SecretKey, bool)>,
+    ) -> Result<Self, SilentPaymentError> {
+        let mut sum: Option<SecretKey> = None;
+        for (mut sk, xonly) in input_keys {
+            if xonly && sk.x_only_public_key(SECP256K1).1 == Parity::Odd {
+                sk = sk.negate();
+            }
+            sum = Some(match sum {
+                None => sk,
+                Some(sum) => {
+                    sum.add_tweak(&Scalar::from(sk)).map_err(|_| SilentPaymentError::ZeroKeySum)?
+                }
+            });
+        }
+        let sum = sum.ok_or(SilentPaymentError::NoInputs)?;
+        let input_hash = input_hash(outpoints, sum.public_key(SECP256K1))?;
+        let secret = sum.mul_tweak(&input_hash).expect("negligible probability");
+        Ok(SilentPaymentSender { secret })
+    }
+
+    /// Derives output keys for the provided recipients, returned in the same
+    /// order.
+    pub fn derive_outputs(&self, recipients: &[SilentAddress]) -> Vec<OutputPk> {
+        let mut counters = Vec::<(CompressedPk, u32)>::new();
+        recipients
+            .iter()
+            .map(|recipient| {
+                let k = match counters.iter_mut().find(|(pk, _)| *pk == recipient.scan_pk) {
+                    Some((_, k)) => {
+                        *k += 1;
+                        *k
+                    }
+                    None => {
+                        counters.push((recipient.scan_pk, 0));
+                        0
+                    }
+                };
+                let shared_secret = recipient
+                    .scan_pk
+                    .mul_tweak(SECP256K1, &Scalar::from(self.secret))
+                    .expect("negligible probability");
+                output_key(recipient.spend_pk, shared_tweak(shared_secret, k))
+            })
+            .collect()
+    }
+}
+
+impl Drop for SilentPaymentSender {
+    fn drop(&mut self) { self.secret.non_secure_erase() }
+}
+
+/// Silent payment output detected by [`SilentPaymentScanner`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SilentPaymentOutput {
+    pub output_pk: OutputPk,
+    /// Tweak which must be added to the spend private key to spend the output.
+    pub tweak: Scalar,
+}
+
+/// Receiver-side scanning of transactions for silent payment outputs.
+///
+/// The scan private key is erased from the memory when the value is dropped.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SilentPaymentScanner {
+    scan_key: SecretKey,
+    spend_pk: CompressedPk,
+}
+
+impl SilentPaymentScanner {
+    pub fn new(scan_key: SecretKey, spend_pk: CompressedPk) -> Self {
+        SilentPaymentScanner { scan_key, spend_pk }
+    }
+
+    /// Returns silent payment address for receiving to this scanner.
+    pub fn address(&self, testnet: bool) -> SilentAddress {
+        SilentAddress::new(
+            testnet,
+            CompressedPk::from(self.scan_key.public_key(SECP256K1)),
+            self.spend_pk,
+        )
+    }
+
+    /// Scans transaction outputs for the payments to this receiver.
+    ///
+    /// Input public keys must be provided only for the inputs eligible for
+    /// silent payments; x-only keys of taproot inputs must be converted to
+    /// compressed keys with even Y coordinate.
+    pub fn scan(
+        &self,
+        outpoints: impl IntoIterator<Item = Outpoint>,
+        input_pks: impl IntoIterator<Item = CompressedPk>,
+        outputs: &[OutputPk],
+    ) -> Result<Vec<SilentPaymentOutput>, SilentPaymentError> {
+        let input_pks = input_pks.into_iter().map(|pk| *pk).collect::<Vec<_>>();
+        if input_pks.is_empty() {
+            return Err(SilentPaymentError::NoInputs);
+        }
+        let key_sum = PublicKey::combine_keys(&input_pks.iter().collect::<Vec<_>>())
+            .map_err(|_| SilentPaymentError::ZeroKeySum)?;
+        let input_hash = input_hash(outpoints, key_sum)?;
+        let secret = self.scan_key.mul_tweak(&input_hash).expect("negligible probability");
+        let shared_secret =
+            key_sum.mul_tweak(SECP256K1, &Scalar::from(secret)).expect("negligible probability");
+
+        let mut found = vec![];
+        for k in 0u32.. {
+            let tweak = shared_tweak(shared_secret, k);
+            let output_pk = output_key(self.spend_pk, tweak);
+            if !outputs.contains(&output_pk) {
+                break;
+            }
+            found.push(SilentPaymentOutput { output_pk, tweak });
+        }
+        Ok(found)
+    }
+}
+
+impl Drop for SilentPaymentScanner {
+    fn drop(&mut self) { self.scan_key.non_secure_erase() }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::{FromHex, ToHex};
+    use bc::{Txid, Vout};
+
+    use super::*;
+
+    fn sk(byte: u8) -> SecretKey { SecretKey::from_slice(&[byte; 32]).unwrap() }
+
+    fn pk(byte: u8) -> CompressedPk { CompressedPk::from(sk(byte).public_key(SECP256K1)) }
+
+    fn outpoints() -> [Outpoint; 2] {
+        [
+            Outpoint::new(Txid::from([0xAB; 32]), Vout::from_u32(1)),
+            Outpoint::new(Txid::from([0x01; 32]), Vout::from_u32(7)),
+        ]
+    }
+
+    #[test]
+    fn address_roundtrip() {
+        for testnet in [false, true] {
+            let addr = SilentAddress::new(testnet, pk(1), pk(2));
+            let s = addr.to_string();
+            assert!(s.starts_with(if testnet { "tsp1q" } else { "sp1q" }));
+            assert_eq!(SilentAddress::from_str(&s).unwrap(), addr);
+        }
+        assert_eq!(
+            SilentAddress::from_str(
+                "tb1p5kgdjdf99vfa2xwufd2cx2qru468z79s2arn3jf5feg95d9m62gqzpnjjk"
+            ),
+            Err(SilentAddressError::InvalidHrp(s!("tb")))
+        );
+    }
+
+    #[test]
+    fn send_receive() {
+        let scanner = SilentPaymentScanner::new(sk(3), pk(4));
+        let other = SilentAddress::new(false, pk(5), pk(6));
+        let recipients = [scanner.address(false), other, scanner.address(false)];
+
+        let input_keys = [(sk(7), false), (sk(8), true)];
+        let sender = SilentPaymentSender::new(outpoints(), input_keys).unwrap();
+        let outputs = sender.derive_outputs(&recipients);
+        assert_eq!(outputs.len(), 3);
+        assert_ne!(outputs[0], outputs[2]);
+
+        let input_pks = input_keys.map(|(sk, xonly)| {
+            let pk = sk.public_key(SECP256K1);
+            match xonly {
+                false => CompressedPk::from(pk),
+                true => {
+                    let (xonly, _) = pk.x_only_public_key();
+                    CompressedPk::from(xonly.public_key(Parity::Even))
+                }
+            }
+        });
+        let found = scanner.scan(outpoints(), input_pks, &outputs).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].output_pk, outputs[0]);
+        assert_eq!(found[1].output_pk, outputs[2]);
+
+        // The receiver is able to spend the outputs
+        for output in found {
+            let spend_key = sk(4).add_tweak(&output.tweak).unwrap();
+            assert_eq!(
+                OutputPk::from_unchecked(XOnlyPk::from(spend_key.public_key(SECP256K1))),
+                output.output_pk
+            );
+        }
+
+        let found =
+            SilentPaymentScanner::new(sk(9), pk(4)).scan(outpoints(), input_pks, &outputs).unwrap();
+        assert!(found.is_empty());
+    }
+
+    // Keys and outputs from BIP-352 `send_and_receive_test_vectors.json`
+    const BIP352_SCAN_KEY: &str =
+        "0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c";
+    const BIP352_SPEND_KEY: &str =
+        "9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3";
+    const BIP352_ADDRESS: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+    const BIP352_ADDRESS_SAME_SCAN: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjex54dmqmmv6rw353tsuqhs99ydvadxzrsy9nuvk74epvee55drs734pqq";
+    const BIP352_INPUT_KEYS: [&str; 5] = [
+        "eadc78165ff1f8ea94ad7cfdc54990738a4c53f6e0507b42154201b8e5dff3b1",
+        "93f5ed907ad5b2bdbbdcb5d9116ebc0a4e1f92f910d5260237fa45a9408aad16",
+        "fc8716a97a48ba9a05a98ae47b5cd201a25a7fd5d8b73c203c5f7b6b6b3b6ad7",
+        "1d37787c2b7116ee983e9f9c13269df29091b391c04db94239e0d2bc2182c3bf",
+        "8d4751f6e8a3586880fb66c19ae277969bd5aa06f61c4ee2f1e2486efdf666d3",
+    ];
+    #[allow(clippy::type_complexity)]
+    const BIP352_VECTORS: [(&str, [(usize, bool); 2], &str); 7] = [
+        (
+            "Simple send: two inputs",
+            [(0, false), (1, false)],
+            "3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1",
+        ),
+        (
+            "Simple send: two inputs, order reversed",
+            [(1, false), (0, false)],
+            "3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1",
+        ),
+        (
+            "Single recipient: multiple UTXOs from the same public key",
+            [(0, false), (0, false)],
+            "548ae55c8eec1e736e8d3e520f011f1f42a56d166116ad210b3937599f87f566",
+        ),
+        (
+            "Single recipient: taproot only inputs with even y-values",
+            [(0, true), (2, true)],
+            "de88bea8e7ffc9ce1af30d1132f910323c505185aec8eae361670421e749a1fb",
+        ),
+        (
+            "Single recipient: taproot only with mixed even/odd y-values",
+            [(0, true), (3, true)],
+            "77cab7dd12b10259ee82c6ea4b509774e33e7078e7138f568092241bf26b99f1",
+        ),
+        (
+            "Single recipient: taproot input with even y-value and non-taproot input",
+            [(0, true), (4, false)],
+            "30523cca96b2a9ae3c98beb5e60f7d190ec5bc79b2d11a0b2d4d09a608c448f0",
+        ),
+        (
+            "Single recipient: taproot input with odd y-value and non-taproot input",
+            [(3, true), (4, false)],
+            "359358f59ee9e9eec3f00bdf4882570fd5c182e451aa2650b788544aff012a3a",
+        ),
+    ];
+
+    fn bip352_sk(hex: &str) -> SecretKey {
+        SecretKey::from_slice(&Vec::<u8>::from_hex(hex).unwrap()).unwrap()
+    }
+
+    fn bip352_outpoints() -> [Outpoint; 2] {
+        [
+            Outpoint::new(
+                "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16".parse().unwrap(),
+                Vout::from_u32(0),
+            ),
+            Outpoint::new(
+                "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d".parse().unwrap(),
+                Vout::from_u32(0),
+            ),
+        ]
+    }
+
+    fn bip352_scanner() -> SilentPaymentScanner {
+        let spend_pk = bip352_sk(BIP352_SPEND_KEY).public_key(SECP256K1);
+        SilentPaymentScanner::new(bip352_sk(BIP352_SCAN_KEY), CompressedPk::from(spend_pk))
+    }
+
+    /// Public key of an input as seen by the receiver, i.e. with even Y for taproot inputs.
+    fn bip352_input_pk((sk, xonly): (SecretKey, bool)) -> CompressedPk {
+        let pk = sk.public_key(SECP256K1);
+        match xonly {
+            false => CompressedPk::from(pk),
+            true => CompressedPk::from(pk.x_only_public_key().0.public_key(Parity::Even)),
+        }
+    }
+
+    #[test]
+    fn bip352_send_receive() {
+        let scanner = bip352_scanner();
+        let address = SilentAddress::from_str(BIP352_ADDRESS).unwrap();
+        assert_eq!(scanner.address(false), address);
+        assert_eq!(address.to_string(), BIP352_ADDRESS);
+
+        for (name, inputs, expected) in BIP352_VECTORS {
+            let input_keys = inputs.map(|(no, xonly)| (bip352_sk(BIP352_INPUT_KEYS[no]), xonly));
+            let sender = SilentPaymentSender::new(bip352_outpoints(), input_keys).unwrap();
+            let outputs = sender.derive_outputs(&[address]);
+            assert_eq!(outputs[0].to_byte_array().to_hex(), expected, "{name}");
+
+            let input_pks = input_keys.map(bip352_input_pk);
+            let found = scanner.scan(bip352_outpoints(), input_pks, &outputs).unwrap();
+            assert_eq!(found.len(), 1, "{name}");
+            let spend_key = bip352_sk(BIP352_SPEND_KEY).add_tweak(&found[0].tweak).unwrap();
+            assert_eq!(
+                OutputPk::from_unchecked(XOnlyPk::from(spend_key.public_key(SECP256K1))),
+                outputs[0],
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn bip352_same_scan_key() {
+        let scanner = bip352_scanner();
+        let address = SilentAddress::from_str(BIP352_ADDRESS).unwrap();
+        let other = SilentAddress::from_str(BIP352_ADDRESS_SAME_SCAN).unwrap();
+        assert_eq!(other.scan_pk, address.scan_pk);
+        assert_ne!(other.spend_pk, address.spend_pk);
+
+        let input_keys =
+            BIP352_VECTORS[0].1.map(|(no, xonly)| (bip352_sk(BIP352_INPUT_KEYS[no]), xonly));
+        let sender = SilentPaymentSender::new(bip352_outpoints(), input_keys).unwrap();
+        let outputs = sender.derive_outputs(&[address, address, other]);
+        assert_eq!(outputs[0].to_byte_array().to_hex(), BIP352_VECTORS[0].2);
+        assert_ne!(outputs[1], outputs[0]);
+        // The counter `k` is shared by all recipients with the same scan key
+        assert_ne!(outputs[2], sender.derive_outputs(&[other])[0]);
+
+        let input_pks = input_keys.map(bip352_input_pk);
+        let found = scanner.scan(bip352_outpoints(), input_pks, &outputs).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].output_pk, outputs[0]);
+        assert_eq!(found[1].output_pk, outputs[1]);
+    }
+
+    #[test]
+    fn no_inputs() {
+        assert_eq!(SilentPaymentSender::new(outpoints(), []), Err(SilentPaymentError::NoInputs));
+        assert_eq!(
+            SilentPaymentSender::new([], [(sk(1), false)]),
+            Err(SilentPaymentError::NoInputs)
+        );
+        let key = sk(1);
+        assert_eq!(
+            SilentPaymentSender::new(outpoints(), [(key, false), (key.negate(), false)]),
+            Err(SilentPaymentError::ZeroKeySum)
+        );
+    }
+}
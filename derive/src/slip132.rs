@@ -0,0 +1,182 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SLIP-132 extended key version bytes, which encode the script type of the wallet the
+//! extended key is used with.
+
+use crate::xpub::{
+    XPRIV_MAINNET_MAGIC, XPRIV_TESTNET_MAGIC, XPUB_MAINNET_MAGIC, XPUB_TESTNET_MAGIC,
+};
+
+/// Application of an extended key, implied by its SLIP-132 version bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[display(lowercase)]
+pub enum KeyApplication {
+    /// P2PKH or any other script type (`xpub`/`tpub`); the standard BIP-32 versions.
+    #[default]
+    Legacy,
+
+    /// P2WPKH nested in P2SH (`ypub`/`upub`).
+    #[display("sh-wpkh")]
+    ShWpkh,
+
+    /// Native P2WPKH (`zpub`/`vpub`).
+    Wpkh,
+
+    /// P2WSH multisig nested in P2SH (`Ypub`/`Upub`).
+    #[display("sh-wsh-multi")]
+    ShWshMulti,
+
+    /// Native P2WSH multisig (`Zpub`/`Vpub`).
+    #[display("wsh-multi")]
+    WshMulti,
+}
+
+/// Version bytes: application, mainnet xpub, mainnet xpriv, testnet xpub, testnet xpriv.
+const VERSIONS: [(KeyApplication, [[u8; 4]; 4]); 5] = [
+    (KeyApplication::Legacy, [
+        XPUB_MAINNET_MAGIC,
+        XPRIV_MAINNET_MAGIC,
+        XPUB_TESTNET_MAGIC,
+        XPRIV_TESTNET_MAGIC,
+    ]),
+    (KeyApplication::ShWpkh, [
+        [0x04, 0x9D, 0x7C, 0xB2],
+        [0x04, 0x9D, 0x78, 0x78],
+        [0x04, 0x4A, 0x52, 0x62],
+        [0x04, 0x4A, 0x4E, 0x28],
+    ]),
+    (KeyApplication::Wpkh, [
+        [0x04, 0xB2, 0x47, 0x46],
+        [0x04, 0xB2, 0x43, 0x0C],
+        [0x04, 0x5F, 0x1C, 0xF6],
+        [0x04, 0x5F, 0x18, 0xBC],
+    ]),
+    (KeyApplication::ShWshMulti, [
+        [0x02, 0x95, 0xB4, 0x3F],
+        [0x02, 0x95, 0xB0, 0x05],
+        [0x02, 0x42, 0x89, 0xEF],
+        [0x02, 0x42, 0x85, 0xB5],
+    ]),
+    (KeyApplication::WshMulti, [
+        [0x02, 0xAA, 0x7E, 0xD3],
+        [0x02, 0xAA, 0x7A, 0x99],
+        [0x02, 0x57, 0x54, 0x83],
+        [0x02, 0x57, 0x50, 0x48],
+    ]),
+];
+
+impl KeyApplication {
+    const fn versions(self) -> [[u8; 4]; 4] { VERSIONS[self as usize].1 }
+
+    /// Version bytes of an extended public key.
+    pub const fn xpub_version(self, testnet: bool) -> [u8; 4] {
+        self.versions()[if testnet { 2 } else { 0 }]
+    }
+
+    /// Version bytes of an extended private key.
+    pub const fn xpriv_version(self, testnet: bool) -> [u8; 4] {
+        self.versions()[if testnet { 3 } else { 1 }]
+    }
+
+    /// Detects key application and whether the key is a testnet key from the version bytes
+    /// of an extended public key.
+    pub fn with_xpub_version(version: [u8; 4]) -> Option<(Self, bool)> {
+        VERSIONS.iter().find_map(|(app, versions)| match version {
+            v if v == versions[0] => Some((*app, false)),
+            v if v == versions[2] => Some((*app, true)),
+            _ => None,
+        })
+    }
+
+    /// Detects key application and whether the key is a testnet key from the version bytes
+    /// of an extended private key.
+    pub fn with_xpriv_version(version: [u8; 4]) -> Option<(Self, bool)> {
+        VERSIONS.iter().find_map(|(app, versions)| match version {
+            v if v == versions[1] => Some((*app, false)),
+            v if v == versions[3] => Some((*app, true)),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{Xpriv, Xpub};
+
+    const XPRIV: &str = "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF";
+
+    #[test]
+    fn versions() {
+        for (app, _) in VERSIONS {
+            for testnet in [false, true] {
+                assert_eq!(
+                    KeyApplication::with_xpub_version(app.xpub_version(testnet)),
+                    Some((app, testnet))
+                );
+                assert_eq!(
+                    KeyApplication::with_xpriv_version(app.xpriv_version(testnet)),
+                    Some((app, testnet))
+                );
+            }
+        }
+        assert_eq!(KeyApplication::with_xpub_version([0x04, 0x88, 0xAD, 0xE4]), None);
+    }
+
+    #[test]
+    fn prefixes() {
+        let mainnet = Xpriv::from_str(XPRIV).unwrap();
+        let testnet_xpriv = Xpriv::new_master(true, &[0x42; 32]);
+        for (app, testnet, pub_prefix, priv_prefix) in [
+            (KeyApplication::Legacy, false, "xpub", "xprv"),
+            (KeyApplication::Legacy, true, "tpub", "tprv"),
+            (KeyApplication::ShWpkh, false, "ypub", "yprv"),
+            (KeyApplication::ShWpkh, true, "upub", "uprv"),
+            (KeyApplication::Wpkh, false, "zpub", "zprv"),
+            (KeyApplication::Wpkh, true, "vpub", "vprv"),
+            (KeyApplication::ShWshMulti, false, "Ypub", "Yprv"),
+            (KeyApplication::ShWshMulti, true, "Upub", "Uprv"),
+            (KeyApplication::WshMulti, false, "Zpub", "Zprv"),
+            (KeyApplication::WshMulti, true, "Vpub", "Vprv"),
+        ] {
+            let xpriv = if testnet { testnet_xpriv.clone() } else { mainnet.clone() };
+            let xpub = xpriv.to_xpub();
+            let s = xpub.to_string_slip132(app);
+            assert!(s.starts_with(pub_prefix));
+            assert_eq!(Xpub::from_str_slip132(&s).unwrap(), (xpub, app));
+            assert_eq!(Xpub::from_str(&s).unwrap(), xpub);
+
+            let s = xpriv.to_string_slip132(app);
+            assert!(s.starts_with(priv_prefix));
+            assert_eq!(Xpriv::from_str_slip132(&s).unwrap(), (xpriv.clone(), app));
+            assert_eq!(Xpriv::from_str(&s).unwrap(), xpriv);
+        }
+    }
+}
@@ -0,0 +1,736 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SLIP-39 Shamir's secret-sharing for mnemonic codes.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use bitcoin_hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+
+use crate::xpub::erase;
+
+/// Number of words in a SLIP-39 wordlist.
+pub const SLIP39_WORDLIST_LEN: usize = 1024;
+
+/// Maximal number of groups and of member shares in a group.
+pub const SLIP39_MAX_SHARES: u8 = 16;
+
+/// Number of words in the share header: identifier, extendable flag, iteration exponent,
+/// group and member parameters.
+const HEADER_WORDS: usize = 4;
+
+/// Number of words in the share checksum.
+const CHECKSUM_WORDS: usize = 3;
+
+/// Minimal length of the master secret, in bytes.
+const MIN_SECRET_LEN: usize = 16;
+
+/// Number of Feistel rounds in the master secret encryption.
+const ROUND_COUNT: u8 = 4;
+
+/// Base number of PBKDF2 iterations, split among the Feistel rounds.
+const BASE_ITERATIONS: u32 = 10000;
+
+/// Share index holding the shared secret.
+const SECRET_INDEX: u8 = 255;
+
+/// Share index holding the shared secret digest.
+const DIGEST_INDEX: u8 = 254;
+
+/// Length of the shared secret digest.
+const DIGEST_LEN: usize = 4;
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Slip39Error {
+    /// invalid number of share words {0}.
+    WordCount(usize),
+
+    /// word '{0}' is absent in the SLIP-39 wordlist.
+    UnknownWord(String),
+
+    /// invalid share checksum.
+    Checksum,
+
+    /// share value has invalid padding.
+    Padding,
+
+    /// invalid master secret length of {0} bytes; it must be an even number of at least 16
+    /// bytes.
+    SecretLength(usize),
+
+    /// iteration exponent {0} exceeds the maximal value of 15.
+    IterationExponent(u8),
+
+    /// invalid group threshold {0} for {1} groups.
+    GroupThreshold(u8, usize),
+
+    /// invalid member threshold {0} for {1} members of the group; multiple member shares with
+    /// the threshold of 1 are not allowed.
+    MemberThreshold(u8, u8),
+
+    /// no shares were provided.
+    NoShares,
+
+    /// shares belong to different secrets or have inconsistent parameters.
+    MismatchedShares,
+
+    /// share group or member index is out of range or repeated.
+    InvalidIndex,
+
+    /// insufficient number of shares to recover the secret.
+    InsufficientShares,
+
+    /// invalid digest of the shared secret; some of the shares are corrupted.
+    Digest,
+}
+
+/// Wordlist for SLIP-39 shares, with words ordered by their index.
+pub fn slip39_wordlist() -> &'static [&'static str] {
+    static WORDLIST: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDLIST.get_or_init(|| {
+        let list = include_str!("wordlists/slip39.txt").lines().collect::<Vec<_>>();
+        debug_assert_eq!(list.len(), SLIP39_WORDLIST_LEN);
+        list
+    })
+}
+
+/// SLIP-39 mnemonic share of a master secret.
+///
+/// Shares are produced with [`Slip39Share::split`] and recovered into the master secret with
+/// [`Slip39Share::combine`]; the recovered master secret is used as a BIP-32 seed. The share
+/// value is erased from the memory when the share is dropped.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Slip39Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Slip39Share {
+    /// Splits master secret into groups of shares.
+    ///
+    /// Any `group_threshold` groups out of `groups` are required to recover the secret, where
+    /// each group is described by its member threshold and member count. The master secret is
+    /// encrypted with the passphrase (which may be empty and must consist of printable ASCII
+    /// characters) using `(10000 << iteration_exponent)` PBKDF2 iterations.
+    ///
+    /// The function doesn't generate randomness by itself; `random` must fill the provided
+    /// buffers from a cryptographically secure random source.
+    pub fn split(
+        master_secret: &[u8],
+        passphrase: &str,
+        group_threshold: u8,
+        groups: &[(u8, u8)],
+        iteration_exponent: u8,
+        extendable: bool,
+        random: &mut impl FnMut(&mut [u8]),
+    ) -> Result<Vec<Vec<Slip39Share>>, Slip39Error> {
+        if master_secret.len() < MIN_SECRET_LEN || master_secret.len() % 2 != 0 {
+            return Err(Slip39Error::SecretLength(master_secret.len()));
+        }
+        if iteration_exponent > 0x0F {
+            return Err(Slip39Error::IterationExponent(iteration_exponent));
+        }
+        if group_threshold == 0
+            || group_threshold as usize > groups.len()
+            || groups.len() > SLIP39_MAX_SHARES as usize
+        {
+            return Err(Slip39Error::GroupThreshold(group_threshold, groups.len()));
+        }
+        for (threshold, count) in groups.iter().copied() {
+            if threshold == 0
+                || threshold > count
+                || count > SLIP39_MAX_SHARES
+                || (threshold == 1 && count > 1)
+            {
+                return Err(Slip39Error::MemberThreshold(threshold, count));
+            }
+        }
+
+        let mut id = [0u8; 2];
+        random(&mut id);
+        let identifier = u16::from_be_bytes(id) & 0x7FFF;
+
+        let ems = feistel(
+            master_secret,
+            passphrase,
+            iteration_exponent,
+            identifier,
+            extendable,
+            0..ROUND_COUNT,
+        );
+        let group_shares = split_secret(group_threshold, groups.len() as u8, &ems, random);
+        Ok(group_shares
+            .into_iter()
+            .zip(groups)
+            .map(|((group_index, group_secret), (member_threshold, member_count))| {
+                split_secret(*member_threshold, *member_count, &group_secret, random)
+                    .into_iter()
+                    .map(|(member_index, value)| Slip39Share {
+                        identifier,
+                        extendable,
+                        iteration_exponent,
+                        group_index,
+                        group_threshold,
+                        group_count: groups.len() as u8,
+                        member_index,
+                        member_threshold: *member_threshold,
+                        value,
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Recovers master secret from the shares, decrypting it with the passphrase.
+    ///
+    /// Shares from groups which have fewer shares than the group member threshold are ignored.
+    pub fn combine(shares: &[Slip39Share], passphrase: &str) -> Result<Vec<u8>, Slip39Error> {
+        let first = shares.first().ok_or(Slip39Error::NoShares)?;
+        let mut groups = BTreeMap::<u8, BTreeMap<u8, &Slip39Share>>::new();
+        for share in shares {
+            if share.identifier != first.identifier
+                || share.extendable != first.extendable
+                || share.iteration_exponent != first.iteration_exponent
+                || share.group_threshold != first.group_threshold
+                || share.group_count != first.group_count
+                || share.value.len() != first.value.len()
+            {
+                return Err(Slip39Error::MismatchedShares);
+            }
+            if share.group_index >= share.group_count {
+                return Err(Slip39Error::InvalidIndex);
+            }
+            let group = groups.entry(share.group_index).or_default();
+            if group.values().any(|other| other.member_threshold != share.member_threshold) {
+                return Err(Slip39Error::MismatchedShares);
+            }
+            if group.insert(share.member_index, share).is_some() {
+                return Err(Slip39Error::InvalidIndex);
+            }
+        }
+
+        let group_shares = groups
+            .into_iter()
+            .filter_map(|(group_index, members)| {
+                let threshold = members.values().next()?.member_threshold;
+                if members.len() < threshold as usize {
+                    return None;
+                }
+                let members = members
+                    .into_iter()
+                    .take(threshold as usize)
+                    .map(|(index, share)| (index, share.value.clone()))
+                    .collect::<Vec<_>>();
+                Some(recover_secret(threshold, &members).map(|secret| (group_index, secret)))
+            })
+            .take(first.group_threshold as usize)
+            .collect::<Result<Vec<_>, _>>()?;
+        if group_shares.len() < first.group_threshold as usize {
+            return Err(Slip39Error::InsufficientShares);
+        }
+
+        let ems = recover_secret(first.group_threshold, &group_shares)?;
+        Ok(feistel(
+            &ems,
+            passphrase,
+            first.iteration_exponent,
+            first.identifier,
+            first.extendable,
+            (0..ROUND_COUNT).rev(),
+        ))
+    }
+
+    /// Parses share from its mnemonic words.
+    pub fn parse(s: &str) -> Result<Self, Slip39Error> {
+        let wordlist = slip39_wordlist();
+        let words = s
+            .split_whitespace()
+            .map(|word| {
+                wordlist
+                    .iter()
+                    .position(|w| *w == word)
+                    .map(|pos| pos as u16)
+                    .ok_or_else(|| Slip39Error::UnknownWord(word.to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let value_words = words.len().saturating_sub(HEADER_WORDS + CHECKSUM_WORDS);
+        let padding = value_words * 10 % 16;
+        if padding > 8 || value_words * 10 - padding < MIN_SECRET_LEN * 8 {
+            return Err(Slip39Error::WordCount(words.len()));
+        }
+
+        let header = words[..HEADER_WORDS].iter().fold(0u64, |acc, w| (acc << 10) | *w as u64);
+        let extendable = header & (1 << 24) != 0;
+        if checksum(customization(extendable), &words) != 1 {
+            return Err(Slip39Error::Checksum);
+        }
+
+        let value_words = &words[HEADER_WORDS..words.len() - CHECKSUM_WORDS];
+        let mut value = Vec::with_capacity((value_words.len() * 10 - padding) / 8);
+        let mut acc = 0u32;
+        let mut bits = 0usize;
+        let mut skip = padding;
+        for word in value_words {
+            acc = (acc << 10) | *word as u32;
+            bits += 10;
+            if skip > 0 {
+                if acc >> (bits - skip) != 0 {
+                    return Err(Slip39Error::Padding);
+                }
+                bits -= skip;
+                skip = 0;
+            }
+            while bits >= 8 {
+                bits -= 8;
+                value.push((acc >> bits) as u8);
+            }
+            acc &= (1 << bits) - 1;
+        }
+
+        let group_threshold = ((header >> 12) & 0x0F) as u8 + 1;
+        let group_count = ((header >> 8) & 0x0F) as u8 + 1;
+        if group_threshold > group_count {
+            return Err(Slip39Error::GroupThreshold(group_threshold, group_count as usize));
+        }
+        Ok(Slip39Share {
+            identifier: (header >> 25) as u16,
+            extendable,
+            iteration_exponent: ((header >> 20) & 0x0F) as u8,
+            group_index: ((header >> 16) & 0x0F) as u8,
+            group_threshold,
+            group_count,
+            member_index: ((header >> 4) & 0x0F) as u8,
+            member_threshold: (header & 0x0F) as u8 + 1,
+            value,
+        })
+    }
+
+    /// Random identifier shared by all shares of the same master secret.
+    pub fn identifier(&self) -> u16 { self.identifier }
+
+    /// Whether the master secret encryption doesn't depend on the identifier, allowing to
+    /// produce new shares for the same master secret with a different identifier.
+    pub fn is_extendable(&self) -> bool { self.extendable }
+
+    /// Exponent of the number of PBKDF2 iterations used in the master secret encryption.
+    pub fn iteration_exponent(&self) -> u8 { self.iteration_exponent }
+
+    /// Index of the group the share belongs to.
+    pub fn group_index(&self) -> u8 { self.group_index }
+
+    /// Number of groups required to recover the master secret.
+    pub fn group_threshold(&self) -> u8 { self.group_threshold }
+
+    /// Total number of groups.
+    pub fn group_count(&self) -> u8 { self.group_count }
+
+    /// Index of the share within its group.
+    pub fn member_index(&self) -> u8 { self.member_index }
+
+    /// Number of shares required to recover the group secret.
+    pub fn member_threshold(&self) -> u8 { self.member_threshold }
+
+    /// Word indexes of the share mnemonic.
+    fn words(&self) -> Vec<u16> {
+        let header = ((self.identifier as u64) << 25)
+            | ((self.extendable as u64) << 24)
+            | ((self.iteration_exponent as u64) << 20)
+            | ((self.group_index as u64) << 16)
+            | (((self.group_threshold - 1) as u64) << 12)
+            | (((self.group_count - 1) as u64) << 8)
+            | ((self.member_index as u64) << 4)
+            | (self.member_threshold - 1) as u64;
+        let mut words = (0..HEADER_WORDS)
+            .rev()
+            .map(|no| ((header >> (no * 10)) & 0x3FF) as u16)
+            .collect::<Vec<_>>();
+
+        let value_words = (self.value.len() * 8).div_ceil(10);
+        let mut acc = 0u32;
+        let mut bits = value_words * 10 - self.value.len() * 8;
+        for byte in &self.value {
+            acc = (acc << 8) | *byte as u32;
+            bits += 8;
+            if bits >= 10 {
+                bits -= 10;
+                words.push((acc >> bits) as u16 & 0x3FF);
+                acc &= (1 << bits) - 1;
+            }
+        }
+
+        words.extend([0; CHECKSUM_WORDS]);
+        let chk = checksum(customization(self.extendable), &words) ^ 1;
+        let len = words.len();
+        for no in 0..CHECKSUM_WORDS {
+            words[len - 1 - no] = ((chk >> (no * 10)) & 0x3FF) as u16;
+        }
+        words
+    }
+}
+
+impl Drop for Slip39Share {
+    fn drop(&mut self) { self.value.iter_mut().for_each(erase) }
+}
+
+impl Debug for Slip39Share {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Slip39Share")
+            .field("identifier", &self.identifier)
+            .field("group_index", &self.group_index)
+            .field("member_index", &self.member_index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Slip39Share {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let wordlist = slip39_wordlist();
+        for (no, word) in self.words().into_iter().enumerate() {
+            if no > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(wordlist[word as usize])?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Slip39Share {
+    type Err = Slip39Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::parse(s) }
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    match extendable {
+        false => b"shamir",
+        true => b"shamir_extendable",
+    }
+}
+
+/// RS1024 checksum over the customization string and the share words.
+fn checksum(customization: &[u8], words: &[u16]) -> u32 {
+    const GEN: [u32; 10] = [
+        0x00E0_E040,
+        0x01C1_C080,
+        0x0383_8100,
+        0x0707_0200,
+        0x0E0E_0009,
+        0x1C0C_2412,
+        0x3808_6C24,
+        0x3090_FC48,
+        0x21B1_F890,
+        0x03F3_F120,
+    ];
+    customization.iter().map(|b| *b as u32).chain(words.iter().map(|w| *w as u32)).fold(
+        1u32,
+        |chk, value| {
+            let top = chk >> 20;
+            let chk = ((chk & 0xFFFFF) << 10) ^ value;
+            (0..10).filter(|i| (top >> i) & 1 == 1).fold(chk, |chk, i| chk ^ GEN[i])
+        },
+    )
+}
+
+/// Four-round Feistel network encrypting (or decrypting, with the reverse round order) the
+/// master secret.
+fn feistel(
+    secret: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    rounds: impl Iterator<Item = u8>,
+) -> Vec<u8> {
+    let half = secret.len() / 2;
+    let (mut l, mut r) = (secret[..half].to_vec(), secret[half..].to_vec());
+    let mut salt = Vec::new();
+    if !extendable {
+        salt.extend(b"shamir");
+        salt.extend(identifier.to_be_bytes());
+    }
+    let iterations = (BASE_ITERATIONS << iteration_exponent) / ROUND_COUNT as u32;
+    for round in rounds {
+        let mut password = vec![round];
+        password.extend(passphrase.as_bytes());
+        let mut f = pbkdf2_sha256(&password, &[salt.as_slice(), &r].concat(), iterations, half);
+        f.iter_mut().zip(&l).for_each(|(f, l)| *f ^= l);
+        l = r;
+        r = f;
+    }
+    r.extend(l);
+    r
+}
+
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(len);
+    for block in 1u32.. {
+        if output.len() >= len {
+            break;
+        }
+        let mut engine = HmacEngine::<sha256::Hash>::new(password);
+        engine.input(salt);
+        engine.input(&block.to_be_bytes());
+        let mut u = Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut engine = HmacEngine::<sha256::Hash>::new(password);
+            engine.input(&u);
+            u = Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+            t.iter_mut().zip(u).for_each(|(t, u)| *t ^= u);
+        }
+        output.extend(t);
+    }
+    output.truncate(len);
+    output
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut engine = HmacEngine::<sha256::Hash>::new(random);
+    engine.input(secret);
+    let mut digest = [0u8; DIGEST_LEN];
+    digest
+        .copy_from_slice(&Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()[..DIGEST_LEN]);
+    digest
+}
+
+fn split_secret(
+    threshold: u8,
+    count: u8,
+    secret: &[u8],
+    random: &mut impl FnMut(&mut [u8]),
+) -> Vec<(u8, Vec<u8>)> {
+    if threshold == 1 {
+        return (0..count).map(|index| (index, secret.to_vec())).collect();
+    }
+
+    let mut base = (0..threshold - 2)
+        .map(|index| {
+            let mut share = vec![0u8; secret.len()];
+            random(&mut share);
+            (index, share)
+        })
+        .collect::<Vec<_>>();
+    let mut digest_share = vec![0u8; secret.len()];
+    random(&mut digest_share[DIGEST_LEN..]);
+    let digest = digest(&digest_share[DIGEST_LEN..], secret);
+    digest_share[..DIGEST_LEN].copy_from_slice(&digest);
+    base.push((DIGEST_INDEX, digest_share));
+    base.push((SECRET_INDEX, secret.to_vec()));
+
+    let mut shares = base[..threshold as usize - 2].to_vec();
+    shares.extend((threshold - 2..count).map(|index| (index, interpolate(&base, index))));
+    shares
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, Slip39Error> {
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest_share = interpolate(shares, DIGEST_INDEX);
+    if digest(&digest_share[DIGEST_LEN..], &secret) != digest_share[..DIGEST_LEN] {
+        return Err(Slip39Error::Digest);
+    }
+    Ok(secret)
+}
+
+/// Logarithm and exponent tables of GF(256) with the Rijndael polynomial.
+const GF256: ([u8; 256], [u8; 255]) = {
+    let mut log = [0u8; 256];
+    let mut exp = [0u8; 255];
+    let mut poly = 1u16;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = poly as u8;
+        log[poly as usize] = i as u8;
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11B;
+        }
+        i += 1;
+    }
+    (log, exp)
+};
+
+/// Lagrange interpolation of the polynomial defined by the shares at point `x`.
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Vec<u8> {
+    if let Some((_, value)) = shares.iter().find(|(index, _)| *index == x) {
+        return value.clone();
+    }
+    let (log, exp) = &GF256;
+    let log_prod = shares.iter().map(|(index, _)| log[(*index ^ x) as usize] as u32).sum::<u32>();
+    let mut result = vec![0u8; shares[0].1.len()];
+    for (index, value) in shares {
+        let log_basis = (log_prod + 255 * shares.len() as u32
+            - log[(*index ^ x) as usize] as u32
+            - shares
+                .iter()
+                .filter(|(other, _)| other != index)
+                .map(|(other, _)| log[(*index ^ *other) as usize] as u32)
+                .sum::<u32>())
+            % 255;
+        for (res, byte) in result.iter_mut().zip(value) {
+            if *byte != 0 {
+                *res ^= exp[((log[*byte as usize] as u32 + log_basis) % 255) as usize];
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::hex::ToHex;
+
+    use super::*;
+
+    // Test vectors from SLIP-39 reference implementation, all using "TREZOR" passphrase
+    const VECTORS: [(&[&str], &str); 3] = [
+        (
+            &["duckling enlarge academic academic agency result length solution fridge kidney \
+               coal piece deal husband erode duke ajar critical decision keyboard"],
+            "bb54aac4b89dc868ba37d9cc21b2cece",
+        ),
+        (
+            &[
+                "shadow pistol academic always adequate wildlife fancy gross oasis cylinder \
+                 mustang wrist rescue view short owner flip making coding armed",
+                "shadow pistol academic acid actress prayer class unknown daughter sweater depict \
+                 flip twice unkind craft early superior advocate guest smoking",
+            ],
+            "b43ceb7e57a0ea8766221624d01b0864",
+        ),
+        (
+            &["theory painting academic academic armed sweater year military elder discuss acne \
+               wildlife boring employer fused large satoshi bundle carbon diagnose anatomy \
+               hamster leaves tracks paces beyond phantom capital marvel lips brave detect luck"],
+            "989baf9dcaad5b10ca33dfd8cc75e42477025dce88ae83e75a230086a0e00e92",
+        ),
+    ];
+
+    fn random() -> impl FnMut(&mut [u8]) {
+        let mut counter = 0u8;
+        move |buf: &mut [u8]| {
+            for byte in buf {
+                counter = counter.wrapping_mul(73).wrapping_add(41);
+                *byte = counter;
+            }
+        }
+    }
+
+    #[test]
+    fn wordlist() {
+        let list = slip39_wordlist();
+        assert_eq!(list.len(), SLIP39_WORDLIST_LEN);
+        assert_eq!(list[0], "academic");
+        assert_eq!(list[1023], "zero");
+    }
+
+    #[test]
+    fn vectors() {
+        for (mnemonics, secret) in VECTORS {
+            let shares =
+                mnemonics.iter().map(|s| Slip39Share::from_str(s).unwrap()).collect::<Vec<_>>();
+            for (share, mnemonic) in shares.iter().zip(mnemonics) {
+                assert_eq!(&share.to_string(), mnemonic);
+            }
+            assert_eq!(Slip39Share::combine(&shares, "TREZOR").unwrap().to_hex(), secret);
+        }
+    }
+
+    #[test]
+    fn split_combine() {
+        let secret = *b"ABCDEFGHIJKLMNOPQRSTUVWXYZ012345";
+        for extendable in [false, true] {
+            let groups = Slip39Share::split(
+                &secret,
+                "TREZOR",
+                2,
+                &[(1, 1), (2, 3), (3, 5)],
+                0,
+                extendable,
+                &mut random(),
+            )
+            .unwrap();
+            assert_eq!(groups.iter().map(Vec::len).collect::<Vec<_>>(), [1, 3, 5]);
+
+            let shares =
+                [&groups[1][2], &groups[2][4], &groups[1][0], &groups[2][1], &groups[2][3]]
+                    .map(|share| Slip39Share::from_str(&share.to_string()).unwrap());
+            assert!(shares.iter().all(|share| share.is_extendable() == extendable));
+            assert_eq!(Slip39Share::combine(&shares, "TREZOR").unwrap(), secret);
+            assert_ne!(Slip39Share::combine(&shares, "").unwrap(), secret);
+
+            let shares = [groups[0][0].clone(), groups[1][1].clone(), groups[2][0].clone()];
+            assert_eq!(
+                Slip39Share::combine(&shares, "TREZOR"),
+                Err(Slip39Error::InsufficientShares)
+            );
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            Slip39Share::split(&[0u8; 15], "", 1, &[(1, 1)], 0, false, &mut random()),
+            Err(Slip39Error::SecretLength(15))
+        );
+        assert_eq!(
+            Slip39Share::split(&[0u8; 16], "", 2, &[(1, 1)], 0, false, &mut random()),
+            Err(Slip39Error::GroupThreshold(2, 1))
+        );
+        assert_eq!(
+            Slip39Share::split(&[0u8; 16], "", 1, &[(1, 2)], 0, false, &mut random()),
+            Err(Slip39Error::MemberThreshold(1, 2))
+        );
+        assert_eq!(
+            Slip39Share::from_str(&VECTORS[0].0[0].replace("keyboard", "kidney")),
+            Err(Slip39Error::Checksum)
+        );
+        assert_eq!(
+            Slip39Share::from_str(&VECTORS[0].0[0].replace("keyboard", "bitcoin")),
+            Err(Slip39Error::UnknownWord(s!("bitcoin")))
+        );
+        assert_eq!(
+            Slip39Share::from_str("duckling enlarge academic"),
+            Err(Slip39Error::WordCount(3))
+        );
+
+        let mut shares =
+            VECTORS[1].0.iter().map(|s| Slip39Share::from_str(s).unwrap()).collect::<Vec<_>>();
+        shares[1].value[0] ^= 1;
+        assert_eq!(Slip39Share::combine(&shares, "TREZOR"), Err(Slip39Error::Digest));
+    }
+}
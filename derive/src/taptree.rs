@@ -20,17 +20,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::iter::Peekable;
 use std::ops::Deref;
 use std::{slice, vec};
 
+use amplify::confinement::Confined;
 use amplify::num::u7;
+use amplify::ByteArray;
 use bc::{
-    ControlBlock, InternalPk, LeafScript, OutputPk, Parity, TapLeafHash, TapMerklePath,
-    TapNodeHash, TapScript,
+    ControlBlock, InternalPk, LeafScript, OutputPk, Parity, TapBranchHash, TapLeafHash,
+    TapMerklePath, TapNodeHash, TapScript,
 };
 use commit_verify::merkle::MerkleBuoy;
 
-use crate::{KeyOrigin, Terminal, XpubOrigin};
+use crate::{KeyOrigin, TapTweak, Terminal, TweakedPk, XpubOrigin};
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
 pub enum InvalidTree {
@@ -41,6 +44,9 @@ pub enum InvalidTree {
     #[from(FinalizedTree)]
     #[display("tap tree contains too many script leafs which doesn't fit a single Merkle tree")]
     MountainRange,
+
+    #[display("tap tree contains script leafs deeper than 127 levels")]
+    TooDeep,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
@@ -139,17 +145,175 @@ impl TapTree {
         builder.finish()
     }
 
-    pub fn merkle_root(&self) -> TapNodeHash {
-        if self.0.len() == 1 {
-            TapLeafHash::with_leaf_script(&self.0[0].script).into()
-        } else {
-            todo!("implement TapTree::merkle_root for trees with more than one leaf")
+    pub fn merkle_root(&self) -> TapNodeHash { self.merkle_paths().0 }
+
+    /// Computes Merkle root of the tree together with Merkle paths for each of the leafs, in the
+    /// same order as the leafs are present in the tree.
+    ///
+    /// Merkle path contains hashes of the sibling nodes starting from the leaf level up to the
+    /// root.
+    pub fn merkle_paths(&self) -> (TapNodeHash, Vec<TapMerklePath>) {
+        // Stack of nodes (depth, hash, leaf indexes under the node) which are not yet merged
+        let mut stack = Vec::<(u8, TapNodeHash, Vec<usize>)>::with_capacity(self.0.len());
+        let mut paths = vec![Vec::<TapBranchHash>::new(); self.0.len()];
+        for (no, leaf) in self.0.iter().enumerate() {
+            let mut node = (
+                leaf.depth.to_u8(),
+                TapNodeHash::from(TapLeafHash::with_leaf_script(&leaf.script)),
+                vec![no],
+            );
+            while let Some((depth, hash, leafs)) = stack.pop() {
+                if depth != node.0 || depth == 0 {
+                    stack.push((depth, hash, leafs));
+                    break;
+                }
+                for no in &leafs {
+                    paths[*no].push(TapBranchHash::from(node.1.to_byte_array()));
+                }
+                for no in &node.2 {
+                    paths[*no].push(TapBranchHash::from(hash.to_byte_array()));
+                }
+                let mut merged = leafs;
+                merged.extend(node.2);
+                node = (depth - 1, TapBranchHash::with_nodes(hash, node.1).into(), merged);
+            }
+            stack.push(node);
         }
+        debug_assert_eq!(stack.len(), 1, "tap tree is always finalized");
+        let root = stack.pop().expect("tap tree is always non-empty").1;
+        let paths = paths
+            .into_iter()
+            .map(|path| {
+                TapMerklePath::from(
+                    Confined::try_from(path).expect("tap tree depth is always less than 128"),
+                )
+            })
+            .collect();
+        (root, paths)
     }
 
     pub fn into_vec(self) -> Vec<LeafInfo> { self.0 }
 }
 
+/// Node of a taproot script tree in its hierarchical form, which is an alternative to the flat
+/// depth-first list of leafs used by [`TapTree`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TapNode {
+    Leaf(LeafScript),
+    Branch(Box<TapBranch>),
+}
+
+/// Branch of a taproot script tree with two child nodes.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TapBranch {
+    pub left: TapNode,
+    pub right: TapNode,
+}
+
+impl TapNode {
+    pub fn leaf(script: impl Into<LeafScript>) -> Self { TapNode::Leaf(script.into()) }
+
+    pub fn branch(left: TapNode, right: TapNode) -> Self {
+        TapNode::Branch(Box::new(TapBranch { left, right }))
+    }
+
+    /// Computes hash of the node, which for the root node is the Merkle root of the tree.
+    pub fn node_hash(&self) -> TapNodeHash {
+        match self {
+            TapNode::Leaf(script) => TapLeafHash::with_leaf_script(script).into(),
+            TapNode::Branch(branch) => {
+                TapBranchHash::with_nodes(branch.left.node_hash(), branch.right.node_hash()).into()
+            }
+        }
+    }
+
+    fn collect_leafs(&self, depth: u8, leafs: &mut Vec<LeafInfo>) -> Result<(), InvalidTree> {
+        match self {
+            TapNode::Leaf(script) => leafs.push(LeafInfo {
+                depth: u7::try_from(depth).map_err(|_| InvalidTree::TooDeep)?,
+                script: script.clone(),
+            }),
+            TapNode::Branch(branch) => {
+                let depth = depth.checked_add(1).ok_or(InvalidTree::TooDeep)?;
+                branch.left.collect_leafs(depth, leafs)?;
+                branch.right.collect_leafs(depth, leafs)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn from_leafs(leafs: &mut Peekable<slice::Iter<LeafInfo>>, depth: u8) -> TapNode {
+        let leaf = leafs.peek().expect("tap tree is always finalized");
+        if leaf.depth.to_u8() == depth {
+            let leaf = leafs.next().expect("just peeked");
+            return TapNode::Leaf(leaf.script.clone());
+        }
+        let left = TapNode::from_leafs(leafs, depth + 1);
+        let right = TapNode::from_leafs(leafs, depth + 1);
+        TapNode::branch(left, right)
+    }
+}
+
+impl TryFrom<&TapNode> for TapTree {
+    type Error = InvalidTree;
+
+    fn try_from(node: &TapNode) -> Result<Self, Self::Error> {
+        let mut leafs = vec![];
+        node.collect_leafs(0, &mut leafs)?;
+        TapTree::from_leafs(leafs)
+    }
+}
+
+impl From<&TapTree> for TapNode {
+    fn from(tree: &TapTree) -> Self { TapNode::from_leafs(&mut tree.iter().peekable(), 0) }
+}
+
+/// Serializes control block for the use in a witness stack.
+///
+/// NB: consensus encoding of [`ControlBlock`] provided by `bp-consensus` loses the output key
+/// parity bit, thus this function must be used instead.
+pub fn serialize_control_block(control_block: &ControlBlock) -> Vec<u8> {
+    let mut data = Vec::with_capacity(33 + 32 * control_block.merkle_branch.len());
+    data.push(
+        control_block.leaf_version.to_consensus_u8()
+            | control_block.output_key_parity.to_consensus_u8(),
+    );
+    data.extend(control_block.internal_pk.to_byte_array());
+    for step in &control_block.merkle_branch {
+        data.extend(step.to_byte_array());
+    }
+    data
+}
+
+/// Computes Merkle root of the script tree from the leaf script and the Merkle path of a
+/// control block.
+pub fn control_block_merkle_root(control_block: &ControlBlock, script: &LeafScript) -> TapNodeHash {
+    control_block.merkle_branch.iter().fold(
+        TapNodeHash::from(TapLeafHash::with_leaf_script(script)),
+        |node, sibling| {
+            TapBranchHash::with_nodes(node, TapNodeHash::from(sibling.to_byte_array())).into()
+        },
+    )
+}
+
+/// Verifies that the control block and the leaf script commit to the taproot output key, as
+/// it is done by the consensus rules for script path spending.
+pub fn verify_control_block(
+    control_block: &ControlBlock,
+    script: &LeafScript,
+    output_pk: OutputPk,
+) -> bool {
+    if control_block.leaf_version != script.version {
+        return false;
+    }
+    let merkle_root = control_block_merkle_root(control_block, script);
+    let tweaked = TweakedPk {
+        output_pk,
+        parity: control_block.output_key_parity,
+    };
+    tweaked.verify(control_block.internal_pk, Some(merkle_root))
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 #[cfg_attr(
     feature = "serde",
@@ -179,7 +343,7 @@ pub struct ControlBlockFactory {
     merkle_root: TapNodeHash,
 
     #[getter(skip)]
-    merkle_path: TapMerklePath,
+    merkle_paths: Vec<TapMerklePath>,
     #[getter(skip)]
     remaining_leaves: Vec<LeafInfo>,
 }
@@ -187,14 +351,14 @@ pub struct ControlBlockFactory {
 impl ControlBlockFactory {
     #[inline]
     pub fn with(internal_pk: InternalPk, tap_tree: TapTree) -> Self {
-        let merkle_root = tap_tree.merkle_root();
-        let (output_pk, parity) = internal_pk.to_output_pk(Some(merkle_root));
+        let (merkle_root, merkle_paths) = tap_tree.merkle_paths();
+        let TweakedPk { output_pk, parity } = internal_pk.tap_tweak(Some(merkle_root));
         ControlBlockFactory {
             internal_pk,
             output_pk,
             parity,
             merkle_root,
-            merkle_path: empty!(),
+            merkle_paths,
             remaining_leaves: tap_tree.into_vec(),
         }
     }
@@ -208,13 +372,10 @@ impl Iterator for ControlBlockFactory {
 
     fn next(&mut self) -> Option<Self::Item> {
         let leaf = self.remaining_leaves.pop()?;
+        let merkle_path = self.merkle_paths.pop()?;
         let leaf_script = leaf.script;
-        let control_block = ControlBlock::with(
-            leaf_script.version,
-            self.internal_pk,
-            self.parity,
-            self.merkle_path.clone(),
-        );
+        let control_block =
+            ControlBlock::with(leaf_script.version, self.internal_pk, self.parity, merkle_path);
         Some((control_block, leaf_script))
     }
 }
@@ -246,3 +407,108 @@ impl TapDerivation {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bc::ConsensusDecode;
+
+    use super::*;
+
+    fn leaf(depth: u8, byte: u8) -> LeafInfo {
+        LeafInfo::tap_script(u7::with(depth), TapScript::from_unsafe(vec![byte]))
+    }
+
+    fn leaf_hash(leaf: &LeafInfo) -> TapNodeHash {
+        TapLeafHash::with_leaf_script(&leaf.script).into()
+    }
+
+    #[test]
+    fn merkle_root_single() {
+        let tree = TapTree::from_leafs([leaf(0, 0x51)]).unwrap();
+        assert_eq!(tree.merkle_root(), leaf_hash(&tree[0]));
+        assert!(tree.merkle_paths().1[0].is_empty());
+    }
+
+    #[test]
+    fn merkle_root_unbalanced() {
+        // {A,{B,C}}
+        let tree = TapTree::from_leafs([leaf(1, 0x51), leaf(2, 0x52), leaf(2, 0x53)]).unwrap();
+        let (a, b, c) = (leaf_hash(&tree[0]), leaf_hash(&tree[1]), leaf_hash(&tree[2]));
+        let bc = TapNodeHash::from(TapBranchHash::with_nodes(b, c));
+        let root = TapNodeHash::from(TapBranchHash::with_nodes(a, bc));
+        assert_eq!(tree.merkle_root(), root);
+
+        let (_, paths) = tree.merkle_paths();
+        assert_eq!(paths[0].to_vec(), vec![TapBranchHash::from(bc.to_byte_array())]);
+        assert_eq!(paths[1].to_vec(), vec![
+            TapBranchHash::from(c.to_byte_array()),
+            TapBranchHash::from(a.to_byte_array())
+        ]);
+    }
+
+    #[test]
+    fn control_blocks_commit_to_root() {
+        // {{A,B},{C,{D,E}}}
+        let tree = TapTree::from_leafs([
+            leaf(2, 0x51),
+            leaf(2, 0x52),
+            leaf(2, 0x53),
+            leaf(3, 0x54),
+            leaf(3, 0x55),
+        ])
+        .unwrap();
+        let root = tree.merkle_root();
+        // x-coordinate of the secp256k1 generator point
+        let internal_pk = InternalPk::from_bytes([
+            0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87,
+            0x0b, 0x07, 0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b,
+            0x16, 0xf8, 0x17, 0x98,
+        ])
+        .unwrap();
+        let factory = ControlBlockFactory::with(internal_pk, tree.clone());
+        let mut count = 0;
+        for (control_block, leaf_script) in factory {
+            let mut node = TapNodeHash::from(TapLeafHash::with_leaf_script(&leaf_script));
+            for sibling in &control_block.merkle_branch {
+                node = TapBranchHash::with_nodes(node, TapNodeHash::from(sibling.to_byte_array()))
+                    .into();
+            }
+            assert_eq!(node, root);
+            assert_eq!(control_block_merkle_root(&control_block, &leaf_script), root);
+
+            let (output_pk, _) = internal_pk.to_output_pk(Some(root));
+            assert!(verify_control_block(&control_block, &leaf_script, output_pk));
+            let other = LeafScript::from_tap_script(TapScript::from_unsafe(vec![0x60]));
+            assert!(!verify_control_block(&control_block, &other, output_pk));
+
+            let data = serialize_control_block(&control_block);
+            assert_eq!(data.len(), 33 + 32 * control_block.merkle_branch.len());
+            assert_eq!(ControlBlock::consensus_deserialize(data).unwrap(), control_block);
+            count += 1;
+        }
+        assert_eq!(count, tree.len());
+    }
+
+    #[test]
+    fn tap_node_hierarchy() {
+        // {A,{B,C}}
+        let tree = TapTree::from_leafs([leaf(1, 0x51), leaf(2, 0x52), leaf(2, 0x53)]).unwrap();
+        let node = TapNode::from(&tree);
+        let script = |byte: u8| TapScript::from_unsafe(vec![byte]);
+        assert_eq!(
+            node,
+            TapNode::branch(
+                TapNode::leaf(script(0x51)),
+                TapNode::branch(TapNode::leaf(script(0x52)), TapNode::leaf(script(0x53)))
+            )
+        );
+        assert_eq!(node.node_hash(), tree.merkle_root());
+        assert_eq!(TapTree::try_from(&node).unwrap(), tree);
+
+        let single = TapNode::leaf(script(0x51));
+        assert_eq!(TapTree::try_from(&single).unwrap(), TapTree::with_single_leaf(script(0x51)));
+
+        let deep = (0..128).fold(single.clone(), |node, _| TapNode::branch(node, single.clone()));
+        assert_eq!(TapTree::try_from(&deep), Err(InvalidTree::TooDeep));
+    }
+}
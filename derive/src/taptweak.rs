@@ -0,0 +1,123 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BIP-341 taproot key tweaking, shared by descriptors, which derive output
+//! keys, and signers, which tweak the private keys for key path spending.
+
+use bc::secp256k1::{Keypair, Scalar, SecretKey, SECP256K1};
+use bc::{InternalPk, OutputPk, Parity, TapNodeHash, XOnlyPk};
+use commit_verify::{DigestExt, Sha256};
+
+const TAG_TAPTWEAK: &[u8] = b"TapTweak";
+
+/// Computes BIP-341 `TapTweak` hash of an internal key and an optional script tree merkle root.
+pub fn tap_tweak_hash(internal_key: XOnlyPk, merkle_root: Option<TapNodeHash>) -> [u8; 32] {
+    let mut engine = Sha256::from_tag(TAG_TAPTWEAK);
+    engine.input_raw(&internal_key.to_byte_array());
+    if let Some(merkle_root) = merkle_root {
+        engine.input_raw(merkle_root.as_ref());
+    }
+    engine.finish()
+}
+
+fn tweak_scalar(internal_key: XOnlyPk, merkle_root: Option<TapNodeHash>) -> Scalar {
+    Scalar::from_be_bytes(tap_tweak_hash(internal_key, merkle_root))
+        .expect("hash value greater than curve order")
+}
+
+/// Taproot output key together with the parity of its full (non-x-only) form, which is
+/// required for constructing control blocks.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TweakedPk {
+    pub output_pk: OutputPk,
+    pub parity: Parity,
+}
+
+impl TweakedPk {
+    /// Checks that the output key is the internal key tweaked with the given merkle root.
+    pub fn verify(&self, internal_pk: InternalPk, merkle_root: Option<TapNodeHash>) -> bool {
+        let tweak = tweak_scalar(*internal_pk, merkle_root);
+        let parity = match self.parity {
+            Parity::Even => bc::secp256k1::Parity::Even,
+            Parity::Odd => bc::secp256k1::Parity::Odd,
+        };
+        internal_pk.tweak_add_check(SECP256K1, &self.output_pk, parity, tweak)
+    }
+}
+
+/// BIP-341 taproot tweaking of keys with an optional script tree merkle root.
+pub trait TapTweak {
+    type Tweaked;
+
+    /// Tweaks the key with the `TapTweak` hash of its x-only public key and the merkle root.
+    /// Key-only outputs must use `None` as the merkle root.
+    fn tap_tweak(&self, merkle_root: Option<TapNodeHash>) -> Self::Tweaked;
+}
+
+impl TapTweak for InternalPk {
+    type Tweaked = TweakedPk;
+
+    fn tap_tweak(&self, merkle_root: Option<TapNodeHash>) -> TweakedPk {
+        let (output_pk, parity) = self.to_output_pk(merkle_root);
+        TweakedPk { output_pk, parity }
+    }
+}
+
+impl TapTweak for Keypair {
+    type Tweaked = Keypair;
+
+    fn tap_tweak(&self, merkle_root: Option<TapNodeHash>) -> Keypair {
+        let internal_key = XOnlyPk::from(self.x_only_public_key().0);
+        self.add_xonly_tweak(SECP256K1, &tweak_scalar(internal_key, merkle_root))
+            .expect("negligible probability")
+    }
+}
+
+impl TapTweak for SecretKey {
+    type Tweaked = Keypair;
+
+    fn tap_tweak(&self, merkle_root: Option<TapNodeHash>) -> Keypair {
+        Keypair::from_secret_key(SECP256K1, self).tap_tweak(merkle_root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tweak_consistency() {
+        let sk = SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let internal_pk =
+            InternalPk::from_unchecked(XOnlyPk::from(sk.x_only_public_key(SECP256K1).0));
+        for merkle_root in [None, Some(TapNodeHash::from([7u8; 32]))] {
+            let tweaked = internal_pk.tap_tweak(merkle_root);
+            assert!(tweaked.verify(internal_pk, merkle_root));
+            assert!(!tweaked.verify(internal_pk, Some(TapNodeHash::from([8u8; 32]))));
+
+            let keypair = sk.tap_tweak(merkle_root);
+            let (xonly, parity) = keypair.x_only_public_key();
+            assert_eq!(XOnlyPk::from(xonly), *tweaked.output_pk);
+            assert_eq!(Parity::from(parity), tweaked.parity);
+        }
+    }
+}
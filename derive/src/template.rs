@@ -0,0 +1,406 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Derivation path components expanding into multiple indexes, used in descriptor templates and
+//! range imports.
+
+use core::fmt::{self, Display, Formatter};
+use core::ops::Range;
+use core::str::FromStr;
+
+use crate::{
+    DerivationIndex, DerivationPath, HardenedIndex, IdxBase, IndexParseError, NormalIndex,
+    HARDENED_INDEX_BOUNDARY,
+};
+
+/// Derivation path component which may expand into multiple indexes: a single index, an
+/// inclusive range of indexes (`{0-9}`) or a wildcard (`*`). Ranges and wildcards may be
+/// hardened.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, From)]
+pub enum RangedIndex {
+    /// Single index.
+    #[from]
+    #[from(NormalIndex)]
+    #[from(HardenedIndex)]
+    Index(DerivationIndex),
+
+    /// Inclusive range of child numbers.
+    Range {
+        first: NormalIndex,
+        last: NormalIndex,
+        hardened: bool,
+    },
+
+    /// Any child number.
+    Wildcard { hardened: bool },
+}
+
+impl RangedIndex {
+    /// Constructs inclusive range of child numbers, returning `None` if `first` exceeds `last`.
+    pub fn range(first: NormalIndex, last: NormalIndex, hardened: bool) -> Option<Self> {
+        if first > last {
+            return None;
+        }
+        Some(RangedIndex::Range {
+            first,
+            last,
+            hardened,
+        })
+    }
+
+    /// Detects whether the component expands into hardened indexes.
+    pub fn is_hardened(&self) -> bool {
+        match self {
+            RangedIndex::Index(index) => index.is_hardened(),
+            RangedIndex::Range { hardened, .. } | RangedIndex::Wildcard { hardened } => *hardened,
+        }
+    }
+
+    /// Detects whether the component is a wildcard.
+    pub fn is_wildcard(&self) -> bool { matches!(self, RangedIndex::Wildcard { .. }) }
+
+    /// Checks whether the component expands into the given `index`.
+    pub fn contains(&self, index: DerivationIndex) -> bool {
+        if self.is_hardened() != index.is_hardened() {
+            return false;
+        }
+        let (start, end) = self.bounds(0..HARDENED_INDEX_BOUNDARY);
+        (start..end).contains(&index.child_number())
+    }
+
+    /// Returns iterator over all indexes of the component. Since wildcards are unbounded, they
+    /// iterate only over child numbers in the `wildcard` range.
+    pub fn indexes(&self, wildcard: Range<u32>) -> impl Iterator<Item = DerivationIndex> {
+        let (start, end) = self.bounds(wildcard);
+        let hardened = self.is_hardened();
+        (start..end).map(move |child_number| index_at(child_number, hardened))
+    }
+
+    fn bounds(&self, wildcard: Range<u32>) -> (u32, u32) {
+        match self {
+            RangedIndex::Index(index) => (index.child_number(), index.child_number() + 1),
+            RangedIndex::Range { first, last, .. } => {
+                (first.child_number(), last.child_number() + 1)
+            }
+            RangedIndex::Wildcard { .. } => {
+                (wildcard.start, wildcard.end.min(HARDENED_INDEX_BOUNDARY))
+            }
+        }
+    }
+}
+
+fn index_at(child_number: u32, hardened: bool) -> DerivationIndex {
+    if hardened {
+        DerivationIndex::from_index(child_number + HARDENED_INDEX_BOUNDARY)
+    } else {
+        DerivationIndex::from_index(child_number)
+    }
+}
+
+impl Display for RangedIndex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RangedIndex::Index(index) => return Display::fmt(index, f),
+            RangedIndex::Range { first, last, .. } => write!(f, "{{{first}-{last}}}")?,
+            RangedIndex::Wildcard { .. } => f.write_str("*")?,
+        }
+        match self.is_hardened() {
+            true if f.alternate() => f.write_str("'"),
+            true => f.write_str("h"),
+            false => Ok(()),
+        }
+    }
+}
+
+impl FromStr for RangedIndex {
+    type Err = IndexParseError;
+
+    /// Parses component in form of an index, wildcard `*` or a range `{first-last}`, where the
+    /// range may also use square brackets `[first-last]`. Hardened components use either `h` or
+    /// `'` suffix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (body, hardened) = match s.strip_suffix(['h', 'H', '\'']) {
+            Some(body) => (body, true),
+            None => (s, false),
+        };
+        if body == "*" {
+            return Ok(RangedIndex::Wildcard { hardened });
+        }
+        let Some(range) = body
+            .strip_prefix('{')
+            .and_then(|b| b.strip_suffix('}'))
+            .or_else(|| body.strip_prefix('[').and_then(|b| b.strip_suffix(']')))
+        else {
+            return DerivationIndex::from_str(s).map(RangedIndex::Index);
+        };
+        let (first, last) =
+            range.split_once('-').ok_or_else(|| IndexParseError::InvalidRange(s.to_owned()))?;
+        RangedIndex::range(NormalIndex::from_str(first)?, NormalIndex::from_str(last)?, hardened)
+            .ok_or_else(|| IndexParseError::InvalidRange(s.to_owned()))
+    }
+}
+
+impl DerivationPath<RangedIndex> {
+    /// Detects whether any of the path components expands into multiple indexes.
+    pub fn is_ranged(&self) -> bool {
+        self.iter().any(|component| !matches!(component, RangedIndex::Index(_)))
+    }
+
+    /// Checks whether a concrete derivation path is produced by this ranged path.
+    pub fn contains(&self, path: &DerivationPath<DerivationIndex>) -> bool {
+        self.len() == path.len()
+            && self.iter().zip(path).all(|(range, index)| range.contains(index))
+    }
+
+    /// Returns iterator over all concrete paths produced by this ranged path, with wildcards
+    /// iterating over child numbers in the `wildcard` range.
+    pub fn paths(&self, wildcard: Range<u32>) -> RangedPaths {
+        let bounds = self
+            .iter()
+            .map(|component| {
+                let (start, end) = component.bounds(wildcard.clone());
+                (start, end, component.is_hardened())
+            })
+            .collect::<Vec<_>>();
+        let next = bounds
+            .iter()
+            .all(|(start, end, _)| start < end)
+            .then(|| bounds.iter().map(|(start, ..)| *start).collect());
+        RangedPaths { bounds, next }
+    }
+}
+
+/// Iterator over concrete derivation paths produced by a [`DerivationPath<RangedIndex>`], in
+/// lexicographic order of their indexes.
+#[derive(Clone, Debug)]
+pub struct RangedPaths {
+    bounds: Vec<(u32, u32, bool)>,
+    next: Option<Vec<u32>>,
+}
+
+impl Iterator for RangedPaths {
+    type Item = DerivationPath<DerivationIndex>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.take()?;
+        let path = current
+            .iter()
+            .zip(&self.bounds)
+            .map(|(child_number, (_, _, hardened))| index_at(*child_number, *hardened))
+            .collect();
+
+        let mut next = current;
+        for (pos, (start, end, _)) in self.bounds.iter().enumerate().rev() {
+            next[pos] += 1;
+            if next[pos] < *end {
+                self.next = Some(next);
+                break;
+            }
+            next[pos] = *start;
+        }
+        Some(path)
+    }
+}
+
+/// Section of a BIP-88 derivation path template: a non-empty list of indexes and index ranges
+/// sharing the same hardening, written as `{0,2-5,7}`, or a wildcard `*`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TemplateSeg(Vec<RangedIndex>);
+
+impl From<RangedIndex> for TemplateSeg {
+    fn from(index: RangedIndex) -> Self { TemplateSeg(vec![index]) }
+}
+
+impl TemplateSeg {
+    /// Constructs template section from the list of indexes and ranges, returning `None` if the
+    /// list is empty or its items differ in hardening.
+    pub fn with(items: impl IntoIterator<Item = RangedIndex>) -> Option<Self> {
+        let items = items.into_iter().collect::<Vec<_>>();
+        let hardened = items.first()?.is_hardened();
+        if items.iter().any(|item| item.is_hardened() != hardened) {
+            return None;
+        }
+        Some(TemplateSeg(items))
+    }
+
+    /// Detects whether the section matches hardened indexes.
+    pub fn is_hardened(&self) -> bool { self.0[0].is_hardened() }
+
+    /// Returns indexes and ranges of the section.
+    pub fn items(&self) -> &[RangedIndex] { &self.0 }
+
+    /// Checks whether the section matches the given `index`.
+    pub fn contains(&self, index: DerivationIndex) -> bool {
+        self.0.iter().any(|item| item.contains(index))
+    }
+}
+
+impl Display for TemplateSeg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let [item] = self.0.as_slice() {
+            return Display::fmt(item, f);
+        }
+        f.write_str("{")?;
+        for (pos, item) in self.0.iter().enumerate() {
+            if pos > 0 {
+                f.write_str(",")?;
+            }
+            match item {
+                RangedIndex::Index(index) => write!(f, "{}", index.child_number())?,
+                RangedIndex::Range { first, last, .. } => write!(f, "{first}-{last}")?,
+                RangedIndex::Wildcard { .. } => f.write_str("*")?,
+            }
+        }
+        f.write_str("}")?;
+        match self.is_hardened() {
+            true if f.alternate() => f.write_str("'"),
+            true => f.write_str("h"),
+            false => Ok(()),
+        }
+    }
+}
+
+impl FromStr for TemplateSeg {
+    type Err = IndexParseError;
+
+    /// Parses template section, which may be a single index, a wildcard, a range in square
+    /// brackets (`[0-1]`) or a comma-separated list of indexes and ranges in curly braces
+    /// (`{0,2-5}`), followed by an optional `h` or `'` hardened suffix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (body, hardened) = match s.strip_suffix(['h', 'H', '\'']) {
+            Some(body) => (body, true),
+            None => (s, false),
+        };
+        let Some(list) = body.strip_prefix('{').and_then(|b| b.strip_suffix('}')) else {
+            return RangedIndex::from_str(s).map(TemplateSeg::from);
+        };
+        let items = list
+            .split(',')
+            .map(|item| {
+                let (first, last) = item.split_once('-').unwrap_or((item, item));
+                let first = NormalIndex::from_str(first)?;
+                let last = NormalIndex::from_str(last)?;
+                if first == last {
+                    return Ok(RangedIndex::Index(index_at(first.child_number(), hardened)));
+                }
+                RangedIndex::range(first, last, hardened)
+                    .ok_or_else(|| IndexParseError::InvalidRange(s.to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TemplateSeg(items))
+    }
+}
+
+/// BIP-88 derivation path template, like `m/84h/{0-1}h/0h/{0,1}/*`.
+pub type PathTemplate = DerivationPath<TemplateSeg>;
+
+impl DerivationPath<TemplateSeg> {
+    /// Checks whether a concrete derivation path conforms to the template.
+    pub fn matches(&self, path: &DerivationPath<DerivationIndex>) -> bool {
+        self.len() == path.len() && self.iter().zip(path).all(|(seg, index)| seg.contains(index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranged_index() {
+        for s in ["5", "5h", "*", "*h", "{0-1}", "{10-20}h"] {
+            assert_eq!(RangedIndex::from_str(s).unwrap().to_string(), s);
+        }
+        assert_eq!(RangedIndex::from_str("[0-1]'").unwrap().to_string(), "{0-1}h");
+        assert_eq!(format!("{:#}", RangedIndex::from_str("*'").unwrap()), "*'");
+        assert!(RangedIndex::from_str("{2-1}").is_err());
+        assert!(RangedIndex::from_str("{2}").is_err());
+        assert!(RangedIndex::from_str("{0-2147483648}").is_err());
+
+        let range = RangedIndex::from_str("{1-3}h").unwrap();
+        assert!(range.contains(DerivationIndex::hardened(2)));
+        assert!(!range.contains(DerivationIndex::normal(2)));
+        assert!(!range.contains(DerivationIndex::hardened(4)));
+        assert!(RangedIndex::Wildcard { hardened: false }.contains(DerivationIndex::normal(999)));
+        assert_eq!(range.indexes(0..100).count(), 3);
+        assert_eq!(RangedIndex::Wildcard { hardened: true }.indexes(5..10).count(), 5);
+    }
+
+    #[test]
+    fn ranged_paths() {
+        let ranged =
+            DerivationPath::<RangedIndex>::from_str("m/84h/{0-1}h/0h/<0;1>/*").unwrap_err();
+        assert!(matches!(ranged, crate::DerivationParseError::InvalidIndex(..)));
+
+        let ranged = DerivationPath::<RangedIndex>::from_str("m/84h/[0-1]'/0h/{0-1}/*").unwrap();
+        assert!(ranged.is_ranged());
+        assert_eq!(ranged.to_string(), "/84h/{0-1}h/0h/{0-1}/*");
+
+        let paths = ranged.paths(0..3).map(|path| path.to_string()).collect::<Vec<_>>();
+        assert_eq!(paths.len(), 12);
+        assert_eq!(paths[0], "/84h/0h/0h/0/0");
+        assert_eq!(paths[1], "/84h/0h/0h/0/1");
+        assert_eq!(paths[3], "/84h/0h/0h/1/0");
+        assert_eq!(paths[11], "/84h/1h/0h/1/2");
+
+        let path = DerivationPath::<DerivationIndex>::from_str("84h/1h/0h/1/1000").unwrap();
+        assert!(ranged.contains(&path));
+        let path = DerivationPath::<DerivationIndex>::from_str("84h/2h/0h/1/1000").unwrap();
+        assert!(!ranged.contains(&path));
+
+        assert_eq!(ranged.paths(0..0).count(), 0);
+        let single = DerivationPath::<RangedIndex>::from_str("84h/0h").unwrap();
+        assert!(!single.is_ranged());
+        assert_eq!(single.paths(0..0).count(), 1);
+    }
+
+    #[test]
+    fn bip88() {
+        let template = PathTemplate::from_str("m/84'/[0-1]'/0'/{0,1}/*").unwrap();
+        assert_eq!(template.to_canonical_string(), "m/84h/{0-1}h/0h/{0,1}/*");
+        assert_eq!(format!("{template:#}"), "/84'/{0-1}'/0'/{0,1}/*");
+
+        let path = |s| DerivationPath::<DerivationIndex>::from_str(s).unwrap();
+        assert!(template.matches(&path("m/84h/0h/0h/0/0")));
+        assert!(template.matches(&path("m/84h/1h/0h/1/4000")));
+        assert!(!template.matches(&path("m/84h/2h/0h/1/5")));
+        assert!(!template.matches(&path("m/84h/1h/0h/2/5")));
+        assert!(!template.matches(&path("m/84h/1h/0/1/5")));
+        assert!(!template.matches(&path("m/84h/1h/0h/1")));
+        assert!(!template.matches(&path("m/84h/1h/0h/1/5/6")));
+
+        let template = PathTemplate::from_str("{0-10,20-30}h/*h").unwrap();
+        assert_eq!(template.to_string(), "/{0-10,20-30}h/*h");
+        assert!(template.matches(&path("25h/7h")));
+        assert!(!template.matches(&path("15h/7h")));
+        assert!(!template.matches(&path("25h/7")));
+
+        for invalid in ["{}", "{1h,2}", "{3-1}", "{0,a}", "{0,1", "84h/**"] {
+            assert!(PathTemplate::from_str(invalid).is_err(), "{invalid}");
+        }
+        assert!(TemplateSeg::with([]).is_none());
+        assert!(TemplateSeg::with([
+            RangedIndex::from(NormalIndex::from(1u8)),
+            RangedIndex::from(HardenedIndex::from(2u8))
+        ])
+        .is_none());
+    }
+}
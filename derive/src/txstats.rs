@@ -0,0 +1,345 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transaction size and signature operation accounting, matching the rules Bitcoin Core applies
+//! for the standardness of transactions relayed by the network.
+
+use bc::opcodes::{
+    OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_PUSHBYTES_0,
+    OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4, OP_PUSHNUM_1, OP_PUSHNUM_16,
+};
+use bc::{Tx, TxOut, VBytes, Weight, WeightUnits};
+
+/// Maximal weight of a transaction relayed by the nodes under the default policy.
+pub const MAX_STANDARD_TX_WEIGHT: u32 = 400_000;
+
+/// Maximal signature operation cost of a transaction relayed by the nodes under the default
+/// policy.
+pub const MAX_STANDARD_TX_SIGOPS_COST: u32 = 16_000;
+
+/// Maximal signature operation cost of all transactions in a block.
+pub const MAX_BLOCK_SIGOPS_COST: u32 = 80_000;
+
+/// Number of virtual bytes each signature operation accounts for in the sigop-adjusted virtual
+/// size, under the default policy.
+pub const DEFAULT_BYTES_PER_SIGOP: u32 = 20;
+
+const WITNESS_SCALE_FACTOR: u32 = 4;
+const MAX_PUBKEYS_PER_MULTISIG: u32 = 20;
+
+/// number of spent outputs ({prevouts}) doesn't match the number of transaction inputs
+/// ({inputs}).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub struct PrevoutsMismatch {
+    pub prevouts: usize,
+    pub inputs: usize,
+}
+
+/// Iterator over the operations of a script, yielding the opcode together with the pushed data.
+/// A push exceeding the script length yields `None` and ends the iteration.
+struct Ops<'a>(&'a [u8]);
+
+impl<'a> Iterator for Ops<'a> {
+    type Item = Option<(u8, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&op, rest) = self.0.split_first()?;
+        let push = match op {
+            OP_PUSHDATA1 => read_len(rest, 1),
+            OP_PUSHDATA2 => read_len(rest, 2),
+            OP_PUSHDATA4 => read_len(rest, 4),
+            len if len < OP_PUSHDATA1 => Some((len as usize, rest)),
+            _ => Some((0, rest)),
+        };
+        let Some((len, rest)) = push.filter(|(len, rest)| rest.len() >= *len) else {
+            self.0 = &[];
+            return Some(None);
+        };
+        let (data, rest) = rest.split_at(len);
+        self.0 = rest;
+        Some(Some((op, data)))
+    }
+}
+
+fn read_len(bytes: &[u8], len_bytes: usize) -> Option<(usize, &[u8])> {
+    let prefix = bytes.get(..len_bytes)?;
+    let mut buf = [0u8; 4];
+    buf[..len_bytes].copy_from_slice(prefix);
+    Some((u32::from_le_bytes(buf) as usize, &bytes[len_bytes..]))
+}
+
+/// Counts signature operations in a script.
+///
+/// With `accurate` set, multisig operations preceded by a small integer opcode count the number
+/// of the public keys; otherwise (and always in legacy counting) they count for the maximum of
+/// 20 keys.
+pub fn count_sigops(script: &[u8], accurate: bool) -> u32 {
+    let mut count = 0;
+    let mut last = None;
+    for (op, _) in Ops(script).map_while(|op| op) {
+        match op {
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => count += 1,
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => match last {
+                Some(n @ OP_PUSHNUM_1..=OP_PUSHNUM_16) if accurate => {
+                    count += (n - OP_PUSHNUM_1 + 1) as u32
+                }
+                _ => count += MAX_PUBKEYS_PER_MULTISIG,
+            },
+            _ => {}
+        }
+        last = Some(op);
+    }
+    count
+}
+
+/// Iterates over the data pushed by the script, stopping at a malformed push.
+pub fn script_pushes(script: &[u8]) -> impl Iterator<Item = &[u8]> {
+    Ops(script).map_while(|op| op).filter(|(op, _)| *op <= OP_PUSHDATA4).map(|(_, data)| data)
+}
+
+/// Returns data pushed last by a push-only script, which for P2SH inputs is the redeem script.
+fn last_push(script: &[u8]) -> Option<&[u8]> {
+    let mut last = None;
+    for op in Ops(script) {
+        let (op, data) = op?;
+        if op > OP_PUSHNUM_16 {
+            return None;
+        }
+        last = Some(data);
+    }
+    last
+}
+
+/// Parses witness version and program from a script, if it is a witness program.
+fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    if !(4..=42).contains(&script.len()) || script[1] as usize + 2 != script.len() {
+        return None;
+    }
+    match script[0] {
+        OP_PUSHBYTES_0 => Some((0, &script[2..])),
+        v @ OP_PUSHNUM_1..=OP_PUSHNUM_16 => Some((v - OP_PUSHNUM_1 + 1, &script[2..])),
+        _ => None,
+    }
+}
+
+fn witness_sigops<'a>(version: u8, program: &[u8], witness: impl Iterator<Item = &'a [u8]>) -> u32 {
+    match (version, program.len()) {
+        (0, 20) => 1,
+        (0, 32) => witness.last().map(|script| count_sigops(script, true)).unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+/// Size and signature operation statistics of a transaction.
+///
+/// Virtual size without the sigop adjustment is provided by [`Weight::vbytes`].
+pub trait TxStats: Weight {
+    /// Size of the transaction serialized without the witness data.
+    fn stripped_size(&self) -> usize;
+
+    /// Size of the witness data, including segwit marker and flag.
+    fn witness_size(&self) -> usize;
+
+    /// Size of the transaction serialized with the witness data.
+    #[inline]
+    fn total_size(&self) -> usize { self.stripped_size() + self.witness_size() }
+
+    /// Counts signature operations in the input and output scripts without taking into account
+    /// the scripts of the spent outputs.
+    fn legacy_sigops(&self) -> u32;
+
+    /// Computes signature operation cost of the transaction, which includes legacy, P2SH and
+    /// witness signature operations. Outputs spent by each of the transaction inputs must be
+    /// provided in the same order as the inputs.
+    fn sigop_cost(&self, prevouts: &[TxOut]) -> Result<u32, PrevoutsMismatch>;
+
+    /// Virtual size used by the nodes for the fee rate computation, in which each signature
+    /// operation accounts for at least [`DEFAULT_BYTES_PER_SIGOP`] virtual bytes.
+    fn sigop_adjusted_vbytes(&self, sigop_cost: u32) -> VBytes {
+        let weight = self.weight_units().to_u32().max(sigop_cost * DEFAULT_BYTES_PER_SIGOP);
+        VBytes::from(WeightUnits::witness_discount(weight as usize))
+    }
+
+    /// Checks whether the transaction fits into the weight and signature operation limits of the
+    /// default relay policy.
+    fn is_standard_size(&self, sigop_cost: u32) -> bool {
+        self.weight_units().to_u32() <= MAX_STANDARD_TX_WEIGHT
+            && sigop_cost <= MAX_STANDARD_TX_SIGOPS_COST
+    }
+}
+
+impl TxStats for Tx {
+    fn stripped_size(&self) -> usize {
+        let weight = self.weight_units().to_u32() as usize;
+        (weight - self.witness_size()) / WITNESS_SCALE_FACTOR as usize
+    }
+
+    fn witness_size(&self) -> usize {
+        if !self.is_segwit() {
+            return 0;
+        }
+        // marker and flag bytes
+        2 + self.inputs().map(|txin| txin.witness.weight_units().to_u32() as usize).sum::<usize>()
+    }
+
+    fn legacy_sigops(&self) -> u32 {
+        self.inputs().map(|txin| count_sigops(&txin.sig_script, false)).sum::<u32>()
+            + self.outputs().map(|txout| count_sigops(&txout.script_pubkey, false)).sum::<u32>()
+    }
+
+    fn sigop_cost(&self, prevouts: &[TxOut]) -> Result<u32, PrevoutsMismatch> {
+        if self.inputs.len() != prevouts.len() {
+            return Err(PrevoutsMismatch {
+                prevouts: prevouts.len(),
+                inputs: self.inputs.len(),
+            });
+        }
+
+        let mut cost = self.legacy_sigops() * WITNESS_SCALE_FACTOR;
+        for (txin, prevout) in self.inputs().zip(prevouts) {
+            let witness = || txin.witness.elements();
+            let spk = &prevout.script_pubkey;
+            if let Some((version, program)) = witness_program(spk) {
+                cost += witness_sigops(version, program, witness());
+            } else if spk.is_p2sh() {
+                let Some(redeem_script) = last_push(&txin.sig_script) else {
+                    continue;
+                };
+                cost += count_sigops(redeem_script, true) * WITNESS_SCALE_FACTOR;
+                if let Some((version, program)) = witness_program(redeem_script) {
+                    cost += witness_sigops(version, program, witness());
+                }
+            }
+        }
+        Ok(cost)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bc::{
+        ConsensusEncode, LockTime, Outpoint, RedeemScript, Sats, ScriptPubkey, SeqNo, SigScript,
+        TxIn, TxVer, Txid, VarIntArray, Witness, WitnessScript,
+    };
+
+    use super::*;
+
+    fn multisig(m: u8, n: u8) -> Vec<u8> {
+        let mut script = vec![OP_PUSHNUM_1 + m - 1];
+        for _ in 0..n {
+            script.push(33);
+            script.extend([0x02; 33]);
+        }
+        script.extend([OP_PUSHNUM_1 + n - 1, OP_CHECKMULTISIG]);
+        script
+    }
+
+    fn txin(vout: u32, sig_script: Vec<u8>, witness: Vec<Vec<u8>>) -> TxIn {
+        TxIn {
+            prev_output: Outpoint::new(Txid::from([1; 32]), vout),
+            sig_script: SigScript::from_unsafe(sig_script),
+            sequence: SeqNo::from_consensus_u32(0xFFFF_FFFF),
+            witness: Witness::from_consensus_stack(witness),
+        }
+    }
+
+    fn push(data: &[u8]) -> Vec<u8> {
+        let mut script = match data.len() {
+            len if len < OP_PUSHDATA1 as usize => vec![len as u8],
+            len => vec![OP_PUSHDATA1, len as u8],
+        };
+        script.extend(data);
+        script
+    }
+
+    #[test]
+    fn sigops_count() {
+        let script = multisig(2, 3);
+        assert_eq!(count_sigops(&script, true), 3);
+        assert_eq!(count_sigops(&script, false), 20);
+        assert_eq!(count_sigops(&ScriptPubkey::p2pkh([0; 20]), false), 1);
+        assert_eq!(count_sigops(&[OP_PUSHBYTES_0, OP_CHECKMULTISIG], true), 20);
+        // Truncated push stops counting
+        assert_eq!(count_sigops(&[OP_CHECKSIG, OP_PUSHDATA1, 10, OP_CHECKSIG], false), 1);
+        assert_eq!(count_sigops(&[OP_CHECKSIG, 0x05, OP_CHECKSIG], false), 1);
+    }
+
+    #[test]
+    fn tx_stats() {
+        let redeem_script = multisig(2, 3);
+        let witness_script = multisig(1, 2);
+        let wsh = WitnessScript::from_unsafe(witness_script.clone());
+        let sh_wsh = wsh.to_redeem_script();
+
+        let mut sh_sig = vec![OP_PUSHBYTES_0];
+        sh_sig.extend(push(&[0x30; 72]));
+        sh_sig.extend(push(&redeem_script));
+        let tx = Tx {
+            version: TxVer::V2,
+            inputs: VarIntArray::from_iter_unsafe([
+                txin(0, push(&[0x30; 72]).into_iter().chain(push(&[0x02; 33])).collect(), vec![]),
+                txin(1, sh_sig, vec![]),
+                txin(2, vec![], vec![vec![0x30; 72], vec![0x02; 33]]),
+                txin(3, vec![], vec![vec![], vec![0x30; 72], witness_script.clone()]),
+                txin(4, push(&sh_wsh), vec![vec![], vec![0x30; 72], witness_script]),
+            ]),
+            outputs: VarIntArray::from_iter_unsafe([
+                TxOut::new(ScriptPubkey::p2pkh([1; 20]), Sats::from_sats(1000u32)),
+                TxOut::new(ScriptPubkey::p2wpkh([2; 20]), Sats::from_sats(1000u32)),
+            ]),
+            lock_time: LockTime::ZERO,
+        };
+        let prevouts = [
+            ScriptPubkey::p2pkh([3; 20]),
+            RedeemScript::from_unsafe(redeem_script).to_script_pubkey(),
+            ScriptPubkey::p2wpkh([4; 20]),
+            wsh.to_script_pubkey(),
+            sh_wsh.to_script_pubkey(),
+        ]
+        .map(|spk| TxOut::new(spk, Sats::from_sats(10_000u32)));
+
+        let serialized = tx.consensus_serialize();
+        assert_eq!(tx.total_size(), serialized.len());
+        let mut stripped = tx.clone();
+        stripped.inputs.iter_mut().for_each(|txin| txin.witness = Witness::new());
+        assert_eq!(tx.stripped_size(), stripped.consensus_serialize().len());
+        assert_eq!(tx.weight_units().to_u32() as usize, tx.stripped_size() * 3 + tx.total_size());
+
+        // Single P2PKH output
+        assert_eq!(tx.legacy_sigops(), 1);
+        // Legacy and P2SH multisig are scaled, P2WPKH and both P2WSH multisigs are not
+        assert_eq!(tx.sigop_cost(&prevouts), Ok(4 + 3 * 4 + 1 + 2 + 2));
+        assert_eq!(
+            tx.sigop_cost(&prevouts[1..]),
+            Err(PrevoutsMismatch {
+                prevouts: 4,
+                inputs: 5
+            })
+        );
+
+        assert_eq!(tx.sigop_adjusted_vbytes(1), tx.vbytes());
+        assert_eq!(tx.sigop_adjusted_vbytes(1000).to_u32(), 5000);
+        assert!(tx.is_standard_size(21));
+        assert!(!tx.is_standard_size(MAX_STANDARD_TX_SIGOPS_COST + 1));
+    }
+}
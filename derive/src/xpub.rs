@@ -21,21 +21,33 @@
 // limitations under the License.
 
 use std::borrow::Borrow;
+use std::collections::BTreeMap;
 use std::fmt::{self, Display, Formatter};
+use std::hash::{self as std_hash, Hasher};
+use std::ptr;
 use std::str::FromStr;
+use std::sync::atomic;
 
 use amplify::{confinement, hex, ByteArray, Bytes20, Bytes32, Bytes4, Wrapper};
 use bc::secp256k1::SECP256K1;
-use bc::{secp256k1, CompressedPk, InvalidPubkey, LegacyPk, XOnlyPk};
+use bc::{secp256k1, CompressedPk, InvalidPubkey, LegacyPk, TapNodeHash, XOnlyPk};
 use bitcoin_hashes::{hash160, sha512, Hash, HashEngine, Hmac, HmacEngine};
 
 use crate::{
-    base58, DerivationIndex, DerivationParseError, DerivationPath, DerivationSeg, HardenedIndex,
-    Idx, IdxBase, IndexParseError, Keychain, NormalIndex, SegParseError, Terminal,
+    base58, DerivationIndex, DerivationParseError, DerivationPath, DerivationScheme, DerivationSeg,
+    HardenedIndex, Idx, IdxBase, IndexParseError, KeyApplication, Keychain, Network, NormalIndex,
+    Seed, SegParseError, TapTweak, Terminal,
 };
 
 pub const XPUB_MAINNET_MAGIC: [u8; 4] = [0x04u8, 0x88, 0xB2, 0x1E];
 pub const XPUB_TESTNET_MAGIC: [u8; 4] = [0x04u8, 0x35, 0x87, 0xCF];
+pub const XPRIV_MAINNET_MAGIC: [u8; 4] = [0x04u8, 0x88, 0xAD, 0xE4];
+pub const XPRIV_TESTNET_MAGIC: [u8; 4] = [0x04u8, 0x35, 0x83, 0x94];
+
+/// Error deriving extended key beyond the maximal BIP-32 depth.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display("derived extended key exceeds the maximal depth of 255.")]
+pub struct DepthOverflow;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
 #[display(doc_comments)]
@@ -43,7 +55,7 @@ pub enum XpubDecodeError {
     /// wrong length of extended pubkey data ({0}).
     WrongExtendedKeyLength(usize),
 
-    /// provided key is not a standard BIP-32 extended pubkey
+    /// provided key is not a standard BIP-32 or SLIP-132 extended pubkey
     UnknownKeyType([u8; 4]),
 
     /// extended pubkey contains {0}
@@ -108,7 +120,7 @@ impl From<OriginParseError> for XpubParseError {
 }
 
 /// BIP32 chain code used for hierarchical derivation
-#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug, From)]
 #[wrapper(Deref, RangeOps)]
 pub struct ChainCode(Bytes32);
 
@@ -133,6 +145,37 @@ pub struct XpubCore {
     pub chain_code: ChainCode,
 }
 
+impl XpubCore {
+    /// Compute the scalar tweak added to this key to get a child key
+    pub fn ckd_pub_tweak(&self, child_no: NormalIndex) -> (secp256k1::Scalar, ChainCode) {
+        let mut hmac_engine: HmacEngine<sha512::Hash> = HmacEngine::new(self.chain_code.as_ref());
+        hmac_engine.input(&self.public_key.serialize());
+        hmac_engine.input(&child_no.to_be_bytes());
+
+        let hmac_result: Hmac<sha512::Hash> = Hmac::from_engine(hmac_engine);
+
+        let private_key = secp256k1::SecretKey::from_slice(&hmac_result[..32])
+            .expect("negligible probability")
+            .into();
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&hmac_result[32..]);
+        let chain_code = ChainCode::from_byte_array(bytes);
+        (private_key, chain_code)
+    }
+
+    /// Public->Public child key derivation. Unlike [`Xpub::ckd_pub`] it doesn't track the key
+    /// metadata and thus can't fail.
+    pub fn ckd_pub(&self, child_no: NormalIndex) -> XpubCore {
+        let (scalar, chain_code) = self.ckd_pub_tweak(child_no);
+        let tweaked =
+            self.public_key.add_exp_tweak(SECP256K1, &scalar).expect("negligible probability");
+        XpubCore {
+            public_key: tweaked.into(),
+            chain_code,
+        }
+    }
+}
+
 #[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug, Display, From)]
 #[wrapper(RangeOps, Hex, FromStr)]
 #[display(LowerHex)]
@@ -192,22 +235,42 @@ pub struct Xpub {
 }
 
 impl Xpub {
+    /// Constructs extended public key from its metadata and deterministic part.
+    pub fn with(testnet: bool, meta: XpubMeta, core: XpubCore) -> Self {
+        Xpub {
+            testnet,
+            meta,
+            core,
+        }
+    }
+
+    /// Returns deterministic part of the extended public key.
+    pub fn core(&self) -> XpubCore { self.core }
+
+    /// Whether the key is a testnet key, as indicated by its version bytes (`tpub`, `upub`,
+    /// `vpub` etc.).
+    pub fn is_testnet(&self) -> bool { self.testnet }
+
+    /// Decodes extended key, accepting both BIP-32 and SLIP-132 version bytes.
     pub fn decode(data: impl Borrow<[u8]>) -> Result<Xpub, XpubDecodeError> {
+        Self::decode_slip132(data).map(|(key, _)| key)
+    }
+
+    /// Decodes extended key, accepting both BIP-32 and SLIP-132 version bytes, and returns it
+    /// together with the key application implied by the version.
+    pub fn decode_slip132(
+        data: impl Borrow<[u8]>,
+    ) -> Result<(Xpub, KeyApplication), XpubDecodeError> {
         let data = data.borrow();
 
         if data.len() != 78 {
             return Err(XpubDecodeError::WrongExtendedKeyLength(data.len()));
         }
 
-        let testnet = match &data[0..4] {
-            magic if magic == XPUB_MAINNET_MAGIC => false,
-            magic if magic == XPUB_TESTNET_MAGIC => true,
-            unknown => {
-                let mut magic = [0u8; 4];
-                magic.copy_from_slice(unknown);
-                return Err(XpubDecodeError::UnknownKeyType(magic));
-            }
-        };
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(&data[0..4]);
+        let (application, testnet) = KeyApplication::with_xpub_version(magic)
+            .ok_or(XpubDecodeError::UnknownKeyType(magic))?;
         let depth = data[4];
 
         let mut parent_fp = [0u8; 4];
@@ -222,7 +285,7 @@ impl Xpub {
 
         let public_key = CompressedPk::from_bytes(&data[45..78])?;
 
-        Ok(Xpub {
+        let key = Xpub {
             testnet,
             meta: XpubMeta {
                 depth,
@@ -233,15 +296,17 @@ impl Xpub {
                 public_key,
                 chain_code: chain_code.into(),
             },
-        })
+        };
+        Ok((key, application))
     }
 
-    pub fn encode(&self) -> [u8; 78] {
+    /// Encodes extended key with the standard BIP-32 version bytes.
+    pub fn encode(&self) -> [u8; 78] { self.encode_slip132(KeyApplication::Legacy) }
+
+    /// Encodes extended key with the SLIP-132 version bytes for the given key application.
+    pub fn encode_slip132(&self, application: KeyApplication) -> [u8; 78] {
         let mut ret = [0; 78];
-        ret[0..4].copy_from_slice(&match self.testnet {
-            false => XPUB_MAINNET_MAGIC,
-            true => XPUB_TESTNET_MAGIC,
-        });
+        ret[0..4].copy_from_slice(&application.xpub_version(self.testnet));
         ret[4] = self.meta.depth;
         ret[5..9].copy_from_slice(self.meta.parent_fp.as_ref());
         ret[9..13].copy_from_slice(&self.meta.child_number.index().to_be_bytes());
@@ -274,7 +339,8 @@ impl Xpub {
     /// Attempts to derive an extended public key from a path.
     ///
     /// The `path` argument can be any type implementing `AsRef<ChildNumber>`, such as
-    /// `DerivationPath`, for instance.
+    /// `DerivationPath`, for instance. The depth of the derived key saturates at 255; use
+    /// [`Xpub::checked_derive_pub`] to detect the overflow.
     pub fn derive_pub(&self, path: impl AsRef<[NormalIndex]>) -> Self {
         let mut pk = *self;
         for cnum in path.as_ref() {
@@ -283,43 +349,42 @@ impl Xpub {
         pk
     }
 
+    /// Attempts to derive an extended public key from a path, failing if the derived key depth
+    /// exceeds 255.
+    pub fn checked_derive_pub(
+        &self,
+        path: impl AsRef<[NormalIndex]>,
+    ) -> Result<Self, DepthOverflow> {
+        path.as_ref().iter().try_fold(*self, |pk, cnum| pk.checked_ckd_pub(*cnum))
+    }
+
     /// Compute the scalar tweak added to this key to get a child key
     pub fn ckd_pub_tweak(&self, child_no: NormalIndex) -> (secp256k1::Scalar, ChainCode) {
-        let mut hmac_engine: HmacEngine<sha512::Hash> =
-            HmacEngine::new(self.core.chain_code.as_ref());
-        hmac_engine.input(&self.core.public_key.serialize());
-        hmac_engine.input(&child_no.to_be_bytes());
-
-        let hmac_result: Hmac<sha512::Hash> = Hmac::from_engine(hmac_engine);
-
-        let private_key = secp256k1::SecretKey::from_slice(&hmac_result[..32])
-            .expect("negligible probability")
-            .into();
-        let mut bytes = [0u8; 32];
-        bytes.copy_from_slice(&hmac_result[32..]);
-        let chain_code = ChainCode::from_byte_array(bytes);
-        (private_key, chain_code)
+        self.core.ckd_pub_tweak(child_no)
     }
 
-    /// Public->Public child key derivation
+    /// Public->Public child key derivation. The depth of the derived key saturates at 255; use
+    /// [`Xpub::checked_ckd_pub`] to detect the overflow.
     pub fn ckd_pub(&self, child_no: NormalIndex) -> Xpub {
-        let (scalar, chain_code) = self.ckd_pub_tweak(child_no);
-        let tweaked =
-            self.core.public_key.add_exp_tweak(SECP256K1, &scalar).expect("negligible probability");
+        self.child_pub(self.meta.depth.saturating_add(1), child_no)
+    }
+
+    /// Public->Public child key derivation, failing if the key already has the maximal depth.
+    pub fn checked_ckd_pub(&self, child_no: NormalIndex) -> Result<Xpub, DepthOverflow> {
+        let depth = self.meta.depth.checked_add(1).ok_or(DepthOverflow)?;
+        Ok(self.child_pub(depth, child_no))
+    }
 
+    fn child_pub(&self, depth: u8, child_no: NormalIndex) -> Xpub {
         let meta = XpubMeta {
-            depth: self.meta.depth + 1,
+            depth,
             parent_fp: self.fingerprint(),
             child_number: child_no.into(),
         };
-        let core = XpubCore {
-            public_key: tweaked.into(),
-            chain_code,
-        };
         Xpub {
             testnet: self.testnet,
             meta,
-            core,
+            core: self.core.ckd_pub(child_no),
         }
     }
 }
@@ -339,6 +404,376 @@ impl FromStr for Xpub {
     }
 }
 
+impl Xpub {
+    /// Formats extended key using the SLIP-132 version bytes for the given key application.
+    pub fn to_string_slip132(&self, application: KeyApplication) -> String {
+        base58::encode_check(&self.encode_slip132(application))
+    }
+
+    /// Parses extended key with either BIP-32 or SLIP-132 version bytes, returning it
+    /// together with the key application implied by the version.
+    pub fn from_str_slip132(inp: &str) -> Result<(Xpub, KeyApplication), XpubParseError> {
+        let data = base58::decode_check(inp)?;
+        Ok(Xpub::decode_slip132(data)?)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum XprivDecodeError {
+    /// wrong length of extended private key data ({0}).
+    WrongExtendedKeyLength(usize),
+
+    /// provided key is not a standard BIP-32 or SLIP-132 extended private key
+    UnknownKeyType([u8; 4]),
+
+    /// extended private key contains invalid secret key data.
+    #[from(bc::secp256k1::Error)]
+    InvalidSecretKey,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+pub enum XprivParseError {
+    /// wrong Base58 encoding of extended private key data - {0}
+    #[display(doc_comments)]
+    #[from]
+    Base58(base58::Error),
+
+    #[display(inner)]
+    #[from]
+    Decode(XprivDecodeError),
+
+    #[display(inner)]
+    #[from]
+    Origin(OriginParseError),
+
+    /// no xpriv key origin information.
+    #[display(doc_comments)]
+    NoOrigin,
+
+    /// xpriv depth and origin mismatch.
+    #[display(doc_comments)]
+    DepthMismatch,
+}
+
+/// Deterministic part of the extended private key.
+///
+/// The secret key and the chain code are erased from the memory when the value is dropped.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct XprivCore {
+    /// Secret key
+    pub private_key: secp256k1::SecretKey,
+    /// BIP32 chain code used for hierarchical derivation
+    pub chain_code: ChainCode,
+}
+
+impl XprivCore {
+    /// Private->Private child key derivation. Unlike [`Xpriv::ckd_priv`] it doesn't track the
+    /// key metadata and thus can't fail.
+    pub fn ckd_priv(&self, child_no: impl Into<DerivationIndex>) -> XprivCore {
+        let child_no = child_no.into();
+        let mut hmac_engine: HmacEngine<sha512::Hash> = HmacEngine::new(self.chain_code.as_ref());
+        if child_no.is_hardened() {
+            hmac_engine.input(&[0u8]);
+            hmac_engine.input(&self.private_key.secret_bytes());
+        } else {
+            hmac_engine.input(&self.private_key.public_key(SECP256K1).serialize());
+        }
+        hmac_engine.input(&child_no.index().to_be_bytes());
+        let hmac_result: Hmac<sha512::Hash> = Hmac::from_engine(hmac_engine);
+
+        let mut tweak = [0u8; 32];
+        tweak.copy_from_slice(&hmac_result[..32]);
+        let tweak = secp256k1::Scalar::from_be_bytes(tweak).expect("negligible probability");
+        let private_key = self.private_key.add_tweak(&tweak).expect("negligible probability");
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&hmac_result[32..]);
+
+        XprivCore {
+            private_key,
+            chain_code: chain_code.into(),
+        }
+    }
+}
+
+impl Drop for XprivCore {
+    fn drop(&mut self) {
+        self.private_key.non_secure_erase();
+        erase(&mut self.chain_code);
+    }
+}
+
+/// Overwrites value with its default using a volatile write, such that the compiler doesn't
+/// optimize the write away as a dead store.
+pub(crate) fn erase<T: Copy + Default>(value: &mut T) {
+    // SAFETY: the pointer comes from a valid mutable reference and `T: Copy` has no destructor
+    unsafe { ptr::write_volatile(value, T::default()) };
+    atomic::compiler_fence(atomic::Ordering::SeqCst);
+}
+
+/// Serialized extended private key, which is erased from the memory when dropped.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct XprivBytes([u8; 78]);
+
+impl XprivBytes {
+    pub fn as_bytes(&self) -> &[u8; 78] { &self.0 }
+}
+
+impl AsRef<[u8]> for XprivBytes {
+    fn as_ref(&self) -> &[u8] { &self.0 }
+}
+
+impl Borrow<[u8]> for XprivBytes {
+    fn borrow(&self) -> &[u8] { &self.0 }
+}
+
+impl Drop for XprivBytes {
+    fn drop(&mut self) { self.0.iter_mut().for_each(erase) }
+}
+
+impl fmt::Debug for XprivBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str("XprivBytes(..)") }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Xpriv {
+    testnet: bool,
+    meta: XpubMeta,
+    core: XprivCore,
+}
+
+impl Xpriv {
+    /// Constructs master extended private key from a BIP-39 seed.
+    pub fn from_seed(testnet: bool, seed: &Seed) -> Xpriv {
+        Self::new_master(testnet, seed.as_ref())
+    }
+
+    /// Constructs master extended private key from a seed value.
+    pub fn new_master(testnet: bool, seed: &[u8]) -> Xpriv {
+        let mut hmac_engine: HmacEngine<sha512::Hash> = HmacEngine::new(b"Bitcoin seed");
+        hmac_engine.input(seed);
+        let hmac_result: Hmac<sha512::Hash> = Hmac::from_engine(hmac_engine);
+
+        let private_key =
+            secp256k1::SecretKey::from_slice(&hmac_result[..32]).expect("negligible probability");
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&hmac_result[32..]);
+
+        Xpriv {
+            testnet,
+            meta: XpubMeta {
+                depth: 0,
+                parent_fp: XpubFp::default(),
+                child_number: DerivationIndex::ZERO,
+            },
+            core: XprivCore {
+                private_key,
+                chain_code: chain_code.into(),
+            },
+        }
+    }
+
+    /// Decodes extended key, accepting both BIP-32 and SLIP-132 version bytes.
+    pub fn decode(data: impl Borrow<[u8]>) -> Result<Xpriv, XprivDecodeError> {
+        Self::decode_slip132(data).map(|(key, _)| key)
+    }
+
+    /// Decodes extended key, accepting both BIP-32 and SLIP-132 version bytes, and returns it
+    /// together with the key application implied by the version.
+    pub fn decode_slip132(
+        data: impl Borrow<[u8]>,
+    ) -> Result<(Xpriv, KeyApplication), XprivDecodeError> {
+        let data = data.borrow();
+
+        if data.len() != 78 {
+            return Err(XprivDecodeError::WrongExtendedKeyLength(data.len()));
+        }
+
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(&data[0..4]);
+        let (application, testnet) = KeyApplication::with_xpriv_version(magic)
+            .ok_or(XprivDecodeError::UnknownKeyType(magic))?;
+        let depth = data[4];
+
+        let mut parent_fp = [0u8; 4];
+        parent_fp.copy_from_slice(&data[5..9]);
+
+        let mut child_number = [0u8; 4];
+        child_number.copy_from_slice(&data[9..13]);
+        let child_number = u32::from_be_bytes(child_number);
+
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&data[13..45]);
+
+        if data[45] != 0 {
+            return Err(XprivDecodeError::InvalidSecretKey);
+        }
+        let private_key = secp256k1::SecretKey::from_slice(&data[46..78])?;
+
+        let key = Xpriv {
+            testnet,
+            meta: XpubMeta {
+                depth,
+                parent_fp: parent_fp.into(),
+                child_number: child_number.into(),
+            },
+            core: XprivCore {
+                private_key,
+                chain_code: chain_code.into(),
+            },
+        };
+        Ok((key, application))
+    }
+
+    /// Encodes extended key with the standard BIP-32 version bytes. The returned data contain
+    /// the secret key and are erased from the memory when dropped.
+    pub fn encode(&self) -> XprivBytes { self.encode_slip132(KeyApplication::Legacy) }
+
+    /// Encodes extended key with the SLIP-132 version bytes for the given key application. The
+    /// returned data contain the secret key and are erased from the memory when dropped.
+    pub fn encode_slip132(&self, application: KeyApplication) -> XprivBytes {
+        let mut ret = XprivBytes([0; 78]);
+        let data = &mut ret.0;
+        data[0..4].copy_from_slice(&application.xpriv_version(self.testnet));
+        data[4] = self.meta.depth;
+        data[5..9].copy_from_slice(self.meta.parent_fp.as_ref());
+        data[9..13].copy_from_slice(&self.meta.child_number.index().to_be_bytes());
+        data[13..45].copy_from_slice(self.core.chain_code.as_ref());
+        let mut secret = self.core.private_key.secret_bytes();
+        data[46..78].copy_from_slice(&secret);
+        secret.iter_mut().for_each(erase);
+        ret
+    }
+
+    #[must_use]
+    pub fn is_testnet(&self) -> bool { self.testnet }
+
+    /// Constructs extended public key matching this extended private key.
+    pub fn to_xpub(&self) -> Xpub {
+        Xpub {
+            testnet: self.testnet,
+            meta: self.meta,
+            core: XpubCore {
+                public_key: self.to_compr_pub(),
+                chain_code: self.core.chain_code,
+            },
+        }
+    }
+
+    /// Returns the HASH160 of the public key corresponding to this private key.
+    pub fn identifier(&self) -> XpubId { self.to_xpub().identifier() }
+
+    pub fn fingerprint(&self) -> XpubFp { self.to_xpub().fingerprint() }
+
+    /// Constructs ECDSA public key.
+    pub fn to_compr_pub(&self) -> CompressedPk {
+        CompressedPk::from(self.core.private_key.public_key(SECP256K1))
+    }
+
+    /// Constructs BIP340 public key matching internal public key representation.
+    pub fn to_xonly_pub(&self) -> XOnlyPk { XOnlyPk::from(self.to_compr_pub()) }
+
+    /// Returns deterministic part of the extended private key.
+    pub fn core(&self) -> &XprivCore { &self.core }
+
+    /// Returns secret key for ECDSA signing.
+    ///
+    /// The returned key is a copy of the secret which is not erased when dropped; the caller
+    /// must erase it with [`secp256k1::SecretKey::non_secure_erase`] once it is not needed.
+    pub fn to_private_ecdsa(&self) -> secp256k1::SecretKey { self.core.private_key }
+
+    /// Returns key pair for BIP340 signing.
+    pub fn to_keypair_bip340(&self) -> secp256k1::Keypair {
+        secp256k1::Keypair::from_secret_key(SECP256K1, &self.core.private_key)
+    }
+
+    /// Returns key pair for BIP340 signing in taproot key path spending, with
+    /// the key tweaked by BIP341 `TapTweak` using an optional script tree
+    /// merkle root.
+    pub fn to_keypair_bip341(&self, merkle_root: Option<TapNodeHash>) -> secp256k1::Keypair {
+        self.core.private_key.tap_tweak(merkle_root)
+    }
+
+    /// Attempts to derive an extended private key from a path. The depth of the derived key
+    /// saturates at 255; use [`Xpriv::checked_derive_priv`] to detect the overflow.
+    pub fn derive_priv<I: Into<DerivationIndex>>(&self, path: impl IntoIterator<Item = I>) -> Self {
+        let mut sk = self.clone();
+        for cnum in path {
+            sk = sk.ckd_priv(cnum)
+        }
+        sk
+    }
+
+    /// Attempts to derive an extended private key from a path, failing if the derived key depth
+    /// exceeds 255.
+    pub fn checked_derive_priv<I: Into<DerivationIndex>>(
+        &self,
+        path: impl IntoIterator<Item = I>,
+    ) -> Result<Self, DepthOverflow> {
+        path.into_iter().try_fold(self.clone(), |sk, cnum| sk.checked_ckd_priv(cnum))
+    }
+
+    /// Private->Private child key derivation. The depth of the derived key saturates at 255; use
+    /// [`Xpriv::checked_ckd_priv`] to detect the overflow.
+    pub fn ckd_priv(&self, child_no: impl Into<DerivationIndex>) -> Xpriv {
+        self.child_priv(self.meta.depth.saturating_add(1), child_no.into())
+    }
+
+    /// Private->Private child key derivation, failing if the key already has the maximal depth.
+    pub fn checked_ckd_priv(
+        &self,
+        child_no: impl Into<DerivationIndex>,
+    ) -> Result<Xpriv, DepthOverflow> {
+        let depth = self.meta.depth.checked_add(1).ok_or(DepthOverflow)?;
+        Ok(self.child_priv(depth, child_no.into()))
+    }
+
+    fn child_priv(&self, depth: u8, child_no: DerivationIndex) -> Xpriv {
+        Xpriv {
+            testnet: self.testnet,
+            meta: XpubMeta {
+                depth,
+                parent_fp: self.fingerprint(),
+                child_number: child_no,
+            },
+            core: self.core.ckd_priv(child_no),
+        }
+    }
+}
+
+impl Display for Xpriv {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        base58::encode_check_to_fmt(f, self.encode().as_bytes())
+    }
+}
+
+impl FromStr for Xpriv {
+    type Err = XprivParseError;
+
+    fn from_str(inp: &str) -> Result<Xpriv, XprivParseError> {
+        let mut data = base58::decode_check(inp)?;
+        let xpriv = Xpriv::decode(data.as_slice());
+        data.iter_mut().for_each(erase);
+        Ok(xpriv?)
+    }
+}
+
+impl Xpriv {
+    /// Formats extended key using the SLIP-132 version bytes for the given key application.
+    pub fn to_string_slip132(&self, application: KeyApplication) -> String {
+        base58::encode_check(self.encode_slip132(application).as_bytes())
+    }
+
+    /// Parses extended key with either BIP-32 or SLIP-132 version bytes, returning it
+    /// together with the key application implied by the version.
+    pub fn from_str_slip132(inp: &str) -> Result<(Xpriv, KeyApplication), XprivParseError> {
+        let mut data = base58::decode_check(inp)?;
+        let xpriv = Xpriv::decode_slip132(data.as_slice());
+        data.iter_mut().for_each(erase);
+        Ok(xpriv?)
+    }
+}
+
 #[derive(Getters, Clone, Eq, PartialEq, Hash, Debug, Display)]
 #[display("{master_fp}{derivation}", alt = "{master_fp}{derivation:#}")]
 #[cfg_attr(
@@ -365,14 +800,18 @@ impl FromStr for XpubOrigin {
     type Err = OriginParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (master_fp, path) = match s.split_once('/') {
-            None => (XpubFp::default(), ""),
-            Some(("00000000", p)) | Some(("m", p)) => (XpubFp::default(), p),
-            Some((fp, p)) => (XpubFp::from_str(fp)?, p),
+        let (master_fp, path) = s.split_once('/').unwrap_or((s, ""));
+        let master_fp = match master_fp {
+            "" | "m" | "00000000" => XpubFp::default(),
+            fp => XpubFp::from_str(fp)?,
+        };
+        let derivation = match path {
+            "" => DerivationPath::new(),
+            path => DerivationPath::from_str(path)?,
         };
         Ok(XpubOrigin {
             master_fp,
-            derivation: DerivationPath::from_str(path)?,
+            derivation,
         })
     }
 }
@@ -406,14 +845,18 @@ impl FromStr for KeyOrigin {
     type Err = XpubParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (master_fp, path) = match s.split_once('/') {
-            None => (XpubFp::default(), ""),
-            Some(("00000000", p)) | Some(("m", p)) => (XpubFp::default(), p),
-            Some((fp, p)) => (XpubFp::from_str(fp)?, p),
+        let (master_fp, path) = s.split_once('/').unwrap_or((s, ""));
+        let master_fp = match master_fp {
+            "" | "m" | "00000000" => XpubFp::default(),
+            fp => XpubFp::from_str(fp)?,
+        };
+        let derivation = match path {
+            "" => DerivationPath::new(),
+            path => DerivationPath::from_str(path)?,
         };
         Ok(KeyOrigin {
             master_fp,
-            derivation: DerivationPath::from_str(path)?,
+            derivation,
         })
     }
 }
@@ -427,10 +870,8 @@ impl KeyOrigin {
     }
 
     pub fn with(xpub_origin: XpubOrigin, terminal: Terminal) -> Self {
-        let mut derivation = DerivationPath::new();
-        derivation.extend(xpub_origin.derivation().iter().copied().map(DerivationIndex::from));
-        derivation.push(terminal.keychain.into());
-        derivation.push(DerivationIndex::Normal(terminal.index));
+        let derivation =
+            DerivationPath::new().join(xpub_origin.derivation()).with_terminal(terminal);
         KeyOrigin {
             master_fp: xpub_origin.master_fp(),
             derivation,
@@ -488,38 +929,68 @@ impl FromStr for XpubSpec {
     }
 }
 
-#[derive(Getters, Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Getters, Clone, Debug)]
 pub struct XpubDerivable {
     spec: XpubSpec,
     variant: Option<NormalIndex>,
     pub(crate) keychains: DerivationSeg<Keychain>,
+    /// Keychain-level extended public keys, precomputed such that deriving a key for each new
+    /// index requires a single BIP-32 derivation step.
+    #[getter(skip)]
+    keychain_xpubs: BTreeMap<Keychain, Xpub>,
+}
+
+// The precomputed keychain keys are a cache fully defined by the other fields, so they are
+// excluded from the comparison and hashing.
+impl PartialEq for XpubDerivable {
+    fn eq(&self, other: &Self) -> bool {
+        self.spec == other.spec
+            && self.variant == other.variant
+            && self.keychains == other.keychains
+    }
+}
+
+impl Eq for XpubDerivable {}
+
+impl std_hash::Hash for XpubDerivable {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.spec.hash(state);
+        self.variant.hash(state);
+        self.keychains.hash(state);
+    }
 }
 
 impl From<XpubSpec> for XpubDerivable {
     fn from(spec: XpubSpec) -> Self {
-        XpubDerivable {
-            spec,
-            variant: None,
-            keychains: DerivationSeg::from([Keychain::INNER, Keychain::OUTER]),
-        }
+        XpubDerivable::with(spec, None, DerivationSeg::from([Keychain::INNER, Keychain::OUTER]))
     }
 }
 
 impl XpubDerivable {
-    pub fn new_standard(xpub: Xpub, origin: XpubOrigin) -> Self {
+    fn with(
+        spec: XpubSpec,
+        variant: Option<NormalIndex>,
+        keychains: DerivationSeg<Keychain>,
+    ) -> Self {
+        let keychain_xpubs = keychains
+            .as_set()
+            .iter()
+            .map(|keychain| (*keychain, spec.xpub.ckd_pub((*keychain).into())))
+            .collect();
         XpubDerivable {
-            spec: XpubSpec::new(xpub, origin),
-            variant: None,
-            keychains: DerivationSeg::from([Keychain::INNER, Keychain::OUTER]),
+            spec,
+            variant,
+            keychains,
+            keychain_xpubs,
         }
     }
 
+    pub fn new_standard(xpub: Xpub, origin: XpubOrigin) -> Self {
+        XpubDerivable::from(XpubSpec::new(xpub, origin))
+    }
+
     pub fn new_custom(xpub: Xpub, origin: XpubOrigin, keychains: &'static [Keychain]) -> Self {
-        XpubDerivable {
-            spec: XpubSpec::new(xpub, origin),
-            variant: None,
-            keychains: DerivationSeg::from(keychains),
-        }
+        XpubDerivable::with(XpubSpec::new(xpub, origin), None, DerivationSeg::from(keychains))
     }
 
     pub fn try_custom(
@@ -527,15 +998,35 @@ impl XpubDerivable {
         origin: XpubOrigin,
         keychains: impl IntoIterator<Item = Keychain>,
     ) -> Result<Self, confinement::Error> {
-        Ok(XpubDerivable {
-            spec: XpubSpec::new(xpub, origin),
-            variant: None,
-            keychains: DerivationSeg::with(keychains)?,
-        })
+        Ok(XpubDerivable::with(XpubSpec::new(xpub, origin), None, DerivationSeg::with(keychains)?))
+    }
+
+    /// Derives account of the derivation `scheme` from the `master` extended private key,
+    /// returning its extended public key with the master fingerprint and the origin path,
+    /// deriving keys for the standard receive and change keychains.
+    pub fn with_scheme(
+        master: &Xpriv,
+        scheme: DerivationScheme,
+        account: impl Into<HardenedIndex>,
+        network: Network,
+    ) -> Self {
+        XpubDerivable::from(
+            XprivAccount::with_scheme(master, scheme, account, network).to_xpub_spec(),
+        )
     }
 
     pub fn xpub(&self) -> Xpub { self.spec.xpub }
 
+    /// Returns extended public key of the `keychain`, from which the keys are derived by their
+    /// indexes. Uses precomputed keys for the keychains of the descriptor, deriving the key only
+    /// for other keychains.
+    pub fn keychain_xpub(&self, keychain: Keychain) -> Xpub {
+        self.keychain_xpubs
+            .get(&keychain)
+            .copied()
+            .unwrap_or_else(|| self.spec.xpub.ckd_pub(keychain.into()))
+    }
+
     pub fn origin(&self) -> &XpubOrigin { &self.spec.origin }
 }
 
@@ -553,19 +1044,28 @@ impl Display for XpubDerivable {
 impl FromStr for XpubDerivable {
     type Err = XpubParseError;
 
+    /// Parses key expression `[fp/origin]xpub/<0;1>/*`. The origin in square brackets may be
+    /// omitted only for master keys (with zero depth), which get their own fingerprint and an
+    /// empty path as the origin.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.starts_with('[') {
-            return Err(XpubParseError::NoOrigin);
-        }
-        let (origin, remains) =
-            s.trim_start_matches('[').split_once(']').ok_or(XpubParseError::NoOrigin)?;
+        let (origin, remains) = match s.strip_prefix('[') {
+            Some(s) => {
+                let (origin, remains) = s.split_once(']').ok_or(XpubParseError::NoOrigin)?;
+                (Some(XpubOrigin::from_str(origin)?), remains)
+            }
+            None => (None, s),
+        };
 
-        let origin = XpubOrigin::from_str(origin)?;
         let mut segs = remains.split('/');
         let Some(xpub) = segs.next() else {
             return Err(XpubParseError::NoXpub);
         };
         let xpub = Xpub::from_str(xpub)?;
+        let origin = match origin {
+            Some(origin) => origin,
+            None if xpub.meta.depth == 0 => XpubOrigin::new(xpub.fingerprint(), empty!()),
+            None => return Err(XpubParseError::NoOrigin),
+        };
 
         let (variant, keychains) = match (segs.next(), segs.next(), segs.next(), segs.next()) {
             (Some(var), Some(keychains), Some("*"), None) => {
@@ -575,11 +1075,112 @@ impl FromStr for XpubDerivable {
             _ => return Err(XpubParseError::InvalidTerminal),
         };
 
-        Ok(XpubDerivable {
-            spec: XpubSpec::new(xpub, origin),
-            variant,
-            keychains,
-        })
+        Ok(XpubDerivable::with(XpubSpec::new(xpub, origin), variant, keychains))
+    }
+}
+
+/// Extended private key for an account, together with its origin information.
+#[derive(Getters, Clone, Eq, PartialEq, Debug)]
+pub struct XprivAccount {
+    origin: XpubOrigin,
+    xpriv: Xpriv,
+}
+
+impl XprivAccount {
+    pub fn new(xpriv: Xpriv, origin: XpubOrigin) -> Self { XprivAccount { xpriv, origin } }
+
+    /// Constructs account from a master extended private key.
+    pub fn new_master(xpriv: Xpriv) -> Self {
+        XprivAccount {
+            origin: XpubOrigin::new(xpriv.fingerprint(), empty!()),
+            xpriv,
+        }
+    }
+
+    /// Derives account of the derivation `scheme` from the `master` extended private key,
+    /// recording the master fingerprint and the origin path of the account.
+    pub fn with_scheme(
+        master: &Xpriv,
+        scheme: DerivationScheme,
+        account: impl Into<HardenedIndex>,
+        network: Network,
+    ) -> Self {
+        XprivAccount::new_master(master.clone())
+            .derive(scheme.account_path(network, account).as_slice())
+    }
+
+    /// Derives hardened sub-account, extending its origin information. The depth of the derived
+    /// key saturates at 255; use [`XprivAccount::checked_derive`] to detect the overflow.
+    pub fn derive(&self, path: impl AsRef<[HardenedIndex]>) -> Self {
+        let path = path.as_ref();
+        self.with_xpriv(path, self.xpriv.derive_priv(path.iter().copied()))
+    }
+
+    /// Derives hardened sub-account, extending its origin information, failing if the derived
+    /// key depth exceeds 255.
+    pub fn checked_derive(&self, path: impl AsRef<[HardenedIndex]>) -> Result<Self, DepthOverflow> {
+        let path = path.as_ref();
+        let xpriv = self.xpriv.checked_derive_priv(path.iter().copied())?;
+        Ok(self.with_xpriv(path, xpriv))
+    }
+
+    fn with_xpriv(&self, path: &[HardenedIndex], xpriv: Xpriv) -> Self {
+        let mut derivation = self.origin.derivation.clone();
+        derivation.extend(path);
+        XprivAccount {
+            origin: XpubOrigin::new(self.origin.master_fp, derivation),
+            xpriv,
+        }
+    }
+
+    /// Returns extended public key specification for this account.
+    pub fn to_xpub_spec(&self) -> XpubSpec {
+        XpubSpec::new(self.xpriv.to_xpub(), self.origin.clone())
+    }
+
+    /// Derives private key for the provided key origin, if it belongs to this
+    /// account.
+    pub fn derive_origin(&self, origin: &KeyOrigin) -> Option<Xpriv> {
+        if origin.master_fp() != self.origin.master_fp() {
+            return None;
+        }
+        let prefix = self.origin.derivation();
+        let path = origin.derivation();
+        if path.len() < prefix.len()
+            || path.iter().zip(prefix.iter()).any(|(a, b)| *a != DerivationIndex::Hardened(*b))
+        {
+            return None;
+        }
+        self.xpriv.checked_derive_priv(path[prefix.len()..].iter().copied()).ok()
+    }
+}
+
+impl Display for XprivAccount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        Display::fmt(&self.origin, f)?;
+        f.write_str("]")?;
+        write!(f, "{}", self.xpriv)
+    }
+}
+
+impl FromStr for XprivAccount {
+    type Err = XprivParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.starts_with('[') {
+            return Err(XprivParseError::NoOrigin);
+        }
+        let (origin, xpriv) =
+            s.trim_start_matches('[').split_once(']').ok_or(XprivParseError::NoOrigin)?;
+        let origin = XpubOrigin::from_str(origin)?;
+        let xpriv = Xpriv::from_str(xpriv)?;
+
+        if origin.derivation.len() != xpriv.meta.depth as usize {
+            return Err(XprivParseError::DepthMismatch);
+        }
+
+        Ok(XprivAccount { origin, xpriv })
     }
 }
 
@@ -656,6 +1257,7 @@ mod _serde {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::Derive;
 
     #[test]
     fn test_xpub_derivable_from_str_with_hardened_index() {
@@ -664,6 +1266,47 @@ mod test {
         assert_eq!(s, xpub.to_string());
     }
 
+    #[test]
+    fn xpub_derivable_keychain_cache() {
+        let s = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+        let xpub = XpubDerivable::from_str(s).unwrap();
+        for keychain in [0u8, 1, 2] {
+            let keychain = Keychain::with(keychain);
+            assert_eq!(xpub.keychain_xpub(keychain), xpub.xpub().derive_pub([keychain.into()]));
+            for index in [0u8, 1, 100] {
+                let index = NormalIndex::from(index);
+                assert_eq!(
+                    Derive::<CompressedPk>::derive(&xpub, keychain, index),
+                    xpub.xpub().derive_pub([keychain.into(), index]).to_compr_pub()
+                );
+            }
+        }
+        assert_eq!(XpubDerivable::from(xpub.spec().clone()), xpub);
+
+        // The cache doesn't affect equality and hashing
+        let mut uncached = xpub.clone();
+        uncached.keychain_xpubs.clear();
+        assert_eq!(uncached, xpub);
+        let hash = |key: &XpubDerivable| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            std_hash::Hash::hash(key, &mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&uncached), hash(&xpub));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn xpub_derivable_par() {
+        let s = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+        let xpub = XpubDerivable::from_str(s).unwrap();
+        let range = NormalIndex::from(10u8)..NormalIndex::from(500u16);
+        let par: Vec<XOnlyPk> = xpub.derive_batch_par(1, range.clone());
+        let seq = Derive::<XOnlyPk>::derive_batch(&xpub, 1, range).collect::<Vec<_>>();
+        assert_eq!(par.len(), 490);
+        assert_eq!(par, seq);
+    }
+
     #[test]
     fn test_xpub_derivable_from_str_with_normal_index() {
         let s = "[643a7adc/86'/1'/0']tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
@@ -677,4 +1320,155 @@ mod test {
         let xpub = XpubDerivable::from_str(s).unwrap();
         assert_eq!(s, format!("{xpub:#}"));
     }
+
+    #[test]
+    fn xpriv_bip32_vector1() {
+        let seed = [0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+        let master = Xpriv::new_master(false, &seed);
+        assert_eq!(
+            master.to_xpub().to_string(),
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"
+        );
+        let child = master.ckd_priv(HardenedIndex::hardened(0));
+        assert_eq!(
+            child.to_string(),
+            "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7"
+        );
+        assert_eq!(
+            child.to_xpub().to_string(),
+            "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw"
+        );
+        assert_eq!(Xpriv::from_str(&child.to_string()).unwrap(), child);
+    }
+
+    #[test]
+    fn xpriv_normal_derivation() {
+        let master = Xpriv::new_master(true, &[0xA5; 32]);
+        let path = [NormalIndex::ONE, NormalIndex::ZERO];
+        assert_eq!(master.derive_priv(path).to_xpub(), master.to_xpub().derive_pub(path));
+    }
+
+    #[test]
+    fn depth_overflow() {
+        let mut data = *Xpriv::new_master(true, &[0xA5; 32]).encode().as_bytes();
+        data[4] = u8::MAX;
+        let xpriv = Xpriv::decode(data).unwrap();
+        assert_eq!(xpriv.checked_ckd_priv(NormalIndex::ONE), Err(DepthOverflow));
+        assert_eq!(xpriv.checked_derive_priv([NormalIndex::ONE]), Err(DepthOverflow));
+        assert_eq!(xpriv.checked_derive_priv::<NormalIndex>([]), Ok(xpriv.clone()));
+        let child = xpriv.ckd_priv(NormalIndex::ONE);
+        assert_eq!(child.meta.depth, u8::MAX);
+        assert_eq!(child.core, xpriv.core.ckd_priv(NormalIndex::ONE));
+
+        let xpub = xpriv.to_xpub();
+        assert_eq!(xpub.checked_ckd_pub(NormalIndex::ONE), Err(DepthOverflow));
+        assert_eq!(xpub.checked_derive_pub([NormalIndex::ONE]), Err(DepthOverflow));
+        assert_eq!(xpub.ckd_pub(NormalIndex::ONE), child.to_xpub());
+
+        let origin = XpubOrigin::new(
+            XpubFp::default(),
+            DerivationPath::from(vec![HardenedIndex::hardened(0); 255]),
+        );
+        let account = XprivAccount::new(xpriv, origin);
+        assert_eq!(account.checked_derive([HardenedIndex::hardened(0)]), Err(DepthOverflow));
+        assert_eq!(account.derive([HardenedIndex::hardened(0)]).xpriv().meta.depth, u8::MAX);
+    }
+
+    #[test]
+    fn erase_secrets() {
+        let mut chain_code = ChainCode::from([0xA5u8; 32]);
+        erase(&mut chain_code);
+        assert_eq!(chain_code, ChainCode::default());
+    }
+
+    #[test]
+    fn xpriv_account_origin() {
+        let master = XprivAccount::new_master(Xpriv::new_master(true, &[0xA5; 32]));
+        let account = master.derive([
+            HardenedIndex::hardened(86),
+            HardenedIndex::hardened(1),
+            HardenedIndex::hardened(0),
+        ]);
+        assert_eq!(account.origin().derivation().len(), 3);
+        assert_eq!(XprivAccount::from_str(&account.to_string()).unwrap(), account);
+
+        let origin =
+            KeyOrigin::with(account.origin().clone(), Terminal::new(1u8, NormalIndex::from(7u8)));
+        let xpriv = account.derive_origin(&origin).unwrap();
+        assert_eq!(master.derive_origin(&origin), Some(xpriv.clone()));
+        assert_eq!(
+            xpriv.to_xpub(),
+            account.xpriv().to_xpub().derive_pub([NormalIndex::ONE, NormalIndex::from(7u8)])
+        );
+
+        let foreign = KeyOrigin::new(XpubFp::default(), origin.derivation().clone());
+        assert_eq!(account.derive_origin(&foreign), None);
+    }
+
+    #[test]
+    fn xpub_network() {
+        let xpub = Xpriv::new_master(true, &[0xA5; 32]).to_xpub();
+        assert!(xpub.is_testnet());
+        for app in [KeyApplication::Legacy, KeyApplication::ShWpkh, KeyApplication::Wpkh] {
+            let s = xpub.to_string_slip132(app);
+            assert!(["tpub", "upub", "vpub"].contains(&&s[..4]));
+            let parsed = Xpub::from_str(&s).unwrap();
+            assert!(parsed.is_testnet());
+            assert_eq!(parsed, xpub);
+        }
+        assert!(!Xpriv::new_master(false, &[0xA5; 32]).to_xpub().is_testnet());
+    }
+
+    #[test]
+    fn xpub_derivable_origin() {
+        let xpub = "tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2";
+        let s = format!("[643a7adc/86h/1h/0h]{xpub}/<0;1>/*");
+        let key = XpubDerivable::from_str(&s).unwrap();
+        assert_eq!(key.to_string(), s);
+        let alt = format!("[643a7adc/86'/1'/0']{xpub}/<0;1>/*");
+        assert_eq!(format!("{key:#}"), alt);
+        assert_eq!(XpubDerivable::from_str(&alt).unwrap(), key);
+
+        // Origin can't be synthesized for non-master keys
+        assert_eq!(XpubDerivable::from_str(&format!("{xpub}/0/*")), Err(XpubParseError::NoOrigin));
+
+        let master = Xpriv::new_master(true, &[0xA5; 32]).to_xpub();
+        let bare = XpubDerivable::from_str(&format!("{master}/0/*")).unwrap();
+        assert_eq!(bare.xpub(), master);
+        assert_eq!(bare.origin().master_fp(), master.fingerprint());
+        assert!(bare.origin().derivation().is_empty());
+        assert_eq!(XpubDerivable::from_str(&bare.to_string()).unwrap(), bare);
+
+        assert_eq!(
+            XpubDerivable::from_str(&format!("[643a7adc/86h{xpub}/0/*")),
+            Err(XpubParseError::NoOrigin)
+        );
+    }
+
+    #[test]
+    fn xpriv_account_scheme() {
+        let master = Xpriv::new_master(true, &[0xA5; 32]);
+        let account =
+            XprivAccount::with_scheme(&master, DerivationScheme::Bip86, 0u8, Network::Testnet3);
+        assert_eq!(
+            account,
+            XprivAccount::new_master(master.clone()).derive([
+                HardenedIndex::hardened(86),
+                HardenedIndex::hardened(1),
+                HardenedIndex::hardened(0),
+            ])
+        );
+        assert_eq!(account.origin().master_fp(), master.fingerprint());
+
+        let xpub = XpubDerivable::with_scheme(
+            &master,
+            DerivationScheme::Bip84,
+            HardenedIndex::hardened(2),
+            Network::Mainnet,
+        );
+        assert_eq!(xpub.origin().derivation().to_string(), "/84h/0h/2h");
+        assert_eq!(xpub.origin().master_fp(), master.fingerprint());
+        assert_eq!(xpub.keychains.as_set().len(), 2);
+        assert!(xpub.to_string().ends_with("/<0;1>/*"));
+    }
 }
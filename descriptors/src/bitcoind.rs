@@ -0,0 +1,230 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of descriptors in the format consumed by Bitcoin Core `importdescriptors` RPC.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use derive::Keychain;
+
+use crate::descriptor_checksum;
+
+/// Time from which Bitcoin Core rescans the blockchain for the transactions of an imported
+/// descriptor.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display)]
+pub enum ImportTimestamp {
+    /// Descriptor has no history and doesn't require rescanning.
+    #[default]
+    #[display("\"now\"")]
+    Now,
+
+    /// UNIX timestamp of the earliest transaction of the descriptor.
+    #[display(inner)]
+    Time(u64),
+}
+
+/// Single-keychain descriptor request of Bitcoin Core `importdescriptors` RPC.
+///
+/// Displays as a JSON object.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CoreDescriptor {
+    /// Descriptor with checksum.
+    pub desc: String,
+    pub keychain: Keychain,
+    /// Whether the descriptor is used by the wallet for new addresses. Only the receiving and
+    /// change keychains can be active.
+    pub active: bool,
+    /// Range of derivation indexes to import (inclusive).
+    pub range: (u32, u32),
+    pub next_index: u32,
+    pub timestamp: ImportTimestamp,
+}
+
+impl CoreDescriptor {
+    /// Whether the descriptor is imported as a change descriptor; `None` for keychains not
+    /// known to Bitcoin Core.
+    pub fn internal(&self) -> Option<bool> {
+        match self.keychain {
+            Keychain::OUTER => Some(false),
+            Keychain::INNER => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CoreDescriptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            r#"{{"desc":"{}","active":{},"range":[{},{}],"next_index":{},"timestamp":{}"#,
+            self.desc, self.active, self.range.0, self.range.1, self.next_index, self.timestamp
+        )?;
+        if let Some(internal) = self.internal() {
+            write!(f, r#","internal":{internal}"#)?;
+        }
+        f.write_str("}")
+    }
+}
+
+/// Request of Bitcoin Core `importdescriptors` RPC, containing a descriptor for each of the
+/// wallet keychains.
+///
+/// Displays as a JSON array.
+#[derive(Wrapper, WrapperMut, Clone, Eq, PartialEq, Hash, Debug, Default, From)]
+#[wrapper(Deref)]
+#[wrapper_mut(DerefMut)]
+pub struct CoreImport(Vec<CoreDescriptor>);
+
+impl CoreImport {
+    /// Splits descriptor with BIP-389 multipath key derivation (`<0;1>`) into descriptors for
+    /// each of the keychains. Descriptors without multipath derivation are imported for the
+    /// `default_keychain`.
+    ///
+    /// All keychains except the receiving and change ones are imported as inactive.
+    pub fn with(
+        descr: &str,
+        default_keychain: Keychain,
+        timestamp: ImportTimestamp,
+        range_end: u32,
+    ) -> Self {
+        let descr = descr.split_once('#').map(|(d, _)| d).unwrap_or(descr);
+        let descrs = expand_multipath(descr)
+            .unwrap_or_else(|| vec![(default_keychain.to_string(), descr.to_owned())]);
+        let items = descrs.into_iter().map(|(keychain, descr)| {
+            let keychain = Keychain::from_str(&keychain).unwrap_or(default_keychain);
+            let checksum = descriptor_checksum(&descr).unwrap_or_default();
+            CoreDescriptor {
+                desc: format!("{descr}#{checksum}"),
+                keychain,
+                active: matches!(keychain, Keychain::OUTER | Keychain::INNER),
+                range: (0, range_end),
+                next_index: 0,
+                timestamp,
+            }
+        });
+        CoreImport(items.collect())
+    }
+}
+
+impl Display for CoreImport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (no, descr) in self.0.iter().enumerate() {
+            if no > 0 {
+                f.write_str(",")?;
+            }
+            Display::fmt(descr, f)?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// Expands all multipath derivation segments `<a;b;...>` of the descriptor, returning the
+/// first segment element of each expansion together with the expanded descriptor. Returns
+/// `None` if the descriptor has no multipath segments or their lengths mismatch.
+fn expand_multipath(descr: &str) -> Option<Vec<(String, String)>> {
+    let mut parts = vec![];
+    let mut groups = Vec::<Vec<&str>>::new();
+    let mut rest = descr;
+    while let Some((head, tail)) = rest.split_once('<') {
+        let (group, tail) = tail.split_once('>')?;
+        parts.push(head);
+        groups.push(group.split(';').collect());
+        rest = tail;
+    }
+    let count = groups.first()?.len();
+    if groups.iter().any(|group| group.len() != count) {
+        return None;
+    }
+    let expanded = (0..count)
+        .map(|no| {
+            let mut s = String::with_capacity(descr.len());
+            for (head, group) in parts.iter().zip(&groups) {
+                s.push_str(head);
+                s.push_str(group[no]);
+            }
+            s.push_str(rest);
+            (groups[0][no].to_owned(), s)
+        })
+        .collect();
+    Some(expanded)
+}
+
+#[cfg(test)]
+mod test {
+    use derive::XpubDerivable;
+
+    use super::*;
+    use crate::{Descriptor, StdDescr};
+
+    const KEY1: &str = "[643a7adc/48h/1h/0h/2h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2";
+    const KEY2: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC";
+
+    #[test]
+    fn multipath_split() {
+        let descr = format!("wsh(sortedmulti(1,{KEY1}/<0;1>/*,{KEY2}/<0;1>/*))");
+        let import = CoreImport::with(&descr, Keychain::OUTER, ImportTimestamp::Now, 999);
+        assert_eq!(import.len(), 2);
+        assert_eq!(import[0].keychain, Keychain::OUTER);
+        assert!(import[0].desc.starts_with(&format!("wsh(sortedmulti(1,{KEY1}/0/*,{KEY2}/0/*))#")));
+        assert_eq!(import[1].internal(), Some(true));
+        assert!(import[1].desc.starts_with(&format!("wsh(sortedmulti(1,{KEY1}/1/*,{KEY2}/1/*))#")));
+
+        let (descr, checksum) = import[0].desc.split_once('#').unwrap();
+        assert_eq!(descriptor_checksum(descr).unwrap(), checksum);
+    }
+
+    #[test]
+    fn json() {
+        let descr = format!("wpkh({KEY1}/<0;1;2>/*)#00000000");
+        let import =
+            CoreImport::with(&descr, Keychain::OUTER, ImportTimestamp::Time(1700000000), 99);
+        assert!(!import[2].active);
+        assert_eq!(import[2].internal(), None);
+        let json = import.to_string();
+        assert!(json.starts_with(&format!(r#"[{{"desc":"wpkh({KEY1}/0/*)#"#)));
+        assert!(json.contains(
+            r#""active":true,"range":[0,99],"next_index":0,"timestamp":1700000000,"internal":true}"#
+        ));
+        assert!(json
+            .ends_with(r#""active":false,"range":[0,99],"next_index":0,"timestamp":1700000000}]"#));
+    }
+
+    #[test]
+    fn single_path() {
+        let descr = format!("wpkh({KEY1}/1/*)");
+        let import = CoreImport::with(&descr, Keychain::INNER, ImportTimestamp::Now, 10);
+        assert_eq!(import.len(), 1);
+        assert_eq!(import[0].internal(), Some(true));
+        assert!(import.to_string().contains(r#""timestamp":"now""#));
+    }
+
+    #[test]
+    fn std_descr() {
+        let descr = StdDescr::<XpubDerivable>::from_str(&format!("tr({KEY2}/<0;1>/*)")).unwrap();
+        let import = descr.to_core_descriptors(ImportTimestamp::Now, 999);
+        assert_eq!(import.len(), 2);
+        assert_eq!(import[0].desc.split_once('#').unwrap().0, format!("tr({KEY2}/0/*)"));
+        assert_eq!(import[1].desc.split_once('#').unwrap().0, format!("tr({KEY2}/1/*)"));
+    }
+}
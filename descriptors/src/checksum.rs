@@ -0,0 +1,122 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Descriptor checksums as defined in BIP-380.
+
+use crate::DescrParseError;
+
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!\
+                             ^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATORS: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+
+/// Length of the descriptor checksum in characters.
+pub const CHECKSUM_LEN: usize = 8;
+
+fn polymod(mut c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    c = ((c & 0x7ffffffff) << 5) ^ val;
+    for (bit, gen) in GENERATORS.iter().enumerate() {
+        if c0 & (1 << bit) != 0 {
+            c ^= gen;
+        }
+    }
+    c
+}
+
+/// Computes 8-character checksum of a descriptor string (without the `#` separator and the
+/// checksum itself).
+///
+/// Returns `None` if the descriptor contains characters which are not allowed in descriptors.
+pub fn descriptor_checksum(descr: &str) -> Option<String> {
+    let mut c = 1u64;
+    let mut cls = 0u64;
+    let mut count = 0u8;
+    for ch in descr.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        c = polymod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        count += 1;
+        if count == 3 {
+            c = polymod(c, cls);
+            cls = 0;
+            count = 0;
+        }
+    }
+    if count > 0 {
+        c = polymod(c, cls);
+    }
+    for _ in 0..CHECKSUM_LEN {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    let checksum = (0..CHECKSUM_LEN)
+        .map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect();
+    Some(checksum)
+}
+
+/// Splits descriptor string into the descriptor itself and its checksum, verifying the checksum
+/// if it is present.
+pub(crate) fn check_checksum(s: &str) -> Result<&str, DescrParseError> {
+    let Some((descr, checksum)) = s.rsplit_once('#') else {
+        return Ok(s);
+    };
+    let expected = descriptor_checksum(descr)
+        .ok_or_else(|| DescrParseError::InvalidSyntax(descr.to_owned()))?;
+    if checksum != expected {
+        return Err(DescrParseError::ChecksumMismatch {
+            expected,
+            found: checksum.to_owned(),
+        });
+    }
+    Ok(descr)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checksum_vectors() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(
+            descriptor_checksum(
+                "pkh([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)"
+            )
+            .unwrap(),
+            "ml40v0wf"
+        );
+        assert_eq!(descriptor_checksum("raw(deadbeef)é"), None);
+    }
+
+    #[test]
+    fn checksum_verification() {
+        assert_eq!(check_checksum("raw(deadbeef)#89f8spxm").unwrap(), "raw(deadbeef)");
+        assert_eq!(check_checksum("raw(deadbeef)").unwrap(), "raw(deadbeef)");
+        assert!(matches!(
+            check_checksum("raw(deadbeef)#89f8spxn"),
+            Err(DescrParseError::ChecksumMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Coldcard multisig setup file format, which is also used by many other wallets for sharing
+//! multisig wallet configuration between cosigners.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use derive::{
+    DerivationParseError, DerivationPath, HardenedIndex, Xpub, XpubDerivable, XpubFp, XpubOrigin,
+    XpubParseError,
+};
+
+use crate::{MultisigError, WshSortedMulti};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum MultisigSetupError {
+    /// multisig setup file doesn't contain `{0}` field.
+    NoField(&'static str),
+
+    /// invalid multisig policy '{0}'.
+    InvalidPolicy(String),
+
+    /// multisig policy requires {expected} cosigners, while the file provides {found}.
+    CosignerCount { expected: usize, found: usize },
+
+    /// unsupported multisig script format '{0}'; only P2WSH is supported.
+    UnsupportedFormat(String),
+
+    /// cosigner {0} has no derivation path specified.
+    NoDerivation(XpubFp),
+
+    /// invalid derivation path - {0}
+    #[from]
+    Derivation(DerivationParseError),
+
+    /// invalid cosigner extended public key - {0}
+    #[from]
+    Xpub(XpubParseError),
+
+    #[display(inner)]
+    #[from]
+    Multisig(MultisigError),
+}
+
+/// Multisig wallet configuration in the Coldcard setup file format:
+///
+/// ```text
+/// Name: Vault
+/// Policy: 2 of 3
+/// Derivation: m/48'/0'/0'/2'
+/// Format: P2WSH
+///
+/// 0F056943: xpub...
+/// ```
+///
+/// Coldcard multisig wallets always use lexicographically sorted keys, thus the setup maps onto
+/// the `wsh(sortedmulti(...))` descriptor. When cosigners use different derivation paths, a
+/// `Derivation` line precedes each of the cosigner lines.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MultisigSetup {
+    pub name: String,
+    pub descriptor: WshSortedMulti<XpubDerivable>,
+}
+
+impl MultisigSetup {
+    pub fn new(name: impl Into<String>, descriptor: WshSortedMulti<XpubDerivable>) -> Self {
+        MultisigSetup {
+            name: name.into(),
+            descriptor,
+        }
+    }
+}
+
+fn parse_derivation(s: &str) -> Result<DerivationPath<HardenedIndex>, DerivationParseError> {
+    let s = s.strip_prefix(['m', 'M']).unwrap_or(s);
+    DerivationPath::from_str(s)
+}
+
+fn parse_policy(s: &str) -> Option<(u16, usize)> {
+    let (threshold, count) = s.split_once(" of ").or_else(|| s.split_once('/'))?;
+    Some((threshold.trim().parse().ok()?, count.trim().parse().ok()?))
+}
+
+impl FromStr for MultisigSetup {
+    type Err = MultisigSetupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut policy = None;
+        let mut derivation = None;
+        let mut keys = vec![];
+
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "name" => name = Some(value.to_owned()),
+                "policy" => {
+                    policy = Some(
+                        parse_policy(value)
+                            .ok_or_else(|| MultisigSetupError::InvalidPolicy(value.to_owned()))?,
+                    )
+                }
+                "derivation" => derivation = Some(parse_derivation(value)?),
+                "format" if value.eq_ignore_ascii_case("P2WSH") => {}
+                "format" => return Err(MultisigSetupError::UnsupportedFormat(value.to_owned())),
+                key => {
+                    // Unknown fields are ignored for forward compatibility
+                    let Ok(master_fp) = XpubFp::from_str(key) else {
+                        continue;
+                    };
+                    let derivation =
+                        derivation.clone().ok_or(MultisigSetupError::NoDerivation(master_fp))?;
+                    let (xpub, _) = Xpub::from_str_slip132(value)?;
+                    let origin = XpubOrigin::new(master_fp, derivation);
+                    keys.push(XpubDerivable::new_standard(xpub, origin));
+                }
+            }
+        }
+
+        let name = name.ok_or(MultisigSetupError::NoField("Name"))?;
+        let (threshold, count) = policy.ok_or(MultisigSetupError::NoField("Policy"))?;
+        if count != keys.len() {
+            return Err(MultisigSetupError::CosignerCount {
+                expected: count,
+                found: keys.len(),
+            });
+        }
+        Ok(MultisigSetup {
+            name,
+            descriptor: WshSortedMulti::new(threshold, keys)?,
+        })
+    }
+}
+
+impl Display for MultisigSetup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let keys = self.descriptor.as_keys();
+        let common = keys
+            .first()
+            .map(|key| key.origin().derivation())
+            .filter(|first| keys.iter().all(|key| key.origin().derivation() == *first));
+
+        writeln!(f, "Name: {}", self.name)?;
+        writeln!(f, "Policy: {} of {}", self.descriptor.threshold(), keys.len())?;
+        if let Some(derivation) = common {
+            writeln!(f, "Derivation: m{derivation:#}")?;
+        }
+        writeln!(f, "Format: P2WSH")?;
+        for key in keys {
+            writeln!(f)?;
+            if common.is_none() {
+                writeln!(f, "Derivation: m{:#}", key.origin().derivation())?;
+            }
+            let master_fp = key.origin().master_fp().to_string().to_uppercase();
+            writeln!(f, "{master_fp}: {}", key.xpub())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY1: &str = "tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2";
+    const KEY2: &str = "tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC";
+
+    #[test]
+    fn roundtrip_common_derivation() {
+        let s = format!(
+            "# Coldcard Multisig setup file\n#\nName: Vault\nPolicy: 1 of 2\nDerivation: \
+             m/48'/1'/0'/2'\nFormat: P2WSH\n\n643A7ADC: {KEY1}\n\n5A0804E3: {KEY2}\n"
+        );
+        let setup = MultisigSetup::from_str(&s).unwrap();
+        assert_eq!(setup.name, "Vault");
+        assert_eq!(setup.descriptor.threshold(), 1);
+        let keys = setup.descriptor.as_keys();
+        assert_eq!(keys[0].to_string(), format!("[643a7adc/48h/1h/0h/2h]{KEY1}/<0;1>/*"));
+        assert_eq!(keys[1].origin().to_string(), "5a0804e3/48h/1h/0h/2h");
+        assert_eq!(setup.to_string(), s.split_once("#\n").unwrap().1);
+    }
+
+    #[test]
+    fn roundtrip_custom_derivation() {
+        let s = format!(
+            "Name: Mixed\nPolicy: 2 of 2\nFormat: P2WSH\n\nDerivation: m/48'/1'/0'/2'\n643A7ADC: \
+             {KEY1}\n\nDerivation: m/48'/1'/1'/2'\n5A0804E3: {KEY2}\n"
+        );
+        let setup = MultisigSetup::from_str(&s).unwrap();
+        assert_eq!(setup.descriptor.as_keys()[1].origin().to_string(), "5a0804e3/48h/1h/1h/2h");
+        assert_eq!(setup.to_string(), s);
+    }
+
+    #[test]
+    fn invalid() {
+        let base = format!("Name: Vault\nDerivation: m/48'/1'/0'/2'\n643A7ADC: {KEY1}\n");
+        assert_eq!(MultisigSetup::from_str(&base), Err(MultisigSetupError::NoField("Policy")));
+        assert_eq!(
+            MultisigSetup::from_str(&format!("Policy: 1 of 2\n{base}")),
+            Err(MultisigSetupError::CosignerCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            MultisigSetup::from_str(&format!("Policy: 1 of 1\nFormat: P2SH\n{base}")),
+            Err(MultisigSetupError::UnsupportedFormat(s!("P2SH")))
+        );
+        assert_eq!(
+            MultisigSetup::from_str(&format!("Policy: 2 of 1\n{base}")),
+            Err(MultisigSetupError::Multisig(MultisigError::ThresholdExceedsKeys {
+                threshold: 2,
+                keys: 1
+            }))
+        );
+    }
+}
@@ -0,0 +1,223 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multi-format key watcher `combo(KEY)`, used to recover funds when the address type used by
+//! the original wallet is unknown.
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use derive::{
+    CompressedPk, Derive, DeriveCompr, DeriveLegacy, DeriveScripts, DeriveSet, DerivedScript,
+    Keychain, NormalIndex, PubkeyHash, RedeemScript, ScriptPubkey, Terminal, WPubkeyHash,
+    XpubDerivable,
+};
+
+use crate::checksum::{check_checksum, descriptor_checksum};
+use crate::descriptor::script_args;
+use crate::{DescrParseError, Pkh, ShWpkh, StdDescr, Wpkh};
+
+/// Multi-format key watcher `combo(KEY)`, which tracks P2PKH, P2WPKH and P2SH-P2WPKH script
+/// pubkeys of the key at each terminal.
+///
+/// Since each terminal produces several script pubkeys, the watcher is not a
+/// [`crate::Descriptor`]. Once the funds are found, the descriptor of the used format is
+/// provided by [`Combo::terminal_of`].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate",))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
+pub struct Combo<K: DeriveCompr = XpubDerivable>(K);
+
+impl<K: DeriveCompr> Combo<K> {
+    pub fn as_key(&self) -> &K { &self.0 }
+    pub fn into_key(self) -> K { self.0 }
+}
+
+impl<K: DeriveSet<Legacy = K, Compr = K> + DeriveLegacy + DeriveCompr + Clone> Combo<K> {
+    /// Descriptors of all the tracked formats: P2PKH, P2WPKH and P2SH-P2WPKH.
+    pub fn descriptors(&self) -> [StdDescr<K>; 3] {
+        [
+            Pkh::from(self.0.clone()).into(),
+            Wpkh::from(self.0.clone()).into(),
+            ShWpkh::from(self.0.clone()).into(),
+        ]
+    }
+
+    /// Finds the terminal at which the script pubkey is derived together with the descriptor of
+    /// its format, scanning all keychains for the indexes below the `horizon` (see
+    /// [`DeriveScripts::terminal_of`]).
+    pub fn terminal_of(
+        &self,
+        script_pubkey: &ScriptPubkey,
+        horizon: NormalIndex,
+    ) -> Option<(Terminal, StdDescr<K>)> {
+        self.descriptors()
+            .into_iter()
+            .find_map(|descr| Some((descr.terminal_of(script_pubkey, horizon)?, descr)))
+    }
+}
+
+impl<K: DeriveCompr> Derive<[DerivedScript; 3]> for Combo<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.0.default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { self.0.keychains() }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> [DerivedScript; 3] {
+        let key: CompressedPk = self.0.derive(keychain, index);
+        let witness_program = ScriptPubkey::p2wpkh(WPubkeyHash::from(key));
+        [
+            DerivedScript::Bare(ScriptPubkey::p2pkh(PubkeyHash::from(key))),
+            DerivedScript::Bare(witness_program.clone()),
+            DerivedScript::Bip13(RedeemScript::from_unsafe(witness_program.to_vec())),
+        ]
+    }
+}
+
+impl<K: DeriveCompr + Display> Display for Combo<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("combo(")?;
+        Display::fmt(&self.0, f)?;
+        f.write_str(")")
+    }
+}
+
+impl<K: DeriveCompr + FromStr> FromStr for Combo<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = script_args(s.trim(), "combo")
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        Ok(Self(K::from_str(key)?))
+    }
+}
+
+/// Watched descriptor expression: either a standard descriptor or a multi-format `combo(KEY)`
+/// key watcher. Parsing dispatches `combo(...)` expressions to [`Combo`] and all others to
+/// [`StdDescr`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
+pub enum WatchDescr<K: DeriveSet<Legacy = K, Compr = K, XOnly = K> + DeriveCompr = XpubDerivable> {
+    #[from]
+    Std(StdDescr<K>),
+
+    #[from]
+    Combo(Combo<K>),
+}
+
+impl<K: DeriveSet<Legacy = K, Compr = K, XOnly = K> + DeriveLegacy + DeriveCompr + Clone>
+    WatchDescr<K>
+{
+    /// Standard descriptors watched by the expression: either the descriptor itself or all the
+    /// formats tracked by the [`Combo`] watcher.
+    pub fn descriptors(&self) -> Vec<StdDescr<K>> {
+        match self {
+            WatchDescr::Std(descr) => vec![descr.clone()],
+            WatchDescr::Combo(combo) => combo.descriptors().to_vec(),
+        }
+    }
+}
+
+impl<K: DeriveSet<Legacy = K, Compr = K, XOnly = K> + DeriveCompr + Display> Display
+    for WatchDescr<K>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchDescr::Std(descr) => Display::fmt(descr, f),
+            WatchDescr::Combo(combo) => {
+                let descr = combo.to_string();
+                f.write_str(&descr)?;
+                if let Some(checksum) = descriptor_checksum(&descr) {
+                    write!(f, "#{checksum}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<K: DeriveSet<Legacy = K, Compr = K, XOnly = K> + DeriveCompr + FromStr> FromStr
+    for WatchDescr<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with("combo(") {
+            Combo::from_str(check_checksum(s)?).map(Self::Combo)
+        } else {
+            StdDescr::from_str(s).map(Self::Std)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const XPUB: &str = "[643a7adc/84h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+
+    #[test]
+    fn combo() {
+        let s = format!("combo({XPUB})");
+        let combo = Combo::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(combo.to_string(), s);
+
+        let [pkh, wpkh, sh_wpkh] = combo.derive(1, 4u8);
+        let [pkh_descr, wpkh_descr, sh_wpkh_descr] = combo.descriptors();
+        assert_eq!(pkh, pkh_descr.derive(1, 4u8));
+        assert_eq!(wpkh, wpkh_descr.derive(1, 4u8));
+        assert_eq!(sh_wpkh, sh_wpkh_descr.derive(1, 4u8));
+
+        let horizon = NormalIndex::from(10u8);
+        let (terminal, descr) = combo.terminal_of(&sh_wpkh.to_script_pubkey(), horizon).unwrap();
+        assert_eq!(terminal, Terminal::change(NormalIndex::from(4u8)));
+        assert_eq!(descr, sh_wpkh_descr);
+        let (_, descr) = combo.terminal_of(&pkh.to_script_pubkey(), horizon).unwrap();
+        assert_eq!(descr, pkh_descr);
+        assert!(combo.terminal_of(&pkh.to_script_pubkey(), NormalIndex::from(4u8)).is_none());
+    }
+
+    #[test]
+    fn watch_descr() {
+        let combo = WatchDescr::<XpubDerivable>::from_str(&format!("combo({XPUB})")).unwrap();
+        let WatchDescr::Combo(inner) = &combo else {
+            panic!("combo expression parsed as {combo:?}");
+        };
+        assert_eq!(combo.descriptors(), inner.descriptors().to_vec());
+        assert_eq!(WatchDescr::<XpubDerivable>::from_str(&combo.to_string()).unwrap(), combo);
+
+        let wpkh = WatchDescr::<XpubDerivable>::from_str(&format!("wpkh({XPUB})")).unwrap();
+        assert!(matches!(wpkh, WatchDescr::Std(StdDescr::Wpkh(_))));
+        assert!(matches!(
+            WatchDescr::<XpubDerivable>::from_str(&format!("combo({XPUB})#00000000")),
+            Err(DescrParseError::ChecksumMismatch { .. })
+        ));
+    }
+}
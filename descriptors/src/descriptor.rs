@@ -21,18 +21,31 @@
 // limitations under the License.
 
 use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
 use std::iter;
+use std::str::FromStr;
 
 use derive::{
-    CompressedPk, Derive, DeriveCompr, DeriveScripts, DeriveSet, DeriveXOnly, DerivedScript,
-    KeyOrigin, Keychain, NormalIndex, Sats, TapDerivation, Terminal, XOnlyPk, XpubDerivable,
-    XpubSpec,
+    Address, AddressError, AddressNetwork, AddressParseError, CompressedPk, Derive, DeriveCompr,
+    DeriveLegacy, DeriveScripts, DeriveSet, DeriveXOnly, DerivedScript, InvalidTree, KeyOrigin,
+    Keychain, NormalIndex, Sats, TapDerivation, Terminal, VarInt, WeightUnits, XOnlyPk,
+    XpubDerivable, XpubParseError, XpubSpec,
 };
 use indexmap::IndexMap;
 
-use crate::{TrKey, Wpkh};
+use crate::checksum::{check_checksum, descriptor_checksum};
+use crate::miniscript::MiniscriptError;
+use crate::{
+    Addr, CoreImport, ImportTimestamp, MultisigError, Pkh, Raw, ShMulti, ShSortedMulti, ShWpkh,
+    Timelock, TrKey, TrMusig, TrScript, Wpkh, Wsh, WshMulti, WshSortedMulti,
+};
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
 #[display(lowercase)]
 pub enum SpkClass {
     Bare,
@@ -44,20 +57,162 @@ pub enum SpkClass {
 }
 
 impl SpkClass {
+    /// Dust limit of the outputs of this class under the default relay policy (see
+    /// [`output_dust_limit`]). Bare scripts have zero limit, since it depends on the script
+    /// length.
     pub const fn dust_limit(self) -> Sats {
-        match self {
-            SpkClass::Bare => Sats(0),
-            SpkClass::P2pkh => Sats(546),
-            SpkClass::P2sh => Sats(540),
-            SpkClass::P2wpkh => Sats(294),
-            SpkClass::P2wsh | SpkClass::P2tr => Sats(330),
+        let (script_len, witness_program) = match self {
+            SpkClass::Bare => return Sats(0),
+            SpkClass::P2pkh => (25, false),
+            SpkClass::P2sh => (23, false),
+            SpkClass::P2wpkh => (22, true),
+            SpkClass::P2wsh | SpkClass::P2tr => (34, true),
+        };
+        output_dust_limit(script_len, witness_program, DUST_RELAY_FEE)
+    }
+}
+
+/// Dust relay fee rate of the default relay policy (3 sat/vB), in satoshis per 1000 weight
+/// units.
+pub const DUST_RELAY_FEE: u64 = 750;
+
+/// Computes the minimal value of an output with a script pubkey of `script_len` bytes which is
+/// not considered dust under the relay policy with the given dust relay fee rate (in sat/kWU).
+///
+/// The limit is the fee for relaying both the output and an input spending it, assuming 148
+/// bytes for spending non-segwit outputs and 67 virtual bytes for witness programs.
+pub const fn output_dust_limit(
+    script_len: usize,
+    witness_program: bool,
+    dust_relay_fee: u64,
+) -> Sats {
+    let len_prefix = match script_len {
+        0..=0xFC => 1,
+        0xFD..=0xFFFF => 3,
+        _ => 5,
+    };
+    let output_size = 8 + len_prefix + script_len;
+    let input_size = if witness_program { 32 + 4 + 1 + 107 / 4 + 4 } else { 32 + 4 + 1 + 107 + 4 };
+    let vbytes = (output_size + input_size) as u64;
+    Sats(dust_relay_fee.saturating_mul(4 * vbytes) / 1000)
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum DescrParseError {
+    /// descriptor '{0}' uses unknown or unsupported script type.
+    UnknownScript(String),
+
+    /// invalid descriptor syntax in '{0}'.
+    InvalidSyntax(String),
+
+    /// descriptor checksum mismatch: expected {expected}, found {found}.
+    ChecksumMismatch { expected: String, found: String },
+
+    /// multipath keys in the descriptor use different sets of keychains.
+    MultipathMismatch,
+
+    /// invalid key in descriptor - {0}
+    #[from]
+    Key(XpubParseError),
+
+    /// invalid taproot script tree - {0}
+    #[from]
+    TapTree(InvalidTree),
+
+    /// invalid miniscript - {0}
+    #[from]
+    Miniscript(MiniscriptError),
+
+    /// invalid multisig descriptor - {0}
+    #[from]
+    Multisig(MultisigError),
+
+    /// invalid address in descriptor - {0}
+    #[from]
+    Address(AddressParseError),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum DescrAddressError {
+    /// extended keys of the descriptor belong to a different network than the requested
+    /// address.
+    NetworkMismatch,
+
+    #[display(inner)]
+    #[from]
+    Address(AddressError),
+}
+
+/// Extracts arguments of a descriptor script expression `name(args)`. Returns `None` if the
+/// expression has a different name or is not properly closed.
+pub(crate) fn script_args<'s>(s: &'s str, name: &str) -> Option<&'s str> {
+    s.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+/// Splits descriptor script arguments by commas, ignoring the commas which are nested inside
+/// brackets of a sub-expression, key origin or a script tree.
+pub(crate) fn split_args(s: &str) -> Result<Vec<&str>, DescrParseError> {
+    let mut args = vec![];
+    let mut depth = 0usize;
+    let mut start = 0usize;
+    for (pos, c) in s.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?
+            }
+            ',' if depth == 0 => {
+                args.push(&s[start..pos]);
+                start = pos + 1;
+            }
+            _ => {}
         }
     }
+    if depth != 0 {
+        return Err(DescrParseError::InvalidSyntax(s.to_owned()));
+    }
+    args.push(&s[start..]);
+    Ok(args)
+}
+
+/// Maximum witness size of an ECDSA signature with its sighash type byte.
+pub(crate) const MAX_ECDSA_SIG_SIZE: usize = 1 + 73;
+/// Maximum witness size of a BIP-340 signature with its sighash type byte.
+pub(crate) const MAX_BIP340_SIG_SIZE: usize = 1 + 65;
+
+/// Weight of the input signature script and witness, where the witness consists of the
+/// stack with the size `stack` (including length prefixes of its elements) followed by the
+/// `script` element.
+pub(crate) fn satisfaction_weight(
+    sig_script: usize,
+    stack: usize,
+    script: Option<usize>,
+) -> WeightUnits {
+    let script = script.map(|len| VarInt::with(len).len() + len).unwrap_or_default();
+    WeightUnits::no_discount(VarInt::with(sig_script).len() + sig_script)
+        + WeightUnits::witness_discount(1 + stack + script)
 }
 
 pub trait Descriptor<K = XpubDerivable, V = ()>: DeriveScripts {
     fn class(&self) -> SpkClass;
 
+    /// Maximum weight of the signature script and witness of an input spending the
+    /// descriptor output, used to estimate transaction fees before signing.
+    ///
+    /// The weight doesn't include the previous outpoint and the sequence number of the input
+    /// (40 bytes, taking 160 weight units).
+    fn max_satisfaction_weight(&self) -> WeightUnits;
+
+    /// Maximum weight of an input spending the descriptor output, including the previous
+    /// outpoint and the sequence number (see [`Descriptor::max_satisfaction_weight`]).
+    fn max_input_weight(&self) -> WeightUnits {
+        WeightUnits::no_discount(32 + 4 + 4) + self.max_satisfaction_weight()
+    }
+
     fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
     where K: 'a;
     fn vars<'a>(&'a self) -> impl Iterator<Item = &'a V>
@@ -66,6 +221,39 @@ pub trait Descriptor<K = XpubDerivable, V = ()>: DeriveScripts {
 
     fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin>;
     fn xonly_keyset(&self, terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation>;
+
+    /// Timelocks used by the descriptor spending conditions, which the spending transaction
+    /// must meet when the spending path containing them is used.
+    fn timelocks(&self) -> Vec<Timelock> { vec![] }
+
+    /// Detects whether the descriptor keys are testnet keys. Returns `None` if the descriptor
+    /// has no extended keys or mixes testnet and mainnet keys.
+    fn is_testnet(&self) -> Option<bool> {
+        let mut xpubs = self.xpubs().map(|spec| spec.xpub().is_testnet());
+        let testnet = xpubs.next()?;
+        xpubs.all(|other| other == testnet).then_some(testnet)
+    }
+
+    /// Derives address, checking that the descriptor keys belong to the address network.
+    /// Use [`DeriveScripts::derive_address`] to derive address for any network.
+    fn derive_network_address(
+        &self,
+        network: AddressNetwork,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> Result<Address, DescrAddressError> {
+        if self.is_testnet().is_some_and(|testnet| testnet != network.is_testnet()) {
+            return Err(DescrAddressError::NetworkMismatch);
+        }
+        Ok(self.derive_address(network, keychain, index)?)
+    }
+
+    /// Constructs Bitcoin Core `importdescriptors` request importing `0..=range_end` addresses
+    /// of each of the descriptor keychains (see [`CoreImport::with`]).
+    fn to_core_descriptors(&self, timestamp: ImportTimestamp, range_end: u32) -> CoreImport
+    where Self: Display {
+        CoreImport::with(&self.to_string(), self.default_keychain(), timestamp, range_end)
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
@@ -76,8 +264,10 @@ pub trait Descriptor<K = XpubDerivable, V = ()>: DeriveScripts {
         crate = "serde_crate",
         rename_all = "camelCase",
         bound(
-            serialize = "S::Compr: serde::Serialize, S::XOnly: serde::Serialize",
-            deserialize = "S::Compr: serde::Deserialize<'de>, S::XOnly: serde::Deserialize<'de>"
+            serialize = "S::Legacy: serde::Serialize, S::Compr: serde::Serialize, S::XOnly: \
+                         serde::Serialize",
+            deserialize = "S::Legacy: serde::Deserialize<'de>, S::Compr: serde::Deserialize<'de>, \
+                           S::XOnly: serde::Deserialize<'de>"
         )
     )
 )]
@@ -86,7 +276,7 @@ pub enum StdDescr<S: DeriveSet = XpubDerivable> {
     /*
     #[from]
     Bare(Bare<S::Legacy>),
-
+     */
     #[from]
     Pkh(Pkh<S::Legacy>),
 
@@ -96,22 +286,29 @@ pub enum StdDescr<S: DeriveSet = XpubDerivable> {
     #[from]
     ShSortedMulti(ShSortedMulti<S::Legacy>),
 
+    /*
     #[from]
     ShTlMulti(ShTlMulti<S::Legacy>),
 
     #[from]
     ShTemplate(ShTemplate<S::Legacy>),
      */
+    #[from]
+    ShWpkh(ShWpkh<S::Compr>),
+
     #[from]
     Wpkh(Wpkh<S::Compr>),
 
-    /*
+    #[from]
+    Wsh(Wsh<S::Compr>),
+
     #[from]
     WshMulti(WshMulti<S::Compr>),
 
     #[from]
     WshSortedMulti(WshSortedMulti<S::Compr>),
 
+    /*
     #[from]
     WshTlMulti(WshTlMulti<S::Compr>),
 
@@ -120,18 +317,24 @@ pub enum StdDescr<S: DeriveSet = XpubDerivable> {
      */
     #[from]
     TrKey(TrKey<S::XOnly>),
-    /*
+
     #[from]
-    TrMusig(TrMusig<S::XOnly>),
+    TrScript(TrScript<S::XOnly>),
 
     #[from]
-    TrMulti(TrMulti<S::XOnly>),
+    TrMusig(TrMusig<S::Compr>),
 
     #[from]
-    TrTlMulti(TrTlMulti<S::XOnly>),
+    Raw(Raw),
+
+    #[from]
+    Addr(Addr),
+    /*
+    #[from]
+    TrMulti(TrMulti<S::XOnly>),
 
     #[from]
-    TrTree(TrTree<S::XOnly>),
+    TrTlMulti(TrTlMulti<S::XOnly>),
 
     // This should go into LNP:
     Bolt(Bolt<S::Compr>)
@@ -157,15 +360,37 @@ pub enum StdDescr<S: DeriveSet = XpubDerivable> {
 impl<S: DeriveSet> Derive<DerivedScript> for StdDescr<S> {
     fn default_keychain(&self) -> Keychain {
         match self {
+            StdDescr::Pkh(d) => d.default_keychain(),
+            StdDescr::ShMulti(d) => d.default_keychain(),
+            StdDescr::ShSortedMulti(d) => d.default_keychain(),
+            StdDescr::ShWpkh(d) => d.default_keychain(),
             StdDescr::Wpkh(d) => d.default_keychain(),
+            StdDescr::Wsh(d) => d.default_keychain(),
+            StdDescr::WshMulti(d) => d.default_keychain(),
+            StdDescr::WshSortedMulti(d) => d.default_keychain(),
             StdDescr::TrKey(d) => d.default_keychain(),
+            StdDescr::TrScript(d) => d.default_keychain(),
+            StdDescr::TrMusig(d) => d.default_keychain(),
+            StdDescr::Raw(d) => d.default_keychain(),
+            StdDescr::Addr(d) => d.default_keychain(),
         }
     }
 
     fn keychains(&self) -> BTreeSet<Keychain> {
         match self {
+            StdDescr::Pkh(d) => d.keychains(),
+            StdDescr::ShMulti(d) => d.keychains(),
+            StdDescr::ShSortedMulti(d) => d.keychains(),
+            StdDescr::ShWpkh(d) => d.keychains(),
             StdDescr::Wpkh(d) => d.keychains(),
+            StdDescr::Wsh(d) => d.keychains(),
+            StdDescr::WshMulti(d) => d.keychains(),
+            StdDescr::WshSortedMulti(d) => d.keychains(),
             StdDescr::TrKey(d) => d.keychains(),
+            StdDescr::TrScript(d) => d.keychains(),
+            StdDescr::TrMusig(d) => d.keychains(),
+            StdDescr::Raw(d) => d.keychains(),
+            StdDescr::Addr(d) => d.keychains(),
         }
     }
 
@@ -175,27 +400,154 @@ impl<S: DeriveSet> Derive<DerivedScript> for StdDescr<S> {
         index: impl Into<NormalIndex>,
     ) -> DerivedScript {
         match self {
+            StdDescr::Pkh(d) => d.derive(keychain, index),
+            StdDescr::ShMulti(d) => d.derive(keychain, index),
+            StdDescr::ShSortedMulti(d) => d.derive(keychain, index),
+            StdDescr::ShWpkh(d) => d.derive(keychain, index),
             StdDescr::Wpkh(d) => d.derive(keychain, index),
+            StdDescr::Wsh(d) => d.derive(keychain, index),
+            StdDescr::WshMulti(d) => d.derive(keychain, index),
+            StdDescr::WshSortedMulti(d) => d.derive(keychain, index),
             StdDescr::TrKey(d) => d.derive(keychain, index),
+            StdDescr::TrScript(d) => d.derive(keychain, index),
+            StdDescr::TrMusig(d) => d.derive(keychain, index),
+            StdDescr::Raw(d) => d.derive(keychain, index),
+            StdDescr::Addr(d) => d.derive(keychain, index),
         }
     }
 }
 
-impl<K: DeriveSet<Compr = K, XOnly = K> + DeriveCompr + DeriveXOnly> Descriptor<K> for StdDescr<K>
+impl<S: DeriveSet> Display for StdDescr<S>
+where
+    S::Legacy: Display,
+    S::Compr: Display,
+    S::XOnly: Display,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let descr = match (self, f.alternate()) {
+            (StdDescr::Pkh(d), false) => d.to_string(),
+            (StdDescr::Pkh(d), true) => format!("{d:#}"),
+            (StdDescr::ShMulti(d), false) => d.to_string(),
+            (StdDescr::ShMulti(d), true) => format!("{d:#}"),
+            (StdDescr::ShSortedMulti(d), false) => d.to_string(),
+            (StdDescr::ShSortedMulti(d), true) => format!("{d:#}"),
+            (StdDescr::ShWpkh(d), false) => d.to_string(),
+            (StdDescr::ShWpkh(d), true) => format!("{d:#}"),
+            (StdDescr::Wpkh(d), false) => d.to_string(),
+            (StdDescr::Wpkh(d), true) => format!("{d:#}"),
+            (StdDescr::Wsh(d), false) => d.to_string(),
+            (StdDescr::Wsh(d), true) => format!("{d:#}"),
+            (StdDescr::WshMulti(d), false) => d.to_string(),
+            (StdDescr::WshMulti(d), true) => format!("{d:#}"),
+            (StdDescr::WshSortedMulti(d), false) => d.to_string(),
+            (StdDescr::WshSortedMulti(d), true) => format!("{d:#}"),
+            (StdDescr::TrKey(d), false) => d.to_string(),
+            (StdDescr::TrKey(d), true) => format!("{d:#}"),
+            (StdDescr::TrScript(d), false) => d.to_string(),
+            (StdDescr::TrScript(d), true) => format!("{d:#}"),
+            (StdDescr::TrMusig(d), false) => d.to_string(),
+            (StdDescr::TrMusig(d), true) => format!("{d:#}"),
+            (StdDescr::Raw(d), _) => d.to_string(),
+            (StdDescr::Addr(d), _) => d.to_string(),
+        };
+        f.write_str(&descr)?;
+        if let Some(checksum) = descriptor_checksum(&descr) {
+            write!(f, "#{checksum}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: DeriveSet> FromStr for StdDescr<S>
+where
+    S::Legacy: FromStr,
+    S::Compr: FromStr,
+    S::XOnly: FromStr,
+    DescrParseError: From<<S::Legacy as FromStr>::Err>
+        + From<<S::Compr as FromStr>::Err>
+        + From<<S::XOnly as FromStr>::Err>,
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = check_checksum(s.trim())?;
+        let (name, _) =
+            s.split_once('(').ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        Ok(match name {
+            "pkh" => Pkh::from_str(s)?.into(),
+            "sh" if s.starts_with("sh(sortedmulti(") => ShSortedMulti::from_str(s)?.into(),
+            "sh" if s.starts_with("sh(multi(") => ShMulti::from_str(s)?.into(),
+            "sh" => ShWpkh::from_str(s)?.into(),
+            "wpkh" => Wpkh::from_str(s)?.into(),
+            "wsh" if s.starts_with("wsh(sortedmulti(") => WshSortedMulti::from_str(s)?.into(),
+            "wsh" if s.starts_with("wsh(multi(") => WshMulti::from_str(s)?.into(),
+            "wsh" => Wsh::from_str(s)?.into(),
+            "tr" if s.starts_with("tr(musig(") => TrMusig::from_str(s)?.into(),
+            "tr" if s.contains(',') => TrScript::from_str(s)?.into(),
+            "tr" => TrKey::from_str(s)?.into(),
+            "raw" => Raw::from_str(s)?.into(),
+            "addr" => Addr::from_str(s)?.into(),
+            _ => return Err(DescrParseError::UnknownScript(s.to_owned())),
+        })
+    }
+}
+
+impl<K: DeriveSet<Legacy = K, Compr = K, XOnly = K> + DeriveLegacy + DeriveCompr + DeriveXOnly>
+    Descriptor<K> for StdDescr<K>
 where Self: Derive<DerivedScript>
 {
     fn class(&self) -> SpkClass {
         match self {
+            StdDescr::Pkh(d) => d.class(),
+            StdDescr::ShMulti(d) => d.class(),
+            StdDescr::ShSortedMulti(d) => d.class(),
+            StdDescr::ShWpkh(d) => d.class(),
             StdDescr::Wpkh(d) => d.class(),
+            StdDescr::Wsh(d) => d.class(),
+            StdDescr::WshMulti(d) => d.class(),
+            StdDescr::WshSortedMulti(d) => d.class(),
             StdDescr::TrKey(d) => d.class(),
+            StdDescr::TrScript(d) => d.class(),
+            StdDescr::TrMusig(d) => d.class(),
+            StdDescr::Raw(d) => Descriptor::<K>::class(d),
+            StdDescr::Addr(d) => Descriptor::<K>::class(d),
+        }
+    }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        match self {
+            StdDescr::Pkh(d) => d.max_satisfaction_weight(),
+            StdDescr::ShMulti(d) => d.max_satisfaction_weight(),
+            StdDescr::ShSortedMulti(d) => d.max_satisfaction_weight(),
+            StdDescr::ShWpkh(d) => d.max_satisfaction_weight(),
+            StdDescr::Wpkh(d) => d.max_satisfaction_weight(),
+            StdDescr::Wsh(d) => d.max_satisfaction_weight(),
+            StdDescr::WshMulti(d) => d.max_satisfaction_weight(),
+            StdDescr::WshSortedMulti(d) => d.max_satisfaction_weight(),
+            StdDescr::TrKey(d) => d.max_satisfaction_weight(),
+            StdDescr::TrScript(d) => d.max_satisfaction_weight(),
+            StdDescr::TrMusig(d) => d.max_satisfaction_weight(),
+            StdDescr::Raw(d) => Descriptor::<K>::max_satisfaction_weight(d),
+            StdDescr::Addr(d) => Descriptor::<K>::max_satisfaction_weight(d),
         }
     }
 
     fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
     where K: 'a {
         match self {
+            StdDescr::Pkh(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::ShMulti(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::ShSortedMulti(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::ShWpkh(d) => d.keys().collect::<Vec<_>>(),
             StdDescr::Wpkh(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::Wsh(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::WshMulti(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::WshSortedMulti(d) => d.keys().collect::<Vec<_>>(),
             StdDescr::TrKey(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::TrScript(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::TrMusig(d) => d.keys().collect::<Vec<_>>(),
+            StdDescr::Raw(d) => Descriptor::<K>::keys(d).collect::<Vec<_>>(),
+            StdDescr::Addr(d) => Descriptor::<K>::keys(d).collect::<Vec<_>>(),
         }
         .into_iter()
     }
@@ -207,23 +559,319 @@ where Self: Derive<DerivedScript>
 
     fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> {
         match self {
+            StdDescr::Pkh(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::ShMulti(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::ShSortedMulti(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::ShWpkh(d) => d.xpubs().collect::<Vec<_>>(),
             StdDescr::Wpkh(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::Wsh(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::WshMulti(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::WshSortedMulti(d) => d.xpubs().collect::<Vec<_>>(),
             StdDescr::TrKey(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::TrScript(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::TrMusig(d) => d.xpubs().collect::<Vec<_>>(),
+            StdDescr::Raw(d) => Descriptor::<K>::xpubs(d).collect::<Vec<_>>(),
+            StdDescr::Addr(d) => Descriptor::<K>::xpubs(d).collect::<Vec<_>>(),
         }
         .into_iter()
     }
 
     fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
         match self {
+            StdDescr::Pkh(d) => d.compr_keyset(terminal),
+            StdDescr::ShMulti(d) => d.compr_keyset(terminal),
+            StdDescr::ShSortedMulti(d) => d.compr_keyset(terminal),
+            StdDescr::ShWpkh(d) => d.compr_keyset(terminal),
             StdDescr::Wpkh(d) => d.compr_keyset(terminal),
+            StdDescr::Wsh(d) => d.compr_keyset(terminal),
+            StdDescr::WshMulti(d) => d.compr_keyset(terminal),
+            StdDescr::WshSortedMulti(d) => d.compr_keyset(terminal),
             StdDescr::TrKey(d) => d.compr_keyset(terminal),
+            StdDescr::TrScript(d) => d.compr_keyset(terminal),
+            StdDescr::TrMusig(d) => d.compr_keyset(terminal),
+            StdDescr::Raw(d) => Descriptor::<K>::compr_keyset(d, terminal),
+            StdDescr::Addr(d) => Descriptor::<K>::compr_keyset(d, terminal),
         }
     }
 
     fn xonly_keyset(&self, terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
         match self {
+            StdDescr::Pkh(d) => d.xonly_keyset(terminal),
+            StdDescr::ShMulti(d) => d.xonly_keyset(terminal),
+            StdDescr::ShSortedMulti(d) => d.xonly_keyset(terminal),
+            StdDescr::ShWpkh(d) => d.xonly_keyset(terminal),
             StdDescr::Wpkh(d) => d.xonly_keyset(terminal),
+            StdDescr::Wsh(d) => d.xonly_keyset(terminal),
+            StdDescr::WshMulti(d) => d.xonly_keyset(terminal),
+            StdDescr::WshSortedMulti(d) => d.xonly_keyset(terminal),
             StdDescr::TrKey(d) => d.xonly_keyset(terminal),
+            StdDescr::TrScript(d) => d.xonly_keyset(terminal),
+            StdDescr::TrMusig(d) => d.xonly_keyset(terminal),
+            StdDescr::Raw(d) => Descriptor::<K>::xonly_keyset(d, terminal),
+            StdDescr::Addr(d) => Descriptor::<K>::xonly_keyset(d, terminal),
         }
     }
+
+    fn timelocks(&self) -> Vec<Timelock> {
+        match self {
+            StdDescr::Pkh(d) => d.timelocks(),
+            StdDescr::ShMulti(d) => d.timelocks(),
+            StdDescr::ShSortedMulti(d) => d.timelocks(),
+            StdDescr::ShWpkh(d) => d.timelocks(),
+            StdDescr::Wpkh(d) => d.timelocks(),
+            StdDescr::Wsh(d) => d.timelocks(),
+            StdDescr::WshMulti(d) => d.timelocks(),
+            StdDescr::WshSortedMulti(d) => d.timelocks(),
+            StdDescr::TrKey(d) => d.timelocks(),
+            StdDescr::TrScript(d) => d.timelocks(),
+            StdDescr::TrMusig(d) => d.timelocks(),
+            StdDescr::Raw(d) => Descriptor::<K>::timelocks(d),
+            StdDescr::Addr(d) => Descriptor::<K>::timelocks(d),
+        }
+    }
+
+    fn is_testnet(&self) -> Option<bool> {
+        if let StdDescr::Addr(d) = self {
+            return Descriptor::<K>::is_testnet(d);
+        }
+        let mut xpubs = self.xpubs().map(|spec| spec.xpub().is_testnet());
+        let testnet = xpubs.next()?;
+        xpubs.all(|other| other == testnet).then_some(testnet)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::{AddressNetwork, DeriveScripts, Idx};
+
+    use super::*;
+
+    const XPUB: &str = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+
+    #[test]
+    fn dust_limits() {
+        for (class, limit) in [
+            (SpkClass::Bare, 0u64),
+            (SpkClass::P2pkh, 546),
+            (SpkClass::P2sh, 540),
+            (SpkClass::P2wpkh, 294),
+            (SpkClass::P2wsh, 330),
+            (SpkClass::P2tr, 330),
+        ] {
+            assert_eq!(class.dust_limit(), Sats(limit));
+        }
+    }
+
+    #[test]
+    fn std_descr_from_str() {
+        let s = format!("wpkh({XPUB})");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::Wpkh(_)));
+        assert_eq!(descr.to_string(), format!("{s}#{}", descriptor_checksum(&s).unwrap()));
+
+        let s = format!("pkh({XPUB})");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::Pkh(_)));
+
+        let s = format!("sh(wpkh({XPUB}))");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::ShWpkh(_)));
+
+        let s = format!("sh(multi(1,{XPUB},{XPUB}))");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::ShMulti(_)));
+        assert_eq!(descr.class(), SpkClass::P2sh);
+
+        let s = format!("sh(sortedmulti(1,{XPUB},{XPUB}))");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::ShSortedMulti(_)));
+        assert_eq!(StdDescr::<XpubDerivable>::from_str(&descr.to_string()).unwrap(), descr);
+
+        let s = "raw(6a0568656c6c6f)";
+        let descr = StdDescr::<XpubDerivable>::from_str(s).unwrap();
+        assert!(matches!(descr, StdDescr::Raw(_)));
+        assert_eq!(StdDescr::<XpubDerivable>::from_str(&descr.to_string()).unwrap(), descr);
+
+        let s = "addr(bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq)";
+        let descr = StdDescr::<XpubDerivable>::from_str(s).unwrap();
+        assert!(matches!(descr, StdDescr::Addr(_)));
+        assert_eq!(descr.class(), SpkClass::P2wpkh);
+        assert_eq!(descr.is_testnet(), Some(false));
+        assert_eq!(StdDescr::<XpubDerivable>::from_str(&descr.to_string()).unwrap(), descr);
+
+        let s = format!("tr({XPUB})");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::TrKey(_)));
+        assert_eq!(StdDescr::<XpubDerivable>::from_str(&descr.to_string()).unwrap(), descr);
+
+        let s = format!("tr({XPUB},pk({XPUB}))");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::TrScript(_)));
+
+        let s = format!("tr(musig({XPUB},{XPUB}))");
+        let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(descr, StdDescr::TrMusig(_)));
+        assert_eq!(StdDescr::<XpubDerivable>::from_str(&descr.to_string()).unwrap(), descr);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn std_descr_serde() {
+        for s in [
+            format!("wpkh({XPUB})"),
+            format!("wsh(sortedmulti(1,{XPUB},{XPUB}))"),
+            format!("tr({XPUB},pk({XPUB}))"),
+        ] {
+            let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+            let json = serde_json::to_string(&descr).unwrap();
+            assert_eq!(serde_json::from_str::<StdDescr>(&json).unwrap(), descr);
+        }
+        let json = serde_json::to_string(&SpkClass::P2tr).unwrap();
+        assert_eq!(serde_json::from_str::<SpkClass>(&json).unwrap(), SpkClass::P2tr);
+    }
+
+    #[test]
+    fn std_descr_network() {
+        let descr = StdDescr::<XpubDerivable>::from_str(&format!("tr({XPUB})")).unwrap();
+        assert_eq!(descr.is_testnet(), Some(true));
+        assert!(descr.derive_network_address(AddressNetwork::Testnet, 0, 0u8).is_ok());
+        assert!(descr.derive_network_address(AddressNetwork::Regtest, 0, 0u8).is_ok());
+        assert_eq!(
+            descr.derive_network_address(AddressNetwork::Mainnet, 0, 0u8),
+            Err(DescrAddressError::NetworkMismatch)
+        );
+        assert!(descr.derive_address(AddressNetwork::Mainnet, 0, 0u8).is_ok());
+    }
+
+    #[test]
+    fn std_descr_satisfaction_weight() {
+        const XPUB2: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+        for (s, weight) in [
+            (format!("pkh({XPUB})"), 436),
+            (format!("wpkh({XPUB})"), 113),
+            (format!("sh(wpkh({XPUB}))"), 205),
+            (format!("wsh(multi(2,{XPUB},{XPUB2}))"), 226),
+            (format!("wsh(sortedmulti(1,{XPUB},{XPUB2}))"), 152),
+            (format!("wsh(and_v(v:pk({XPUB}),older(144)))"), 119),
+            (format!("tr({XPUB})"), 71),
+            (format!("tr({XPUB},pk({XPUB2}))"), 140),
+            (format!("tr(musig({XPUB},{XPUB2}))"), 71),
+        ] {
+            let descr = StdDescr::<XpubDerivable>::from_str(&s).unwrap();
+            assert_eq!(descr.max_satisfaction_weight().to_u32(), weight, "{s}");
+            assert_eq!(descr.max_input_weight().to_u32(), weight + 160, "{s}");
+        }
+    }
+
+    #[test]
+    fn std_descr_core_keys() {
+        let s = "wpkh([643a7adc/86'/1'/0']tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/0/*)";
+        let descr = StdDescr::<XpubDerivable>::from_str(s).unwrap();
+        assert_eq!(format!("{descr:#}"), format!("{s}#{}", descriptor_checksum(s).unwrap()));
+    }
+
+    #[test]
+    fn std_descr_multipath() {
+        let descr = StdDescr::<XpubDerivable>::from_str(&format!("wpkh({XPUB})")).unwrap();
+        assert_eq!(descr.keychains(), bset![Keychain::OUTER, Keychain::INNER]);
+        for keychain in [0u8, 1] {
+            let single = XPUB.replace("<0;1>", &keychain.to_string());
+            let single = StdDescr::<XpubDerivable>::from_str(&format!("wpkh({single})")).unwrap();
+            assert_eq!(single.keychains(), bset![Keychain::with(keychain)]);
+            for index in 0u8..3 {
+                assert_eq!(descr.derive(keychain, index), single.derive(keychain, index));
+            }
+        }
+
+        let single = XPUB.replace("<0;1>", "0");
+        assert!(matches!(
+            StdDescr::<XpubDerivable>::from_str(&format!("wsh(multi(1,{XPUB},{single}))")),
+            Err(DescrParseError::MultipathMismatch)
+        ));
+        assert!(matches!(
+            StdDescr::<XpubDerivable>::from_str(&format!("tr({XPUB},pk({single}))")),
+            Err(DescrParseError::MultipathMismatch)
+        ));
+    }
+
+    #[test]
+    fn std_descr_addresses() {
+        let descr = StdDescr::<XpubDerivable>::from_str(&format!("tr({XPUB})")).unwrap();
+        let mut iter = descr.addresses(AddressNetwork::Testnet, Keychain::INNER);
+        for index in 0u8..3 {
+            let derived = iter.next().unwrap();
+            assert_eq!(derived.terminal, Terminal::change(NormalIndex::from(index)));
+            assert_eq!(
+                derived.addr,
+                descr.derive_address(AddressNetwork::Testnet, 1, index).unwrap()
+            );
+        }
+        let derived = iter.nth(996).unwrap();
+        assert_eq!(derived.terminal.index, NormalIndex::from(999u16));
+        assert_eq!(derived.addr, descr.derive_address(AddressNetwork::Testnet, 1, 999u16).unwrap());
+        assert_eq!(iter.next().unwrap().terminal.index, NormalIndex::from(1000u16));
+    }
+
+    #[test]
+    fn std_descr_terminal_of() {
+        let descr = StdDescr::<XpubDerivable>::from_str(&format!("wpkh({XPUB})")).unwrap();
+        let horizon = NormalIndex::from(20u8);
+        let spk = descr.derive(1, 7u8).to_script_pubkey();
+        assert_eq!(
+            descr.terminal_of(&spk, horizon),
+            Some(Terminal::change(NormalIndex::from(7u8)))
+        );
+        assert_eq!(descr.terminal_of(&spk, NormalIndex::from(7u8)), None);
+
+        let addr = descr.derive_address(AddressNetwork::Testnet, 0, 19u8).unwrap();
+        assert!(descr.is_mine(&addr, horizon));
+        let addr = descr.derive_address(AddressNetwork::Testnet, 0, 20u8).unwrap();
+        assert!(!descr.is_mine(&addr, horizon));
+    }
+
+    #[test]
+    fn std_descr_batch() {
+        let descr = StdDescr::<XpubDerivable>::from_str(&format!("wpkh({XPUB})")).unwrap();
+        let index = |i: u16| NormalIndex::from(i);
+        let batch = descr.derive_batch(0, index(5)..=index(7)).collect::<Vec<_>>();
+        assert_eq!(batch, (5u8..=7).map(|i| descr.derive(0, i)).collect::<Vec<_>>());
+        assert_eq!(descr.derive_batch(0, index(5)..index(5)).count(), 0);
+        assert_eq!(descr.derive_batch(1, ..index(300)).count(), 300);
+
+        let mut batch = descr.derive_batch(1, index(1000)..).take(500);
+        assert_eq!(batch.next(), Some(descr.derive(1, 1000u16)));
+        assert_eq!(batch.nth(498), Some(descr.derive(1, 1499u16)));
+        assert_eq!(batch.next(), None);
+
+        assert_eq!(descr.derive_batch(0, NormalIndex::MAX..).count(), 1);
+
+        let addrs = descr.derive_address_batch(AddressNetwork::Testnet, 0, ..index(3)).unwrap();
+        assert_eq!(
+            addrs,
+            descr.addresses(AddressNetwork::Testnet, 0).take(3).map(|d| d.addr).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn std_descr_invalid() {
+        assert!(matches!(
+            StdDescr::<XpubDerivable>::from_str(&format!("wpkh({XPUB}")),
+            Err(DescrParseError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            StdDescr::<XpubDerivable>::from_str("wpkh(tpub)"),
+            Err(DescrParseError::Key(_))
+        ));
+        assert!(matches!(
+            StdDescr::<XpubDerivable>::from_str(&format!("wpkh({XPUB})#00000000")),
+            Err(DescrParseError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn split_nested_args() {
+        assert_eq!(split_args("2,[fp/1h]a/*,b").unwrap(), vec!["2", "[fp/1h]a/*", "b"]);
+        assert_eq!(split_args("a,{pk(b),pk(c)}").unwrap(), vec!["a", "{pk(b),pk(c)}"]);
+        assert!(split_args("a,pk(b").is_err());
+    }
 }
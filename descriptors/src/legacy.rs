@@ -0,0 +1,414 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::iter;
+use std::str::FromStr;
+
+use derive::{
+    CompressedPk, Derive, DeriveLegacy, DerivedScript, KeyOrigin, Keychain, LegacyPk, NormalIndex,
+    PubkeyHash, RedeemScript, ScriptPubkey, TapDerivation, Terminal, VarInt, WeightUnits, XOnlyPk,
+    XpubDerivable, XpubSpec,
+};
+use indexmap::IndexMap;
+
+use crate::descriptor::{script_args, MAX_ECDSA_SIG_SIZE};
+use crate::multisig::{
+    check_keychains, check_multisig, common_keychains, fmt_multi, legacy_multisig_script,
+    parse_multi,
+};
+use crate::{DescrParseError, Descriptor, MultisigError, SpkClass};
+
+/// Maximal number of keys which can be used in a `multi` and `sortedmulti` descriptors inside
+/// P2SH.
+pub const MAX_SH_MULTI_KEYS: usize = 15;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate",))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
+pub struct Pkh<K: DeriveLegacy = XpubDerivable>(K);
+
+impl<K: DeriveLegacy> Pkh<K> {
+    pub fn as_key(&self) -> &K { &self.0 }
+    pub fn into_key(self) -> K { self.0 }
+}
+
+impl<K: DeriveLegacy> Derive<DerivedScript> for Pkh<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.0.default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { self.0.keychains() }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let key = self.0.derive(keychain, index);
+        DerivedScript::Bare(ScriptPubkey::p2pkh(PubkeyHash::from(key)))
+    }
+}
+
+impl<K: DeriveLegacy + Display> Display for Pkh<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("pkh(")?;
+        Display::fmt(&self.0, f)?;
+        f.write_str(")")
+    }
+}
+
+impl<K: DeriveLegacy + FromStr> FromStr for Pkh<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = script_args(s.trim(), "pkh")
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        Ok(Self(K::from_str(key)?))
+    }
+}
+
+impl<K: DeriveLegacy> Descriptor<K> for Pkh<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2pkh }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        let key = self.0.derive(self.0.default_keychain(), 0u8);
+        let key_size = if key.compressed { 1 + 33 } else { 1 + 65 };
+        WeightUnits::no_discount(1 + MAX_ECDSA_SIG_SIZE + key_size)
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        iter::once(&self.0)
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { iter::once(self.0.xpub_spec()) }
+
+    fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        let mut map = IndexMap::with_capacity(1);
+        let key = self.0.derive(terminal.keychain, terminal.index);
+        // PSBT key derivation information can be provided only for compressed keys
+        if key.compressed {
+            let origin = KeyOrigin::with(self.0.xpub_spec().origin().clone(), terminal);
+            map.insert(CompressedPk::from(key.pubkey), origin);
+        }
+        map
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+/// Derives legacy keys for a terminal, sorting them lexicographically according to BIP-67 if
+/// `sorted` flag is set.
+fn derive_legacy_keys<K: DeriveLegacy>(
+    keys: &[K],
+    terminal: Terminal,
+    sorted: bool,
+) -> Vec<LegacyPk> {
+    let mut derived = keys
+        .iter()
+        .map(|key| key.derive(terminal.keychain, terminal.index))
+        .collect::<Vec<LegacyPk>>();
+    if sorted {
+        derived.sort_by_key(LegacyPk::to_vec);
+    }
+    derived
+}
+
+fn legacy_multisig_redeem_script<K: DeriveLegacy>(
+    threshold: u16,
+    keys: &[K],
+    terminal: Terminal,
+    sorted: bool,
+) -> RedeemScript {
+    let keys = derive_legacy_keys(keys, terminal, sorted);
+    RedeemScript::from_unsafe(legacy_multisig_script(threshold, keys.into_iter()))
+}
+
+/// Maximum weight of the signature script satisfying P2SH `multi` script.
+fn legacy_multisig_satisfaction_weight<K: DeriveLegacy>(threshold: u16, keys: &[K]) -> WeightUnits {
+    let terminal = Terminal::new(keys[0].default_keychain(), NormalIndex::normal(0));
+    let script = legacy_multisig_redeem_script(threshold, keys, terminal, false).len();
+    let script_push = match script {
+        0..=75 => 1,
+        76..=255 => 2,
+        _ => 3,
+    };
+    // an extra empty element is consumed by OP_CHECKMULTISIG due to the off-by-one bug
+    let sig_script = 1 + threshold as usize * MAX_ECDSA_SIG_SIZE + script_push + script;
+    WeightUnits::no_discount(VarInt::with(sig_script).len() + sig_script)
+}
+
+fn legacy_keyset<K: DeriveLegacy>(
+    keys: &[K],
+    terminal: Terminal,
+) -> IndexMap<CompressedPk, KeyOrigin> {
+    keys.iter()
+        .map(|key| (key, key.derive(terminal.keychain, terminal.index)))
+        // PSBT key derivation information can be provided only for compressed keys
+        .filter(|(_, pk)| pk.compressed)
+        .map(|(key, pk)| {
+            let origin = KeyOrigin::with(key.xpub_spec().origin().clone(), terminal);
+            (CompressedPk::from(pk.pubkey), origin)
+        })
+        .collect()
+}
+
+/// P2SH multisig descriptor `sh(multi(...))`, which puts keys into the redeem script in the
+/// order they are provided.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ShMulti<K: DeriveLegacy = XpubDerivable> {
+    threshold: u16,
+    keys: Vec<K>,
+}
+
+impl<K: DeriveLegacy> ShMulti<K> {
+    pub fn new(threshold: u16, keys: Vec<K>) -> Result<Self, MultisigError> {
+        check_multisig(threshold, keys.len(), MAX_SH_MULTI_KEYS)?;
+        Ok(Self { threshold, keys })
+    }
+
+    pub fn threshold(&self) -> u16 { self.threshold }
+
+    pub fn as_keys(&self) -> &[K] { &self.keys }
+}
+
+impl<K: DeriveLegacy> Derive<DerivedScript> for ShMulti<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.keys[0].default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { common_keychains::<_, LegacyPk>(&self.keys) }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let terminal = Terminal::new(keychain, index.into());
+        DerivedScript::Bip13(legacy_multisig_redeem_script(
+            self.threshold,
+            &self.keys,
+            terminal,
+            false,
+        ))
+    }
+}
+
+impl<K: DeriveLegacy + Display> Display for ShMulti<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt_multi(f, "sh", "multi", self.threshold, &self.keys)
+    }
+}
+
+impl<K: DeriveLegacy + FromStr> FromStr for ShMulti<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (threshold, keys) = parse_multi(s, "sh", "multi", MAX_SH_MULTI_KEYS)?;
+        check_keychains::<_, LegacyPk>(&keys)?;
+        Ok(Self { threshold, keys })
+    }
+}
+
+impl<K: DeriveLegacy> Descriptor<K> for ShMulti<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2sh }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        legacy_multisig_satisfaction_weight(self.threshold, &self.keys)
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        self.keys.iter()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { self.keys.iter().map(K::xpub_spec) }
+
+    fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        legacy_keyset(&self.keys, terminal)
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+/// P2SH multisig descriptor `sh(sortedmulti(...))`, which orders derived keys
+/// lexicographically (BIP-67) in the redeem script.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ShSortedMulti<K: DeriveLegacy = XpubDerivable> {
+    threshold: u16,
+    keys: Vec<K>,
+}
+
+impl<K: DeriveLegacy> ShSortedMulti<K> {
+    pub fn new(threshold: u16, keys: Vec<K>) -> Result<Self, MultisigError> {
+        check_multisig(threshold, keys.len(), MAX_SH_MULTI_KEYS)?;
+        Ok(Self { threshold, keys })
+    }
+
+    pub fn threshold(&self) -> u16 { self.threshold }
+
+    pub fn as_keys(&self) -> &[K] { &self.keys }
+}
+
+impl<K: DeriveLegacy> Derive<DerivedScript> for ShSortedMulti<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.keys[0].default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { common_keychains::<_, LegacyPk>(&self.keys) }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let terminal = Terminal::new(keychain, index.into());
+        DerivedScript::Bip13(legacy_multisig_redeem_script(
+            self.threshold,
+            &self.keys,
+            terminal,
+            true,
+        ))
+    }
+}
+
+impl<K: DeriveLegacy + Display> Display for ShSortedMulti<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt_multi(f, "sh", "sortedmulti", self.threshold, &self.keys)
+    }
+}
+
+impl<K: DeriveLegacy + FromStr> FromStr for ShSortedMulti<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (threshold, keys) = parse_multi(s, "sh", "sortedmulti", MAX_SH_MULTI_KEYS)?;
+        check_keychains::<_, LegacyPk>(&keys)?;
+        Ok(Self { threshold, keys })
+    }
+}
+
+impl<K: DeriveLegacy> Descriptor<K> for ShSortedMulti<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2sh }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        legacy_multisig_satisfaction_weight(self.threshold, &self.keys)
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        self.keys.iter()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { self.keys.iter().map(K::xpub_spec) }
+
+    fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        legacy_keyset(&self.keys, terminal)
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::LegacyPk;
+
+    use super::*;
+
+    #[test]
+    fn pkh_derive() {
+        let s = "pkh([643a7adc/44h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*)";
+        let pkh = Pkh::<XpubDerivable>::from_str(s).unwrap();
+        assert_eq!(pkh.to_string(), s);
+        let spk = pkh.derive(0, 7u8).to_script_pubkey();
+        assert!(spk.is_p2pkh());
+        let key: LegacyPk = pkh.as_key().derive(0, 7u8);
+        assert_eq!(spk, ScriptPubkey::p2pkh(PubkeyHash::from(key)));
+        assert_eq!(pkh.compr_keyset(Terminal::new(0, NormalIndex::from(7u8))).len(), 1);
+    }
+
+    const KEY1: &str = "[643a7adc/45h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+    const KEY2: &str = "[643a7adc/45h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+
+    #[test]
+    fn sh_multi() {
+        let s = format!("sh(multi(2,{KEY1},{KEY2}))");
+        let multi = ShMulti::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(multi.to_string(), s);
+        let script = multi.derive(0, 3u8);
+        let redeem_script = script.to_redeem_script().unwrap();
+        assert!(script.to_script_pubkey().is_p2sh());
+        assert_eq!(script.to_script_pubkey(), redeem_script.to_script_pubkey());
+        assert!(!script.is_segwit());
+        assert_eq!(redeem_script.len(), 3 + 2 * 34);
+        assert_eq!(multi.compr_keyset(Terminal::new(0, NormalIndex::from(3u8))).len(), 2);
+        // OP_0, 2 signatures and the redeem script push with length prefix
+        assert_eq!(multi.max_satisfaction_weight(), WeightUnits::no_discount(2 + 2 * 74 + 71 + 1));
+
+        let s = format!("sh(sortedmulti(1,{KEY2},{KEY1}))");
+        let sorted = ShSortedMulti::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(sorted.to_string(), s);
+        let reordered = ShSortedMulti::new(1, vec![
+            XpubDerivable::from_str(KEY1).unwrap(),
+            XpubDerivable::from_str(KEY2).unwrap(),
+        ])
+        .unwrap();
+        assert_eq!(sorted.derive(1, 5u8), reordered.derive(1, 5u8));
+
+        assert!(matches!(
+            ShMulti::<XpubDerivable>::from_str(&format!("sh(multi(3,{KEY1},{KEY2}))")),
+            Err(DescrParseError::Multisig(MultisigError::ThresholdExceedsKeys { .. }))
+        ));
+    }
+}
@@ -27,12 +27,37 @@ extern crate amplify;
 extern crate serde_crate as serde;
 
 mod factory;
+mod checksum;
+mod bitcoind;
+mod combo;
+mod coldcard;
 mod descriptor;
+mod legacy;
+mod lift;
+mod miniscript;
 mod multisig;
+mod raw;
 mod segwit;
 mod taproot;
 
-pub use descriptor::{Descriptor, SpkClass, StdDescr};
+pub use bitcoind::{CoreDescriptor, CoreImport, ImportTimestamp};
+pub use checksum::{descriptor_checksum, CHECKSUM_LEN};
+pub use coldcard::{MultisigSetup, MultisigSetupError};
+pub use combo::{Combo, WatchDescr};
+pub use descriptor::{
+    output_dust_limit, DescrAddressError, DescrParseError, Descriptor, SpkClass, StdDescr,
+    DUST_RELAY_FEE,
+};
 pub use factory::AddressFactory;
-pub use segwit::Wpkh;
-pub use taproot::TrKey;
+pub use legacy::{Pkh, ShMulti, ShSortedMulti, MAX_SH_MULTI_KEYS};
+pub use lift::SpkTemplate;
+pub use miniscript::{
+    BaseType, Dissat, Fragment, Miniscript, MiniscriptError, MsCtx, Policy, Timelock, Type,
+    MAX_WSH_SCRIPT_SIZE,
+};
+pub use multisig::{
+    bip67_sort, is_bip67_sorted, MultisigError, WshMulti, WshSortedMulti, MAX_WSH_MULTI_KEYS,
+};
+pub use raw::{Addr, Raw};
+pub use segwit::{ShWpkh, Wpkh, Wsh};
+pub use taproot::{TapLeafDescr, TrKey, TrMusig, TrScript, MAX_MULTI_A_KEYS};
@@ -0,0 +1,269 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lifting of arbitrary script pubkeys into templates of known output types, used for analysis
+//! of the outputs which don't belong to the wallet descriptors.
+
+use derive::{
+    AddressPayload, AddressType, FutureProgram, LegacyPk, OutputPk, PubkeyHash, ScriptHash,
+    ScriptPubkey, WPubkeyHash, WScriptHash, WitnessProgram, WitnessVer,
+};
+
+use crate::SpkClass;
+
+const OP_PUSHNUM_1: u8 = 0x51;
+const OP_PUSHNUM_16: u8 = 0x60;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// Template of a script pubkey, with the keys and hashes it commits to.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SpkTemplate {
+    /// Bare public key output: `<KEY> OP_CHECKSIG`.
+    Pk(LegacyPk),
+
+    /// Bare multisig output: `k <KEY_1> ... <KEY_n> n OP_CHECKMULTISIG`.
+    Multi { threshold: u8, keys: Vec<LegacyPk> },
+
+    /// P2PKH output.
+    Pkh(PubkeyHash),
+
+    /// P2SH output, which may also be a nested segwit output.
+    Sh(ScriptHash),
+
+    /// P2WPKH output.
+    Wpkh(WPubkeyHash),
+
+    /// P2WSH output.
+    Wsh(WScriptHash),
+
+    /// P2TR output.
+    Tr(OutputPk),
+
+    /// Witness program of a future segwit version (or of a non-standard length).
+    Witness(WitnessProgram),
+
+    /// Provably unspendable `OP_RETURN` output, with the bytes following the `OP_RETURN`.
+    NullData(Vec<u8>),
+
+    /// Script of any other (non-standard) form.
+    NonStandard(ScriptPubkey),
+}
+
+impl SpkTemplate {
+    /// Classifies the script pubkey, extracting the keys and hashes it contains.
+    pub fn lift(script_pubkey: &ScriptPubkey) -> Self {
+        if let Ok(payload) = AddressPayload::from_script(script_pubkey) {
+            return match payload {
+                AddressPayload::Pkh(hash) => SpkTemplate::Pkh(hash),
+                AddressPayload::Sh(hash) => SpkTemplate::Sh(hash),
+                AddressPayload::Wpkh(hash) => SpkTemplate::Wpkh(hash),
+                AddressPayload::Wsh(hash) => SpkTemplate::Wsh(hash),
+                AddressPayload::Tr(output_key) => SpkTemplate::Tr(output_key),
+                AddressPayload::Future(program) => SpkTemplate::Witness(
+                    WitnessProgram::new(program.version(), program.program().to_vec())
+                        .expect("future witness program is always valid"),
+                ),
+            };
+        }
+        if script_pubkey.is_op_return() {
+            return SpkTemplate::NullData(script_pubkey[1..].to_vec());
+        }
+        if let Some(program) = lift_witness(script_pubkey) {
+            return SpkTemplate::Witness(program);
+        }
+        lift_pk(script_pubkey)
+            .or_else(|| lift_multi(script_pubkey))
+            .unwrap_or_else(|| SpkTemplate::NonStandard(script_pubkey.clone()))
+    }
+
+    /// Class of the script pubkey. Bare, null data, future witness version and non-standard
+    /// scripts are all reported as [`SpkClass::Bare`].
+    pub fn class(&self) -> SpkClass {
+        match self {
+            SpkTemplate::Pkh(_) => SpkClass::P2pkh,
+            SpkTemplate::Sh(_) => SpkClass::P2sh,
+            SpkTemplate::Wpkh(_) => SpkClass::P2wpkh,
+            SpkTemplate::Wsh(_) => SpkClass::P2wsh,
+            SpkTemplate::Tr(_) => SpkClass::P2tr,
+            SpkTemplate::Pk(_)
+            | SpkTemplate::Multi { .. }
+            | SpkTemplate::Witness(_)
+            | SpkTemplate::NullData(_)
+            | SpkTemplate::NonStandard(_) => SpkClass::Bare,
+        }
+    }
+
+    /// Type of the address which can be used for the script pubkey, if any.
+    pub fn address_type(&self) -> Option<AddressType> {
+        Some(match self {
+            SpkTemplate::Pkh(_) => AddressType::P2pkh,
+            SpkTemplate::Sh(_) => AddressType::P2sh,
+            SpkTemplate::Wpkh(_) => AddressType::P2wpkh,
+            SpkTemplate::Wsh(_) => AddressType::P2wsh,
+            SpkTemplate::Tr(_) => AddressType::P2tr,
+            SpkTemplate::Witness(program) => {
+                FutureProgram::new(program.version(), program.program()).ok()?;
+                AddressType::Future(program.version())
+            }
+            _ => return None,
+        })
+    }
+
+    /// Public keys put into the script pubkey in plain form (for bare outputs only).
+    pub fn keys(&self) -> &[LegacyPk] {
+        match self {
+            SpkTemplate::Pk(key) => std::slice::from_ref(key),
+            SpkTemplate::Multi { keys, .. } => keys,
+            _ => &[],
+        }
+    }
+}
+
+impl From<&ScriptPubkey> for SpkTemplate {
+    fn from(script_pubkey: &ScriptPubkey) -> Self { SpkTemplate::lift(script_pubkey) }
+}
+
+/// Splits serialized public key (33 or 65 bytes with the length prefix) from the script.
+fn split_key(script: &[u8]) -> Option<(LegacyPk, &[u8])> {
+    let (&len, rest) = script.split_first()?;
+    if !matches!(len, 33 | 65) || rest.len() < len as usize {
+        return None;
+    }
+    let (key, rest) = rest.split_at(len as usize);
+    Some((LegacyPk::from_bytes(key).ok()?, rest))
+}
+
+fn lift_witness(script: &[u8]) -> Option<WitnessProgram> {
+    let (&version, rest) = script.split_first()?;
+    let (&len, program) = rest.split_first()?;
+    let version = match version {
+        0 => 0,
+        OP_PUSHNUM_1..=OP_PUSHNUM_16 => version - OP_PUSHNUM_1 + 1,
+        _ => return None,
+    };
+    if !(2..=40).contains(&len) || program.len() != len as usize {
+        return None;
+    }
+    let version = WitnessVer::from_version_no(version).ok()?;
+    WitnessProgram::new(version, program.to_vec()).ok()
+}
+
+fn lift_pk(script: &[u8]) -> Option<SpkTemplate> {
+    match split_key(script)? {
+        (key, [OP_CHECKSIG]) => Some(SpkTemplate::Pk(key)),
+        _ => None,
+    }
+}
+
+fn lift_multi(script: &[u8]) -> Option<SpkTemplate> {
+    let (&first, rest) = script.split_first()?;
+    let (&last, rest) = rest.split_last()?;
+    let (&count, mut rest) = rest.split_last()?;
+    if !(OP_PUSHNUM_1..=OP_PUSHNUM_16).contains(&first)
+        || !(OP_PUSHNUM_1..=OP_PUSHNUM_16).contains(&count)
+        || last != OP_CHECKMULTISIG
+    {
+        return None;
+    }
+    let threshold = first - OP_PUSHNUM_1 + 1;
+    let count = count - OP_PUSHNUM_1 + 1;
+
+    let mut keys = Vec::with_capacity(count as usize);
+    while !rest.is_empty() {
+        let (key, remains) = split_key(rest)?;
+        keys.push(key);
+        rest = remains;
+    }
+    if keys.len() != count as usize || threshold > count {
+        return None;
+    }
+    Some(SpkTemplate::Multi { threshold, keys })
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use derive::{CompressedPk, Derive, XpubDerivable};
+
+    use super::*;
+
+    const XPUB: &str = "[643a7adc/84h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+
+    fn key(index: u8) -> CompressedPk { XpubDerivable::from_str(XPUB).unwrap().derive(0, index) }
+
+    #[test]
+    fn lift_address_types() {
+        let hash = WPubkeyHash::from([7u8; 20]);
+        let spk = ScriptPubkey::p2wpkh(hash);
+        let template = SpkTemplate::lift(&spk);
+        assert_eq!(template, SpkTemplate::Wpkh(hash));
+        assert_eq!(template.class(), SpkClass::P2wpkh);
+        assert_eq!(template.address_type(), Some(AddressType::P2wpkh));
+
+        let spk = ScriptPubkey::p2sh(ScriptHash::from([1u8; 20]));
+        assert_eq!(SpkTemplate::lift(&spk).class(), SpkClass::P2sh);
+
+        let program = WitnessProgram::new(WitnessVer::V2, vec![3u8; 32]).unwrap();
+        let mut script = vec![OP_PUSHNUM_1 + 1, 32];
+        script.extend([3u8; 32]);
+        let spk = ScriptPubkey::from_unsafe(script);
+        assert_eq!(SpkTemplate::lift(&spk), SpkTemplate::Witness(program));
+        assert_eq!(
+            SpkTemplate::lift(&spk).address_type(),
+            Some(AddressType::Future(WitnessVer::V2))
+        );
+
+        let spk = ScriptPubkey::op_return(b"hello");
+        assert_eq!(SpkTemplate::lift(&spk), SpkTemplate::NullData(spk[1..].to_vec()));
+    }
+
+    #[test]
+    fn lift_bare() {
+        let mut script = vec![33];
+        script.extend(key(0).to_byte_array());
+        script.push(OP_CHECKSIG);
+        let template = SpkTemplate::lift(&ScriptPubkey::from_unsafe(script.clone()));
+        assert_eq!(template, SpkTemplate::Pk(key(0).into()));
+        assert_eq!(template.keys(), &[LegacyPk::from(key(0))]);
+
+        let mut script = vec![OP_PUSHNUM_1];
+        for index in 0..2 {
+            script.push(33);
+            script.extend(key(index).to_byte_array());
+        }
+        script.extend([OP_PUSHNUM_1 + 1, OP_CHECKMULTISIG]);
+        let template = SpkTemplate::lift(&ScriptPubkey::from_unsafe(script.clone()));
+        assert_eq!(template, SpkTemplate::Multi {
+            threshold: 1,
+            keys: vec![key(0).into(), key(1).into()]
+        });
+        assert_eq!(template.class(), SpkClass::Bare);
+
+        // Key count doesn't match the number of keys
+        let len = script.len();
+        script[len - 2] = OP_PUSHNUM_1 + 2;
+        let spk = ScriptPubkey::from_unsafe(script);
+        assert_eq!(SpkTemplate::lift(&spk), SpkTemplate::NonStandard(spk));
+    }
+}
@@ -0,0 +1,222 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compilation of miniscript into bitcoin script.
+
+use derive::opcodes::*;
+
+use super::{Fragment, Miniscript};
+use crate::multisig::push_num;
+
+impl<K> Fragment<K> {
+    /// For fragments ending with an opcode which has a `VERIFY` version provides pair of the
+    /// opcode and its `VERIFY` version.
+    fn verify_opcode(&self) -> Option<(u8, u8)> {
+        match self {
+            Fragment::Check(_) => Some((OP_CHECKSIG, OP_CHECKSIGVERIFY)),
+            Fragment::Sha256(_)
+            | Fragment::Hash256(_)
+            | Fragment::Ripemd160(_)
+            | Fragment::Hash160(_)
+            | Fragment::Thresh(_, _) => Some((OP_EQUAL, OP_EQUALVERIFY)),
+            Fragment::Multi(_, _) => Some((OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY)),
+            Fragment::MultiA(_, _) => Some((OP_NUMEQUAL, OP_NUMEQUALVERIFY)),
+            _ => None,
+        }
+    }
+}
+
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    debug_assert!(data.len() < OP_PUSHDATA1 as usize);
+    script.push(data.len() as u8);
+    script.extend(data);
+}
+
+fn push_int(script: &mut Vec<u8>, num: usize) {
+    match num {
+        0 => script.push(OP_PUSHBYTES_0),
+        _ => push_num(script, num),
+    }
+}
+
+impl<K> Miniscript<K> {
+    /// Compiles miniscript into a script, using `key_bytes` to serialize keys (33-byte
+    /// compressed keys for segwit context and 32-byte x-only keys for tapscript).
+    pub fn encode(&self, key_bytes: &impl Fn(&K) -> Vec<u8>) -> Vec<u8> {
+        let mut script = Vec::new();
+        self.encode_to(&mut script, key_bytes);
+        script
+    }
+
+    fn encode_to(&self, script: &mut Vec<u8>, key_bytes: &impl Fn(&K) -> Vec<u8>) {
+        match &self.node {
+            Fragment::False => script.push(OP_PUSHBYTES_0),
+            Fragment::True => script.push(OP_PUSHNUM_1),
+            Fragment::PkK(key) => push_data(script, &key_bytes(key)),
+            Fragment::PkH(key) => {
+                script.extend([OP_DUP, OP_HASH160]);
+                push_data(script, &key_hash160(&key_bytes(key)));
+                script.push(OP_EQUALVERIFY);
+            }
+            Fragment::Older(n) => {
+                push_int(script, *n as usize);
+                script.push(OP_CSV);
+            }
+            Fragment::After(n) => {
+                push_int(script, *n as usize);
+                script.push(OP_CLTV);
+            }
+            Fragment::Sha256(h) | Fragment::Hash256(h) => {
+                script.extend([OP_SIZE, 1, 32, OP_EQUALVERIFY]);
+                script.push(match self.node {
+                    Fragment::Sha256(_) => OP_SHA256,
+                    _ => OP_HASH256,
+                });
+                push_data(script, h);
+                script.push(OP_EQUAL);
+            }
+            Fragment::Ripemd160(h) | Fragment::Hash160(h) => {
+                script.extend([OP_SIZE, 1, 32, OP_EQUALVERIFY]);
+                script.push(match self.node {
+                    Fragment::Ripemd160(_) => OP_RIPEMD160,
+                    _ => OP_HASH160,
+                });
+                push_data(script, h);
+                script.push(OP_EQUAL);
+            }
+            Fragment::Alt(x) => {
+                script.push(OP_TOALTSTACK);
+                x.encode_to(script, key_bytes);
+                script.push(OP_FROMALTSTACK);
+            }
+            Fragment::Swap(x) => {
+                script.push(OP_SWAP);
+                x.encode_to(script, key_bytes);
+            }
+            Fragment::Check(x) => {
+                x.encode_to(script, key_bytes);
+                script.push(OP_CHECKSIG);
+            }
+            Fragment::DupIf(x) => {
+                script.extend([OP_DUP, OP_IF]);
+                x.encode_to(script, key_bytes);
+                script.push(OP_ENDIF);
+            }
+            Fragment::Verify(x) => {
+                x.encode_to(script, key_bytes);
+                match x.node.verify_opcode() {
+                    Some((opcode, verify)) => {
+                        debug_assert_eq!(script.last(), Some(&opcode));
+                        *script.last_mut().expect("non-empty script") = verify;
+                    }
+                    None => script.push(OP_VERIFY),
+                }
+            }
+            Fragment::NonZero(x) => {
+                script.extend([OP_SIZE, OP_0NOTEQUAL, OP_IF]);
+                x.encode_to(script, key_bytes);
+                script.push(OP_ENDIF);
+            }
+            Fragment::ZeroNotEqual(x) => {
+                x.encode_to(script, key_bytes);
+                script.push(OP_0NOTEQUAL);
+            }
+            Fragment::AndV(x, y) => {
+                x.encode_to(script, key_bytes);
+                y.encode_to(script, key_bytes);
+            }
+            Fragment::AndB(x, y) => {
+                x.encode_to(script, key_bytes);
+                y.encode_to(script, key_bytes);
+                script.push(OP_BOOLAND);
+            }
+            Fragment::AndOr(x, y, z) => {
+                x.encode_to(script, key_bytes);
+                script.push(OP_NOTIF);
+                z.encode_to(script, key_bytes);
+                script.push(OP_ELSE);
+                y.encode_to(script, key_bytes);
+                script.push(OP_ENDIF);
+            }
+            Fragment::OrB(x, z) => {
+                x.encode_to(script, key_bytes);
+                z.encode_to(script, key_bytes);
+                script.push(OP_BOOLOR);
+            }
+            Fragment::OrC(x, z) => {
+                x.encode_to(script, key_bytes);
+                script.push(OP_NOTIF);
+                z.encode_to(script, key_bytes);
+                script.push(OP_ENDIF);
+            }
+            Fragment::OrD(x, z) => {
+                x.encode_to(script, key_bytes);
+                script.extend([OP_IFDUP, OP_NOTIF]);
+                z.encode_to(script, key_bytes);
+                script.push(OP_ENDIF);
+            }
+            Fragment::OrI(x, z) => {
+                script.push(OP_IF);
+                x.encode_to(script, key_bytes);
+                script.push(OP_ELSE);
+                z.encode_to(script, key_bytes);
+                script.push(OP_ENDIF);
+            }
+            Fragment::Thresh(k, subs) => {
+                for (no, sub) in subs.iter().enumerate() {
+                    sub.encode_to(script, key_bytes);
+                    if no > 0 {
+                        script.push(OP_ADD);
+                    }
+                }
+                push_int(script, *k as usize);
+                script.push(OP_EQUAL);
+            }
+            Fragment::Multi(k, keys) => {
+                push_int(script, *k as usize);
+                for key in keys {
+                    push_data(script, &key_bytes(key));
+                }
+                push_int(script, keys.len());
+                script.push(OP_CHECKMULTISIG);
+            }
+            Fragment::MultiA(k, keys) => {
+                for (no, key) in keys.iter().enumerate() {
+                    push_data(script, &key_bytes(key));
+                    script.push(if no == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD });
+                }
+                push_int(script, *k as usize);
+                script.push(OP_NUMEQUAL);
+            }
+        }
+    }
+}
+
+pub(crate) fn key_hash160(data: &[u8]) -> [u8; 20] {
+    use commit_verify::{DigestExt, Ripemd160, Sha256};
+
+    let mut engine = Sha256::default();
+    engine.input_raw(data);
+    let mut engine2 = Ripemd160::default();
+    engine2.input_raw(&engine.finish());
+    engine2.finish()
+}
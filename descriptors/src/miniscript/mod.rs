@@ -0,0 +1,468 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Miniscript: structured representation of a subset of bitcoin script (for P2WSH and
+//! tapscript contexts), allowing analysis of its correctness and malleability, as well as
+//! composition of arbitrary spending conditions.
+
+mod encode;
+mod parse;
+mod policy;
+mod satisfy;
+mod timelock;
+mod types;
+
+use std::fmt::{self, Display, Formatter};
+
+use amplify::hex::ToHex;
+pub use policy::Policy;
+pub use timelock::Timelock;
+pub use types::{BaseType, Dissat, Type};
+
+/// Maximum size of a P2WSH witness script under standardness rules.
+pub const MAX_WSH_SCRIPT_SIZE: usize = 3600;
+
+/// Script context in which miniscript is used.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display)]
+pub enum MsCtx {
+    /// Witness script of P2WSH and P2SH-P2WSH outputs.
+    #[display("segwit")]
+    Segwit,
+
+    /// Tapscript leaf of a taproot script tree.
+    #[display("tapscript")]
+    Tapscript,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MiniscriptError {
+    /// unknown miniscript fragment '{0}'.
+    UnknownFragment(String),
+
+    /// unknown miniscript wrapper '{0}'.
+    UnknownWrapper(char),
+
+    /// invalid arguments of miniscript fragment '{0}'.
+    InvalidArgs(String),
+
+    /// fragment `{0}` is not allowed in {1} context.
+    ContextMismatch(&'static str, MsCtx),
+
+    /// miniscript fragment `{0}` type check failed: {1}.
+    TypeCheck(&'static str, &'static str),
+
+    /// top-level miniscript must have B type, while the provided miniscript has type {0}.
+    NotTopLevel(BaseType),
+
+    /// miniscript is malleable.
+    Malleable,
+
+    /// miniscript can be satisfied without a signature.
+    Unsafe,
+
+    /// witness script size {0} exceeds standard limit of 3600 bytes.
+    ScriptSize(usize),
+
+    /// policy can't be compiled into a safe non-malleable miniscript.
+    Uncompilable,
+}
+
+/// Miniscript AST node.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Fragment<K> {
+    /// `0`
+    False,
+    /// `1`
+    True,
+    /// `pk_k(KEY)`
+    PkK(K),
+    /// `pk_h(KEY)`
+    PkH(K),
+    /// `older(n)`
+    Older(u32),
+    /// `after(n)`
+    After(u32),
+    /// `sha256(h)`
+    Sha256([u8; 32]),
+    /// `hash256(h)`
+    Hash256([u8; 32]),
+    /// `ripemd160(h)`
+    Ripemd160([u8; 20]),
+    /// `hash160(h)`
+    Hash160([u8; 20]),
+    /// `a:X`
+    Alt(Box<Miniscript<K>>),
+    /// `s:X`
+    Swap(Box<Miniscript<K>>),
+    /// `c:X`
+    Check(Box<Miniscript<K>>),
+    /// `d:X`
+    DupIf(Box<Miniscript<K>>),
+    /// `v:X`
+    Verify(Box<Miniscript<K>>),
+    /// `j:X`
+    NonZero(Box<Miniscript<K>>),
+    /// `n:X`
+    ZeroNotEqual(Box<Miniscript<K>>),
+    /// `and_v(X,Y)`
+    AndV(Box<Miniscript<K>>, Box<Miniscript<K>>),
+    /// `and_b(X,Y)`
+    AndB(Box<Miniscript<K>>, Box<Miniscript<K>>),
+    /// `andor(X,Y,Z)`
+    AndOr(Box<Miniscript<K>>, Box<Miniscript<K>>, Box<Miniscript<K>>),
+    /// `or_b(X,Z)`
+    OrB(Box<Miniscript<K>>, Box<Miniscript<K>>),
+    /// `or_c(X,Z)`
+    OrC(Box<Miniscript<K>>, Box<Miniscript<K>>),
+    /// `or_d(X,Z)`
+    OrD(Box<Miniscript<K>>, Box<Miniscript<K>>),
+    /// `or_i(X,Z)`
+    OrI(Box<Miniscript<K>>, Box<Miniscript<K>>),
+    /// `thresh(k,X1,...,Xn)`
+    Thresh(u16, Vec<Miniscript<K>>),
+    /// `multi(k,KEY1,...,KEYn)`; allowed only in segwit context.
+    Multi(u16, Vec<K>),
+    /// `multi_a(k,KEY1,...,KEYn)`; allowed only in tapscript context.
+    MultiA(u16, Vec<K>),
+}
+
+impl<K> Fragment<K> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fragment::False => "0",
+            Fragment::True => "1",
+            Fragment::PkK(_) => "pk_k",
+            Fragment::PkH(_) => "pk_h",
+            Fragment::Older(_) => "older",
+            Fragment::After(_) => "after",
+            Fragment::Sha256(_) => "sha256",
+            Fragment::Hash256(_) => "hash256",
+            Fragment::Ripemd160(_) => "ripemd160",
+            Fragment::Hash160(_) => "hash160",
+            Fragment::Alt(_) => "a:",
+            Fragment::Swap(_) => "s:",
+            Fragment::Check(_) => "c:",
+            Fragment::DupIf(_) => "d:",
+            Fragment::Verify(_) => "v:",
+            Fragment::NonZero(_) => "j:",
+            Fragment::ZeroNotEqual(_) => "n:",
+            Fragment::AndV(_, _) => "and_v",
+            Fragment::AndB(_, _) => "and_b",
+            Fragment::AndOr(_, _, _) => "andor",
+            Fragment::OrB(_, _) => "or_b",
+            Fragment::OrC(_, _) => "or_c",
+            Fragment::OrD(_, _) => "or_d",
+            Fragment::OrI(_, _) => "or_i",
+            Fragment::Thresh(_, _) => "thresh",
+            Fragment::Multi(_, _) => "multi",
+            Fragment::MultiA(_, _) => "multi_a",
+        }
+    }
+}
+
+/// Type-checked miniscript expression.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Miniscript<K> {
+    ctx: MsCtx,
+    node: Fragment<K>,
+    ty: Type,
+}
+
+impl<K> Miniscript<K> {
+    /// Constructs miniscript expression out of a fragment, checking that the fragment is
+    /// well-typed and allowed in the given context.
+    ///
+    /// All sub-expressions of the fragment must be constructed for the same context.
+    pub fn new(ctx: MsCtx, node: Fragment<K>) -> Result<Self, MiniscriptError> {
+        let ty = Type::with_fragment(&node, ctx)?;
+        Ok(Miniscript { ctx, node, ty })
+    }
+
+    pub fn ctx(&self) -> MsCtx { self.ctx }
+
+    pub fn node(&self) -> &Fragment<K> { &self.node }
+
+    pub fn ty(&self) -> Type { self.ty }
+
+    /// Checks that the miniscript can be used as a top-level script: it must have a `B` type,
+    /// must be non-malleable and must always require a signature for the satisfaction.
+    pub fn check_top_level(&self) -> Result<(), MiniscriptError> {
+        if self.ty.base != BaseType::B {
+            return Err(MiniscriptError::NotTopLevel(self.ty.base));
+        }
+        if !self.ty.non_malleable {
+            return Err(MiniscriptError::Malleable);
+        }
+        if !self.ty.safe {
+            return Err(MiniscriptError::Unsafe);
+        }
+        Ok(())
+    }
+
+    /// Direct sub-expressions of this miniscript.
+    pub fn subs(&self) -> Vec<&Miniscript<K>> {
+        match &self.node {
+            Fragment::False
+            | Fragment::True
+            | Fragment::PkK(_)
+            | Fragment::PkH(_)
+            | Fragment::Older(_)
+            | Fragment::After(_)
+            | Fragment::Sha256(_)
+            | Fragment::Hash256(_)
+            | Fragment::Ripemd160(_)
+            | Fragment::Hash160(_)
+            | Fragment::Multi(_, _)
+            | Fragment::MultiA(_, _) => vec![],
+            Fragment::Alt(x)
+            | Fragment::Swap(x)
+            | Fragment::Check(x)
+            | Fragment::DupIf(x)
+            | Fragment::Verify(x)
+            | Fragment::NonZero(x)
+            | Fragment::ZeroNotEqual(x) => vec![x],
+            Fragment::AndV(x, y)
+            | Fragment::AndB(x, y)
+            | Fragment::OrB(x, y)
+            | Fragment::OrC(x, y)
+            | Fragment::OrD(x, y)
+            | Fragment::OrI(x, y) => vec![x, y],
+            Fragment::AndOr(x, y, z) => vec![x, y, z],
+            Fragment::Thresh(_, subs) => subs.iter().collect(),
+        }
+    }
+
+    /// All keys used by the miniscript, in the order of their appearance (keys used multiple
+    /// times are returned multiple times).
+    pub fn keys(&self) -> Vec<&K> {
+        match &self.node {
+            Fragment::PkK(key) | Fragment::PkH(key) => vec![key],
+            Fragment::Multi(_, keys) | Fragment::MultiA(_, keys) => keys.iter().collect(),
+            _ => self.subs().into_iter().flat_map(Miniscript::keys).collect(),
+        }
+    }
+}
+
+impl<K: Display> Display for Miniscript<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut wrappers = String::new();
+        let mut node = self;
+        loop {
+            let (wrapper, inner) = match &node.node {
+                Fragment::Alt(x) => ('a', x),
+                Fragment::Swap(x) => ('s', x),
+                Fragment::Check(x) => ('c', x),
+                Fragment::DupIf(x) => ('d', x),
+                Fragment::Verify(x) => ('v', x),
+                Fragment::NonZero(x) => ('j', x),
+                Fragment::ZeroNotEqual(x) => ('n', x),
+                Fragment::AndV(x, y) if matches!(y.node, Fragment::True) => ('t', x),
+                Fragment::OrI(x, y) if matches!(x.node, Fragment::False) => ('l', y),
+                Fragment::OrI(x, y) if matches!(y.node, Fragment::False) => ('u', x),
+                _ => break,
+            };
+            wrappers.push(wrapper);
+            node = inner;
+        }
+
+        let key_sugar = match &node.node {
+            Fragment::PkK(_) => Some("pk"),
+            Fragment::PkH(_) => Some("pkh"),
+            _ => None,
+        };
+        let sugar = match key_sugar {
+            Some(name) if wrappers.ends_with('c') => {
+                wrappers.pop();
+                Some(name)
+            }
+            _ => None,
+        };
+        if !wrappers.is_empty() {
+            write!(f, "{wrappers}:")?;
+        }
+
+        let fmt_keys = |f: &mut Formatter<'_>, keys: &[K]| -> fmt::Result {
+            for key in keys {
+                f.write_str(",")?;
+                Display::fmt(key, f)?;
+            }
+            Ok(())
+        };
+        let fmt_subs = |f: &mut Formatter<'_>, subs: &[&Miniscript<K>]| -> fmt::Result {
+            for (no, sub) in subs.iter().enumerate() {
+                if no > 0 {
+                    f.write_str(",")?;
+                }
+                Display::fmt(sub, f)?;
+            }
+            Ok(())
+        };
+
+        match &node.node {
+            Fragment::False => f.write_str("0"),
+            Fragment::True => f.write_str("1"),
+            Fragment::PkK(key) | Fragment::PkH(key) => {
+                write!(f, "{}(", sugar.unwrap_or(node.node.name()))?;
+                Display::fmt(key, f)?;
+                f.write_str(")")
+            }
+            Fragment::Older(n) | Fragment::After(n) => write!(f, "{}({n})", node.node.name()),
+            Fragment::Sha256(h) | Fragment::Hash256(h) => {
+                write!(f, "{}({})", node.node.name(), h.to_hex())
+            }
+            Fragment::Ripemd160(h) | Fragment::Hash160(h) => {
+                write!(f, "{}({})", node.node.name(), h.to_hex())
+            }
+            Fragment::Multi(k, keys) | Fragment::MultiA(k, keys) => {
+                write!(f, "{}({k}", node.node.name())?;
+                fmt_keys(f, keys)?;
+                f.write_str(")")
+            }
+            Fragment::Thresh(k, subs) => {
+                write!(f, "thresh({k},")?;
+                fmt_subs(f, &subs.iter().collect::<Vec<_>>())?;
+                f.write_str(")")
+            }
+            _ => {
+                write!(f, "{}(", node.node.name())?;
+                fmt_subs(f, &node.subs())?;
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::opcodes::*;
+    use derive::{CompressedPk, Derive, XOnlyPk, XpubDerivable};
+
+    use super::*;
+    use crate::DescrParseError;
+
+    const KEY1: &str = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+    const KEY2: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+
+    fn parse(s: &str, ctx: MsCtx) -> Result<Miniscript<XpubDerivable>, DescrParseError> {
+        let s = s.replace("@A", KEY1).replace("@B", KEY2);
+        Miniscript::parse(&s, ctx)
+    }
+
+    fn compr(key: &XpubDerivable) -> Vec<u8> {
+        let pk: CompressedPk = key.derive(0, 0u8);
+        pk.to_byte_array().to_vec()
+    }
+
+    fn xonly(key: &XpubDerivable) -> Vec<u8> {
+        let pk: XOnlyPk = key.derive(0, 0u8);
+        pk.to_byte_array().to_vec()
+    }
+
+    #[test]
+    fn roundtrip() {
+        for s in [
+            "pk(@A)",
+            "and_v(v:pk(@A),older(144))",
+            "or_d(pk(@A),and_v(v:pkh(@B),after(500000)))",
+            "andor(pk(@A),older(1008),pk(@B))",
+            "thresh(2,pk(@A),s:pk(@B),sln:older(12960))",
+            "or_i(and_v(v:pk(@A),hash160(0000000000000000000000000000000000000000)),pk(@B))",
+            "t:or_c(pk(@A),v:pkh(@B))",
+            "multi(1,@A,@B)",
+        ] {
+            let ms = parse(s, MsCtx::Segwit).unwrap();
+            let expected = s.replace("@A", KEY1).replace("@B", KEY2);
+            assert_eq!(ms.to_string(), expected);
+            assert_eq!(Miniscript::<XpubDerivable>::parse(&expected, MsCtx::Segwit).unwrap(), ms);
+        }
+    }
+
+    #[test]
+    fn type_check() {
+        let ty = parse("pk(@A)", MsCtx::Segwit).unwrap().ty();
+        assert_eq!(ty.base, BaseType::B);
+        assert!(ty.one_arg && ty.nonzero && ty.dissatisfiable && ty.unit);
+        assert!(ty.safe && ty.non_malleable);
+
+        assert!(matches!(
+            parse("and_b(pk(@A),pk(@B))", MsCtx::Segwit),
+            Err(DescrParseError::Miniscript(MiniscriptError::TypeCheck("and_b", _)))
+        ));
+        assert!(parse("and_b(pk(@A),s:pk(@B))", MsCtx::Segwit).is_ok());
+        assert!(matches!(
+            parse("v:older(1)", MsCtx::Segwit),
+            Err(DescrParseError::Miniscript(MiniscriptError::NotTopLevel(BaseType::V)))
+        ));
+        assert!(matches!(
+            parse("older(144)", MsCtx::Segwit),
+            Err(DescrParseError::Miniscript(MiniscriptError::Unsafe))
+        ));
+        assert!(matches!(
+            parse("or_i(pk(@A),older(144))", MsCtx::Segwit),
+            Err(DescrParseError::Miniscript(MiniscriptError::Unsafe))
+        ));
+        assert!(matches!(
+            parse("multi_a(1,@A,@B)", MsCtx::Segwit),
+            Err(DescrParseError::Miniscript(MiniscriptError::ContextMismatch("multi_a", _)))
+        ));
+        assert!(parse("multi_a(1,@A,@B)", MsCtx::Tapscript).is_ok());
+    }
+
+    #[test]
+    fn malleability() {
+        let hash =
+            Miniscript::<XpubDerivable>::new(MsCtx::Segwit, Fragment::Sha256([0; 32])).unwrap();
+        assert!(!hash.ty().safe);
+        assert_eq!(hash.ty().dissat, Dissat::Unknown);
+        // Hash preimage satisfaction can be replaced by third parties
+        assert!(matches!(
+            parse(
+                "or_b(pk(@A),a:\
+                 sha256(0000000000000000000000000000000000000000000000000000000000000000))",
+                MsCtx::Segwit
+            ),
+            Err(DescrParseError::Miniscript(MiniscriptError::Malleable))
+        ));
+    }
+
+    #[test]
+    fn encode() {
+        let key = KEY1.parse::<XpubDerivable>().unwrap();
+        let ms = parse("and_v(v:pk(@A),older(144))", MsCtx::Segwit).unwrap();
+        let mut expected = vec![33];
+        expected.extend(compr(&key));
+        expected.extend([OP_CHECKSIGVERIFY, 2, 0x90, 0x00, OP_CSV]);
+        assert_eq!(ms.encode(&compr), expected);
+
+        let ms = parse("and_v(v:pk(@A),or_d(pk(@A),older(1)))", MsCtx::Tapscript).unwrap();
+        let mut expected = vec![32];
+        expected.extend(xonly(&key));
+        expected.extend([OP_CHECKSIGVERIFY, 32]);
+        expected.extend(xonly(&key));
+        expected.extend([OP_CHECKSIG, OP_IFDUP, OP_NOTIF, OP_PUSHNUM_1, OP_CSV, OP_ENDIF]);
+        assert_eq!(ms.encode(&xonly), expected);
+    }
+}
@@ -0,0 +1,148 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing miniscript from its string representation.
+
+use std::str::FromStr;
+
+use amplify::hex::FromHex;
+
+use super::{Fragment, Miniscript, MiniscriptError, MsCtx};
+use crate::descriptor::split_args;
+use crate::DescrParseError;
+
+impl<K: FromStr> Miniscript<K>
+where DescrParseError: From<K::Err>
+{
+    /// Parses top-level miniscript expression for the given context, checking its type,
+    /// malleability and that it requires a signature to be satisfied.
+    pub fn parse(s: &str, ctx: MsCtx) -> Result<Self, DescrParseError> {
+        let ms = Self::parse_expr(s, ctx)?;
+        ms.check_top_level()?;
+        Ok(ms)
+    }
+
+    /// Parses miniscript expression for the given context, checking its type correctness but
+    /// not requiring it to be a valid top-level expression.
+    pub fn parse_expr(s: &str, ctx: MsCtx) -> Result<Self, DescrParseError> {
+        let s = s.trim();
+        let name_end = s.find('(').unwrap_or(s.len());
+        let (wrappers, expr) = match s[..name_end].find(':') {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => ("", s),
+        };
+        let mut ms = Self::parse_fragment(expr, ctx)?;
+        for wrapper in wrappers.chars().rev() {
+            ms = ms.wrap(wrapper)?;
+        }
+        Ok(ms)
+    }
+
+    fn parse_fragment(s: &str, ctx: MsCtx) -> Result<Self, DescrParseError> {
+        let invalid = || MiniscriptError::InvalidArgs(s.to_owned());
+        let (name, args) = match s.split_once('(') {
+            None => (s, vec![]),
+            Some((name, rest)) => (name, split_args(rest.strip_suffix(')').ok_or_else(invalid)?)?),
+        };
+        let expr = |no: usize| -> Result<Box<Self>, DescrParseError> {
+            Ok(Box::new(Self::parse_expr(args[no], ctx)?))
+        };
+        let key = || -> Result<K, DescrParseError> { Ok(K::from_str(args[0])?) };
+        let num = || u32::from_str(args[0].trim()).map_err(|_| invalid());
+        let threshold = || u16::from_str(args[0].trim()).map_err(|_| invalid());
+        let hash32 = || <[u8; 32]>::from_hex(args[0].trim()).map_err(|_| invalid());
+        let hash20 = || <[u8; 20]>::from_hex(args[0].trim()).map_err(|_| invalid());
+
+        let arity = match name {
+            "0" | "1" => 0,
+            "pk_k" | "pk_h" | "pk" | "pkh" | "older" | "after" | "sha256" | "hash256"
+            | "ripemd160" | "hash160" => 1,
+            "and_v" | "and_b" | "and_n" | "or_b" | "or_c" | "or_d" | "or_i" => 2,
+            "andor" => 3,
+            "thresh" | "multi" | "multi_a" => args.len().max(2),
+            _ => return Err(MiniscriptError::UnknownFragment(name.to_owned()).into()),
+        };
+        if args.len() != arity {
+            return Err(invalid().into());
+        }
+
+        let node = match name {
+            "0" => Fragment::False,
+            "1" => Fragment::True,
+            "pk_k" => Fragment::PkK(key()?),
+            "pk_h" => Fragment::PkH(key()?),
+            "pk" => Fragment::Check(Box::new(Self::new(ctx, Fragment::PkK(key()?))?)),
+            "pkh" => Fragment::Check(Box::new(Self::new(ctx, Fragment::PkH(key()?))?)),
+            "older" => Fragment::Older(num()?),
+            "after" => Fragment::After(num()?),
+            "sha256" => Fragment::Sha256(hash32()?),
+            "hash256" => Fragment::Hash256(hash32()?),
+            "ripemd160" => Fragment::Ripemd160(hash20()?),
+            "hash160" => Fragment::Hash160(hash20()?),
+            "and_v" => Fragment::AndV(expr(0)?, expr(1)?),
+            "and_b" => Fragment::AndB(expr(0)?, expr(1)?),
+            "and_n" => {
+                Fragment::AndOr(expr(0)?, expr(1)?, Box::new(Self::new(ctx, Fragment::False)?))
+            }
+            "andor" => Fragment::AndOr(expr(0)?, expr(1)?, expr(2)?),
+            "or_b" => Fragment::OrB(expr(0)?, expr(1)?),
+            "or_c" => Fragment::OrC(expr(0)?, expr(1)?),
+            "or_d" => Fragment::OrD(expr(0)?, expr(1)?),
+            "or_i" => Fragment::OrI(expr(0)?, expr(1)?),
+            "thresh" => Fragment::Thresh(
+                threshold()?,
+                (1..args.len()).map(|no| expr(no).map(|ms| *ms)).collect::<Result<_, _>>()?,
+            ),
+            "multi" | "multi_a" => {
+                let keys =
+                    args[1..].iter().map(|s| K::from_str(s)).collect::<Result<Vec<_>, _>>()?;
+                match name {
+                    "multi" => Fragment::Multi(threshold()?, keys),
+                    _ => Fragment::MultiA(threshold()?, keys),
+                }
+            }
+            _ => unreachable!("unknown fragments are filtered above"),
+        };
+        Ok(Self::new(ctx, node)?)
+    }
+}
+
+impl<K> Miniscript<K> {
+    fn wrap(self, wrapper: char) -> Result<Self, MiniscriptError> {
+        let ctx = self.ctx;
+        let x = Box::new(self);
+        let node = match wrapper {
+            'a' => Fragment::Alt(x),
+            's' => Fragment::Swap(x),
+            'c' => Fragment::Check(x),
+            'd' => Fragment::DupIf(x),
+            'v' => Fragment::Verify(x),
+            'j' => Fragment::NonZero(x),
+            'n' => Fragment::ZeroNotEqual(x),
+            't' => Fragment::AndV(x, Box::new(Self::new(ctx, Fragment::True)?)),
+            'l' => Fragment::OrI(Box::new(Self::new(ctx, Fragment::False)?), x),
+            'u' => Fragment::OrI(x, Box::new(Self::new(ctx, Fragment::False)?)),
+            _ => return Err(MiniscriptError::UnknownWrapper(wrapper)),
+        };
+        Self::new(ctx, node)
+    }
+}
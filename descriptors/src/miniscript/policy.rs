@@ -0,0 +1,525 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level spending policy language and its compiler into miniscript.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use amplify::hex::{FromHex, ToHex};
+
+use super::{BaseType, Fragment, Miniscript, MiniscriptError, MsCtx};
+use crate::descriptor::split_args;
+use crate::DescrParseError;
+
+/// Spending policy, which can be compiled into a miniscript.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Policy<K> {
+    /// `pk(KEY)`: signature with the key.
+    Key(K),
+    /// `after(n)`: absolute timelock.
+    After(u32),
+    /// `older(n)`: relative timelock.
+    Older(u32),
+    /// `sha256(h)`: preimage of a SHA256 hash.
+    Sha256([u8; 32]),
+    /// `hash256(h)`: preimage of a double SHA256 hash.
+    Hash256([u8; 32]),
+    /// `ripemd160(h)`: preimage of a RIPEMD160 hash.
+    Ripemd160([u8; 20]),
+    /// `hash160(h)`: preimage of a HASH160 hash.
+    Hash160([u8; 20]),
+    /// `and(X,Y)`: both sub-policies must be satisfied.
+    And(Box<Policy<K>>, Box<Policy<K>>),
+    /// `or([N@]X,[M@]Y)`: one of the sub-policies must be satisfied. Numbers are relative
+    /// probabilities of each of the branches to be used (both default to 1).
+    Or((u32, Box<Policy<K>>), (u32, Box<Policy<K>>)),
+    /// `thresh(k,X1,...,Xn)`: `k` out of `n` sub-policies must be satisfied.
+    Thresh(u16, Vec<Policy<K>>),
+}
+
+impl<K> Policy<K> {
+    /// All keys used by the policy, in the order of their appearance.
+    pub fn keys(&self) -> Vec<&K> {
+        match self {
+            Policy::Key(key) => vec![key],
+            Policy::After(_)
+            | Policy::Older(_)
+            | Policy::Sha256(_)
+            | Policy::Hash256(_)
+            | Policy::Ripemd160(_)
+            | Policy::Hash160(_) => vec![],
+            Policy::And(x, y) | Policy::Or((_, x), (_, y)) => {
+                x.keys().into_iter().chain(y.keys()).collect()
+            }
+            Policy::Thresh(_, subs) => subs.iter().flat_map(Policy::keys).collect(),
+        }
+    }
+}
+
+impl<K: Clone> Policy<K> {
+    /// Compiles the policy into the miniscript for a given context, choosing the encoding with
+    /// the smallest sum of the script size and the expected satisfaction witness size.
+    ///
+    /// Errors if the policy can't be expressed as a safe non-malleable top-level miniscript
+    /// (for instance when it can be satisfied without any signature).
+    pub fn compile(&self, ctx: MsCtx) -> Result<Miniscript<K>, MiniscriptError> {
+        let best = self
+            .candidates(ctx)
+            .into_iter()
+            .filter(|cand| cand.ms.check_top_level().is_ok())
+            .min_by(|a, b| a.cost().total_cmp(&b.cost()))
+            .ok_or(MiniscriptError::Uncompilable)?;
+        Ok(best.ms)
+    }
+
+    fn candidates(&self, ctx: MsCtx) -> Vec<Candidate<K>> {
+        let mut pool = Pool::new(ctx);
+        let sig = sig_size(ctx);
+        match self {
+            Policy::Key(key) => {
+                pool.insert(Fragment::PkK(key.clone()), Some(sig), Some(1.0));
+                let pk = key_size(ctx) as f64 + 1.0;
+                pool.insert(Fragment::PkH(key.clone()), Some(sig + pk), Some(1.0 + pk));
+            }
+            Policy::After(n) => pool.insert(Fragment::After(*n), Some(0.0), None),
+            Policy::Older(n) => pool.insert(Fragment::Older(*n), Some(0.0), None),
+            Policy::Sha256(h) => pool.insert(Fragment::Sha256(*h), Some(33.0), Some(33.0)),
+            Policy::Hash256(h) => pool.insert(Fragment::Hash256(*h), Some(33.0), Some(33.0)),
+            Policy::Ripemd160(h) => pool.insert(Fragment::Ripemd160(*h), Some(33.0), Some(33.0)),
+            Policy::Hash160(h) => pool.insert(Fragment::Hash160(*h), Some(33.0), Some(33.0)),
+            Policy::And(x, y) => {
+                let (xs, ys) = (x.candidates(ctx), y.candidates(ctx));
+                pool.insert_and(&xs, &ys);
+                pool.insert_and(&ys, &xs);
+            }
+            Policy::Or((wx, x), (wy, y)) => {
+                let (xs, ys) = (x.candidates(ctx), y.candidates(ctx));
+                let px = *wx as f64 / (*wx as f64 + *wy as f64);
+                pool.insert_or(&xs, &ys, px);
+                pool.insert_or(&ys, &xs, 1.0 - px);
+            }
+            Policy::Thresh(k, subs) => {
+                let n = subs.len();
+                let keys = subs
+                    .iter()
+                    .map(|sub| match sub {
+                        Policy::Key(key) => Some(key.clone()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>();
+                let k_f = *k as f64;
+                match (keys, ctx) {
+                    (Some(keys), MsCtx::Segwit) if n <= 20 => pool.insert(
+                        Fragment::Multi(*k, keys),
+                        Some(1.0 + k_f * sig),
+                        Some(1.0 + k_f),
+                    ),
+                    (Some(keys), MsCtx::Tapscript) => pool.insert(
+                        Fragment::MultiA(*k, keys),
+                        Some(k_f * sig + (n as f64 - k_f)),
+                        Some(n as f64),
+                    ),
+                    _ => {}
+                }
+                if *k as usize == n || *k == 1 {
+                    for cand in self.unfold_thresh().candidates(ctx) {
+                        pool.push(cand);
+                    }
+                }
+                pool.insert_thresh(*k, subs.iter().map(|sub| sub.candidates(ctx)).collect());
+            }
+        }
+        pool.close();
+        pool.into_candidates()
+    }
+
+    /// Represents `thresh(n,...)` as a chain of `and`s and `thresh(1,...)` as a chain of
+    /// equiprobable `or`s.
+    fn unfold_thresh(&self) -> Policy<K> {
+        let Policy::Thresh(k, subs) = self else {
+            return self.clone();
+        };
+        let mut iter = subs.iter().rev().cloned();
+        let last = iter.next().expect("threshold policy always has sub-policies");
+        iter.fold(last, |acc, sub| match *k {
+            1 => Policy::Or((1, Box::new(sub)), (subs.len() as u32 - 1, Box::new(acc))),
+            _ => Policy::And(Box::new(sub), Box::new(acc)),
+        })
+    }
+}
+
+const fn sig_size(ctx: MsCtx) -> f64 {
+    match ctx {
+        MsCtx::Segwit => 73.0,
+        MsCtx::Tapscript => 66.0,
+    }
+}
+
+const fn key_size(ctx: MsCtx) -> usize {
+    match ctx {
+        MsCtx::Segwit => 33,
+        MsCtx::Tapscript => 32,
+    }
+}
+
+/// Witness size of a `1` stack element used to select a branch.
+const ONE: f64 = 2.0;
+/// Witness size of an empty stack element.
+const EMPTY: f64 = 1.0;
+
+/// Miniscript compilation candidate with its costs.
+#[derive(Clone, Debug)]
+struct Candidate<K> {
+    ms: Miniscript<K>,
+    script_size: usize,
+    /// Expected size of the satisfaction witness.
+    sat: f64,
+    /// Size of the dissatisfaction witness, if the miniscript can be dissatisfied.
+    dissat: Option<f64>,
+}
+
+impl<K> Candidate<K> {
+    fn cost(&self) -> f64 { self.script_size as f64 + self.sat }
+
+    fn boxed(&self) -> Box<Miniscript<K>>
+    where K: Clone {
+        Box::new(self.ms.clone())
+    }
+}
+
+/// Set of compilation candidates, keeping the cheapest one for each of the miniscript types.
+struct Pool<K> {
+    ctx: MsCtx,
+    candidates: Vec<Candidate<K>>,
+}
+
+impl<K: Clone> Pool<K> {
+    fn new(ctx: MsCtx) -> Self {
+        Pool {
+            ctx,
+            candidates: vec![],
+        }
+    }
+
+    fn into_candidates(self) -> Vec<Candidate<K>> { self.candidates }
+
+    fn insert(&mut self, node: Fragment<K>, sat: Option<f64>, dissat: Option<f64>) {
+        let Some(sat) = sat else {
+            return;
+        };
+        let Ok(ms) = Miniscript::new(self.ctx, node) else {
+            return;
+        };
+        if !ms.ty().non_malleable {
+            return;
+        }
+        let key_len = key_size(self.ctx);
+        let script_size = ms.encode(&|_| vec![0u8; key_len]).len();
+        self.push(Candidate {
+            ms,
+            script_size,
+            sat,
+            dissat,
+        });
+    }
+
+    fn push(&mut self, cand: Candidate<K>) -> bool {
+        match self.candidates.iter_mut().find(|c| c.ms.ty() == cand.ms.ty()) {
+            Some(c) if c.cost() <= cand.cost() => false,
+            Some(c) => {
+                *c = cand;
+                true
+            }
+            None => {
+                self.candidates.push(cand);
+                true
+            }
+        }
+    }
+
+    /// Extends the pool with all wrapped versions of the candidates, until no cheaper
+    /// candidates appear.
+    fn close(&mut self) {
+        for _ in 0..4 {
+            let mut changed = false;
+            for c in self.candidates.clone() {
+                let len = self.candidates.len();
+                let (sat, dissat) = (c.sat, c.dissat);
+                self.insert(Fragment::Alt(c.boxed()), Some(sat), dissat);
+                self.insert(Fragment::Swap(c.boxed()), Some(sat), dissat);
+                self.insert(Fragment::Check(c.boxed()), Some(sat), dissat);
+                self.insert(Fragment::DupIf(c.boxed()), Some(sat + ONE), Some(EMPTY));
+                self.insert(Fragment::Verify(c.boxed()), Some(sat), None);
+                self.insert(Fragment::NonZero(c.boxed()), Some(sat), Some(EMPTY));
+                self.insert(Fragment::ZeroNotEqual(c.boxed()), Some(sat), dissat);
+                let f = Box::new(Miniscript::new(self.ctx, Fragment::False).expect("0 is valid"));
+                self.insert(Fragment::OrI(f.clone(), c.boxed()), Some(sat + EMPTY), Some(ONE));
+                self.insert(Fragment::OrI(c.boxed(), f), Some(sat + ONE), Some(EMPTY));
+                changed |= self.candidates.len() != len;
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    fn insert_and(&mut self, xs: &[Candidate<K>], ys: &[Candidate<K>]) {
+        let f = Box::new(Miniscript::new(self.ctx, Fragment::False).expect("0 is valid"));
+        for x in xs {
+            for y in ys {
+                let sat = Some(x.sat + y.sat);
+                self.insert(Fragment::AndV(x.boxed(), y.boxed()), sat, None);
+                let dissat = x.dissat.zip(y.dissat).map(|(dx, dy)| dx + dy);
+                self.insert(Fragment::AndB(x.boxed(), y.boxed()), sat, dissat);
+                self.insert(Fragment::AndOr(x.boxed(), y.boxed(), f.clone()), sat, x.dissat);
+            }
+        }
+    }
+
+    fn insert_or(&mut self, xs: &[Candidate<K>], zs: &[Candidate<K>], px: f64) {
+        let pz = 1.0 - px;
+        for x in xs {
+            for z in zs {
+                let both_dissat = x.dissat.zip(z.dissat).map(|(dx, dz)| dx + dz);
+                let sat_b = x.dissat.zip(z.dissat).map(|(dx, dz)| {
+                    px * (x.sat + dz) + pz * (dx + z.sat) //
+                });
+                self.insert(Fragment::OrB(x.boxed(), z.boxed()), sat_b, both_dissat);
+                let sat_d = x.dissat.map(|dx| px * x.sat + pz * (dx + z.sat));
+                self.insert(Fragment::OrD(x.boxed(), z.boxed()), sat_d, both_dissat);
+                self.insert(Fragment::OrC(x.boxed(), z.boxed()), sat_d, None);
+                let sat_i = px * (x.sat + ONE) + pz * (z.sat + EMPTY);
+                let dissat_i = match (x.dissat, z.dissat) {
+                    (Some(dx), Some(dz)) => Some((dx + ONE).min(dz + EMPTY)),
+                    (Some(dx), None) => Some(dx + ONE),
+                    (None, Some(dz)) => Some(dz + EMPTY),
+                    (None, None) => None,
+                };
+                self.insert(Fragment::OrI(x.boxed(), z.boxed()), Some(sat_i), dissat_i);
+            }
+        }
+    }
+
+    fn insert_thresh(&mut self, k: u16, subs: Vec<Vec<Candidate<K>>>) {
+        let cheapest = |cands: &[Candidate<K>], base: BaseType| {
+            cands
+                .iter()
+                .filter(|c| c.ms.ty().base == base && c.ms.ty().unit && c.dissat.is_some())
+                .min_by(|a, b| a.cost().total_cmp(&b.cost()))
+                .cloned()
+        };
+        let Some(chosen) = subs
+            .iter()
+            .enumerate()
+            .map(|(no, cands)| cheapest(cands, if no == 0 { BaseType::B } else { BaseType::W }))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return;
+        };
+        let dissat = chosen.iter().filter_map(|c| c.dissat).sum::<f64>();
+        let mut extra =
+            chosen.iter().filter_map(|c| c.dissat.map(|d| c.sat - d)).collect::<Vec<_>>();
+        extra.sort_by(f64::total_cmp);
+        let sat = dissat + extra.into_iter().take(k as usize).sum::<f64>();
+        let subs = chosen.into_iter().map(|c| c.ms).collect();
+        self.insert(Fragment::Thresh(k, subs), Some(sat), Some(dissat));
+    }
+}
+
+impl<K: Display> Display for Policy<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Policy::Key(key) => write!(f, "pk({key})"),
+            Policy::After(n) => write!(f, "after({n})"),
+            Policy::Older(n) => write!(f, "older({n})"),
+            Policy::Sha256(h) => write!(f, "sha256({})", h.to_hex()),
+            Policy::Hash256(h) => write!(f, "hash256({})", h.to_hex()),
+            Policy::Ripemd160(h) => write!(f, "ripemd160({})", h.to_hex()),
+            Policy::Hash160(h) => write!(f, "hash160({})", h.to_hex()),
+            Policy::And(x, y) => write!(f, "and({x},{y})"),
+            Policy::Or((wx, x), (wy, y)) => {
+                f.write_str("or(")?;
+                if *wx != 1 {
+                    write!(f, "{wx}@")?;
+                }
+                write!(f, "{x},")?;
+                if *wy != 1 {
+                    write!(f, "{wy}@")?;
+                }
+                write!(f, "{y})")
+            }
+            Policy::Thresh(k, subs) => {
+                write!(f, "thresh({k}")?;
+                for sub in subs {
+                    write!(f, ",{sub}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl<K: FromStr> FromStr for Policy<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || MiniscriptError::InvalidArgs(s.to_owned());
+        let (name, rest) = s.split_once('(').ok_or_else(invalid)?;
+        let args = split_args(rest.strip_suffix(')').ok_or_else(invalid)?)?
+            .into_iter()
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let arity = match name {
+            "pk" | "after" | "older" | "sha256" | "hash256" | "ripemd160" | "hash160" => 1,
+            "and" | "or" => 2,
+            "thresh" => args.len().max(2),
+            _ => return Err(MiniscriptError::UnknownFragment(name.to_owned()).into()),
+        };
+        if args.len() != arity {
+            return Err(invalid().into());
+        }
+        let num = || u32::from_str(args[0]).map_err(|_| invalid());
+        let hash32 = || <[u8; 32]>::from_hex(args[0]).map_err(|_| invalid());
+        let hash20 = || <[u8; 20]>::from_hex(args[0]).map_err(|_| invalid());
+        let sub = |s: &str| Policy::from_str(s).map(Box::new);
+        let weighted = |s: &str| -> Result<(u32, Box<Policy<K>>), DescrParseError> {
+            match s.split_once('@') {
+                Some((weight, policy)) if !weight.contains('(') => {
+                    let weight = u32::from_str(weight.trim()).map_err(|_| invalid())?;
+                    if weight == 0 {
+                        return Err(invalid().into());
+                    }
+                    Ok((weight, sub(policy)?))
+                }
+                _ => Ok((1, sub(s)?)),
+            }
+        };
+        Ok(match name {
+            "pk" => Policy::Key(K::from_str(args[0])?),
+            "after" => Policy::After(num()?),
+            "older" => Policy::Older(num()?),
+            "sha256" => Policy::Sha256(hash32()?),
+            "hash256" => Policy::Hash256(hash32()?),
+            "ripemd160" => Policy::Ripemd160(hash20()?),
+            "hash160" => Policy::Hash160(hash20()?),
+            "and" => Policy::And(sub(args[0])?, sub(args[1])?),
+            "or" => Policy::Or(weighted(args[0])?, weighted(args[1])?),
+            "thresh" => {
+                let k = u16::from_str(args[0]).map_err(|_| invalid())?;
+                let subs =
+                    args[1..].iter().map(|s| Policy::from_str(s)).collect::<Result<Vec<_>, _>>()?;
+                if k == 0 || k as usize > subs.len() {
+                    return Err(invalid().into());
+                }
+                Policy::Thresh(k, subs)
+            }
+            _ => unreachable!("unknown policies are filtered above"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::XpubDerivable;
+
+    use super::*;
+
+    const KEY1: &str = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+    const KEY2: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+
+    fn keys(s: &str) -> String { s.replace("@A", KEY1).replace("@B", KEY2) }
+
+    fn compile(policy: &str, ctx: MsCtx) -> Result<String, DescrParseError> {
+        let policy = Policy::<XpubDerivable>::from_str(&keys(policy))?;
+        Ok(policy.compile(ctx)?.to_string())
+    }
+
+    #[test]
+    fn policy_roundtrip() {
+        for s in [
+            "pk(@A)",
+            "and(pk(@A),older(144))",
+            "or(9@pk(@A),and(pk(@B),after(500000)))",
+            "thresh(2,pk(@A),pk(@B),older(144))",
+        ] {
+            let policy = Policy::<XpubDerivable>::from_str(&keys(s)).unwrap();
+            assert_eq!(policy.to_string(), keys(s));
+        }
+        let policy = Policy::<XpubDerivable>::from_str(&keys("thresh(2, pk(@A), pk(@B))")).unwrap();
+        assert_eq!(policy.keys().len(), 2);
+        assert!(Policy::<XpubDerivable>::from_str(&keys("thresh(3,pk(@A),pk(@B))")).is_err());
+        assert!(Policy::<XpubDerivable>::from_str(&keys("multi(1,@A,@B)")).is_err());
+    }
+
+    #[test]
+    fn compile_simple() {
+        assert_eq!(compile("pk(@A)", MsCtx::Segwit).unwrap(), keys("pk(@A)"));
+        assert_eq!(
+            compile("and(pk(@A),older(144))", MsCtx::Segwit).unwrap(),
+            keys("and_v(v:pk(@A),older(144))")
+        );
+        assert_eq!(
+            compile("thresh(1,pk(@A),pk(@B))", MsCtx::Segwit).unwrap(),
+            keys("multi(1,@A,@B)")
+        );
+        assert_eq!(
+            compile("thresh(2,pk(@A),pk(@B))", MsCtx::Tapscript).unwrap(),
+            keys("and_v(v:pk(@A),pk(@B))")
+        );
+        assert!(matches!(
+            compile("older(144)", MsCtx::Segwit),
+            Err(DescrParseError::Miniscript(MiniscriptError::Uncompilable))
+        ));
+    }
+
+    #[test]
+    fn compile_complex() {
+        assert_eq!(
+            compile("or(pk(@A),pk(@B))", MsCtx::Segwit).unwrap(),
+            keys("or_b(pk(@A),s:pk(@B))")
+        );
+        assert_eq!(
+            compile("thresh(2,pk(@A),pk(@B),older(144))", MsCtx::Segwit).unwrap(),
+            keys("thresh(2,pk(@A),s:pk(@B),sln:older(144))")
+        );
+
+        for (policy, ctx) in [
+            ("or(pk(@A),pk(@B))", MsCtx::Segwit),
+            ("thresh(2,pk(@A),pk(@B),older(144))", MsCtx::Segwit),
+            ("or(99@pk(@A),and(pk(@B),older(1008)))", MsCtx::Segwit),
+            (
+                "or(pk(@A),and(pk(@B),\
+                 sha256(0000000000000000000000000000000000000000000000000000000000000000)))",
+                MsCtx::Tapscript,
+            ),
+        ] {
+            let ms = compile(policy, ctx).unwrap();
+            let parsed = Miniscript::<XpubDerivable>::parse(&ms, ctx).unwrap();
+            assert_eq!(parsed.keys().len(), 2);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimation of the maximum size of miniscript satisfactions.
+
+use super::{Fragment, Miniscript, MsCtx};
+
+/// Witness size of a `1` stack element used to select a branch.
+const ONE: usize = 2;
+/// Witness size of an empty stack element.
+const EMPTY: usize = 1;
+/// Witness size of a hash preimage (or a wrong preimage used for dissatisfaction).
+const PREIMAGE: usize = 33;
+
+/// Maximum witness size of a signature with its sighash type byte.
+const fn sig_size(ctx: MsCtx) -> usize {
+    match ctx {
+        MsCtx::Segwit => 1 + 73,
+        MsCtx::Tapscript => 1 + 65,
+    }
+}
+
+/// Witness size of a public key.
+const fn key_size(ctx: MsCtx) -> usize {
+    match ctx {
+        MsCtx::Segwit => 1 + 33,
+        MsCtx::Tapscript => 1 + 32,
+    }
+}
+
+fn sum(a: Option<usize>, b: Option<usize>) -> Option<usize> { a.zip(b).map(|(a, b)| a + b) }
+
+fn max(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+impl<K> Miniscript<K> {
+    /// Maximum size of the witness stack elements satisfying the miniscript, including the
+    /// length prefix of each of the elements, but excluding the witness script itself.
+    ///
+    /// Returns `None` if the miniscript can't be satisfied.
+    pub fn max_satisfaction_size(&self) -> Option<usize> { self.max_sizes().0 }
+
+    /// Maximum sizes of the satisfaction and the dissatisfaction, if each of them is possible.
+    fn max_sizes(&self) -> (Option<usize>, Option<usize>) {
+        let ctx = self.ctx();
+        match self.node() {
+            Fragment::False => (None, Some(0)),
+            Fragment::True => (Some(0), None),
+            Fragment::PkK(_) => (Some(sig_size(ctx)), Some(EMPTY)),
+            Fragment::PkH(_) => (Some(sig_size(ctx) + key_size(ctx)), Some(EMPTY + key_size(ctx))),
+            Fragment::Older(_) | Fragment::After(_) => (Some(0), None),
+            Fragment::Sha256(_)
+            | Fragment::Hash256(_)
+            | Fragment::Ripemd160(_)
+            | Fragment::Hash160(_) => (Some(PREIMAGE), Some(PREIMAGE)),
+            Fragment::Alt(x)
+            | Fragment::Swap(x)
+            | Fragment::Check(x)
+            | Fragment::ZeroNotEqual(x) => x.max_sizes(),
+            Fragment::DupIf(x) => (x.max_sizes().0.map(|s| s + ONE), Some(EMPTY)),
+            Fragment::Verify(x) => (x.max_sizes().0, None),
+            Fragment::NonZero(x) => (x.max_sizes().0, Some(EMPTY)),
+            Fragment::AndV(x, y) => {
+                let ((xs, _), (ys, yd)) = (x.max_sizes(), y.max_sizes());
+                (sum(xs, ys), sum(xs, yd))
+            }
+            Fragment::AndB(x, y) => {
+                let ((xs, xd), (ys, yd)) = (x.max_sizes(), y.max_sizes());
+                (sum(xs, ys), sum(xd, yd))
+            }
+            Fragment::AndOr(x, y, z) => {
+                let ((xs, xd), (ys, _), (zs, zd)) = (x.max_sizes(), y.max_sizes(), z.max_sizes());
+                (max(sum(xs, ys), sum(xd, zs)), sum(xd, zd))
+            }
+            Fragment::OrB(x, z) => {
+                let ((xs, xd), (zs, zd)) = (x.max_sizes(), z.max_sizes());
+                (max(sum(xs, zd), sum(xd, zs)), sum(xd, zd))
+            }
+            Fragment::OrC(x, z) => {
+                let ((xs, xd), (zs, _)) = (x.max_sizes(), z.max_sizes());
+                (max(xs, sum(xd, zs)), None)
+            }
+            Fragment::OrD(x, z) => {
+                let ((xs, xd), (zs, zd)) = (x.max_sizes(), z.max_sizes());
+                (max(xs, sum(xd, zs)), sum(xd, zd))
+            }
+            Fragment::OrI(x, z) => {
+                let ((xs, xd), (zs, zd)) = (x.max_sizes(), z.max_sizes());
+                let one = |s: Option<usize>| s.map(|s| s + ONE);
+                let empty = |s: Option<usize>| s.map(|s| s + EMPTY);
+                (max(one(xs), empty(zs)), max(one(xd), empty(zd)))
+            }
+            Fragment::Thresh(k, subs) => {
+                let sizes = subs.iter().map(Miniscript::max_sizes).collect::<Vec<_>>();
+                let dissat = sizes.iter().map(|(_, d)| *d).sum::<Option<usize>>();
+                let mut must = 0usize;
+                let mut base = 0usize;
+                let mut extra = Vec::with_capacity(sizes.len());
+                for (sat, dissat) in sizes {
+                    match (sat, dissat) {
+                        (Some(s), None) => {
+                            must += 1;
+                            base += s;
+                        }
+                        (Some(s), Some(d)) => {
+                            base += d;
+                            extra.push(s as isize - d as isize);
+                        }
+                        (None, Some(d)) => base += d,
+                        (None, None) => return (None, None),
+                    }
+                }
+                let rest = (*k as usize).checked_sub(must);
+                extra.sort_unstable_by(|a, b| b.cmp(a));
+                let sat = rest.filter(|rest| *rest <= extra.len()).map(|rest| {
+                    (base as isize + extra.into_iter().take(rest).sum::<isize>()) as usize
+                });
+                (sat, dissat)
+            }
+            Fragment::Multi(k, _) => {
+                let k = *k as usize;
+                (Some(EMPTY + k * sig_size(ctx)), Some(EMPTY + k * EMPTY))
+            }
+            Fragment::MultiA(k, keys) => {
+                let (k, n) = (*k as usize, keys.len());
+                (Some(k * sig_size(ctx) + (n - k) * EMPTY), Some(n * EMPTY))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::XpubDerivable;
+
+    use super::*;
+
+    const KEY1: &str = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+    const KEY2: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+
+    fn size(s: &str, ctx: MsCtx) -> Option<usize> {
+        let s = s.replace("@A", KEY1).replace("@B", KEY2);
+        Miniscript::<XpubDerivable>::parse_expr(&s, ctx).unwrap().max_satisfaction_size()
+    }
+
+    #[test]
+    fn single_key() {
+        assert_eq!(size("pk(@A)", MsCtx::Segwit), Some(74));
+        assert_eq!(size("pk(@A)", MsCtx::Tapscript), Some(66));
+        assert_eq!(size("pkh(@A)", MsCtx::Segwit), Some(74 + 34));
+        assert_eq!(size("0", MsCtx::Segwit), None);
+    }
+
+    #[test]
+    fn multisig() {
+        assert_eq!(size("multi(2,@A,@B)", MsCtx::Segwit), Some(1 + 2 * 74));
+        assert_eq!(size("multi_a(1,@A,@B)", MsCtx::Tapscript), Some(66 + 1));
+    }
+
+    #[test]
+    fn branches() {
+        // dissatisfying the first key and satisfying the second branch is the most expensive
+        assert_eq!(
+            size("or_d(pk(@A),and_v(v:pkh(@B),after(500000)))", MsCtx::Segwit),
+            Some(1 + 108)
+        );
+        assert_eq!(size("and_v(v:pk(@A),older(144))", MsCtx::Segwit), Some(74));
+        assert_eq!(size("andor(pk(@A),older(1008),pk(@B))", MsCtx::Segwit), Some(1 + 74));
+        assert_eq!(size("thresh(2,pk(@A),s:pk(@B),sln:older(12960))", MsCtx::Segwit), Some(150));
+    }
+}
@@ -0,0 +1,179 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timelock requirements of miniscript spending paths (`after` and `older` fragments).
+
+use std::fmt::{self, Display, Formatter};
+
+use derive::{LockTime, SeqNo, LOCKTIME_THRESHOLD, SEQ_NO_CSV_DISABLE_MASK, SEQ_NO_CSV_TYPE_MASK};
+
+use super::{Fragment, Miniscript};
+
+/// Timelock which must be met by a transaction spending a script path.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Timelock {
+    /// Absolute timelock (`OP_CHECKLOCKTIMEVERIFY`, miniscript `after`) which must be met by
+    /// the transaction lock time.
+    Absolute(LockTime),
+
+    /// Relative timelock (`OP_CHECKSEQUENCEVERIFY`, miniscript `older`) which must be met by
+    /// the sequence number of the spending input.
+    Relative(SeqNo),
+}
+
+impl Timelock {
+    /// Constructs timelock from the argument of miniscript `after` fragment.
+    pub const fn after(n: u32) -> Self { Timelock::Absolute(LockTime::from_consensus_u32(n)) }
+
+    /// Constructs timelock from the argument of miniscript `older` fragment.
+    pub const fn older(n: u32) -> Self { Timelock::Relative(SeqNo::from_consensus_u32(n)) }
+
+    pub const fn is_absolute(self) -> bool { matches!(self, Timelock::Absolute(_)) }
+
+    pub const fn is_relative(self) -> bool { matches!(self, Timelock::Relative(_)) }
+
+    /// Lock time which must be set for the spending transaction, if the timelock is absolute.
+    pub const fn lock_time(self) -> Option<LockTime> {
+        match self {
+            Timelock::Absolute(lock_time) => Some(lock_time),
+            Timelock::Relative(_) => None,
+        }
+    }
+
+    /// Sequence number which must be set for the spending input, if the timelock is relative.
+    pub const fn seq_no(self) -> Option<SeqNo> {
+        match self {
+            Timelock::Absolute(_) => None,
+            Timelock::Relative(seq_no) => Some(seq_no),
+        }
+    }
+
+    /// Checks whether a transaction with the given lock time, spending an input with the given
+    /// sequence number, passes the timelock check, following the consensus rules of
+    /// BIP-65 and BIP-112.
+    ///
+    /// This doesn't check that the transaction is actually mined at the required height or
+    /// time.
+    pub fn is_satisfied_by(self, lock_time: LockTime, seq_no: SeqNo) -> bool {
+        match self {
+            Timelock::Absolute(required) => {
+                let required = required.to_consensus_u32();
+                let actual = lock_time.to_consensus_u32();
+                (required < LOCKTIME_THRESHOLD) == (actual < LOCKTIME_THRESHOLD)
+                    && required <= actual
+                    && seq_no.to_consensus_u32() != u32::MAX
+            }
+            Timelock::Relative(required) => {
+                let required = required.to_consensus_u32();
+                let actual = seq_no.to_consensus_u32();
+                actual & SEQ_NO_CSV_DISABLE_MASK == 0
+                    && required & SEQ_NO_CSV_TYPE_MASK == actual & SEQ_NO_CSV_TYPE_MASK
+                    && required & 0xFFFF <= actual & 0xFFFF
+            }
+        }
+    }
+
+    /// Checks whether both timelocks are measured in the same units (block height or time)
+    /// and thus can be satisfied by the same transaction.
+    pub fn is_compatible(self, other: Timelock) -> bool {
+        match (self, other) {
+            (Timelock::Absolute(a), Timelock::Absolute(b)) => {
+                a.is_height_based() == b.is_height_based()
+            }
+            (Timelock::Relative(a), Timelock::Relative(b)) => {
+                a.to_consensus_u32() & SEQ_NO_CSV_TYPE_MASK
+                    == b.to_consensus_u32() & SEQ_NO_CSV_TYPE_MASK
+            }
+            _ => true,
+        }
+    }
+}
+
+impl Display for Timelock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Timelock::Absolute(lock_time) => write!(f, "after({})", lock_time.to_consensus_u32()),
+            Timelock::Relative(seq_no) => write!(f, "older({})", seq_no.to_consensus_u32()),
+        }
+    }
+}
+
+impl<K> Miniscript<K> {
+    /// All timelocks used by the miniscript, in the order of their appearance.
+    ///
+    /// Each of the timelocks has to be met only if the spending path containing it is used
+    /// for the satisfaction.
+    pub fn timelocks(&self) -> Vec<Timelock> {
+        match self.node() {
+            Fragment::After(n) => vec![Timelock::after(*n)],
+            Fragment::Older(n) => vec![Timelock::older(*n)],
+            _ => self.subs().into_iter().flat_map(Miniscript::timelocks).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::XpubDerivable;
+
+    use super::*;
+    use crate::MsCtx;
+
+    const KEY: &str = "[643a7adc/84h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+
+    #[test]
+    fn timelocks() {
+        let s = "or_d(pk(@A),and_v(v:pkh(@A),andor(pk(@A),older(144),after(800000))))";
+        let ms =
+            Miniscript::<XpubDerivable>::parse_expr(&s.replace("@A", KEY), MsCtx::Segwit).unwrap();
+        assert_eq!(ms.timelocks(), vec![Timelock::older(144), Timelock::after(800_000)]);
+        assert_eq!(ms.timelocks()[0].to_string(), "older(144)");
+    }
+
+    #[test]
+    fn satisfaction() {
+        let rbf = SeqNo::from_consensus_u32(0xFFFF_FFFD);
+        let final_seq = SeqNo::from_consensus_u32(0xFFFF_FFFF);
+        let height = |h| LockTime::from_height(h).unwrap();
+
+        let after = Timelock::after(800_000);
+        assert!(after.is_satisfied_by(height(800_000), rbf));
+        assert!(!after.is_satisfied_by(height(799_999), rbf));
+        assert!(!after.is_satisfied_by(height(800_000), final_seq));
+        assert!(!after.is_satisfied_by(LockTime::from_unix_timestamp(1_700_000_000).unwrap(), rbf));
+
+        let older = Timelock::older(144);
+        assert!(older.is_satisfied_by(LockTime::ZERO, SeqNo::from_height(144)));
+        assert!(!older.is_satisfied_by(LockTime::ZERO, SeqNo::from_height(143)));
+        assert!(!older.is_satisfied_by(LockTime::ZERO, SeqNo::from_intervals(144)));
+        assert!(!older.is_satisfied_by(LockTime::ZERO, rbf));
+
+        assert!(older.is_compatible(after));
+        assert!(!older.is_compatible(Timelock::Relative(SeqNo::from_intervals(1))));
+        assert!(!after.is_compatible(Timelock::after(1_700_000_000)));
+    }
+}
@@ -0,0 +1,451 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Miniscript type system, covering both correctness and malleability properties of the
+//! expressions.
+
+use super::{Fragment, MiniscriptError, MsCtx};
+
+/// Basic miniscript expression type.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+pub enum BaseType {
+    /// Base expression, pushing a non-zero value on success and an exact zero on failure.
+    #[display("B")]
+    B,
+
+    /// Verify expression, which doesn't push anything on success and aborts on failure.
+    #[display("V")]
+    V,
+
+    /// Key expression, pushing a public key for which a signature is to be checked.
+    #[display("K")]
+    K,
+
+    /// Wrapped expression, taking its input from one below the top of the stack.
+    #[display("W")]
+    W,
+}
+
+/// Information about dissatisfactions of an expression.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum Dissat {
+    /// Expression can't be dissatisfied.
+    None,
+
+    /// Expression has a single dissatisfaction which doesn't require a signature.
+    Unique,
+
+    /// Expression may have multiple dissatisfactions.
+    Unknown,
+}
+
+/// Type of miniscript expression.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Type {
+    pub base: BaseType,
+    /// `z`: consumes exactly 0 stack elements.
+    pub zero_arg: bool,
+    /// `o`: consumes exactly 1 stack element.
+    pub one_arg: bool,
+    /// `n`: the top stack element is never zero for a satisfaction.
+    pub nonzero: bool,
+    /// `d`: has a dissatisfaction not requiring a signature.
+    pub dissatisfiable: bool,
+    /// `u`: pushes exactly 1 on satisfaction (when putting anything on the stack).
+    pub unit: bool,
+    pub dissat: Dissat,
+    /// `s`: satisfaction always requires a signature.
+    pub safe: bool,
+    /// `m`: has a non-malleable satisfaction.
+    pub non_malleable: bool,
+}
+
+impl Type {
+    const fn leaf(base: BaseType) -> Self {
+        Type {
+            base,
+            zero_arg: false,
+            one_arg: false,
+            nonzero: false,
+            dissatisfiable: false,
+            unit: false,
+            dissat: Dissat::Unknown,
+            safe: false,
+            non_malleable: true,
+        }
+    }
+
+    /// Computes type of a fragment out of the types of its sub-expressions.
+    pub fn with_fragment<K>(node: &Fragment<K>, ctx: MsCtx) -> Result<Self, MiniscriptError> {
+        use BaseType::*;
+
+        let name = node.name();
+        let fail = |reason: &'static str| Err(MiniscriptError::TypeCheck(name, reason));
+
+        Ok(match node {
+            Fragment::False => Type {
+                zero_arg: true,
+                dissatisfiable: true,
+                unit: true,
+                dissat: Dissat::Unique,
+                safe: true,
+                ..Self::leaf(B)
+            },
+            Fragment::True => Type {
+                zero_arg: true,
+                unit: true,
+                dissat: Dissat::None,
+                ..Self::leaf(B)
+            },
+            Fragment::PkK(_) => Type {
+                one_arg: true,
+                nonzero: true,
+                dissatisfiable: true,
+                unit: true,
+                dissat: Dissat::Unique,
+                safe: true,
+                ..Self::leaf(K)
+            },
+            Fragment::PkH(_) => Type {
+                nonzero: true,
+                dissatisfiable: true,
+                unit: true,
+                dissat: Dissat::Unique,
+                safe: true,
+                ..Self::leaf(K)
+            },
+            Fragment::Older(n) | Fragment::After(n) => {
+                if *n == 0 || *n >= 0x8000_0000 {
+                    return fail("timelock value must be in range 1..2^31");
+                }
+                Type {
+                    zero_arg: true,
+                    dissat: Dissat::None,
+                    ..Self::leaf(B)
+                }
+            }
+            Fragment::Sha256(_)
+            | Fragment::Hash256(_)
+            | Fragment::Ripemd160(_)
+            | Fragment::Hash160(_) => Type {
+                one_arg: true,
+                nonzero: true,
+                dissatisfiable: true,
+                unit: true,
+                ..Self::leaf(B)
+            },
+            Fragment::Multi(k, keys) | Fragment::MultiA(k, keys) => {
+                match (node, ctx) {
+                    (Fragment::Multi(..), MsCtx::Tapscript)
+                    | (Fragment::MultiA(..), MsCtx::Segwit) => {
+                        return Err(MiniscriptError::ContextMismatch(name, ctx));
+                    }
+                    _ => {}
+                }
+                let max = if matches!(node, Fragment::Multi(..)) { 20 } else { 999 };
+                if *k == 0 || *k as usize > keys.len() || keys.len() > max {
+                    return fail("invalid threshold or number of keys");
+                }
+                Type {
+                    nonzero: matches!(node, Fragment::Multi(..)),
+                    dissatisfiable: true,
+                    unit: true,
+                    dissat: Dissat::Unique,
+                    safe: true,
+                    ..Self::leaf(B)
+                }
+            }
+
+            Fragment::Alt(x) | Fragment::Swap(x) => {
+                let x = x.ty;
+                if x.base != B {
+                    return fail("argument must have B type");
+                }
+                if matches!(node, Fragment::Swap(_)) && !x.one_arg {
+                    return fail("argument must consume exactly one stack element");
+                }
+                Type {
+                    base: W,
+                    zero_arg: false,
+                    one_arg: false,
+                    nonzero: false,
+                    ..x
+                }
+            }
+            Fragment::Check(x) => {
+                let x = x.ty;
+                if x.base != K {
+                    return fail("argument must have K type");
+                }
+                Type {
+                    base: B,
+                    unit: true,
+                    safe: true,
+                    ..x
+                }
+            }
+            Fragment::DupIf(x) => {
+                let x = x.ty;
+                if x.base != V || !x.zero_arg {
+                    return fail("argument must have V type and consume no stack elements");
+                }
+                Type {
+                    base: B,
+                    zero_arg: false,
+                    one_arg: true,
+                    nonzero: true,
+                    dissatisfiable: true,
+                    unit: ctx == MsCtx::Tapscript,
+                    dissat: Dissat::Unique,
+                    ..x
+                }
+            }
+            Fragment::Verify(x) => {
+                let x = x.ty;
+                if x.base != B {
+                    return fail("argument must have B type");
+                }
+                Type {
+                    base: V,
+                    dissatisfiable: false,
+                    unit: false,
+                    dissat: Dissat::None,
+                    ..x
+                }
+            }
+            Fragment::NonZero(x) => {
+                let x = x.ty;
+                if x.base != B || !x.nonzero {
+                    return fail("argument must have B type and non-zero top stack element");
+                }
+                Type {
+                    zero_arg: false,
+                    nonzero: true,
+                    dissatisfiable: true,
+                    dissat: match x.dissat {
+                        Dissat::None => Dissat::Unique,
+                        _ => Dissat::Unknown,
+                    },
+                    ..x
+                }
+            }
+            Fragment::ZeroNotEqual(x) => {
+                let x = x.ty;
+                if x.base != B {
+                    return fail("argument must have B type");
+                }
+                Type { unit: true, ..x }
+            }
+
+            Fragment::AndV(x, y) => {
+                let (x, y) = (x.ty, y.ty);
+                if x.base != V || y.base == W {
+                    return fail("arguments must have V and B, K or V types");
+                }
+                Type {
+                    base: y.base,
+                    zero_arg: x.zero_arg && y.zero_arg,
+                    one_arg: (x.zero_arg && y.one_arg) || (x.one_arg && y.zero_arg),
+                    nonzero: x.nonzero || (x.zero_arg && y.nonzero),
+                    dissatisfiable: false,
+                    unit: y.unit,
+                    dissat: match y.dissat {
+                        Dissat::None => Dissat::None,
+                        _ if x.safe => Dissat::Unique,
+                        _ => Dissat::Unknown,
+                    },
+                    safe: x.safe || y.safe,
+                    non_malleable: x.non_malleable && y.non_malleable,
+                }
+            }
+            Fragment::AndB(x, y) => {
+                let (x, y) = (x.ty, y.ty);
+                if x.base != B || y.base != W {
+                    return fail("arguments must have B and W types");
+                }
+                Type {
+                    base: B,
+                    zero_arg: x.zero_arg && y.zero_arg,
+                    one_arg: (x.zero_arg && y.one_arg) || (x.one_arg && y.zero_arg),
+                    nonzero: x.nonzero || (x.zero_arg && y.nonzero),
+                    dissatisfiable: x.dissatisfiable && y.dissatisfiable,
+                    unit: true,
+                    dissat: match (x.dissat, y.dissat) {
+                        (Dissat::None, Dissat::None) => Dissat::None,
+                        (Dissat::Unique, Dissat::Unique) if x.safe && y.safe => Dissat::Unique,
+                        _ => Dissat::Unknown,
+                    },
+                    safe: x.safe || y.safe,
+                    non_malleable: x.non_malleable && y.non_malleable,
+                }
+            }
+            Fragment::AndOr(x, y, z) => {
+                let (x, y, z) = (x.ty, y.ty, z.ty);
+                if x.base != B || !x.dissatisfiable || !x.unit {
+                    return fail("first argument must have B type and be dissatisfiable unit");
+                }
+                if y.base != z.base || y.base == W {
+                    return fail("second and third arguments must have the same B, K or V type");
+                }
+                Type {
+                    base: y.base,
+                    zero_arg: x.zero_arg && y.zero_arg && z.zero_arg,
+                    one_arg: (x.zero_arg && y.one_arg && z.one_arg)
+                        || (x.one_arg && y.zero_arg && z.zero_arg),
+                    nonzero: false,
+                    dissatisfiable: z.dissatisfiable,
+                    unit: y.unit && z.unit,
+                    dissat: match z.dissat {
+                        Dissat::None => Dissat::None,
+                        _ if x.safe || y.safe => z.dissat,
+                        _ => Dissat::Unknown,
+                    },
+                    safe: (x.safe || y.safe) && z.safe,
+                    non_malleable: x.non_malleable
+                        && y.non_malleable
+                        && z.non_malleable
+                        && x.dissat == Dissat::Unique
+                        && (x.safe || y.safe || z.safe),
+                }
+            }
+            Fragment::OrB(x, z) => {
+                let (x, z) = (x.ty, z.ty);
+                if x.base != B || !x.dissatisfiable || z.base != W || !z.dissatisfiable {
+                    return fail("arguments must be dissatisfiable and have B and W types");
+                }
+                Type {
+                    base: B,
+                    zero_arg: x.zero_arg && z.zero_arg,
+                    one_arg: (x.zero_arg && z.one_arg) || (x.one_arg && z.zero_arg),
+                    nonzero: false,
+                    dissatisfiable: true,
+                    unit: true,
+                    dissat: match (x.dissat, z.dissat) {
+                        (Dissat::Unique, Dissat::Unique) => Dissat::Unique,
+                        _ => Dissat::Unknown,
+                    },
+                    safe: x.safe && z.safe,
+                    non_malleable: x.non_malleable
+                        && z.non_malleable
+                        && x.dissat == Dissat::Unique
+                        && z.dissat == Dissat::Unique
+                        && (x.safe || z.safe),
+                }
+            }
+            Fragment::OrC(x, z) | Fragment::OrD(x, z) => {
+                let (x, z) = (x.ty, z.ty);
+                let or_d = matches!(node, Fragment::OrD(..));
+                if x.base != B || !x.dissatisfiable || !x.unit {
+                    return fail("first argument must have B type and be dissatisfiable unit");
+                }
+                if (or_d && z.base != B) || (!or_d && z.base != V) {
+                    return fail("second argument has invalid type");
+                }
+                Type {
+                    base: z.base,
+                    zero_arg: x.zero_arg && z.zero_arg,
+                    one_arg: x.one_arg && z.zero_arg,
+                    nonzero: false,
+                    dissatisfiable: or_d && z.dissatisfiable,
+                    unit: or_d && z.unit,
+                    dissat: match (or_d, x.dissat, z.dissat) {
+                        (false, ..) => Dissat::None,
+                        (true, Dissat::Unique, Dissat::Unique) => Dissat::Unique,
+                        (true, _, Dissat::None) => Dissat::None,
+                        _ => Dissat::Unknown,
+                    },
+                    safe: x.safe && z.safe,
+                    non_malleable: x.non_malleable
+                        && z.non_malleable
+                        && x.dissat == Dissat::Unique
+                        && (x.safe || z.safe),
+                }
+            }
+            Fragment::OrI(x, z) => {
+                let (x, z) = (x.ty, z.ty);
+                if x.base != z.base || x.base == W {
+                    return fail("arguments must have the same B, K or V type");
+                }
+                Type {
+                    base: x.base,
+                    zero_arg: false,
+                    one_arg: x.zero_arg && z.zero_arg,
+                    nonzero: false,
+                    dissatisfiable: x.dissatisfiable || z.dissatisfiable,
+                    unit: x.unit && z.unit,
+                    dissat: match (x.dissat, z.dissat) {
+                        (Dissat::None, Dissat::None) => Dissat::None,
+                        (Dissat::Unique, Dissat::None) | (Dissat::None, Dissat::Unique) => {
+                            Dissat::Unique
+                        }
+                        _ => Dissat::Unknown,
+                    },
+                    safe: x.safe && z.safe,
+                    non_malleable: x.non_malleable && z.non_malleable && (x.safe || z.safe),
+                }
+            }
+            Fragment::Thresh(k, subs) => {
+                let k = *k as usize;
+                if k == 0 || k > subs.len() {
+                    return fail("threshold must be between 1 and the number of sub-expressions");
+                }
+                let mut args = 0usize;
+                let mut all_zero = true;
+                let mut safe_count = 0usize;
+                let mut non_malleable = true;
+                for (no, sub) in subs.iter().enumerate() {
+                    let ty = sub.ty;
+                    let expected = if no == 0 { B } else { W };
+                    if ty.base != expected || !ty.dissatisfiable || !ty.unit {
+                        return fail(
+                            "first argument must be Bdu and the rest of the arguments must be Wdu",
+                        );
+                    }
+                    if !ty.zero_arg {
+                        all_zero = false;
+                        args += if ty.one_arg { 1 } else { 2 };
+                    }
+                    if ty.safe {
+                        safe_count += 1;
+                    }
+                    non_malleable &= ty.non_malleable && ty.dissat == Dissat::Unique;
+                }
+                let n = subs.len();
+                Type {
+                    base: B,
+                    zero_arg: all_zero,
+                    one_arg: args == 1 && subs.iter().all(|sub| sub.ty.zero_arg || sub.ty.one_arg),
+                    nonzero: false,
+                    dissatisfiable: true,
+                    unit: true,
+                    dissat: if safe_count == n { Dissat::Unique } else { Dissat::Unknown },
+                    safe: safe_count > n - k,
+                    non_malleable: non_malleable && safe_count >= n - k,
+                }
+            }
+        })
+    }
+}
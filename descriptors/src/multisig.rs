@@ -19,3 +19,464 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use derive::opcodes::{OP_CHECKMULTISIG, OP_PUSHNUM_1};
+use derive::{
+    CompressedPk, Derive, DeriveCompr, DerivedScript, KeyOrigin, Keychain, LegacyPk, NormalIndex,
+    TapDerivation, Terminal, WeightUnits, WitnessScript, XOnlyPk, XpubDerivable, XpubSpec,
+};
+use indexmap::IndexMap;
+
+use crate::descriptor::{satisfaction_weight, script_args, split_args, MAX_ECDSA_SIG_SIZE};
+use crate::{DescrParseError, Descriptor, SpkClass};
+
+/// Maximal number of keys which can be used in a `multi` and `sortedmulti` descriptors inside
+/// P2WSH.
+pub const MAX_WSH_MULTI_KEYS: usize = 20;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MultisigError {
+    /// multisig threshold must be at least 1.
+    ZeroThreshold,
+
+    /// multisig threshold {threshold} exceeds the number of keys {keys}.
+    ThresholdExceedsKeys { threshold: u16, keys: usize },
+
+    /// multisig uses {keys} keys, which exceeds the maximum of {max} keys.
+    TooManyKeys { keys: usize, max: usize },
+}
+
+pub(crate) fn check_multisig(threshold: u16, keys: usize, max: usize) -> Result<(), MultisigError> {
+    if threshold == 0 {
+        return Err(MultisigError::ZeroThreshold);
+    }
+    if keys > max {
+        return Err(MultisigError::TooManyKeys { keys, max });
+    }
+    if threshold as usize > keys {
+        return Err(MultisigError::ThresholdExceedsKeys { threshold, keys });
+    }
+    Ok(())
+}
+
+/// Pushes a positive number into the script using minimal encoding.
+pub(crate) fn push_num(script: &mut Vec<u8>, num: usize) {
+    if (1..=16).contains(&num) {
+        script.push(OP_PUSHNUM_1 + num as u8 - 1);
+        return;
+    }
+    let mut data = num.to_le_bytes().to_vec();
+    while data.last() == Some(&0) {
+        data.pop();
+    }
+    if data.last().is_some_and(|byte| byte & 0x80 != 0) {
+        data.push(0);
+    }
+    script.push(data.len() as u8);
+    script.extend(data);
+}
+
+/// Constructs `OP_CHECKMULTISIG` script for the given threshold and keys, keeping the order of
+/// the keys.
+/// Length of the `multi` script with the given threshold and number of keys.
+pub(crate) fn multisig_script_len(threshold: u16, count: usize) -> usize {
+    let mut nums = Vec::with_capacity(6);
+    push_num(&mut nums, threshold as usize);
+    push_num(&mut nums, count);
+    nums.len() + count * 34 + 1
+}
+
+/// Maximum weight of the witness satisfying P2WSH `multi` script.
+fn multisig_satisfaction_weight(threshold: u16, count: usize) -> WeightUnits {
+    // an extra empty element is consumed by OP_CHECKMULTISIG due to the off-by-one bug
+    let stack = 1 + threshold as usize * MAX_ECDSA_SIG_SIZE;
+    satisfaction_weight(0, stack, Some(multisig_script_len(threshold, count)))
+}
+
+pub(crate) fn multisig_script(
+    threshold: u16,
+    keys: impl ExactSizeIterator<Item = CompressedPk>,
+) -> Vec<u8> {
+    legacy_multisig_script(threshold, keys.map(|key| LegacyPk::compressed(*key)))
+}
+
+/// Constructs `OP_CHECKMULTISIG` script for the given threshold and keys, which may be
+/// uncompressed, keeping the order of the keys.
+pub(crate) fn legacy_multisig_script(
+    threshold: u16,
+    keys: impl ExactSizeIterator<Item = LegacyPk>,
+) -> Vec<u8> {
+    let count = keys.len();
+    let mut script = Vec::with_capacity(3 + count * 34);
+    push_num(&mut script, threshold as usize);
+    for key in keys {
+        let key = key.to_vec();
+        script.push(key.len() as u8);
+        script.extend(key);
+    }
+    push_num(&mut script, count);
+    script.push(OP_CHECKMULTISIG);
+    script
+}
+
+/// Sorts public keys lexicographically by their compressed serialization, as required by
+/// BIP-67 for the keys of `sortedmulti` scripts.
+pub fn bip67_sort(keys: &mut [CompressedPk]) { keys.sort_by_key(CompressedPk::to_byte_array); }
+
+/// Checks whether the public keys are ordered according to BIP-67, such that the multisig
+/// script constructed by a third party can be verified.
+pub fn is_bip67_sorted(keys: &[CompressedPk]) -> bool {
+    keys.windows(2).all(|pair| pair[0].to_byte_array() <= pair[1].to_byte_array())
+}
+
+/// Derives keys for a terminal, sorting them lexicographically according to BIP-67 if
+/// `sorted` flag is set.
+fn derive_keys<K: DeriveCompr>(keys: &[K], terminal: Terminal, sorted: bool) -> Vec<CompressedPk> {
+    let mut derived = keys
+        .iter()
+        .map(|key| key.derive(terminal.keychain, terminal.index))
+        .collect::<Vec<CompressedPk>>();
+    if sorted {
+        bip67_sort(&mut derived);
+    }
+    derived
+}
+
+/// Keychains which are supported by all the keys.
+pub(crate) fn common_keychains<'k, K: Derive<D> + 'k, D>(
+    keys: impl IntoIterator<Item = &'k K>,
+) -> BTreeSet<Keychain> {
+    let mut iter = keys.into_iter();
+    let Some(first) = iter.next() else {
+        return none!();
+    };
+    iter.fold(first.keychains(), |acc, key| acc.intersection(&key.keychains()).copied().collect())
+}
+
+/// Checks that all the keys use the same set of keychains, as required for multipath
+/// descriptors by BIP-389.
+pub(crate) fn check_keychains<'k, K: Derive<D> + 'k, D>(
+    keys: impl IntoIterator<Item = &'k K>,
+) -> Result<(), DescrParseError> {
+    let mut iter = keys.into_iter();
+    let Some(first) = iter.next() else {
+        return Ok(());
+    };
+    let keychains = first.keychains();
+    if iter.any(|key| key.keychains() != keychains) {
+        return Err(DescrParseError::MultipathMismatch);
+    }
+    Ok(())
+}
+
+fn keyset<K: DeriveCompr>(keys: &[K], terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+    keys.iter()
+        .map(|key| {
+            let pk = key.derive(terminal.keychain, terminal.index);
+            (pk, KeyOrigin::with(key.xpub_spec().origin().clone(), terminal))
+        })
+        .collect()
+}
+
+pub(crate) fn fmt_multi<K: Display>(
+    f: &mut Formatter<'_>,
+    wrapper: &str,
+    name: &str,
+    threshold: u16,
+    keys: &[K],
+) -> fmt::Result {
+    write!(f, "{wrapper}({name}({threshold}")?;
+    for key in keys {
+        f.write_str(",")?;
+        Display::fmt(key, f)?;
+    }
+    f.write_str("))")
+}
+
+pub(crate) fn parse_multi<K: FromStr>(
+    s: &str,
+    wrapper: &str,
+    name: &str,
+    max_keys: usize,
+) -> Result<(u16, Vec<K>), DescrParseError>
+where
+    DescrParseError: From<K::Err>,
+{
+    let args = script_args(s.trim(), wrapper)
+        .and_then(|inner| script_args(inner, name))
+        .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+    let mut args = split_args(args)?.into_iter();
+    let threshold = args
+        .next()
+        .and_then(|t| u16::from_str(t).ok())
+        .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+    let keys = args.map(K::from_str).collect::<Result<Vec<_>, _>>()?;
+    check_multisig(threshold, keys.len(), max_keys)?;
+    Ok((threshold, keys))
+}
+
+/// P2WSH multisig descriptor `wsh(multi(...))`, which puts keys into the script in the order
+/// they are provided.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct WshMulti<K: DeriveCompr = XpubDerivable> {
+    threshold: u16,
+    keys: Vec<K>,
+}
+
+impl<K: DeriveCompr> WshMulti<K> {
+    pub fn new(threshold: u16, keys: Vec<K>) -> Result<Self, MultisigError> {
+        check_multisig(threshold, keys.len(), MAX_WSH_MULTI_KEYS)?;
+        Ok(Self { threshold, keys })
+    }
+
+    pub fn threshold(&self) -> u16 { self.threshold }
+
+    pub fn as_keys(&self) -> &[K] { &self.keys }
+}
+
+impl<K: DeriveCompr> Derive<DerivedScript> for WshMulti<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.keys[0].default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { common_keychains::<_, CompressedPk>(&self.keys) }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let terminal = Terminal::new(keychain, index.into());
+        let keys = derive_keys(&self.keys, terminal, false);
+        let script = multisig_script(self.threshold, keys.into_iter());
+        DerivedScript::Segwit(WitnessScript::from_unsafe(script))
+    }
+}
+
+impl<K: DeriveCompr + Display> Display for WshMulti<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt_multi(f, "wsh", "multi", self.threshold, &self.keys)
+    }
+}
+
+impl<K: DeriveCompr + FromStr> FromStr for WshMulti<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (threshold, keys) = parse_multi(s, "wsh", "multi", MAX_WSH_MULTI_KEYS)?;
+        check_keychains::<_, CompressedPk>(&keys)?;
+        Ok(Self { threshold, keys })
+    }
+}
+
+impl<K: DeriveCompr> Descriptor<K> for WshMulti<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2wsh }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        multisig_satisfaction_weight(self.threshold, self.keys.len())
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        self.keys.iter()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        std::iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { self.keys.iter().map(K::xpub_spec) }
+
+    fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        keyset(&self.keys, terminal)
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+/// P2WSH multisig descriptor `wsh(sortedmulti(...))`, which orders derived keys
+/// lexicographically (BIP-67), such that all cosigners produce the same script regardless of
+/// the order in which the keys were provided.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct WshSortedMulti<K: DeriveCompr = XpubDerivable> {
+    threshold: u16,
+    keys: Vec<K>,
+}
+
+impl<K: DeriveCompr> WshSortedMulti<K> {
+    pub fn new(threshold: u16, keys: Vec<K>) -> Result<Self, MultisigError> {
+        check_multisig(threshold, keys.len(), MAX_WSH_MULTI_KEYS)?;
+        Ok(Self { threshold, keys })
+    }
+
+    pub fn threshold(&self) -> u16 { self.threshold }
+
+    pub fn as_keys(&self) -> &[K] { &self.keys }
+}
+
+impl<K: DeriveCompr> Derive<DerivedScript> for WshSortedMulti<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.keys[0].default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { common_keychains::<_, CompressedPk>(&self.keys) }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let terminal = Terminal::new(keychain, index.into());
+        let keys = derive_keys(&self.keys, terminal, true);
+        let script = multisig_script(self.threshold, keys.into_iter());
+        DerivedScript::Segwit(WitnessScript::from_unsafe(script))
+    }
+}
+
+impl<K: DeriveCompr + Display> Display for WshSortedMulti<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt_multi(f, "wsh", "sortedmulti", self.threshold, &self.keys)
+    }
+}
+
+impl<K: DeriveCompr + FromStr> FromStr for WshSortedMulti<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (threshold, keys) = parse_multi(s, "wsh", "sortedmulti", MAX_WSH_MULTI_KEYS)?;
+        check_keychains::<_, CompressedPk>(&keys)?;
+        Ok(Self { threshold, keys })
+    }
+}
+
+impl<K: DeriveCompr> Descriptor<K> for WshSortedMulti<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2wsh }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        multisig_satisfaction_weight(self.threshold, self.keys.len())
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        self.keys.iter()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        std::iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { self.keys.iter().map(K::xpub_spec) }
+
+    fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        keyset(&self.keys, terminal)
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY1: &str = "[643a7adc/48h/1h/0h/2h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+    const KEY2: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+
+    fn keys() -> (XpubDerivable, XpubDerivable) { (KEY1.parse().unwrap(), KEY2.parse().unwrap()) }
+
+    #[test]
+    fn multisig_script_layout() {
+        let (key1, _) = keys();
+        let pk: CompressedPk = key1.derive(0, 0u8);
+        let script = multisig_script(1, [pk, pk].into_iter());
+        assert_eq!(script.len(), 3 + 2 * 34);
+        assert_eq!(script[0], OP_PUSHNUM_1);
+        assert_eq!(script[1], 33);
+        assert_eq!(&script[2..35], &pk.to_byte_array());
+        assert_eq!(script[69], OP_PUSHNUM_1 + 1);
+        assert_eq!(script[70], OP_CHECKMULTISIG);
+
+        let script = multisig_script(17, [pk; 18].into_iter());
+        assert_eq!(&script[..2], &[1, 17]);
+        assert_eq!(&script[script.len() - 3..], &[1, 18, OP_CHECKMULTISIG]);
+
+        let mut script = vec![];
+        push_num(&mut script, 128);
+        assert_eq!(script, vec![2, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn bip67() {
+        // BIP-67 test vector 1
+        let a = CompressedPk::from_str(
+            "02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8",
+        )
+        .unwrap();
+        let b = CompressedPk::from_str(
+            "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f",
+        )
+        .unwrap();
+        let mut keys = [a, b];
+        assert!(!is_bip67_sorted(&keys));
+        bip67_sort(&mut keys);
+        assert_eq!(keys, [b, a]);
+        assert!(is_bip67_sorted(&keys));
+        assert!(is_bip67_sorted(&[]));
+    }
+
+    #[test]
+    fn wsh_sortedmulti_order_independent() {
+        let (key1, key2) = keys();
+        let a = WshSortedMulti::new(2, vec![key1.clone(), key2.clone()]).unwrap();
+        let b = WshSortedMulti::new(2, vec![key2, key1]).unwrap();
+        for index in 0u8..4 {
+            let script = a.derive(0, index);
+            assert!(script.to_script_pubkey().is_p2wsh());
+            assert_eq!(script, b.derive(0, index));
+        }
+        assert_eq!(a.keychains(), bset![Keychain::OUTER, Keychain::INNER]);
+    }
+
+    #[test]
+    fn wsh_multi_parse() {
+        let s = format!("wsh(multi(2,{KEY1},{KEY1}))");
+        let multi = WshMulti::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(multi.threshold(), 2);
+        assert_eq!(multi.to_string(), s);
+        assert_eq!(multi.keychains(), bset![Keychain::OUTER, Keychain::INNER]);
+        assert_eq!(multi.compr_keyset(Terminal::new(0, NormalIndex::from(0u8))).len(), 1);
+
+        let s = format!("wsh(sortedmulti(1,{KEY1},{KEY2}))");
+        let sorted = WshSortedMulti::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(sorted.to_string(), s);
+
+        assert!(matches!(
+            WshMulti::<XpubDerivable>::from_str(&format!("wsh(multi(3,{KEY1},{KEY2}))")),
+            Err(DescrParseError::Multisig(MultisigError::ThresholdExceedsKeys { .. }))
+        ));
+        assert!(matches!(
+            WshMulti::<XpubDerivable>::from_str(&format!("wsh(multi(0,{KEY1}))")),
+            Err(DescrParseError::Multisig(MultisigError::ZeroThreshold))
+        ));
+    }
+}
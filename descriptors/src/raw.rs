@@ -0,0 +1,206 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Non-derivable `raw(HEX)` and `addr(ADDRESS)` descriptors, which watch a single script pubkey
+//! for all terminals.
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::iter;
+use std::str::FromStr;
+
+use amplify::hex::{FromHex, ToHex};
+use derive::{
+    Address, CompressedPk, Derive, DerivedScript, KeyOrigin, Keychain, NormalIndex, ScriptPubkey,
+    TapDerivation, Terminal, WeightUnits, XOnlyPk, XpubSpec,
+};
+use indexmap::IndexMap;
+
+use crate::descriptor::script_args;
+use crate::{DescrParseError, Descriptor, SpkClass, SpkTemplate};
+
+/// Raw script pubkey descriptor `raw(HEX)`.
+///
+/// The descriptor is watch-only: it has no keys, derives the same script pubkey for every
+/// terminal, and its satisfaction weight is unknown and reported as zero.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate",))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
+pub struct Raw(ScriptPubkey);
+
+impl Raw {
+    pub fn as_script_pubkey(&self) -> &ScriptPubkey { &self.0 }
+    pub fn into_script_pubkey(self) -> ScriptPubkey { self.0 }
+}
+
+impl Derive<DerivedScript> for Raw {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { Keychain::OUTER }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { bset![Keychain::OUTER] }
+
+    fn derive(
+        &self,
+        _keychain: impl Into<Keychain>,
+        _index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        DerivedScript::Bare(self.0.clone())
+    }
+}
+
+impl Display for Raw {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { write!(f, "raw({})", self.0.to_hex()) }
+}
+
+impl FromStr for Raw {
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DescrParseError::InvalidSyntax(s.to_owned());
+        let hex = script_args(s.trim(), "raw").ok_or_else(invalid)?;
+        let script = Vec::<u8>::from_hex(hex).map_err(|_| invalid())?;
+        Ok(Self(ScriptPubkey::from_unsafe(script)))
+    }
+}
+
+impl<K> Descriptor<K> for Raw {
+    fn class(&self) -> SpkClass { SpkTemplate::lift(&self.0).class() }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits { WeightUnits::no_discount(0) }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        iter::empty()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { iter::empty() }
+
+    fn compr_keyset(&self, _terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        IndexMap::new()
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+/// Fixed address descriptor `addr(ADDRESS)`.
+///
+/// The descriptor is watch-only: it has no keys, derives the same script pubkey for every
+/// terminal, and its satisfaction weight is unknown and reported as zero.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate",))]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, From)]
+pub struct Addr(Address);
+
+impl Addr {
+    pub fn address(&self) -> Address { self.0 }
+}
+
+impl Derive<DerivedScript> for Addr {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { Keychain::OUTER }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { bset![Keychain::OUTER] }
+
+    fn derive(
+        &self,
+        _keychain: impl Into<Keychain>,
+        _index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        DerivedScript::Bare(self.0.script_pubkey())
+    }
+}
+
+impl Display for Addr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { write!(f, "addr({})", self.0) }
+}
+
+impl FromStr for Addr {
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let addr = script_args(s.trim(), "addr")
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        Ok(Self(Address::from_str(addr)?))
+    }
+}
+
+impl<K> Descriptor<K> for Addr {
+    fn class(&self) -> SpkClass { SpkTemplate::lift(&self.0.script_pubkey()).class() }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits { WeightUnits::no_discount(0) }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        iter::empty()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { iter::empty() }
+
+    fn compr_keyset(&self, _terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        IndexMap::new()
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+
+    fn is_testnet(&self) -> Option<bool> { Some(self.0.is_testnet()) }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::{AddressType, DeriveScripts};
+
+    use super::*;
+
+    #[test]
+    fn raw() {
+        let s = "raw(6a0568656c6c6f)";
+        let raw = Raw::from_str(s).unwrap();
+        assert_eq!(raw.to_string(), s);
+        assert_eq!(raw.derive(0, 0u8), raw.derive(1, 7u8));
+        assert_eq!(raw.derive(0, 0u8).to_script_pubkey(), ScriptPubkey::op_return(b"hello"));
+        assert_eq!(Descriptor::<()>::class(&raw), SpkClass::Bare);
+        assert!(matches!(Raw::from_str("raw(6a0)"), Err(DescrParseError::InvalidSyntax(_))));
+    }
+
+    #[test]
+    fn addr() {
+        let b32 = "tb1p5kgdjdf99vfa2xwufd2cx2qru468z79s2arn3jf5feg95d9m62gqzpnjjk";
+        let s = format!("addr({b32})");
+        let addr = Addr::from_str(&s).unwrap();
+        assert_eq!(addr.to_string(), s);
+        assert_eq!(addr.address().address_type(), AddressType::P2tr);
+        assert_eq!(Descriptor::<()>::class(&addr), SpkClass::P2tr);
+        assert_eq!(Descriptor::<()>::is_testnet(&addr), Some(true));
+        assert_eq!(addr.derive_address(addr.address().network, 1, 5u8), Ok(addr.address()));
+        assert!(matches!(Addr::from_str("addr(tb1)"), Err(DescrParseError::Address(_))));
+    }
+}
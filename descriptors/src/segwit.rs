@@ -21,15 +21,23 @@
 // limitations under the License.
 
 use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
 use std::iter;
+use std::str::FromStr;
 
 use derive::{
     CompressedPk, Derive, DeriveCompr, DerivedScript, KeyOrigin, Keychain, NormalIndex,
-    ScriptPubkey, TapDerivation, Terminal, WPubkeyHash, XOnlyPk, XpubDerivable, XpubSpec,
+    RedeemScript, ScriptPubkey, TapDerivation, Terminal, WPubkeyHash, WeightUnits, WitnessScript,
+    XOnlyPk, XpubDerivable, XpubSpec,
 };
 use indexmap::IndexMap;
 
-use crate::{Descriptor, SpkClass};
+use crate::descriptor::{satisfaction_weight, script_args, MAX_ECDSA_SIG_SIZE};
+use crate::multisig::{check_keychains, common_keychains};
+use crate::{
+    DescrParseError, Descriptor, Miniscript, MiniscriptError, MsCtx, SpkClass, Timelock,
+    MAX_WSH_SCRIPT_SIZE,
+};
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate",))]
 #[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
@@ -57,9 +65,111 @@ impl<K: DeriveCompr> Derive<DerivedScript> for Wpkh<K> {
     }
 }
 
+impl<K: DeriveCompr + Display> Display for Wpkh<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("wpkh(")?;
+        Display::fmt(&self.0, f)?;
+        f.write_str(")")
+    }
+}
+
+impl<K: DeriveCompr + FromStr> FromStr for Wpkh<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = script_args(s.trim(), "wpkh")
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        Ok(Self(K::from_str(key)?))
+    }
+}
+
 impl<K: DeriveCompr> Descriptor<K> for Wpkh<K> {
     fn class(&self) -> SpkClass { SpkClass::P2wpkh }
 
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        satisfaction_weight(0, MAX_ECDSA_SIG_SIZE + 1 + 33, None)
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        iter::once(&self.0)
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { iter::once(self.0.xpub_spec()) }
+
+    fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        let mut map = IndexMap::with_capacity(1);
+        let key = self.0.derive(terminal.keychain, terminal.index);
+        map.insert(key, KeyOrigin::with(self.0.xpub_spec().origin().clone(), terminal));
+        map
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate",))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
+pub struct ShWpkh<K: DeriveCompr = XpubDerivable>(K);
+
+impl<K: DeriveCompr> ShWpkh<K> {
+    pub fn as_key(&self) -> &K { &self.0 }
+    pub fn into_key(self) -> K { self.0 }
+}
+
+impl<K: DeriveCompr> Derive<DerivedScript> for ShWpkh<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.0.default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { self.0.keychains() }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let key = self.0.derive(keychain, index);
+        let witness_program = ScriptPubkey::p2wpkh(WPubkeyHash::from(key));
+        DerivedScript::Bip13(RedeemScript::from_unsafe(witness_program.to_vec()))
+    }
+}
+
+impl<K: DeriveCompr + Display> Display for ShWpkh<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("sh(wpkh(")?;
+        Display::fmt(&self.0, f)?;
+        f.write_str("))")
+    }
+}
+
+impl<K: DeriveCompr + FromStr> FromStr for ShWpkh<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = script_args(s.trim(), "sh")
+            .and_then(|inner| script_args(inner, "wpkh"))
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        Ok(Self(K::from_str(key)?))
+    }
+}
+
+impl<K: DeriveCompr> Descriptor<K> for ShWpkh<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2sh }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        // signature script pushes P2WPKH redeem script of 22 bytes
+        satisfaction_weight(1 + 22, MAX_ECDSA_SIG_SIZE + 1 + 33, None)
+    }
+
     fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
     where K: 'a {
         iter::once(&self.0)
@@ -81,3 +191,161 @@ impl<K: DeriveCompr> Descriptor<K> for Wpkh<K> {
         IndexMap::new()
     }
 }
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Wsh<K: DeriveCompr = XpubDerivable>(Miniscript<K>);
+
+impl<K: DeriveCompr> Wsh<K> {
+    /// Constructs P2WSH descriptor out of a top-level segwit miniscript, checking that the
+    /// resulting witness script fits into the standardness size limit.
+    pub fn new(ms: Miniscript<K>) -> Result<Self, MiniscriptError> {
+        if ms.ctx() != MsCtx::Segwit {
+            return Err(MiniscriptError::ContextMismatch("wsh", ms.ctx()));
+        }
+        ms.check_top_level()?;
+        let size = ms.encode(&|_| vec![0u8; 33]).len();
+        if size > MAX_WSH_SCRIPT_SIZE {
+            return Err(MiniscriptError::ScriptSize(size));
+        }
+        Ok(Self(ms))
+    }
+
+    pub fn as_miniscript(&self) -> &Miniscript<K> { &self.0 }
+    pub fn into_miniscript(self) -> Miniscript<K> { self.0 }
+}
+
+impl<K: DeriveCompr> Derive<DerivedScript> for Wsh<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.0.keys()[0].default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { common_keychains::<_, CompressedPk>(self.0.keys()) }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let terminal = Terminal::new(keychain, index.into());
+        let script = self.0.encode(&|key| {
+            let pk: CompressedPk = key.derive(terminal.keychain, terminal.index);
+            pk.to_byte_array().to_vec()
+        });
+        DerivedScript::Segwit(WitnessScript::from_unsafe(script))
+    }
+}
+
+impl<K: DeriveCompr + Display> Display for Wsh<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { write!(f, "wsh({})", self.0) }
+}
+
+impl<K: DeriveCompr + FromStr> FromStr for Wsh<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ms = script_args(s.trim(), "wsh")
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        let ms = Miniscript::parse(ms, MsCtx::Segwit)?;
+        check_keychains::<_, CompressedPk>(ms.keys())?;
+        Ok(Self::new(ms)?)
+    }
+}
+
+impl<K: DeriveCompr> Descriptor<K> for Wsh<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2wsh }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        let script = self.0.encode(&|_| vec![0u8; 33]).len();
+        satisfaction_weight(0, self.0.max_satisfaction_size().unwrap_or_default(), Some(script))
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        self.0.keys().into_iter()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> {
+        self.0.keys().into_iter().map(K::xpub_spec)
+    }
+
+    fn compr_keyset(&self, terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        self.0
+            .keys()
+            .into_iter()
+            .map(|key| {
+                let pk = key.derive(terminal.keychain, terminal.index);
+                (pk, KeyOrigin::with(key.xpub_spec().origin().clone(), terminal))
+            })
+            .collect()
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+
+    fn timelocks(&self) -> Vec<Timelock> { self.0.timelocks() }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::XpubDerivable;
+
+    use super::*;
+
+    #[test]
+    fn wpkh_derive() {
+        let xpub = XpubDerivable::from_str("[643a7adc/84h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*").unwrap();
+        let wpkh = Wpkh::from(xpub.clone());
+        assert_eq!(wpkh.class(), SpkClass::P2wpkh);
+        for index in 0u8..4 {
+            let spk = wpkh.derive(0, index).to_script_pubkey();
+            assert!(spk.is_p2wpkh());
+            let key: CompressedPk = xpub.derive(0, index);
+            assert_eq!(spk, ScriptPubkey::p2wpkh(WPubkeyHash::from(key)));
+        }
+        assert_ne!(wpkh.derive(0, 0u8).to_script_pubkey(), wpkh.derive(1, 0u8).to_script_pubkey());
+    }
+
+    #[test]
+    fn sh_wpkh_derive() {
+        let s = "sh(wpkh([643a7adc/49h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*))";
+        let sh_wpkh = ShWpkh::<XpubDerivable>::from_str(s).unwrap();
+        assert_eq!(sh_wpkh.to_string(), s);
+        assert_eq!(sh_wpkh.class(), SpkClass::P2sh);
+
+        let script = sh_wpkh.derive(1, 3u8);
+        let key: CompressedPk = sh_wpkh.as_key().derive(1, 3u8);
+        let redeem_script = script.to_redeem_script().unwrap();
+        assert_eq!(
+            redeem_script.as_slice(),
+            ScriptPubkey::p2wpkh(WPubkeyHash::from(key)).as_slice()
+        );
+        assert!(script.to_script_pubkey().is_p2sh());
+        assert_eq!(script.to_script_pubkey(), redeem_script.to_script_pubkey());
+    }
+
+    #[test]
+    fn wsh_miniscript() {
+        let key1 = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+        let key2 = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+        let s = format!("wsh(or_d(pk({key1}),and_v(v:pk({key2}),older(144))))");
+        let wsh = Wsh::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(wsh.to_string(), s);
+        assert_eq!(wsh.class(), SpkClass::P2wsh);
+        assert_eq!(wsh.keys().count(), 2);
+        assert_eq!(wsh.compr_keyset(Terminal::new(0, NormalIndex::from(3u8))).len(), 2);
+        assert_eq!(wsh.timelocks(), vec![Timelock::older(144)]);
+
+        let derived = wsh.derive(0, 3u8);
+        assert!(derived.to_script_pubkey().is_p2wsh());
+        assert_ne!(derived.to_script_pubkey(), wsh.derive(1, 3u8).to_script_pubkey());
+
+        assert!(Wsh::<XpubDerivable>::from_str("wsh(older(144))").is_err());
+    }
+}
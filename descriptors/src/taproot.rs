@@ -21,15 +21,26 @@
 // limitations under the License.
 
 use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
 use std::iter;
+use std::str::FromStr;
 
+use amplify::num::u7;
+use derive::opcodes::{OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
 use derive::{
-    CompressedPk, Derive, DeriveXOnly, DerivedScript, InternalPk, KeyOrigin, Keychain, NormalIndex,
-    TapDerivation, Terminal, XOnlyPk, XpubDerivable, XpubSpec,
+    CompressedPk, Derive, DeriveCompr, DeriveXOnly, DerivedScript, InternalPk, InvalidTree,
+    KeyAggContext, KeyOrigin, Keychain, LeafInfo, MuSigError, NormalIndex, TapDerivation,
+    TapScript, TapTree, Terminal, UnfinalizedTree, WeightUnits, XOnlyPk, XpubDerivable, XpubSpec,
 };
 use indexmap::IndexMap;
 
-use crate::{Descriptor, SpkClass};
+use crate::descriptor::{satisfaction_weight, script_args, split_args, MAX_BIP340_SIG_SIZE};
+use crate::multisig::{check_keychains, check_multisig, common_keychains, push_num};
+use crate::{DescrParseError, Descriptor, Miniscript, MsCtx, SpkClass, Timelock};
+
+/// Maximal number of keys which can be used in a `multi_a` and `sortedmulti_a` tapscript
+/// descriptors, matching Bitcoin Core limit.
+pub const MAX_MULTI_A_KEYS: usize = 999;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate",))]
 #[derive(Clone, Eq, PartialEq, Hash, Debug, From)]
@@ -57,9 +68,33 @@ impl<K: DeriveXOnly> Derive<DerivedScript> for TrKey<K> {
     }
 }
 
+impl<K: DeriveXOnly + Display> Display for TrKey<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("tr(")?;
+        Display::fmt(&self.0, f)?;
+        f.write_str(")")
+    }
+}
+
+impl<K: DeriveXOnly + FromStr> FromStr for TrKey<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = script_args(s.trim(), "tr")
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        Ok(Self(K::from_str(key)?))
+    }
+}
+
 impl<K: DeriveXOnly> Descriptor<K> for TrKey<K> {
     fn class(&self) -> SpkClass { SpkClass::P2tr }
 
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        satisfaction_weight(0, MAX_BIP340_SIG_SIZE, None)
+    }
+
     fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
     where K: 'a {
         iter::once(&self.0)
@@ -85,9 +120,618 @@ impl<K: DeriveXOnly> Descriptor<K> for TrKey<K> {
     }
 }
 
-/*
-pub struct TrScript<K: DeriveXOnly> {
+/// Taproot key-only descriptor `tr(musig(KEY_1,...,KEY_n))` (BIP-390), using MuSig2 aggregate of
+/// the participant keys as the internal key. The keys are derived first and then sorted, such
+/// that the descriptor doesn't depend on the order in which the keys are provided.
+///
+/// Key-path spending requires MuSig2 signing session between all participants (see
+/// [`derive::MuSigSession`]), thus the descriptor doesn't provide key derivation information
+/// to PSBT signers.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TrMusig<K: DeriveCompr = XpubDerivable> {
+    keys: Vec<K>,
+}
+
+impl<K: DeriveCompr> TrMusig<K> {
+    pub fn new(keys: Vec<K>) -> Result<Self, MuSigError> {
+        if keys.is_empty() {
+            return Err(MuSigError::NoKeys);
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn as_keys(&self) -> &[K] { &self.keys }
+
+    /// MuSig2 key aggregation context for the keys derived at the given terminal, which is
+    /// used to create signing sessions.
+    pub fn key_agg_ctx(&self, terminal: Terminal) -> KeyAggContext {
+        KeyAggContext::sorted(
+            self.keys.iter().map(|key| key.derive(terminal.keychain, terminal.index)),
+        )
+        .expect("MuSig2 aggregated key is at infinity")
+    }
+}
+
+impl<K: DeriveCompr> Derive<DerivedScript> for TrMusig<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.keys[0].default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { common_keychains::<_, CompressedPk>(&self.keys) }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let terminal = Terminal::new(keychain, index.into());
+        let internal_key = self.key_agg_ctx(terminal).agg_xonly_pk();
+        DerivedScript::TaprootKeyOnly(InternalPk::from_unchecked(internal_key))
+    }
+}
+
+impl<K: DeriveCompr + Display> Display for TrMusig<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("tr(musig(")?;
+        for (no, key) in self.keys.iter().enumerate() {
+            if no > 0 {
+                f.write_str(",")?;
+            }
+            Display::fmt(key, f)?;
+        }
+        f.write_str("))")
+    }
+}
+
+impl<K: DeriveCompr + FromStr> FromStr for TrMusig<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args = script_args(s.trim(), "tr")
+            .and_then(|inner| script_args(inner, "musig"))
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        let keys = split_args(args)?.into_iter().map(K::from_str).collect::<Result<Vec<_>, _>>()?;
+        check_keychains::<_, CompressedPk>(&keys)?;
+        Ok(Self { keys })
+    }
+}
+
+impl<K: DeriveCompr> Descriptor<K> for TrMusig<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2tr }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        satisfaction_weight(0, MAX_BIP340_SIG_SIZE, None)
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        self.keys.iter()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { self.keys.iter().map(K::xpub_spec) }
+
+    fn compr_keyset(&self, _terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        IndexMap::new()
+    }
+
+    fn xonly_keyset(&self, _terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        IndexMap::new()
+    }
+}
+
+/// Script descriptor which can be used as a leaf in a taproot script tree.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TapLeafDescr<K: DeriveXOnly = XpubDerivable> {
+    /// `pk(KEY)` leaf: `<KEY> OP_CHECKSIG`.
+    Pk(K),
+
+    /// `multi_a(k,KEY_1,...,KEY_n)` leaf with keys put into the script in the order they are
+    /// provided.
+    MultiA { threshold: u16, keys: Vec<K> },
+
+    /// `sortedmulti_a(k,KEY_1,...,KEY_n)` leaf with derived keys sorted lexicographically.
+    SortedMultiA { threshold: u16, keys: Vec<K> },
+
+    /// Arbitrary tapscript miniscript leaf.
+    Miniscript(Miniscript<K>),
+}
+
+impl<K: DeriveXOnly> TapLeafDescr<K> {
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        match self {
+            TapLeafDescr::Pk(key) => vec![key],
+            TapLeafDescr::MultiA { keys, .. } | TapLeafDescr::SortedMultiA { keys, .. } => {
+                keys.iter().collect()
+            }
+            TapLeafDescr::Miniscript(ms) => ms.keys(),
+        }
+        .into_iter()
+    }
+
+    /// Maximum size of the witness stack elements satisfying the leaf script, not including
+    /// the script and the control block.
+    pub fn max_satisfaction_size(&self) -> usize {
+        match self {
+            TapLeafDescr::Pk(_) => MAX_BIP340_SIG_SIZE,
+            TapLeafDescr::MultiA { threshold, keys }
+            | TapLeafDescr::SortedMultiA { threshold, keys } => {
+                let threshold = *threshold as usize;
+                threshold * MAX_BIP340_SIG_SIZE + (keys.len() - threshold)
+            }
+            TapLeafDescr::Miniscript(ms) => ms.max_satisfaction_size().unwrap_or_default(),
+        }
+    }
+
+    /// Maximum weight of the witness spending the leaf located at the given depth of the
+    /// script tree.
+    pub fn max_satisfaction_weight(&self, depth: u7) -> WeightUnits {
+        let script = match self {
+            TapLeafDescr::Pk(_) => 1 + 32 + 1,
+            TapLeafDescr::MultiA { threshold, keys }
+            | TapLeafDescr::SortedMultiA { threshold, keys } => {
+                let mut num = Vec::with_capacity(3);
+                push_num(&mut num, *threshold as usize);
+                keys.len() * (1 + 32 + 1) + num.len() + 1
+            }
+            TapLeafDescr::Miniscript(ms) => ms.encode(&|_| vec![0u8; 32]).len(),
+        };
+        let control_block = 1 + 33 + 32 * depth.to_u8() as usize;
+        satisfaction_weight(0, self.max_satisfaction_size() + control_block, Some(script))
+    }
+
+    /// Timelocks which must be met for spending the leaf.
+    pub fn timelocks(&self) -> Vec<Timelock> {
+        match self {
+            TapLeafDescr::Miniscript(ms) => ms.timelocks(),
+            _ => vec![],
+        }
+    }
+
+    pub fn derive_script(&self, terminal: Terminal) -> TapScript {
+        let mut script = Vec::new();
+        let (threshold, mut keys) = match self {
+            TapLeafDescr::Pk(key) => {
+                let pk: XOnlyPk = key.derive(terminal.keychain, terminal.index);
+                script.push(32);
+                script.extend(pk.to_byte_array());
+                script.push(OP_CHECKSIG);
+                return TapScript::from_unsafe(script);
+            }
+            TapLeafDescr::Miniscript(ms) => {
+                return TapScript::from_unsafe(ms.encode(&|key| {
+                    let pk: XOnlyPk = key.derive(terminal.keychain, terminal.index);
+                    pk.to_byte_array().to_vec()
+                }));
+            }
+            TapLeafDescr::MultiA { threshold, keys }
+            | TapLeafDescr::SortedMultiA { threshold, keys } => (
+                *threshold,
+                keys.iter()
+                    .map(|key| key.derive(terminal.keychain, terminal.index))
+                    .collect::<Vec<XOnlyPk>>(),
+            ),
+        };
+        if matches!(self, TapLeafDescr::SortedMultiA { .. }) {
+            keys.sort_by_key(XOnlyPk::to_byte_array);
+        }
+        for (no, pk) in keys.into_iter().enumerate() {
+            script.push(32);
+            script.extend(pk.to_byte_array());
+            script.push(if no == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD });
+        }
+        push_num(&mut script, threshold as usize);
+        script.push(OP_NUMEQUAL);
+        TapScript::from_unsafe(script)
+    }
+}
+
+impl<K: DeriveXOnly + Display> Display for TapLeafDescr<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (name, threshold, keys) = match self {
+            TapLeafDescr::Pk(key) => {
+                f.write_str("pk(")?;
+                Display::fmt(key, f)?;
+                return f.write_str(")");
+            }
+            TapLeafDescr::Miniscript(ms) => return Display::fmt(ms, f),
+            TapLeafDescr::MultiA { threshold, keys } => ("multi_a", threshold, keys),
+            TapLeafDescr::SortedMultiA { threshold, keys } => ("sortedmulti_a", threshold, keys),
+        };
+        write!(f, "{name}({threshold}")?;
+        for key in keys {
+            f.write_str(",")?;
+            Display::fmt(key, f)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl<K: DeriveXOnly + FromStr> FromStr for TapLeafDescr<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(key) = script_args(s, "pk") {
+            return Ok(TapLeafDescr::Pk(K::from_str(key)?));
+        }
+        let (sorted, args) = match (script_args(s, "multi_a"), script_args(s, "sortedmulti_a")) {
+            (Some(args), _) => (false, args),
+            (_, Some(args)) => (true, args),
+            _ => return Ok(TapLeafDescr::Miniscript(Miniscript::parse(s, MsCtx::Tapscript)?)),
+        };
+        let mut args = split_args(args)?.into_iter();
+        let threshold = args
+            .next()
+            .and_then(|t| u16::from_str(t).ok())
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        let keys = args.map(K::from_str).collect::<Result<Vec<_>, _>>()?;
+        check_multisig(threshold, keys.len(), MAX_MULTI_A_KEYS)?;
+        Ok(match sorted {
+            false => TapLeafDescr::MultiA { threshold, keys },
+            true => TapLeafDescr::SortedMultiA { threshold, keys },
+        })
+    }
+}
+
+/// Checks that the leaf depths, provided in the depth-first order, form a complete binary tree.
+fn check_tree(depths: impl IntoIterator<Item = u7>) -> Result<(), InvalidTree> {
+    let mut stack = Vec::<u8>::new();
+    for depth in depths {
+        if stack.first() == Some(&0) {
+            return Err(InvalidTree::MountainRange);
+        }
+        let mut depth = depth.to_u8();
+        while stack.last() == Some(&depth) && depth > 0 {
+            stack.pop();
+            depth -= 1;
+        }
+        stack.push(depth);
+    }
+    match stack.as_slice() {
+        [0] => Ok(()),
+        _ => Err(UnfinalizedTree(u7::with(stack.last().copied().unwrap_or_default())).into()),
+    }
+}
+
+/// Taproot descriptor `tr(KEY,TREE)` with a script tree, allowing script-path spending in
+/// addition to the key-path spending with the internal key.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TrScript<K: DeriveXOnly = XpubDerivable> {
     internal_key: K,
-    tap_tree: TapTree<Policy<K>>,
+    /// Tree leafs in the depth-first order, together with the depth of each of the leafs.
+    tap_tree: Vec<(u7, TapLeafDescr<K>)>,
+}
+
+impl<K: DeriveXOnly> TrScript<K> {
+    pub fn new(internal_key: K, tap_tree: Vec<(u7, TapLeafDescr<K>)>) -> Result<Self, InvalidTree> {
+        check_tree(tap_tree.iter().map(|(depth, _)| *depth))?;
+        Ok(Self {
+            internal_key,
+            tap_tree,
+        })
+    }
+
+    pub fn as_internal_key(&self) -> &K { &self.internal_key }
+
+    pub fn as_tap_tree(&self) -> &[(u7, TapLeafDescr<K>)] { &self.tap_tree }
+
+    /// Maximum weight of the witness spending the output with the key path (if `leaf` is
+    /// `None`) or with the script path of the leaf with the given number in the tap tree.
+    ///
+    /// Returns `None` if there is no leaf with the given number.
+    pub fn path_satisfaction_weight(&self, leaf: Option<usize>) -> Option<WeightUnits> {
+        match leaf {
+            None => Some(satisfaction_weight(0, MAX_BIP340_SIG_SIZE, None)),
+            Some(no) => {
+                let (depth, leaf) = self.tap_tree.get(no)?;
+                Some(leaf.max_satisfaction_weight(*depth))
+            }
+        }
+    }
+
+    fn all_keys(&self) -> impl Iterator<Item = &K> {
+        iter::once(&self.internal_key).chain(self.tap_tree.iter().flat_map(|(_, leaf)| leaf.keys()))
+    }
+
+    pub fn derive_tap_tree(&self, terminal: Terminal) -> TapTree {
+        let leafs = self
+            .tap_tree
+            .iter()
+            .map(|(depth, leaf)| LeafInfo::tap_script(*depth, leaf.derive_script(terminal)));
+        TapTree::from_leafs(leafs).expect("tree structure is checked during construction")
+    }
+}
+
+impl<K: DeriveXOnly> Derive<DerivedScript> for TrScript<K> {
+    #[inline]
+    fn default_keychain(&self) -> Keychain { self.internal_key.default_keychain() }
+
+    #[inline]
+    fn keychains(&self) -> BTreeSet<Keychain> { common_keychains::<_, XOnlyPk>(self.all_keys()) }
+
+    fn derive(
+        &self,
+        keychain: impl Into<Keychain>,
+        index: impl Into<NormalIndex>,
+    ) -> DerivedScript {
+        let terminal = Terminal::new(keychain, index.into());
+        let internal_key = self.internal_key.derive(terminal.keychain, terminal.index);
+        let tap_tree = self.derive_tap_tree(terminal);
+        DerivedScript::TaprootScript(InternalPk::from_unchecked(internal_key), tap_tree)
+    }
+}
+
+fn fmt_tree<K: DeriveXOnly + Display>(
+    f: &mut Formatter<'_>,
+    leafs: &[(u7, TapLeafDescr<K>)],
+    pos: &mut usize,
+    depth: u8,
+) -> fmt::Result {
+    let (leaf_depth, leaf) = &leafs[*pos];
+    if leaf_depth.to_u8() == depth {
+        *pos += 1;
+        return Display::fmt(leaf, f);
+    }
+    f.write_str("{")?;
+    fmt_tree(f, leafs, pos, depth + 1)?;
+    f.write_str(",")?;
+    fmt_tree(f, leafs, pos, depth + 1)?;
+    f.write_str("}")
+}
+
+fn parse_tree<K: DeriveXOnly + FromStr>(
+    s: &str,
+    depth: u8,
+    leafs: &mut Vec<(u7, TapLeafDescr<K>)>,
+) -> Result<(), DescrParseError>
+where
+    DescrParseError: From<K::Err>,
+{
+    let s = s.trim();
+    let depth7 = u7::try_from(depth).map_err(|_| DescrParseError::InvalidSyntax(s.to_owned()))?;
+    let Some(inner) = s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) else {
+        leafs.push((depth7, TapLeafDescr::from_str(s)?));
+        return Ok(());
+    };
+    let [left, right] = split_args(inner)?[..] else {
+        return Err(DescrParseError::InvalidSyntax(s.to_owned()));
+    };
+    parse_tree(left, depth + 1, leafs)?;
+    parse_tree(right, depth + 1, leafs)
+}
+
+impl<K: DeriveXOnly + Display> Display for TrScript<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("tr(")?;
+        Display::fmt(&self.internal_key, f)?;
+        f.write_str(",")?;
+        fmt_tree(f, &self.tap_tree, &mut 0, 0)?;
+        f.write_str(")")
+    }
+}
+
+impl<K: DeriveXOnly + FromStr> FromStr for TrScript<K>
+where DescrParseError: From<K::Err>
+{
+    type Err = DescrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args = script_args(s.trim(), "tr")
+            .ok_or_else(|| DescrParseError::InvalidSyntax(s.to_owned()))?;
+        let [key, tree] = split_args(args)?[..] else {
+            return Err(DescrParseError::InvalidSyntax(s.to_owned()));
+        };
+        let internal_key = K::from_str(key)?;
+        let mut tap_tree = vec![];
+        parse_tree(tree, 0, &mut tap_tree)?;
+        let descr = Self::new(internal_key, tap_tree)?;
+        check_keychains::<_, XOnlyPk>(descr.all_keys())?;
+        Ok(descr)
+    }
+}
+
+impl<K: DeriveXOnly> Descriptor<K> for TrScript<K> {
+    fn class(&self) -> SpkClass { SpkClass::P2tr }
+
+    fn max_satisfaction_weight(&self) -> WeightUnits {
+        (0..self.tap_tree.len())
+            .filter_map(|leaf| self.path_satisfaction_weight(Some(leaf)))
+            .fold(satisfaction_weight(0, MAX_BIP340_SIG_SIZE, None), WeightUnits::max)
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a K>
+    where K: 'a {
+        self.all_keys()
+    }
+    fn vars<'a>(&'a self) -> impl Iterator<Item = &'a ()>
+    where (): 'a {
+        iter::empty()
+    }
+    fn xpubs(&self) -> impl Iterator<Item = &XpubSpec> { self.all_keys().map(K::xpub_spec) }
+
+    fn compr_keyset(&self, _terminal: Terminal) -> IndexMap<CompressedPk, KeyOrigin> {
+        IndexMap::new()
+    }
+
+    fn xonly_keyset(&self, terminal: Terminal) -> IndexMap<XOnlyPk, TapDerivation> {
+        let mut map = IndexMap::<XOnlyPk, TapDerivation>::new();
+        let internal_key = self.internal_key.derive(terminal.keychain, terminal.index);
+        map.insert(
+            internal_key,
+            TapDerivation::with_internal_pk(
+                self.internal_key.xpub_spec().origin().clone(),
+                terminal,
+            ),
+        );
+        for (_, leaf) in &self.tap_tree {
+            let leaf_hash = leaf.derive_script(terminal).tap_leaf_hash();
+            for key in leaf.keys() {
+                let pk = key.derive(terminal.keychain, terminal.index);
+                let derivation = map.entry(pk).or_insert_with(|| {
+                    TapDerivation::with_internal_pk(key.xpub_spec().origin().clone(), terminal)
+                });
+                if !derivation.leaf_hashes.contains(&leaf_hash) {
+                    derivation.leaf_hashes.push(leaf_hash);
+                }
+            }
+        }
+        map
+    }
+
+    fn timelocks(&self) -> Vec<Timelock> {
+        self.tap_tree.iter().flat_map(|(_, leaf)| leaf.timelocks()).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derive::opcodes::{OP_CHECKSIGVERIFY, OP_CSV};
+
+    use super::*;
+
+    const KEY1: &str = "[643a7adc/86h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+    const KEY2: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+
+    #[test]
+    fn tree_structure() {
+        let d = |d: u8| u7::with(d);
+        assert!(check_tree([d(0)]).is_ok());
+        assert!(check_tree([d(1), d(2), d(2)]).is_ok());
+        assert!(check_tree([d(2), d(2), d(1)]).is_ok());
+        assert_eq!(check_tree([d(0), d(0)]), Err(InvalidTree::MountainRange));
+        assert!(matches!(check_tree([d(1), d(2)]), Err(InvalidTree::Unfinalized(_))));
+    }
+
+    #[test]
+    fn tr_musig() {
+        let s = format!("tr(musig({KEY1},{KEY2}))");
+        let descr = TrMusig::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(descr.to_string(), s);
+        assert_eq!(descr.keychains(), bset![Keychain::OUTER, Keychain::INNER]);
+
+        let reversed = TrMusig::<XpubDerivable>::from_str(&format!("tr(musig({KEY2},{KEY1}))"));
+        let reversed = reversed.unwrap();
+        for index in 0u8..3 {
+            let script = descr.derive(1, index);
+            assert_eq!(script, reversed.derive(1, index));
+            let terminal = Terminal::new(1, NormalIndex::from(index));
+            let internal_pk = descr.key_agg_ctx(terminal).agg_xonly_pk();
+            assert_eq!(
+                script,
+                DerivedScript::TaprootKeyOnly(InternalPk::from_unchecked(internal_pk))
+            );
+        }
+        assert!(descr.xonly_keyset(Terminal::new(0, NormalIndex::from(0u8))).is_empty());
+        assert_eq!(TrMusig::<XpubDerivable>::new(vec![]), Err(MuSigError::NoKeys));
+        assert!(matches!(
+            TrMusig::<XpubDerivable>::from_str(&format!("tr(musig({KEY1}),pk({KEY2}))")),
+            Err(DescrParseError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn tr_script_roundtrip() {
+        let s = format!(
+            "tr({KEY1},{{pk({KEY2}),{{multi_a(1,{KEY1},{KEY2}),sortedmulti_a(2,{KEY1},{KEY2})}}}})"
+        );
+        let tr = TrScript::<XpubDerivable>::from_str(&s).unwrap();
+        assert_eq!(tr.as_tap_tree().len(), 3);
+        assert_eq!(tr.as_tap_tree()[0].0, u7::with(1));
+        assert_eq!(tr.to_string(), s);
+        assert_eq!(tr.keys().count(), 6);
+        assert_eq!(tr.keychains(), bset![Keychain::OUTER, Keychain::INNER]);
+    }
+
+    #[test]
+    fn tr_miniscript_leaf() {
+        let s = format!("tr({KEY1},and_v(v:pk({KEY2}),older(144)))");
+        let tr = TrScript::<XpubDerivable>::from_str(&s).unwrap();
+        assert!(matches!(tr.as_tap_tree()[0].1, TapLeafDescr::Miniscript(_)));
+        assert_eq!(tr.to_string(), s);
+        assert_eq!(tr.timelocks(), vec![Timelock::older(144)]);
+
+        let terminal = Terminal::new(0, NormalIndex::from(0u8));
+        let pk: XOnlyPk = XpubDerivable::from_str(KEY2).unwrap().derive(0, 0u8);
+        let mut expected = vec![32];
+        expected.extend(pk.to_byte_array());
+        expected.extend([OP_CHECKSIGVERIFY, 2, 0x90, 0x00, OP_CSV]);
+        assert_eq!(tr.as_tap_tree()[0].1.derive_script(terminal).to_vec(), expected);
+
+        assert!(TrScript::<XpubDerivable>::from_str(&format!("tr({KEY1},older(144))")).is_err());
+    }
+
+    #[test]
+    fn tr_script_derive() {
+        let s = format!("tr({KEY1},{{pk({KEY2}),multi_a(2,{KEY1},{KEY2})}})");
+        let tr = TrScript::<XpubDerivable>::from_str(&s).unwrap();
+        let terminal = Terminal::new(0, NormalIndex::from(5u8));
+        let script = tr.derive(terminal.keychain, terminal.index);
+        assert!(script.to_script_pubkey().is_p2tr());
+        let leafs = script.to_leaf_scripts();
+        assert_eq!(leafs.len(), 2);
+        for control_block in leafs.keys() {
+            assert_eq!(control_block.merkle_branch.len(), 1);
+        }
+
+        let keyset = tr.xonly_keyset(terminal);
+        assert_eq!(keyset.len(), 2);
+        let internal: XOnlyPk = tr.as_internal_key().derive(terminal.keychain, terminal.index);
+        // The internal key is also used in the multi_a leaf
+        assert_eq!(keyset[&internal].leaf_hashes.len(), 1);
+        let leaf_hashes = keyset.values().nth(1).unwrap().leaf_hashes.len();
+        assert_eq!(leaf_hashes, 2);
+    }
+
+    #[test]
+    fn tr_script_path_weight() {
+        let s = format!("tr({KEY1},{{pk({KEY2}),multi_a(2,{KEY1},{KEY2})}})");
+        let tr = TrScript::<XpubDerivable>::from_str(&s).unwrap();
+        let weight = |leaf| tr.path_satisfaction_weight(leaf).map(|w| w.to_u32());
+        assert_eq!(weight(None), Some(71));
+        assert_eq!(weight(Some(0)), Some(172));
+        assert_eq!(weight(Some(1)), Some(274));
+        assert_eq!(weight(Some(2)), None);
+        assert_eq!(tr.max_satisfaction_weight().to_u32(), 274);
+    }
+
+    #[test]
+    fn multi_a_script() {
+        let key: XpubDerivable = KEY1.parse().unwrap();
+        let terminal = Terminal::new(0, NormalIndex::from(0u8));
+        let pk: XOnlyPk = key.derive(0, 0u8);
+        let leaf = TapLeafDescr::MultiA {
+            threshold: 2,
+            keys: vec![key.clone(), key],
+        };
+        let script = leaf.derive_script(terminal);
+        let mut expected = vec![32];
+        expected.extend(pk.to_byte_array());
+        expected.push(OP_CHECKSIG);
+        expected.push(32);
+        expected.extend(pk.to_byte_array());
+        expected.extend([OP_CHECKSIGADD, 0x52, OP_NUMEQUAL]);
+        assert_eq!(script.as_slice(), expected.as_slice());
+    }
 }
-*/
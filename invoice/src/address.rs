@@ -26,6 +26,7 @@
 use std::fmt::{self, Debug, Display, Formatter};
 use std::str::FromStr;
 
+use bc::opcodes::OP_PUSHNUM_1;
 use bc::{
     InvalidPubkey, OutputPk, PubkeyHash, ScriptHash, ScriptPubkey, WPubkeyHash, WScriptHash,
     WitnessVer,
@@ -51,6 +52,10 @@ pub enum AddressError {
     InvalidTaprootKey,
     /// scriptPubkey can't be represented with any known address standard.
     UnsupportedScriptPubkey,
+
+    /// witness program must be of a future segwit version (2 to 16) and have
+    /// from 2 to 40 bytes.
+    InvalidFutureProgram,
 }
 
 /// Errors parsing address strings.
@@ -74,12 +79,15 @@ pub enum AddressParseError {
     /// unsupported future taproot version in address `{1}` detected by a length of {0}.
     FutureTaprootVersion(usize, String),
 
-    /// address has an unsupported future witness version {0}.
-    FutureWitnessVersion(WitnessVer),
+    /// address has a witness program of an invalid length {0}.
+    InvalidWitnessProgramLength(usize),
 
     /// address has an invalid Bech32 variant {0:?}.
     InvalidBech32Variant(bech32::Variant),
 
+    /// address human-readable part `{0}` doesn't match the expected `{1}`.
+    HrpMismatch(String, String),
+
     /// unrecognized address format in '{0}'.
     UnrecognizableFormat(String),
 
@@ -128,6 +136,37 @@ impl Address {
 
     /// Detects address type.
     pub fn address_type(self) -> AddressType { self.payload.address_type() }
+    /// Encodes the address using a custom bech32 human-readable part (HRP),
+    /// like the ones used by signet-based sidechains.
+    ///
+    /// Legacy (base58) addresses have no HRP and are encoded with the prefix
+    /// of their network.
+    pub fn to_string_with_hrp(&self, hrp: &str) -> Result<String, bech32::Error> {
+        let Some((version, variant, prog)) = self.payload.witness_program() else {
+            return Ok(self.to_string());
+        };
+        let mut data = vec![u5::try_from_u8(version.version_no()).expect("witness version <= 16")];
+        data.extend(bech32::ToBase32::to_base32(&prog));
+        bech32::encode(hrp, data, variant)
+    }
+
+    /// Parses a bech32 address with a custom human-readable part (HRP), like
+    /// the ones used by signet-based sidechains, assigning it the provided
+    /// network.
+    ///
+    /// Addresses with other HRPs or in base58 encoding are rejected.
+    pub fn from_str_with_hrp(
+        s: &str,
+        hrp: &str,
+        network: impl Into<AddressNetwork>,
+    ) -> Result<Self, AddressParseError> {
+        let (hri, data, variant) = bech32::decode(s)?;
+        if !hri.eq_ignore_ascii_case(hrp) {
+            return Err(AddressParseError::HrpMismatch(hri, hrp.to_owned()));
+        }
+        let payload = AddressPayload::from_bech32(s, &data, variant)?;
+        Ok(Address::new(payload, network.into()))
+    }
 }
 
 impl Display for Address {
@@ -145,17 +184,7 @@ impl Display for Address {
                 prefixed[1..].copy_from_slice(hash.as_ref());
                 return base58::encode_check_to_fmt(f, &prefixed[..]);
             }
-            AddressPayload::Wpkh(hash) => {
-                (WitnessVer::V0, bech32::Variant::Bech32, Box::new(hash) as Box<dyn AsRef<[u8]>>)
-            }
-            AddressPayload::Wsh(hash) => {
-                (WitnessVer::V0, bech32::Variant::Bech32, Box::new(hash) as Box<dyn AsRef<[u8]>>)
-            }
-            AddressPayload::Tr(pk) => (
-                WitnessVer::V1,
-                bech32::Variant::Bech32m,
-                Box::new(pk.to_byte_array()) as Box<dyn AsRef<[u8]>>,
-            ),
+            _ => self.payload.witness_program().expect("segwit payload"),
         };
 
         struct UpperWriter<W: fmt::Write>(W);
@@ -179,7 +208,7 @@ impl Display for Address {
             bech32::Bech32Writer::new(self.network.bech32_hrp(), variant, writer)?;
         let ver_u5 = u5::try_from_u8(version.version_no()).expect("witness version <= 16");
         bech32::WriteBase32::write_u5(&mut bech32_writer, ver_u5)?;
-        bech32::ToBase32::write_base32(&prog.as_ref(), &mut bech32_writer)
+        bech32::ToBase32::write_base32(&prog, &mut bech32_writer)
     }
 }
 
@@ -229,44 +258,7 @@ impl FromStr for Address {
                 "bcrt" | "BCRT" => AddressNetwork::Regtest,
                 _ => return parse_base58(),
             };
-            let (v, p5) = payload.split_at(1);
-            let wv = v[0].to_u8();
-            let version = WitnessVer::from_version_no(wv).map_err(|err| {
-                eprintln!("{err}");
-                AddressParseError::InvalidWitnessVersion(wv)
-            })?;
-            let program: Vec<u8> = bech32::FromBase32::from_base32(p5)?;
-            let payload = match (version, variant) {
-                (WitnessVer::V0, bech32::Variant::Bech32) if program.len() == 20 => {
-                    let mut hash = [0u8; 20];
-                    hash.copy_from_slice(&program);
-                    AddressPayload::Wpkh(hash.into())
-                }
-                (WitnessVer::V0, bech32::Variant::Bech32) if program.len() == 32 => {
-                    let mut hash = [0u8; 32];
-                    hash.copy_from_slice(&program);
-                    AddressPayload::Wsh(hash.into())
-                }
-                (WitnessVer::V1, bech32::Variant::Bech32m) if program.len() == 32 => {
-                    let mut key = [0u8; 32];
-                    key.copy_from_slice(&program);
-                    let pk = OutputPk::from_byte_array(key)?;
-                    AddressPayload::Tr(pk)
-                }
-
-                (WitnessVer::V1, bech32::Variant::Bech32m) => {
-                    return Err(AddressParseError::FutureTaprootVersion(
-                        program.len(),
-                        s.to_owned(),
-                    ))
-                }
-
-                (WitnessVer::V0 | WitnessVer::V1, wrong) => {
-                    return Err(AddressParseError::InvalidBech32Variant(wrong))
-                }
-
-                (future, _) => return Err(AddressParseError::FutureWitnessVersion(future)),
-            };
+            let payload = AddressPayload::from_bech32(s, &payload, variant)?;
             Ok(Address::new(payload, network))
         };
 
@@ -301,6 +293,10 @@ pub enum AddressPayload {
     /// P2TR payload.
     #[from]
     Tr(OutputPk),
+
+    /// Witness program of a future segwit version.
+    #[from]
+    Future(FutureProgram),
 }
 
 impl AddressPayload {
@@ -321,7 +317,7 @@ impl AddressPayload {
             AddressPayload::Pkh(PubkeyHash::from(bytes))
         } else if script.is_p2sh() {
             let mut bytes = [0u8; 20];
-            bytes.copy_from_slice(&script[2..]);
+            bytes.copy_from_slice(&script[2..22]);
             AddressPayload::Sh(ScriptHash::from(bytes))
         } else if script.is_p2wpkh() {
             let mut bytes = [0u8; 20];
@@ -337,11 +333,77 @@ impl AddressPayload {
             AddressPayload::Tr(
                 OutputPk::from_byte_array(bytes).map_err(|_| AddressError::InvalidTaprootKey)?,
             )
+        } else if let Some(program) = FutureProgram::from_script(script) {
+            AddressPayload::Future(program)
         } else {
             return Err(AddressError::UnsupportedScriptPubkey);
         })
     }
 
+    /// Decodes segwit payload from the bech32 data part of the address string
+    /// `s`.
+    fn from_bech32(
+        s: &str,
+        data: &[u5],
+        variant: bech32::Variant,
+    ) -> Result<Self, AddressParseError> {
+        let (v, p5) = data.split_first().ok_or(bech32::Error::InvalidLength)?;
+        let wv = v.to_u8();
+        let version = WitnessVer::from_version_no(wv).map_err(|err| {
+            eprintln!("{err}");
+            AddressParseError::InvalidWitnessVersion(wv)
+        })?;
+        let program: Vec<u8> = bech32::FromBase32::from_base32(p5)?;
+        Ok(match (version, variant) {
+            (WitnessVer::V0, bech32::Variant::Bech32) if program.len() == 20 => {
+                let mut hash = [0u8; 20];
+                hash.copy_from_slice(&program);
+                AddressPayload::Wpkh(hash.into())
+            }
+            (WitnessVer::V0, bech32::Variant::Bech32) if program.len() == 32 => {
+                let mut hash = [0u8; 32];
+                hash.copy_from_slice(&program);
+                AddressPayload::Wsh(hash.into())
+            }
+            (WitnessVer::V1, bech32::Variant::Bech32m) if program.len() == 32 => {
+                let mut key = [0u8; 32];
+                key.copy_from_slice(&program);
+                let pk = OutputPk::from_byte_array(key)?;
+                AddressPayload::Tr(pk)
+            }
+
+            (WitnessVer::V1, bech32::Variant::Bech32m) => {
+                return Err(AddressParseError::FutureTaprootVersion(program.len(), s.to_owned()))
+            }
+
+            (future, bech32::Variant::Bech32m) => FutureProgram::new(future, &program)
+                .map_err(|_| AddressParseError::InvalidWitnessProgramLength(program.len()))?
+                .into(),
+
+            (_, wrong) => return Err(AddressParseError::InvalidBech32Variant(wrong)),
+        })
+    }
+
+    /// Returns witness version, bech32 variant and witness program for the
+    /// segwit payloads, or `None` for the legacy ones.
+    fn witness_program(self) -> Option<(WitnessVer, bech32::Variant, Vec<u8>)> {
+        Some(match self {
+            AddressPayload::Pkh(_) | AddressPayload::Sh(_) => return None,
+            AddressPayload::Wpkh(hash) => {
+                (WitnessVer::V0, bech32::Variant::Bech32, AsRef::<[u8]>::as_ref(&hash).to_vec())
+            }
+            AddressPayload::Wsh(hash) => {
+                (WitnessVer::V0, bech32::Variant::Bech32, AsRef::<[u8]>::as_ref(&hash).to_vec())
+            }
+            AddressPayload::Tr(pk) => {
+                (WitnessVer::V1, bech32::Variant::Bech32m, pk.to_byte_array().to_vec())
+            }
+            AddressPayload::Future(program) => {
+                (program.version(), bech32::Variant::Bech32m, program.program().to_vec())
+            }
+        })
+    }
+
     /// Returns script corresponding to the given address.
     pub fn script_pubkey(self) -> ScriptPubkey {
         match self {
@@ -350,6 +412,7 @@ impl AddressPayload {
             AddressPayload::Wpkh(hash) => ScriptPubkey::p2wpkh(hash),
             AddressPayload::Wsh(hash) => ScriptPubkey::p2wsh(hash),
             AddressPayload::Tr(output_key) => ScriptPubkey::p2tr_tweaked(output_key),
+            AddressPayload::Future(program) => program.script_pubkey(),
         }
     }
 
@@ -361,8 +424,63 @@ impl AddressPayload {
             AddressPayload::Wpkh(_) => AddressType::P2wpkh,
             AddressPayload::Wsh(_) => AddressType::P2wsh,
             AddressPayload::Tr(_) => AddressType::P2tr,
+            AddressPayload::Future(program) => AddressType::Future(program.version()),
         }
     }
+
+    /// Detects whether the payload is a witness program of a future segwit
+    /// version, which can't be spent under the current consensus rules.
+    pub fn is_future_segwit(self) -> bool { matches!(self, AddressPayload::Future(_)) }
+}
+
+/// Witness program of a future segwit version (2 to 16), encoded in addresses
+/// with bech32m.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct FutureProgram {
+    version: WitnessVer,
+    len: u8,
+    program: [u8; 40],
+}
+
+impl FutureProgram {
+    /// Constructs witness program, checking its version and length.
+    pub fn new(version: WitnessVer, program: &[u8]) -> Result<Self, AddressError> {
+        if version.version_no() < 2 || !(2..=40).contains(&program.len()) {
+            return Err(AddressError::InvalidFutureProgram);
+        }
+        let mut data = [0u8; 40];
+        data[..program.len()].copy_from_slice(program);
+        Ok(FutureProgram {
+            version,
+            len: program.len() as u8,
+            program: data,
+        })
+    }
+
+    fn from_script(script: &ScriptPubkey) -> Option<Self> {
+        let (&version, rest) = script.split_first()?;
+        let (&len, program) = rest.split_first()?;
+        if program.len() != len as usize {
+            return None;
+        }
+        let version = WitnessVer::from_version_no(version.checked_sub(OP_PUSHNUM_1 - 1)?).ok()?;
+        Self::new(version, program).ok()
+    }
+
+    /// Returns the witness version.
+    pub fn version(&self) -> WitnessVer { self.version }
+
+    /// Returns the witness program bytes.
+    pub fn program(&self) -> &[u8] { &self.program[..self.len as usize] }
+
+    /// Returns script corresponding to the witness program.
+    pub fn script_pubkey(&self) -> ScriptPubkey {
+        let mut script = Vec::with_capacity(self.len as usize + 2);
+        script.push(self.version as u8);
+        script.push(self.len);
+        script.extend_from_slice(self.program());
+        ScriptPubkey::from_unsafe(script)
+    }
 }
 
 impl From<AddressPayload> for ScriptPubkey {
@@ -391,6 +509,10 @@ pub enum AddressType {
     /// Pay-to-taproot
     #[display("P2TR")]
     P2tr,
+
+    /// Pay-to-witness program of a future segwit version
+    #[display(inner)]
+    Future(WitnessVer),
 }
 
 impl AddressType {
@@ -402,6 +524,7 @@ impl AddressType {
             AddressType::P2sh => None,
             AddressType::P2wpkh | AddressType::P2wsh => Some(WitnessVer::V0),
             AddressType::P2tr => Some(WitnessVer::V1),
+            AddressType::Future(version) => Some(version),
         }
     }
 }
@@ -484,4 +607,64 @@ mod test {
         let b32 = "tb1p5kgdjdf99vfa2xwufd2cx2qru468z79s2arn3jf5feg95d9m62gqzpnjjk";
         assert_eq!(Address::from_str(b32).unwrap().to_string(), b32);
     }
+
+    #[test]
+    fn future_segwit() {
+        let b32 = "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs";
+        let addr = Address::from_str(b32).unwrap();
+        assert!(addr.payload.is_future_segwit());
+        assert_eq!(addr.address_type(), AddressType::Future(WitnessVer::V2));
+        assert_eq!(addr.to_string(), b32);
+        assert_eq!(addr.script_pubkey().as_slice(), [
+            0x52, 0x10, 0x75, 0x1e, 0x76, 0xe8, 0x19, 0x91, 0x96, 0xd4, 0x54, 0x94, 0x1c, 0x45,
+            0xd1, 0xb3, 0xa3, 0x23
+        ]);
+        assert_eq!(AddressPayload::from_script(&addr.script_pubkey()), Ok(addr.payload));
+
+        let addr = Address::from_str("BC1SW50QGDZ25J").unwrap();
+        assert_eq!(addr.address_type(), AddressType::Future(WitnessVer::V16));
+        assert_eq!(format!("{addr:#}"), "BC1SW50QGDZ25J");
+
+        let (hrp, data, _) = bech32::decode(b32).unwrap();
+        let bech32 = bech32::encode(&hrp, data, bech32::Variant::Bech32).unwrap();
+        assert!(matches!(
+            Address::from_str(&bech32),
+            Err(AddressParseError::InvalidBech32Variant(bech32::Variant::Bech32))
+        ));
+        assert_eq!(
+            FutureProgram::new(WitnessVer::V1, &[0u8; 32]),
+            Err(AddressError::InvalidFutureProgram)
+        );
+        assert_eq!(
+            FutureProgram::new(WitnessVer::V3, &[0u8; 41]),
+            Err(AddressError::InvalidFutureProgram)
+        );
+    }
+
+    #[test]
+    fn regtest() {
+        let b32 = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+        let addr = Address::from_str(b32).unwrap();
+        assert_eq!(addr.network, AddressNetwork::Regtest);
+        assert_eq!(addr.address_type(), AddressType::P2wpkh);
+        assert_eq!(addr.to_string(), b32);
+    }
+
+    #[test]
+    fn custom_hrp() {
+        let b32 = "tb1p5kgdjdf99vfa2xwufd2cx2qru468z79s2arn3jf5feg95d9m62gqzpnjjk";
+        let addr = Address::from_str(b32).unwrap();
+        let custom = addr.to_string_with_hrp("tex").unwrap();
+        assert!(custom.starts_with("tex1p"));
+        assert!(Address::from_str(&custom).is_err());
+        assert_eq!(Address::from_str_with_hrp(&custom, "tex", AddressNetwork::Testnet), Ok(addr));
+        assert_eq!(
+            Address::from_str_with_hrp(b32, "tex", AddressNetwork::Testnet),
+            Err(AddressParseError::HrpMismatch(s!("tb"), s!("tex")))
+        );
+        assert!(addr.to_string_with_hrp("").is_err());
+
+        let legacy = Address::from_str("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn").unwrap();
+        assert_eq!(legacy.to_string_with_hrp("tex").unwrap(), legacy.to_string());
+    }
 }
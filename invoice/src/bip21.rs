@@ -0,0 +1,328 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! BIP-21 `bitcoin:` payment URIs.
+
+use std::fmt::{self, Display, Formatter, Write};
+use std::str::FromStr;
+
+use bc::Sats;
+
+use crate::{Address, AddressParseError};
+
+/// URI scheme used by BIP-21 payment URIs.
+pub const BIP21_SCHEME: &str = "bitcoin";
+
+const PARAM_AMOUNT: &str = "amount";
+const PARAM_LABEL: &str = "label";
+const PARAM_MESSAGE: &str = "message";
+const PARAM_LIGHTNING: &str = "lightning";
+const PARAM_PAYJOIN: &str = "pj";
+
+/// Errors parsing BIP-21 payment URIs.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum Bip21ParseError {
+    /// URI '{0}' doesn't start with `bitcoin:` scheme.
+    InvalidScheme(String),
+
+    /// invalid address in payment URI - {0}
+    #[from]
+    Address(AddressParseError),
+
+    /// invalid payment amount '{0}'.
+    InvalidAmount(String),
+
+    /// invalid percent-encoding in '{0}'.
+    InvalidEncoding(String),
+
+    /// query parameter '{0}' is specified more than once.
+    RepeatedParam(String),
+
+    /// payment URI requires support of unknown parameter '{0}'.
+    UnsupportedRequiredParam(String),
+}
+
+/// BIP-21 payment URI.
+///
+/// Parameters besides `amount`, `label` and `message` are kept in
+/// [`Bip21Uri::params`] in the order of their appearance; the commonly used
+/// `lightning` and `pj` (BIP-78 payjoin) ones can be accessed with
+/// [`Bip21Uri::lightning`] and [`Bip21Uri::payjoin`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Bip21Uri {
+    /// Address to pay to.
+    pub address: Address,
+
+    /// Requested amount.
+    pub amount: Option<Sats>,
+
+    /// Label for the address (e.g. name of the receiver).
+    pub label: Option<String>,
+
+    /// Message describing the payment.
+    pub message: Option<String>,
+
+    /// Other query parameters with their percent-decoded values.
+    pub params: Vec<(String, String)>,
+}
+
+impl From<Address> for Bip21Uri {
+    fn from(address: Address) -> Self { Bip21Uri::new(address) }
+}
+
+impl Bip21Uri {
+    /// Constructs payment URI without amount and parameters.
+    pub fn new(address: Address) -> Self {
+        Bip21Uri {
+            address,
+            amount: None,
+            label: None,
+            message: None,
+            params: vec![],
+        }
+    }
+
+    /// Returns value of the query parameter with a given name, if present.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Sets value of the query parameter, replacing the existing one.
+    ///
+    /// Use [`Bip21Uri::amount`], [`Bip21Uri::label`] and [`Bip21Uri::message`]
+    /// fields instead of this method for the standard parameters.
+    pub fn set_param(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+        match self.params.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.params.push((name, value)),
+        }
+    }
+
+    /// Returns BOLT-11 lightning invoice provided as a fallback with `lightning`
+    /// parameter.
+    pub fn lightning(&self) -> Option<&str> { self.param(PARAM_LIGHTNING) }
+
+    /// Returns BIP-78 payjoin endpoint provided with `pj` parameter.
+    pub fn payjoin(&self) -> Option<&str> { self.param(PARAM_PAYJOIN) }
+}
+
+impl Display for Bip21Uri {
+    /// Alternate formatting produces upper-case scheme and address, which
+    /// allows more compact QR codes for bech32 addresses.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}:{:#}", BIP21_SCHEME.to_uppercase(), self.address)?;
+        } else {
+            write!(f, "{BIP21_SCHEME}:{}", self.address)?;
+        }
+        let mut sep = '?';
+        let mut param = |f: &mut Formatter, name: &str| -> fmt::Result {
+            f.write_char(sep)?;
+            sep = '&';
+            write!(f, "{name}=")
+        };
+        if let Some(amount) = self.amount {
+            param(f, PARAM_AMOUNT)?;
+            write_btc(f, amount)?;
+        }
+        if let Some(label) = &self.label {
+            param(f, PARAM_LABEL)?;
+            percent_encode(f, label)?;
+        }
+        if let Some(message) = &self.message {
+            param(f, PARAM_MESSAGE)?;
+            percent_encode(f, message)?;
+        }
+        for (name, value) in &self.params {
+            param(f, name)?;
+            percent_encode(f, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Bip21Uri {
+    type Err = Bip21ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) =
+            s.split_once(':').ok_or_else(|| Bip21ParseError::InvalidScheme(s.to_owned()))?;
+        if !scheme.eq_ignore_ascii_case(BIP21_SCHEME) {
+            return Err(Bip21ParseError::InvalidScheme(s.to_owned()));
+        }
+        let (addr, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut uri = Bip21Uri::new(Address::from_str(addr)?);
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            let repeated = || Bip21ParseError::RepeatedParam(name.to_owned());
+            match name {
+                PARAM_AMOUNT if uri.amount.is_some() => return Err(repeated()),
+                PARAM_AMOUNT => uri.amount = Some(parse_btc(&value)?),
+                PARAM_LABEL if uri.label.is_some() => return Err(repeated()),
+                PARAM_LABEL => uri.label = Some(value),
+                PARAM_MESSAGE if uri.message.is_some() => return Err(repeated()),
+                PARAM_MESSAGE => uri.message = Some(value),
+                name if name.starts_with("req-") => {
+                    return Err(Bip21ParseError::UnsupportedRequiredParam(name.to_owned()))
+                }
+                name if uri.param(name).is_some() => return Err(repeated()),
+                name => uri.params.push((name.to_owned(), value)),
+            }
+        }
+
+        Ok(uri)
+    }
+}
+
+fn write_btc(f: &mut Formatter, amount: Sats) -> fmt::Result {
+    let (btc, sats) = amount.btc_sats();
+    write!(f, "{btc}")?;
+    if sats > 0 {
+        let frac = format!("{sats:08}");
+        write!(f, ".{}", frac.trim_end_matches('0'))?;
+    }
+    Ok(())
+}
+
+fn parse_btc(s: &str) -> Result<Sats, Bip21ParseError> {
+    let err = || Bip21ParseError::InvalidAmount(s.to_owned());
+    let (btc, frac) = s.split_once('.').unwrap_or((s, ""));
+    if (btc.is_empty() && frac.is_empty())
+        || frac.len() > 8
+        || !btc.bytes().chain(frac.bytes()).all(|c| c.is_ascii_digit())
+    {
+        return Err(err());
+    }
+    let btc = match btc {
+        "" => 0,
+        btc => u64::from_str(btc).map_err(|_| err())?,
+    };
+    let sats = match frac {
+        "" => 0,
+        frac => u64::from_str(&format!("{frac:0<8}")).map_err(|_| err())?,
+    };
+    btc.checked_mul(Sats::BTC.0).and_then(|btc| btc.checked_add(sats)).map(Sats).ok_or_else(err)
+}
+
+fn percent_encode(f: &mut Formatter, s: &str) -> fmt::Result {
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                f.write_char(byte as char)?
+            }
+            _ => write!(f, "%{byte:02X}")?,
+        }
+    }
+    Ok(())
+}
+
+fn percent_decode(s: &str) -> Result<String, Bip21ParseError> {
+    let err = || Bip21ParseError::InvalidEncoding(s.to_owned());
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(byte) = iter.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex = [iter.next().ok_or_else(err)?, iter.next().ok_or_else(err)?];
+        let hex = std::str::from_utf8(&hex).map_err(|_| err())?;
+        bytes.push(u8::from_str_radix(hex, 16).map_err(|_| err())?);
+    }
+    String::from_utf8(bytes).map_err(|_| err())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ADDR: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+    #[test]
+    fn address_only() {
+        let s = format!("bitcoin:{ADDR}");
+        let uri = Bip21Uri::from_str(&s).unwrap();
+        assert_eq!(uri, Bip21Uri::new(Address::from_str(ADDR).unwrap()));
+        assert_eq!(uri.to_string(), s);
+        assert_eq!(format!("{uri:#}"), "BITCOIN:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ");
+        assert_eq!(Bip21Uri::from_str(&format!("{uri:#}")).unwrap(), uri);
+    }
+
+    #[test]
+    fn params() {
+        let s = format!(
+            "bitcoin:{ADDR}?amount=20.3&label=Luke-Jr&message=Donation%20for%20project%20xyz&\
+             lightning=lnbc1pvjluez&pj=https%3A%2F%2Fexample.com%2Fpj"
+        );
+        let uri = Bip21Uri::from_str(&s).unwrap();
+        assert_eq!(uri.amount, Some(Sats(2_030_000_000)));
+        assert_eq!(uri.label.as_deref(), Some("Luke-Jr"));
+        assert_eq!(uri.message.as_deref(), Some("Donation for project xyz"));
+        assert_eq!(uri.lightning(), Some("lnbc1pvjluez"));
+        assert_eq!(uri.payjoin(), Some("https://example.com/pj"));
+        assert_eq!(uri.to_string(), s);
+    }
+
+    #[test]
+    fn amounts() {
+        for (s, sats) in
+            [("50", 5_000_000_000), ("0.00000001", 1), (".5", 50_000_000), ("1.", 100_000_000)]
+        {
+            assert_eq!(parse_btc(s).unwrap(), Sats(sats));
+        }
+        for s in ["", ".", "1.000000001", "-1", "1e3", "1,5", "184467440737.09551616"] {
+            assert_eq!(parse_btc(s), Err(Bip21ParseError::InvalidAmount(s.to_owned())));
+        }
+
+        let mut uri = Bip21Uri::new(Address::from_str(ADDR).unwrap());
+        uri.amount = Some(Sats::BTC);
+        assert_eq!(uri.to_string(), format!("bitcoin:{ADDR}?amount=1"));
+        uri.amount = Some(Sats(1_001));
+        assert_eq!(uri.to_string(), format!("bitcoin:{ADDR}?amount=0.00001001"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Bip21Uri::from_str(&format!("litecoin:{ADDR}")),
+            Err(Bip21ParseError::InvalidScheme(_))
+        ));
+        assert!(matches!(Bip21Uri::from_str("bitcoin:invalid"), Err(Bip21ParseError::Address(_))));
+        assert_eq!(
+            Bip21Uri::from_str(&format!("bitcoin:{ADDR}?req-somethingyoudontunderstand=50")),
+            Err(Bip21ParseError::UnsupportedRequiredParam(s!("req-somethingyoudontunderstand")))
+        );
+        assert_eq!(
+            Bip21Uri::from_str(&format!("bitcoin:{ADDR}?amount=1&amount=2")),
+            Err(Bip21ParseError::RepeatedParam(s!("amount")))
+        );
+        assert_eq!(
+            Bip21Uri::from_str(&format!("bitcoin:{ADDR}?label=%2")),
+            Err(Bip21ParseError::InvalidEncoding(s!("%2")))
+        );
+    }
+}
@@ -27,9 +27,12 @@ extern crate serde_crate as serde;
 
 pub mod base58;
 mod address;
+mod bip21;
 mod network;
 
 pub use address::{
     Address, AddressError, AddressNetwork, AddressParseError, AddressPayload, AddressType,
+    FutureProgram,
 };
+pub use bip21::{Bip21ParseError, Bip21Uri, BIP21_SCHEME};
 pub use network::{Network, UnknownNetwork};
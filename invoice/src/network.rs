@@ -37,9 +37,12 @@ pub enum Network {
     #[display("bitcoin")]
     Mainnet,
 
-    /// Bitcoin testnet
+    /// Bitcoin testnet version 3
     Testnet3,
 
+    /// Bitcoin testnet version 4 (BIP-94)
+    Testnet4,
+
     /// Bitcoin signet
     Signet,
 
@@ -57,7 +60,7 @@ impl From<Network> for AddressNetwork {
     fn from(network: Network) -> Self {
         match network {
             Network::Mainnet => AddressNetwork::Mainnet,
-            Network::Testnet3 | Network::Signet => AddressNetwork::Testnet,
+            Network::Testnet3 | Network::Testnet4 | Network::Signet => AddressNetwork::Testnet,
             Network::Regtest => AddressNetwork::Regtest,
         }
     }
@@ -74,9 +77,40 @@ impl FromStr for Network {
         Ok(match s {
             "bitcoin" | "mainnet" => Network::Mainnet,
             "testnet" | "testnet3" => Network::Testnet3,
+            "testnet4" => Network::Testnet4,
             "signet" => Network::Signet,
             "regtest" => Network::Regtest,
             other => return Err(UnknownNetwork(other.to_owned())),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_from_str() {
+        for network in [
+            Network::Mainnet,
+            Network::Testnet3,
+            Network::Testnet4,
+            Network::Signet,
+            Network::Regtest,
+        ] {
+            assert_eq!(Network::from_str(&network.to_string()), Ok(network));
+        }
+        assert_eq!(Network::Testnet4.to_string(), "testnet4");
+        assert_eq!(Network::from_str("mainnet"), Ok(Network::Mainnet));
+        assert_eq!(Network::from_str("testnet"), Ok(Network::Testnet3));
+        assert_eq!(Network::from_str("testnet5"), Err(UnknownNetwork(s!("testnet5"))));
+    }
+
+    #[test]
+    fn address_network() {
+        assert!(Network::Testnet4.is_testnet());
+        assert_eq!(AddressNetwork::from(Network::Testnet4), AddressNetwork::Testnet);
+        assert_eq!(AddressNetwork::from(Network::Testnet3), AddressNetwork::Testnet);
+        assert_eq!(AddressNetwork::from(Network::Regtest), AddressNetwork::Regtest);
+    }
+}
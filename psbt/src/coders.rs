@@ -26,13 +26,13 @@ use std::string::FromUtf8Error;
 use amplify::num::u7;
 use amplify::{confinement, Array, Bytes, Bytes32, Bytes4, IoError, Wrapper};
 use derive::{
-    Bip340Sig, ByteStr, CompressedPk, ConsensusDataError, ConsensusDecode, ConsensusDecodeError,
-    ConsensusEncode, ControlBlock, DerivationPath, Idx, InternalPk, InvalidLeafVer, InvalidTree,
-    KeyOrigin, LeafInfo, LeafScript, LeafVer, LegacyPk, LegacySig, LockHeight, LockTime,
-    LockTimestamp, NonStandardValue, Outpoint, RedeemScript, Sats, ScriptBytes, ScriptPubkey,
-    SeqNo, SigError, SigScript, SighashType, TapDerivation, TapLeafHash, TapNodeHash, TapTree, Tx,
-    TxOut, TxVer, Txid, UncompressedPk, VarInt, VarIntArray, Vout, Witness, WitnessScript, XOnlyPk,
-    Xpub, XpubDecodeError, XpubFp, XpubOrigin,
+    serialize_control_block, Bip340Sig, ByteStr, CompressedPk, ConsensusDataError, ConsensusDecode,
+    ConsensusDecodeError, ConsensusEncode, ControlBlock, DerivationPath, Idx, InternalPk,
+    InvalidLeafVer, InvalidTree, KeyOrigin, LeafInfo, LeafScript, LeafVer, LegacyPk, LegacySig,
+    LockHeight, LockTime, LockTimestamp, NonStandardValue, Outpoint, RedeemScript, Sats,
+    ScriptBytes, ScriptPubkey, SeqNo, SigError, SigScript, SighashType, TapDerivation, TapLeafHash,
+    TapNodeHash, TapTree, Tx, TxOut, TxVer, Txid, UncompressedPk, VarInt, VarIntArray, Vout,
+    Witness, WitnessScript, XOnlyPk, Xpub, XpubDecodeError, XpubFp, XpubOrigin,
 };
 
 use crate::keys::KeyValue;
@@ -185,7 +185,7 @@ pub trait Encode {
     fn encode(&self, writer: &mut dyn Write) -> Result<usize, IoError>;
 }
 
-impl<'a, T: Encode> Encode for &'a T {
+impl<T: Encode> Encode for &T {
     fn encode(&self, writer: &mut dyn Write) -> Result<usize, IoError> { (*self).encode(writer) }
 }
 
@@ -740,7 +740,17 @@ impl Decode for LockHeight {
 }
 
 psbt_code_using_consensus!(Witness);
-psbt_code_using_consensus!(ControlBlock);
+psbt_decode_from_consensus!(ControlBlock);
+
+// Consensus encoding of control block loses output key parity bit, thus we
+// have to do our own encoding here.
+impl Encode for ControlBlock {
+    fn encode(&self, writer: &mut dyn Write) -> Result<usize, IoError> {
+        let data = serialize_control_block(self);
+        writer.write_all(&data)?;
+        Ok(data.len())
+    }
+}
 
 impl Encode for ScriptBytes {
     fn encode(&self, writer: &mut dyn Write) -> Result<usize, IoError> {
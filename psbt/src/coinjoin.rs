@@ -0,0 +1,68 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contribution of the wallet inputs to externally coordinated transactions, like coinjoins.
+//!
+//! The coordinator provides PSBT spending coins of multiple participants. Each participant
+//! identifies its own inputs, fills them with the key derivation information, verifies that the
+//! transaction contains all the outputs it expects (see
+//! [`PsbtConstructor::contribute_coinjoin`]) and signs only its own inputs (see
+//! [`Psbt::sign_inputs`]).
+//!
+//! [`PsbtConstructor::contribute_coinjoin`]: crate::PsbtConstructor::contribute_coinjoin
+
+use derive::TxOut;
+
+use crate::{ConstructionError, Output, Psbt};
+
+#[derive(Clone, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CoinjoinError {
+    #[display(inner)]
+    #[from]
+    Construction(ConstructionError),
+
+    /// coinjoin transaction doesn't spend any of the wallet coins.
+    NoWalletInputs,
+
+    /// input {0} spends a wallet coin, but provides a different spent output.
+    PrevoutMismatch(usize),
+
+    /// expected output #{0} is missing from the coinjoin transaction or has a different amount.
+    MissingOutput(usize),
+}
+
+impl Psbt {
+    /// Checks that the PSBT contains all the `expected` outputs, matching each of them with a
+    /// distinct PSBT output with the same script pubkey and amount.
+    pub fn check_outputs(&self, expected: &[TxOut]) -> Result<(), CoinjoinError> {
+        let mut available = self.outputs().map(Output::to_txout).collect::<Vec<_>>();
+        for (no, txout) in expected.iter().enumerate() {
+            let pos = available
+                .iter()
+                .position(|out| out == txout)
+                .ok_or(CoinjoinError::MissingOutput(no))?;
+            available.swap_remove(pos);
+        }
+        Ok(())
+    }
+}
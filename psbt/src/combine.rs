@@ -0,0 +1,134 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! PSBT combiner role.
+
+use std::hash::Hash;
+
+use derive::Txid;
+use indexmap::IndexMap;
+
+use crate::{Input, KeyData, Output, Psbt, ValueData};
+
+/// PSBTs can't be combined since they are spending different transactions
+/// ({expected} and {found}).
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub struct TxMismatch {
+    pub expected: Txid,
+    pub found: Txid,
+}
+
+impl Psbt {
+    /// Merges into this PSBT partial signatures, key origins, scripts,
+    /// proprietary and unknown fields from another copy of the same PSBT.
+    ///
+    /// Data already present in this PSBT take precedence over the data from
+    /// the `other` PSBT.
+    ///
+    /// # Errors
+    ///
+    /// If the other PSBT represents a different unsigned transaction.
+    pub fn combine(&mut self, other: Psbt) -> Result<(), TxMismatch> {
+        let expected = self.txid();
+        let found = other.txid();
+        if expected != found {
+            return Err(TxMismatch { expected, found });
+        }
+
+        merge_opt(&mut self.tx_modifiable, other.tx_modifiable);
+        merge_map(&mut self.xpubs, other.xpubs);
+        merge_map(&mut self.proprietary, other.proprietary);
+        merge_unknown(&mut self.unknown, other.unknown);
+
+        for (input, theirs) in self.inputs.iter_mut().zip(other.inputs) {
+            input.combine(theirs);
+        }
+        for (output, theirs) in self.outputs.iter_mut().zip(other.outputs) {
+            output.combine(theirs);
+        }
+        Ok(())
+    }
+}
+
+impl Input {
+    fn combine(&mut self, other: Input) {
+        merge_opt(&mut self.required_time_lock, other.required_time_lock);
+        merge_opt(&mut self.required_height_lock, other.required_height_lock);
+        merge_opt(&mut self.non_witness_tx, other.non_witness_tx);
+        merge_opt(&mut self.witness_utxo, other.witness_utxo);
+        merge_map(&mut self.partial_sigs, other.partial_sigs);
+        merge_opt(&mut self.sighash_type, other.sighash_type);
+        merge_opt(&mut self.redeem_script, other.redeem_script);
+        merge_opt(&mut self.witness_script, other.witness_script);
+        merge_map(&mut self.bip32_derivation, other.bip32_derivation);
+        merge_opt(&mut self.final_script_sig, other.final_script_sig);
+        merge_opt(&mut self.final_witness, other.final_witness);
+        merge_opt(&mut self.proof_of_reserves, other.proof_of_reserves);
+        merge_map(&mut self.ripemd160, other.ripemd160);
+        merge_map(&mut self.sha256, other.sha256);
+        merge_map(&mut self.hash160, other.hash160);
+        merge_map(&mut self.hash256, other.hash256);
+        merge_opt(&mut self.tap_key_sig, other.tap_key_sig);
+        merge_map(&mut self.tap_script_sig, other.tap_script_sig);
+        merge_map(&mut self.tap_leaf_script, other.tap_leaf_script);
+        merge_map(&mut self.tap_bip32_derivation, other.tap_bip32_derivation);
+        merge_opt(&mut self.tap_internal_key, other.tap_internal_key);
+        merge_opt(&mut self.tap_merkle_root, other.tap_merkle_root);
+        merge_map(&mut self.proprietary, other.proprietary);
+        merge_unknown(&mut self.unknown, other.unknown);
+    }
+}
+
+impl Output {
+    fn combine(&mut self, other: Output) {
+        merge_opt(&mut self.redeem_script, other.redeem_script);
+        merge_opt(&mut self.witness_script, other.witness_script);
+        merge_map(&mut self.bip32_derivation, other.bip32_derivation);
+        merge_opt(&mut self.tap_internal_key, other.tap_internal_key);
+        merge_opt(&mut self.tap_tree, other.tap_tree);
+        merge_map(&mut self.tap_bip32_derivation, other.tap_bip32_derivation);
+        merge_map(&mut self.proprietary, other.proprietary);
+        merge_unknown(&mut self.unknown, other.unknown);
+    }
+}
+
+fn merge_opt<T>(ours: &mut Option<T>, theirs: Option<T>) {
+    if ours.is_none() {
+        *ours = theirs;
+    }
+}
+
+fn merge_map<K: Hash + Eq, V>(ours: &mut IndexMap<K, V>, theirs: IndexMap<K, V>) {
+    for (key, value) in theirs {
+        ours.entry(key).or_insert(value);
+    }
+}
+
+fn merge_unknown(
+    ours: &mut IndexMap<u8, IndexMap<KeyData, ValueData>>,
+    theirs: IndexMap<u8, IndexMap<KeyData, ValueData>>,
+) {
+    for (key_type, map) in theirs {
+        merge_map(ours.entry(key_type).or_default(), map);
+    }
+}
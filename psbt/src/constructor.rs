@@ -24,12 +24,19 @@ use std::num::ParseIntError;
 use std::str::FromStr;
 
 use derive::{
-    Address, AddressParseError, Keychain, LockTime, Network, NormalIndex, Outpoint, Sats,
-    ScriptPubkey, SeqNo, Terminal, Vout,
+    Address, AddressParseError, Derive, Idx, Keychain, LockTime, Network, NormalIndex, Outpoint,
+    Sats, ScriptPubkey, SeqNo, SighashType, Terminal, Tx, TxOut, Txid, VarInt, Vout, Weight,
+    WeightUnits,
+};
+use descriptors::{Descriptor, Timelock};
+use indexmap::IndexMap;
+
+use crate::report::claimed_terminals;
+use crate::{
+    dust_limit, por_challenge, por_script_pubkey, AnalysisError, CoinjoinError, FeeRate,
+    OutputReport, PayjoinError, PayjoinParams, Prevout, PrevoutError, Psbt, PsbtError, PsbtReport,
+    PsbtVer,
 };
-use descriptors::Descriptor;
-
-use crate::{Prevout, Psbt, PsbtError, PsbtVer};
 
 #[derive(Clone, Debug, Display, Error, From)]
 #[display(doc_comments)]
@@ -37,9 +44,16 @@ pub enum ConstructionError {
     #[display(inner)]
     Psbt(PsbtError),
 
+    #[display(inner)]
+    #[from]
+    Prevout(PrevoutError),
+
     /// impossible to construct transaction having no inputs.
     NoInputs,
 
+    /// coins {0:?} are not known to the wallet.
+    UnknownCoins(Vec<Outpoint>),
+
     /// the total payment amount ({0} sats) exceeds number of sats in existence.
     Overflow(Sats),
 
@@ -50,6 +64,20 @@ pub enum ConstructionError {
         output_value: Sats,
     },
 
+    /// input {0} spends a non-segwit output, but the transaction containing it is not known.
+    NoPrevTx(Outpoint),
+
+    /// payment to {0} uses a future segwit version, which is not allowed by the transaction
+    /// parameters.
+    FutureSegwit(Address),
+
+    /// output paying {amount} sats to {address} is below the dust limit of {dust_limit} sats.
+    DustOutput {
+        address: Address,
+        amount: Sats,
+        dust_limit: Sats,
+    },
+
     /// not enough funds to pay fee of {fee} sats; all inputs contain {input_value} sats and
     /// outputs spends {output_value} sats out of them.
     NoFundsForFee {
@@ -151,6 +179,42 @@ impl FromStr for Beneficiary {
     }
 }
 
+/// Input spending an output not controlled by the wallet, contributed to a jointly constructed
+/// transaction by another party, like a payjoin receiver or a coinjoin participant.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ForeignInput {
+    pub outpoint: Outpoint,
+    pub txout: TxOut,
+    /// Maximum weight of the signature script and witness satisfying the spent output (see
+    /// [`Descriptor::max_satisfaction_weight`]).
+    pub satisfaction_weight: WeightUnits,
+}
+
+impl ForeignInput {
+    /// Maximum weight of the input, including the previous outpoint and the sequence number.
+    pub fn max_input_weight(&self) -> WeightUnits {
+        WeightUnits::no_discount(32 + 4 + 4) + self.satisfaction_weight
+    }
+}
+
+/// Inputs and pre-built outputs not controlled by the wallet, which are added to the transaction
+/// by [`PsbtConstructor::construct_psbt_with`]. Used by protocols composing transactions with
+/// other parties or committing to data in the outputs, like payjoin, coinjoin or RGB.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct TxExtras {
+    pub inputs: Vec<ForeignInput>,
+    pub outputs: Vec<TxOut>,
+}
+
+impl TxExtras {
+    /// Weight added to the transaction by the extra inputs and outputs, which should be
+    /// accounted when computing [`TxParams::fee`].
+    pub fn weight(&self) -> WeightUnits {
+        self.inputs.iter().map(ForeignInput::max_input_weight).sum::<WeightUnits>()
+            + self.outputs.iter().map(TxOut::weight_units).sum::<WeightUnits>()
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct TxParams {
     pub fee: Sats,
@@ -158,20 +222,89 @@ pub struct TxParams {
     pub seq_no: SeqNo,
     pub change_shift: bool,
     pub change_keychain: Keychain,
+    merge_outputs: bool,
+    allow_future_segwit: bool,
 }
 
 impl TxParams {
+    /// Constructs parameters of a transaction paying the `fee`, which signals replace-by-fee in
+    /// all inputs with sequence number `0xFFFFFFFD` and has no lock time.
     pub fn with(fee: Sats) -> Self {
         TxParams {
             fee,
             lock_time: None,
-            seq_no: SeqNo::from_consensus_u32(0),
+            seq_no: SeqNo::from_consensus_u32(SEQ_NO_RBF),
             change_shift: true,
             change_keychain: Keychain::INNER,
+            merge_outputs: false,
+            allow_future_segwit: false,
+        }
+    }
+
+    /// Sets whether payments to the same script pubkey must be merged into a single output.
+    pub fn with_merged_outputs(mut self, merge_outputs: bool) -> Self {
+        self.merge_outputs = merge_outputs;
+        self
+    }
+
+    /// Sets whether payments to the addresses of future segwit versions are allowed. The funds
+    /// sent to them can't be spent until a soft fork defines the version.
+    pub fn with_future_segwit(mut self, allow_future_segwit: bool) -> Self {
+        self.allow_future_segwit = allow_future_segwit;
+        self
+    }
+
+    /// Detects whether payments to the same script pubkey are merged into a single output.
+    pub fn merges_outputs(&self) -> bool { self.merge_outputs }
+
+    /// Detects whether payments to the addresses of future segwit versions are allowed.
+    pub fn allows_future_segwit(&self) -> bool { self.allow_future_segwit }
+
+    /// Sets anti-fee-sniping lock time to the height of the current chain tip, unless the lock
+    /// time is already set. As in Bitcoin Core, with 10% probability the lock time is randomly
+    /// backdated by up to 99 blocks, improving privacy of transactions whose broadcast is
+    /// delayed.
+    ///
+    /// The `entropy` must be a uniformly random value provided by the caller from a
+    /// cryptographically secure source; it decides whether and how far the lock time gets
+    /// backdated.
+    pub fn with_tip_height(mut self, tip_height: u32, entropy: u64) -> Self {
+        if self.lock_time.is_none() {
+            self.lock_time = anti_fee_sniping(tip_height, entropy);
+        }
+        self
+    }
+
+    /// Adjusts lock time or input sequence numbers so that the transaction satisfies the
+    /// timelock of the spending path (see [`Descriptor::timelocks`]).
+    ///
+    /// Absolute lock time already set to a later moment of the same kind (height or time) is
+    /// preserved; relative timelock replaces the sequence number, which keeps signalling
+    /// replace-by-fee.
+    pub fn with_timelock(mut self, timelock: Timelock) -> Self {
+        match timelock {
+            Timelock::Absolute(required) => {
+                self.lock_time = match self.lock_time {
+                    Some(lock_time) if timelock.is_satisfied_by(lock_time, self.seq_no) => {
+                        Some(lock_time)
+                    }
+                    _ => Some(required),
+                };
+            }
+            Timelock::Relative(required) => self.seq_no = required,
         }
+        self
     }
 }
 
+/// Sequence number signalling replace-by-fee without enabling relative time locks.
+const SEQ_NO_RBF: u32 = 0xFFFF_FFFD;
+
+fn anti_fee_sniping(tip_height: u32, entropy: u64) -> Option<LockTime> {
+    let backdate = if entropy % 10 == 0 { (entropy / 10 % 100) as u32 } else { 0 };
+    LockTime::from_height(tip_height.saturating_sub(backdate))
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct PsbtMeta {
     pub change_vout: Option<Vout>,
@@ -188,6 +321,87 @@ pub struct Utxo {
 impl Utxo {
     #[inline]
     pub fn to_prevout(&self) -> Prevout { Prevout::new(self.outpoint, self.value) }
+
+    /// Detects whether the UTXO is a change output, i.e. was derived from the internal keychain.
+    #[inline]
+    pub fn is_change(&self) -> bool { self.terminal.is_change() }
+}
+
+/// Merges payments to the same script pubkey, preserving the order of their first occurrence.
+/// Fixed amounts are summed up; if any of the payments is [`Payment::Max`], the merged payment
+/// is also `Max`, since it receives all the value not spent by the other outputs.
+fn merge_beneficiaries(
+    beneficiaries: Vec<Beneficiary>,
+) -> Result<Vec<Beneficiary>, ConstructionError> {
+    let mut merged = IndexMap::<ScriptPubkey, Beneficiary>::with_capacity(beneficiaries.len());
+    for beneficiary in beneficiaries {
+        let Some(prev) = merged.get_mut(&beneficiary.script_pubkey()) else {
+            merged.insert(beneficiary.script_pubkey(), beneficiary);
+            continue;
+        };
+        prev.amount = match (prev.amount, beneficiary.amount) {
+            (Payment::Fixed(a), Payment::Fixed(b)) => {
+                Payment::Fixed(a.checked_add(b).ok_or(ConstructionError::Overflow(a))?)
+            }
+            _ => Payment::Max,
+        };
+    }
+    Ok(merged.into_values().collect())
+}
+
+fn check_dust(address: Address, amount: Sats) -> Result<(), ConstructionError> {
+    let dust_limit = dust_limit(&address.script_pubkey(), FeeRate::DUST_RELAY);
+    if amount < dust_limit {
+        return Err(ConstructionError::DustOutput {
+            address,
+            amount,
+            dust_limit,
+        });
+    }
+    Ok(())
+}
+
+/// Parameters of UTXO consolidation planning.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ConsolidationParams {
+    /// Fee rate paid by the consolidation transaction.
+    pub fee_rate: FeeRate,
+    /// Expected fee rate at which the coins would be spent if not consolidated now.
+    pub future_fee_rate: FeeRate,
+    /// Coins with the value exceeding the threshold are not consolidated.
+    pub threshold: Sats,
+    /// Minimal number of coins worth consolidating.
+    pub min_coins: usize,
+    /// Keychain receiving the consolidated output.
+    pub keychain: Keychain,
+    /// Whether the derivation index of the consolidated output must be shifted.
+    pub shift: bool,
+}
+
+impl ConsolidationParams {
+    pub fn with(fee_rate: FeeRate, future_fee_rate: FeeRate) -> Self {
+        ConsolidationParams {
+            fee_rate,
+            future_fee_rate,
+            threshold: Sats::from_sats(100_000u32),
+            min_coins: 2,
+            keychain: Keychain::INNER,
+            shift: true,
+        }
+    }
+}
+
+/// Proposed consolidation transaction.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ConsolidationPlan {
+    /// PSBT spending the consolidated coins to a single wallet output.
+    pub psbt: Psbt,
+    /// Consolidated coins.
+    pub coins: Vec<Outpoint>,
+    /// Fee paid by the consolidation transaction.
+    pub fee: Sats,
+    /// Estimated fee savings in comparison to spending all the coins at the future fee rate.
+    pub savings: Sats,
 }
 
 pub trait PsbtConstructor {
@@ -195,15 +409,43 @@ pub trait PsbtConstructor {
     type Descr: Descriptor<Self::Key>;
 
     fn descriptor(&self) -> &Self::Descr;
+
+    /// Descriptor used for the given keychain. Wallets migrating to a new address type may use
+    /// different descriptors for different keychains, like P2WPKH for receiving and P2TR for
+    /// change. Defaults to [`PsbtConstructor::descriptor`] for all keychains.
+    fn keychain_descriptor(&self, _keychain: Keychain) -> &Self::Descr { self.descriptor() }
+
     fn utxo(&self, outpoint: Outpoint) -> Option<Utxo>;
     fn network(&self) -> Network;
     fn next_derivation_index(&mut self, keychain: impl Into<Keychain>, shift: bool) -> NormalIndex;
 
+    /// Transaction with the given id, if known. It is put into the PSBT inputs as a
+    /// non-witness UTXO, which is required for signing non-segwit inputs and protects
+    /// hardware signers of segwit v0 inputs from the fee attacks.
+    fn prev_tx(&self, _txid: Txid) -> Option<Tx> { None }
+
     fn construct_psbt<'a, 'b>(
         &mut self,
         coins: impl IntoIterator<Item = Outpoint>,
         beneficiaries: impl IntoIterator<Item = &'b Beneficiary>,
         params: TxParams,
+    ) -> Result<(Psbt, PsbtMeta), ConstructionError> {
+        self.construct_psbt_with(coins, &TxExtras::default(), beneficiaries, params)
+    }
+
+    /// Constructs PSBT like [`PsbtConstructor::construct_psbt`], adding the `extras` inputs
+    /// after the wallet coins and the `extras` outputs after the payments to the beneficiaries.
+    ///
+    /// The value of the extra inputs and outputs participates in the computation of the change,
+    /// while the caller is responsible for accounting their weight in the fee (see
+    /// [`TxExtras::weight`]). The extra outputs are added as is, without checking them against
+    /// the dust limit.
+    fn construct_psbt_with<'b>(
+        &mut self,
+        coins: impl IntoIterator<Item = Outpoint>,
+        extras: &TxExtras,
+        beneficiaries: impl IntoIterator<Item = &'b Beneficiary>,
+        params: TxParams,
     ) -> Result<(Psbt, PsbtMeta), ConstructionError> {
         let mut psbt = Psbt::create(PsbtVer::V2);
 
@@ -211,38 +453,53 @@ pub trait PsbtConstructor {
         psbt.fallback_locktime = params.lock_time;
 
         // Add xpubs
-        for spec in self.descriptor().xpubs() {
-            psbt.xpubs.insert(*spec.xpub(), spec.origin().clone());
-        }
+        self.construct_xpubs(&mut psbt, [params.change_keychain]);
 
         // 1. Add inputs
         for coin in coins {
             let utxo = self.utxo(coin).expect("wallet data inconsistency");
-            psbt.construct_input_expect(
-                utxo.to_prevout(),
-                self.descriptor(),
-                utxo.terminal,
-                params.seq_no,
-            );
+            self.construct_coin_input(&mut psbt, utxo, params.seq_no)?;
+        }
+        for input in &extras.inputs {
+            psbt.construct_foreign_input(input.outpoint, input.txout.clone(), params.seq_no)
+                .expect("PSBT inputs are expected to be modifiable");
         }
         if psbt.inputs().count() == 0 {
             return Err(ConstructionError::NoInputs);
         }
 
         // 2. Add outputs
-        let input_value = psbt.input_sum();
+        let input_value = psbt.input_sum()?;
         let mut max = Vec::new();
         let mut output_value = Sats::ZERO;
+        let mut beneficiaries = beneficiaries.into_iter().copied().collect::<Vec<_>>();
+        if params.merge_outputs {
+            beneficiaries = merge_beneficiaries(beneficiaries)?;
+        }
+        let mut max_addrs = Vec::new();
         for beneficiary in beneficiaries {
+            if !params.allow_future_segwit && beneficiary.address.payload.is_future_segwit() {
+                return Err(ConstructionError::FutureSegwit(beneficiary.address));
+            }
             let amount = beneficiary.amount.unwrap_or(Sats::ZERO);
+            if !beneficiary.is_max() {
+                check_dust(beneficiary.address, amount)?;
+            }
             output_value
                 .checked_add_assign(amount)
                 .ok_or(ConstructionError::Overflow(output_value))?;
             let out = psbt.construct_output_expect(beneficiary.script_pubkey(), amount);
             if beneficiary.amount.is_max() {
                 max.push(out.index());
+                max_addrs.push(beneficiary.address);
             }
         }
+        for txout in &extras.outputs {
+            output_value
+                .checked_add_assign(txout.value)
+                .ok_or(ConstructionError::Overflow(output_value))?;
+            psbt.construct_output_expect(txout.script_pubkey.clone(), txout.value);
+        }
         let mut remaining_value = input_value
             .checked_sub(output_value)
             .ok_or(ConstructionError::OutputExceedsInputs {
@@ -257,6 +514,9 @@ pub trait PsbtConstructor {
             })?;
         if !max.is_empty() {
             let portion = remaining_value / max.len();
+            for address in max_addrs {
+                check_dust(address, portion)?;
+            }
             for out in psbt.outputs_mut() {
                 if max.contains(&out.index()) {
                     out.amount = portion;
@@ -265,14 +525,19 @@ pub trait PsbtConstructor {
             remaining_value = Sats::ZERO;
         }
 
-        // 3. Add change - only if exceeded the dust limit
+        // 3. Add change - only if it is not below the dust limit. Scripts derived from the same
+        // descriptor differ only by their keys, so the dust limit doesn't depend on the index.
+        let change_descr = self.keychain_descriptor(params.change_keychain);
+        let change_spk =
+            change_descr.derive(params.change_keychain, NormalIndex::ZERO).to_script_pubkey();
         let (change_vout, change_terminal) =
-            if remaining_value > self.descriptor().class().dust_limit() {
+            if remaining_value >= dust_limit(&change_spk, FeeRate::DUST_RELAY) {
                 let change_index =
                     self.next_derivation_index(params.change_keychain, params.change_shift);
                 let change_terminal = Terminal::new(params.change_keychain, change_index);
+                let change_descr = self.keychain_descriptor(params.change_keychain);
                 let change_vout = psbt
-                    .construct_change_expect(self.descriptor(), change_terminal, remaining_value)
+                    .construct_change_expect(change_descr, change_terminal, remaining_value)
                     .index();
                 (Some(Vout::from_u32(change_vout as u32)), Some(change_terminal))
             } else {
@@ -284,4 +549,370 @@ pub trait PsbtConstructor {
             change_terminal,
         }))
     }
+
+    /// Constructs PSBT spending all provided `coins` to a single `destination` output, which
+    /// receives the total value of the coins with the fee deducted. The PSBT has no change
+    /// output.
+    fn construct_drain_psbt(
+        &mut self,
+        coins: impl IntoIterator<Item = Outpoint>,
+        destination: Address,
+        params: TxParams,
+    ) -> Result<Psbt, ConstructionError> {
+        let beneficiary = Beneficiary::with_max(destination);
+        let (psbt, _) = self.construct_psbt(coins, [&beneficiary], params)?;
+        Ok(psbt)
+    }
+
+    /// Plans consolidation of small coins into a single output of the wallet's own keychain,
+    /// which is economic when the current fee rate is lower than the expected future one.
+    ///
+    /// Coins with the value above [`ConsolidationParams::threshold`] or below the fee required
+    /// to spend them at the current fee rate are skipped. Returns `None` if there are not enough
+    /// coins to consolidate or the consolidation doesn't save fees.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConstructionError::UnknownCoins`] listing all the provided coins which are not
+    /// known to the wallet.
+    fn plan_consolidation(
+        &mut self,
+        coins: impl IntoIterator<Item = Outpoint>,
+        params: ConsolidationParams,
+    ) -> Result<Option<ConsolidationPlan>, ConstructionError> {
+        let input_weight =
+            |keychain: Keychain| self.keychain_descriptor(keychain).max_input_weight();
+
+        let utxos = self.known_utxos(coins)?;
+        let (coins, weights): (Vec<_>, Vec<_>) = utxos
+            .into_iter()
+            .map(|utxo| (utxo, input_weight(utxo.terminal.keychain)))
+            .filter(|(utxo, weight)| {
+                utxo.value <= params.threshold
+                    && utxo.value > params.fee_rate.fee_for_weight(*weight)
+            })
+            .map(|(utxo, weight)| (utxo.outpoint, weight))
+            .unzip();
+        if coins.len() < params.min_coins.max(1) {
+            return Ok(None);
+        }
+
+        let output_descr = self.keychain_descriptor(params.keychain);
+        let script = output_descr.derive(params.keychain, NormalIndex::ZERO);
+        let output = TxOut::new(script.to_script_pubkey(), Sats::ZERO);
+        let mut weight = WeightUnits::no_discount(
+            4 + VarInt::with(coins.len()).len() + VarInt::new(1).len() + 4,
+        ) + output.weight_units();
+        if script.is_segwit() {
+            weight += WeightUnits::witness_discount(2);
+        }
+        weight += weights.iter().copied().sum();
+        let fee = params.fee_rate.fee_for_weight(weight);
+
+        let unconsolidated = weights
+            .iter()
+            .map(|weight| params.future_fee_rate.fee_for_weight(*weight))
+            .sum::<Sats>();
+        let output_input_weight = input_weight(params.keychain);
+        let Some(savings) = unconsolidated
+            .checked_sub(fee)
+            .and_then(|saved| {
+                saved.checked_sub(params.future_fee_rate.fee_for_weight(output_input_weight))
+            })
+            .filter(|saved| *saved > Sats::ZERO)
+        else {
+            return Ok(None);
+        };
+
+        let mut tx_params = TxParams::with(fee);
+        tx_params.change_keychain = params.keychain;
+        tx_params.change_shift = params.shift;
+        let (psbt, meta) = self.construct_psbt(coins.iter().copied(), [], tx_params)?;
+        if meta.change_vout.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(ConsolidationPlan {
+            psbt,
+            coins,
+            fee,
+            savings,
+        }))
+    }
+
+    /// Constructs BIP-127 proof of reserves for the provided wallet `coins`, committing to the
+    /// `message`.
+    ///
+    /// The first input of the PSBT spends the challenge outpoint (see [`por_challenge`]) with
+    /// the script derived from the wallet descriptor at the `challenge` terminal, such that it
+    /// gets signed together with the rest of the inputs. The verifier must be provided with the
+    /// same script (see [`crate::verify_reserves`]). The only output sends the total value of
+    /// the coins to an unspendable script.
+    fn construct_reserves_psbt(
+        &self,
+        message: &str,
+        challenge: Terminal,
+        coins: impl IntoIterator<Item = Outpoint>,
+    ) -> Result<Psbt, ConstructionError> {
+        let utxos = self.known_utxos(coins)?;
+        if utxos.is_empty() {
+            return Err(ConstructionError::NoInputs);
+        }
+
+        let mut psbt = Psbt::create(PsbtVer::V2);
+        let keychains = utxos.iter().map(|utxo| utxo.terminal.keychain);
+        self.construct_xpubs(&mut psbt, keychains.chain([challenge.keychain]));
+
+        let seq_no = SeqNo::from_consensus_u32(0xFFFF_FFFF);
+        let prevout = Prevout::new(por_challenge(message), Sats::ZERO);
+        let descr = self.keychain_descriptor(challenge.keychain);
+        psbt.construct_input_expect(prevout, descr, challenge, seq_no).proof_of_reserves =
+            Some(message.to_owned());
+
+        let mut value = Sats::ZERO;
+        for utxo in utxos {
+            value.checked_add_assign(utxo.value).ok_or(ConstructionError::Overflow(value))?;
+            self.construct_coin_input(&mut psbt, utxo, seq_no)?;
+        }
+        psbt.construct_output_expect(por_script_pubkey(), value);
+
+        Ok(psbt)
+    }
+
+    /// Constructs BIP-78 payjoin proposal from the sender's `original` PSBT, adding the wallet
+    /// `coins` as inputs and moving their value to the receiver output `receiver_vout`.
+    ///
+    /// The fee for the added inputs is computed at the fee rate of the original transaction and
+    /// is paid from the sender additional fee output up to the limit given in the `params`,
+    /// with the rest being deducted from the receiver output. The receiver inputs must be signed
+    /// and finalized before the proposal is returned to the sender.
+    fn construct_payjoin_proposal(
+        &self,
+        original: &Psbt,
+        coins: impl IntoIterator<Item = Outpoint>,
+        receiver_vout: Vout,
+        params: &PayjoinParams,
+    ) -> Result<Psbt, PayjoinError> {
+        let fee_rate = original.check_payjoin_original()?;
+        let mut proposal = original.payjoin_proposal_base();
+        let seq_no =
+            original.inputs().next().ok_or(PayjoinError::NoInputs)?.to_unsigned_txin().sequence;
+
+        let mut contribution = Sats::ZERO;
+        let mut weight = WeightUnits::no_discount(0);
+        for coin in coins {
+            let utxo = self.utxo(coin).expect("wallet data inconsistency");
+            contribution
+                .checked_add_assign(utxo.value)
+                .ok_or(ConstructionError::Overflow(contribution))?;
+            weight += self.keychain_descriptor(utxo.terminal.keychain).max_input_weight();
+            self.construct_coin_input(&mut proposal, utxo, seq_no)?;
+        }
+        proposal.apply_payjoin_contribution(
+            receiver_vout,
+            contribution,
+            fee_rate.fee_for_weight(weight),
+            params,
+        )?;
+        proposal.tx_modifiable = original.tx_modifiable.clone();
+        Ok(proposal)
+    }
+
+    /// Prepares externally coordinated PSBT, like a coinjoin, for signing the wallet inputs.
+    ///
+    /// Detects inputs spending the wallet coins and fills them with the spent output, scripts
+    /// and key derivation information, requiring `SIGHASH_ALL` for their signatures. Checks that
+    /// the PSBT contains all the `expected` outputs paying to the wallet or its counterparties.
+    ///
+    /// Returns indexes of the wallet inputs, which should be signed with [`Psbt::sign_inputs`].
+    fn contribute_coinjoin(
+        &self,
+        psbt: &mut Psbt,
+        expected: &[TxOut],
+    ) -> Result<Vec<usize>, CoinjoinError> {
+        psbt.check_outputs(expected)?;
+
+        let mut wallet_inputs = Vec::new();
+        for input in psbt.inputs_mut() {
+            let Some(utxo) = self.utxo(input.previous_outpoint) else {
+                continue;
+            };
+            let mut scratch = Psbt::create(PsbtVer::V2);
+            let seq_no = input.to_unsigned_txin().sequence;
+            self.construct_coin_input(&mut scratch, utxo, seq_no)?;
+            let mut own = scratch.inputs.pop().expect("just constructed");
+            let index = input.index();
+            let has_prevout = input.witness_utxo.is_some() || input.non_witness_tx.is_some();
+            if has_prevout && input.prev_txout() != own.prev_txout() {
+                return Err(CoinjoinError::PrevoutMismatch(index));
+            }
+            own.index = index;
+            own.sighash_type = Some(SighashType::all());
+            *input = own;
+            wallet_inputs.push(index);
+        }
+        if wallet_inputs.is_empty() {
+            return Err(CoinjoinError::NoWalletInputs);
+        }
+        Ok(wallet_inputs)
+    }
+
+    /// Analyzes PSBT received for signing, detecting the wallet inputs and change outputs
+    /// and computing the fee and the estimated fee rate, for presenting them to the user.
+    ///
+    /// Outputs are recognized as change if they have key derivation information of the wallet
+    /// keys; the script pubkey of such outputs must match the one derived by the descriptor of
+    /// the keychain, otherwise [`AnalysisError::InvalidChange`] is returned.
+    fn analyze_psbt(&self, psbt: &Psbt) -> Result<PsbtReport, AnalysisError> {
+        let mut wallet_inputs = Sats::ZERO;
+        let mut foreign_inputs = Sats::ZERO;
+        for input in psbt.inputs() {
+            if self.utxo(input.previous_outpoint).is_some() {
+                wallet_inputs.saturating_add_assign(input.value()?);
+            } else {
+                foreign_inputs.saturating_add_assign(input.value()?);
+            }
+        }
+        let fee = psbt.fee()?.ok_or(AnalysisError::NegativeFee)?;
+
+        let mut fingerprints = Vec::new();
+        for keychain in self.descriptor().keychains() {
+            let descr = self.keychain_descriptor(keychain);
+            fingerprints.extend(descr.xpubs().map(|spec| spec.origin().master_fp()));
+        }
+
+        let mut change = Sats::ZERO;
+        let mut sent = Sats::ZERO;
+        let mut outputs = Vec::with_capacity(psbt.outputs().count());
+        for output in psbt.outputs() {
+            let mut terminal = None;
+            for claimed in claimed_terminals(output, &fingerprints) {
+                let invalid = AnalysisError::InvalidChange(output.index());
+                let claimed = claimed.ok_or(invalid.clone())?;
+                let descr = self.keychain_descriptor(claimed.keychain);
+                let script = descr.derive(claimed.keychain, claimed.index).to_script_pubkey();
+                if script != output.script || terminal.is_some_and(|t| t != claimed) {
+                    return Err(invalid);
+                }
+                terminal = Some(claimed);
+            }
+            match terminal {
+                Some(_) => change.saturating_add_assign(output.value()),
+                None => sent.saturating_add_assign(output.value()),
+            }
+            outputs.push(OutputReport {
+                vout: output.vout(),
+                value: output.value(),
+                address: Address::with(&output.script, self.network()).ok(),
+                change: terminal,
+            });
+        }
+
+        let weight = psbt.estimate_weight_or_unsigned();
+        Ok(PsbtReport {
+            wallet_inputs,
+            foreign_inputs,
+            outputs,
+            change,
+            sent,
+            fee,
+            weight,
+            fee_rate: FeeRate::from_fee(fee, weight),
+        })
+    }
+
+    #[doc(hidden)]
+    fn construct_xpubs(&self, psbt: &mut Psbt, keychains: impl IntoIterator<Item = Keychain>) {
+        let mut all = self.descriptor().keychains();
+        all.extend(keychains);
+        for keychain in all {
+            for spec in self.keychain_descriptor(keychain).xpubs() {
+                psbt.xpubs.insert(*spec.xpub(), spec.origin().clone());
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    fn known_utxos(
+        &self,
+        coins: impl IntoIterator<Item = Outpoint>,
+    ) -> Result<Vec<Utxo>, ConstructionError> {
+        let mut unknown = vec![];
+        let utxos = coins
+            .into_iter()
+            .filter_map(|coin| {
+                let utxo = self.utxo(coin);
+                if utxo.is_none() {
+                    unknown.push(coin);
+                }
+                utxo
+            })
+            .collect();
+        if !unknown.is_empty() {
+            return Err(ConstructionError::UnknownCoins(unknown));
+        }
+        Ok(utxos)
+    }
+
+    #[doc(hidden)]
+    fn construct_coin_input(
+        &self,
+        psbt: &mut Psbt,
+        utxo: Utxo,
+        seq_no: SeqNo,
+    ) -> Result<(), ConstructionError> {
+        let prev_tx = self.prev_tx(utxo.outpoint.txid);
+        let descr = self.keychain_descriptor(utxo.terminal.keychain);
+        let segwit = descr.derive(utxo.terminal.keychain, utxo.terminal.index).is_segwit();
+        let input = psbt.construct_input_expect(utxo.to_prevout(), descr, utxo.terminal, seq_no);
+        match (segwit, prev_tx) {
+            (true, prev_tx) => input.non_witness_tx = prev_tx,
+            (false, Some(prev_tx)) => {
+                input.non_witness_tx = Some(prev_tx);
+                input.witness_utxo = None;
+            }
+            (false, None) => return Err(ConstructionError::NoPrevTx(utxo.outpoint)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn anti_fee_sniping_backdate() {
+        assert_eq!(anti_fee_sniping(800_000, 1), LockTime::from_height(800_000));
+        assert_eq!(anti_fee_sniping(800_000, 10 * 42), LockTime::from_height(799_958));
+        assert_eq!(anti_fee_sniping(800_000, 10 * 199), LockTime::from_height(799_901));
+        assert_eq!(anti_fee_sniping(50, 10 * 99), LockTime::from_height(0));
+        assert_eq!(anti_fee_sniping(500_000_000, 1), None);
+
+        let params = TxParams::with(Sats::ZERO).with_tip_height(800_000, 10 * 42);
+        assert_eq!(params.lock_time, LockTime::from_height(799_958));
+        assert_eq!(params.seq_no.to_consensus_u32(), SEQ_NO_RBF);
+
+        let mut params = TxParams::with(Sats::ZERO);
+        params.lock_time = LockTime::from_height(10);
+        assert_eq!(params.with_tip_height(800_000, 1).lock_time, LockTime::from_height(10));
+    }
+
+    #[test]
+    fn timelock_params() {
+        let params = TxParams::with(Sats::from_sats(1000u32)).with_timelock(Timelock::older(144));
+        assert_eq!(params.seq_no, SeqNo::from_height(144));
+        assert_eq!(params.lock_time, None);
+
+        let params = params.with_timelock(Timelock::after(800_000));
+        assert_eq!(params.lock_time, LockTime::from_height(800_000));
+        let params = params.with_tip_height(900_000, 1).with_timelock(Timelock::after(800_000));
+        assert_eq!(params.lock_time, LockTime::from_height(800_000));
+
+        let mut params = TxParams::with(Sats::from_sats(1000u32));
+        params.lock_time = LockTime::from_height(900_000);
+        let params = params.with_timelock(Timelock::after(800_000));
+        assert_eq!(params.lock_time, LockTime::from_height(900_000));
+        let params = params.with_timelock(Timelock::after(1_700_000_000));
+        assert_eq!(params.lock_time, LockTime::from_unix_timestamp(1_700_000_000));
+    }
 }
@@ -40,6 +40,38 @@ use crate::{KeyData, PropKey, PsbtError, PsbtVer, ValueData};
 #[display("PSBT can't be modified")]
 pub struct Unmodifiable;
 
+/// Maximum size of `OP_RETURN` output data relayed by the nodes under the default standardness
+/// policy (`-datacarriersize` of Bitcoin Core without the `OP_RETURN` and push opcodes).
+pub const MAX_OP_RETURN_DATA: usize = 80;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum OpReturnError {
+    #[from]
+    #[display(inner)]
+    Unmodifiable(Unmodifiable),
+
+    /// OP_RETURN data of {0} bytes exceeds the standard limit of 80 bytes.
+    TooLarge(usize),
+}
+
+/// Errors in the information about the output spent by a PSBT input.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum PrevoutError {
+    /// input {0} doesn't provide information about the spent output.
+    NoPrevout(usize),
+
+    /// non-witness transaction of input {0} doesn't match the spent outpoint transaction id.
+    TxidMismatch(usize),
+
+    /// non-witness transaction of input {0} doesn't have the spent output {1}.
+    NoOutput(usize, Vout),
+
+    /// witness UTXO of input {0} doesn't match the spent output of its non-witness transaction.
+    WitnessUtxoMismatch(usize),
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Prevout {
     pub txid: Txid,
@@ -298,14 +330,20 @@ impl Psbt {
         self.fallback_locktime.unwrap_or(LockTime::ZERO)
     }
 
+    /// Sum of the values of the outputs spent by the inputs, failing if any of the inputs
+    /// doesn't provide valid information about its spent output.
     #[inline]
-    pub fn input_sum(&self) -> Sats { self.inputs().map(Input::value).sum() }
+    pub fn input_sum(&self) -> Result<Sats, PrevoutError> { self.inputs().map(Input::value).sum() }
 
     #[inline]
     pub fn output_sum(&self) -> Sats { self.outputs().map(Output::value).sum() }
 
+    /// Transaction fee, which is `None` if the outputs spend more than present in the inputs.
+    /// Fails if any of the inputs doesn't provide valid information about its spent output.
     #[inline]
-    pub fn fee(&self) -> Option<Sats> { self.input_sum().checked_sub(self.output_sum()) }
+    pub fn fee(&self) -> Result<Option<Sats>, PrevoutError> {
+        Ok(self.input_sum()?.checked_sub(self.output_sum()))
+    }
 
     pub fn xpubs(&self) -> impl Iterator<Item = (&Xpub, &XpubOrigin)> { self.xpubs.iter() }
 
@@ -384,6 +422,28 @@ impl Psbt {
             .expect("PSBT inputs are expected to be modifiable")
     }
 
+    /// Adds input spending an output not controlled by the wallet, which is signed by another
+    /// party. The input has no key derivation information and contains only the spent output.
+    pub fn construct_foreign_input(
+        &mut self,
+        outpoint: Outpoint,
+        txout: TxOut,
+        sequence: SeqNo,
+    ) -> Result<&mut Input, Unmodifiable> {
+        if !self.are_inputs_modifiable() {
+            return Err(Unmodifiable);
+        }
+
+        let txin = UnsignedTxIn {
+            prev_output: outpoint,
+            sequence,
+        };
+        let mut input = Input::with_txin(txin, self.inputs.len());
+        input.witness_utxo = Some(txout);
+        self.inputs.push(input);
+        Ok(self.inputs.last_mut().expect("just inserted"))
+    }
+
     pub fn construct_output(
         &mut self,
         script_pubkey: ScriptPubkey,
@@ -411,6 +471,18 @@ impl Psbt {
             .expect("PSBT outputs are expected to be modifiable")
     }
 
+    /// Adds a zero-value `OP_RETURN` output carrying `data`, which must not exceed
+    /// [`MAX_OP_RETURN_DATA`] bytes to keep the transaction standard.
+    ///
+    /// The output doesn't change the amount of fee paid by the transaction, but increases its
+    /// weight, which should be accounted when the fee is estimated.
+    pub fn construct_op_return(&mut self, data: &[u8]) -> Result<&mut Output, OpReturnError> {
+        if data.len() > MAX_OP_RETURN_DATA {
+            return Err(OpReturnError::TooLarge(data.len()));
+        }
+        Ok(self.construct_output(ScriptPubkey::op_return(data), Sats::ZERO)?)
+    }
+
     pub fn construct_change<K, D: Descriptor<K>>(
         &mut self,
         descriptor: &D,
@@ -748,29 +820,45 @@ impl Input {
         }
     }
 
-    #[inline]
-    pub fn prev_txout(&self) -> &TxOut {
-        // TODO: Add support for nonwitness_utxo
-        match (&self.witness_utxo, None::<&Tx>) {
-            (Some(txout), _) => txout,
-            (None, Some(tx)) => &tx.outputs[self.index],
-            (None, None) => unreachable!(
-                "PSBT input must contain either witness UTXO or a non-witness transaction"
+    /// Returns the output spent by the input, taken from the witness UTXO or the non-witness
+    /// transaction. The non-witness transaction, if present, must match the spent outpoint, and
+    /// if both are present the witness UTXO must match the spent output of the transaction.
+    pub fn prev_txout(&self) -> Result<&TxOut, PrevoutError> {
+        let outpoint = self.previous_outpoint;
+        let prev_tx_out = match &self.non_witness_tx {
+            Some(tx) if tx.txid() != outpoint.txid => {
+                return Err(PrevoutError::TxidMismatch(self.index));
+            }
+            Some(tx) => Some(
+                tx.outputs
+                    .get(outpoint.vout.into_usize())
+                    .ok_or(PrevoutError::NoOutput(self.index, outpoint.vout))?,
             ),
+            None => None,
+        };
+        match (&self.witness_utxo, prev_tx_out) {
+            (Some(utxo), Some(txout)) if utxo != txout => {
+                Err(PrevoutError::WitnessUtxoMismatch(self.index))
+            }
+            (Some(utxo), _) => Ok(utxo),
+            (None, Some(txout)) => Ok(txout),
+            (None, None) => Err(PrevoutError::NoPrevout(self.index)),
         }
     }
 
     #[inline]
-    pub fn prevout(&self) -> Prevout {
-        Prevout {
+    pub fn prevout(&self) -> Result<Prevout, PrevoutError> {
+        Ok(Prevout {
             txid: self.previous_outpoint.txid,
             vout: self.previous_outpoint.vout,
-            value: self.value(),
-        }
+            value: self.value()?,
+        })
     }
 
+    /// Value of the spent output, failing if the input doesn't provide valid information about
+    /// it (see [`Input::prev_txout`]).
     #[inline]
-    pub fn value(&self) -> Sats { self.prev_txout().value }
+    pub fn value(&self) -> Result<Sats, PrevoutError> { self.prev_txout().map(|txout| txout.value) }
 
     #[inline]
     pub fn index(&self) -> usize { self.index }
@@ -890,7 +978,7 @@ impl Output {
         if terminal.len() != 1 {
             return None;
         }
-        return terminal.first().copied();
+        terminal.first().copied()
     }
 }
 
@@ -1035,4 +1123,23 @@ mod tests {
         let result = std::panic::catch_unwind(|| format!("{v0_psbt:#01x}"));
         assert!(result.is_err(), "Should fail on unsupported psbt version");
     }
+
+    #[test]
+    fn op_return_output() {
+        let mut psbt = Psbt::create(PsbtVer::V2);
+        let out = psbt.construct_op_return(b"timestamp").unwrap();
+        assert_eq!(out.amount, Sats::ZERO);
+        assert_eq!(out.script, ScriptPubkey::op_return(b"timestamp"));
+
+        let out = psbt.construct_op_return(&[0xA5; MAX_OP_RETURN_DATA]).unwrap();
+        assert_eq!(out.script.len(), MAX_OP_RETURN_DATA + 3);
+        assert_eq!(
+            psbt.construct_op_return(&[0xA5; MAX_OP_RETURN_DATA + 1]),
+            Err(OpReturnError::TooLarge(MAX_OP_RETURN_DATA + 1))
+        );
+        assert_eq!(psbt.outputs().count(), 2);
+
+        psbt.tx_modifiable = None;
+        assert_eq!(psbt.construct_op_return(b""), Err(OpReturnError::Unmodifiable(Unmodifiable)));
+    }
 }
@@ -0,0 +1,224 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{self, Display, Formatter};
+use std::num::ParseFloatError;
+use std::str::FromStr;
+
+use derive::{Sats, ScriptPubkey, VBytes, WeightUnits};
+use descriptors::{output_dust_limit, DUST_RELAY_FEE};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum FeeRateParseError {
+    /// invalid fee rate value - {0}
+    #[from]
+    Float(ParseFloatError),
+
+    /// fee rate '{0}' is not a finite non-negative number of sat/vB within the supported range.
+    OutOfRange(String),
+}
+
+/// Transaction fee rate.
+///
+/// The rate is stored in satoshis per 1000 weight units (sat/kWU), which allows to
+/// represent fractional sat/vB fee rates with the precision of 0.004 sat/vB.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    /// Zero fee rate.
+    pub const ZERO: FeeRate = FeeRate(0);
+    /// Default minimum relay fee rate of 1 sat/vB.
+    pub const MIN_RELAY: FeeRate = FeeRate(250);
+    /// Default dust relay fee rate of 3 sat/vB.
+    pub const DUST_RELAY: FeeRate = FeeRate(DUST_RELAY_FEE);
+
+    pub const fn from_sat_per_kwu(sat_per_kwu: u64) -> Self { FeeRate(sat_per_kwu) }
+
+    /// Constructs fee rate from a sat/vB value, saturating at the maximal representable rate.
+    pub const fn from_sat_per_vb(sat_per_vb: u64) -> Self {
+        FeeRate(sat_per_vb.saturating_mul(250))
+    }
+
+    /// Constructs fee rate from a fractional sat/vB value, rounding it to the nearest
+    /// sat/kWU. Negative values and NaN produce zero fee rate, and values exceeding the maximal
+    /// representable rate saturate to it; use [`FeeRate::checked_from_sat_per_vb_f64`] to
+    /// detect them.
+    pub fn from_sat_per_vb_f64(sat_per_vb: f64) -> Self {
+        FeeRate((sat_per_vb * 250.0).round().max(0.0) as u64)
+    }
+
+    /// Constructs fee rate from a fractional sat/vB value, rounding it to the nearest
+    /// sat/kWU. Returns `None` for negative and non-finite values and for values exceeding the
+    /// maximal representable rate.
+    pub fn checked_from_sat_per_vb_f64(sat_per_vb: f64) -> Option<Self> {
+        let sat_per_kwu = (sat_per_vb * 250.0).round();
+        if !sat_per_kwu.is_finite() || sat_per_vb < 0.0 || sat_per_kwu >= u64::MAX as f64 {
+            return None;
+        }
+        Some(FeeRate(sat_per_kwu as u64))
+    }
+
+    /// Computes fee rate of a transaction with the given fee and weight, rounding it down.
+    pub fn from_fee(fee: Sats, weight: WeightUnits) -> Self {
+        match weight.to_u32() {
+            0 => FeeRate::ZERO,
+            wu => FeeRate(fee.sats().saturating_mul(1000) / wu as u64),
+        }
+    }
+
+    pub const fn to_sat_per_kwu(self) -> u64 { self.0 }
+
+    pub fn to_sat_per_vb(self) -> f64 { self.0 as f64 / 250.0 }
+
+    /// Fee for the given transaction weight, rounded up to the next satoshi. Saturates at the
+    /// maximal number of satoshis.
+    pub fn fee_for_weight(self, weight: WeightUnits) -> Sats {
+        Sats(self.0.saturating_mul(weight.to_u32() as u64).div_ceil(1000))
+    }
+
+    /// Fee for the given transaction virtual size, rounded up to the next satoshi. Saturates at
+    /// the maximal number of satoshis.
+    pub fn fee_for_vbytes(self, vbytes: VBytes) -> Sats {
+        Sats(self.0.saturating_mul(vbytes.to_u32() as u64).div_ceil(250))
+    }
+}
+
+impl Display for FeeRate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.to_sat_per_vb(), f)?;
+        f.write_str(" sat/vB")
+    }
+}
+
+/// Parses fee rate in sat/vB, with an optional `sat/vB` suffix. Negative, non-finite and
+/// non-representable rates are rejected.
+impl FromStr for FeeRate {
+    type Err = FeeRateParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_suffix("sat/vB").unwrap_or(s).trim_end();
+        let sat_per_vb = f64::from_str(s)?;
+        Self::checked_from_sat_per_vb_f64(sat_per_vb)
+            .ok_or_else(|| FeeRateParseError::OutOfRange(s.to_owned()))
+    }
+}
+
+/// Computes the minimal value of an output with the `script_pubkey` which is not considered
+/// dust under the relay policy with the given dust relay fee rate (see [`FeeRate::DUST_RELAY`]
+/// and [`output_dust_limit`]). Unspendable `OP_RETURN` outputs have zero dust limit.
+pub fn dust_limit(script_pubkey: &ScriptPubkey, dust_relay_fee: FeeRate) -> Sats {
+    if script_pubkey.is_op_return() {
+        return Sats::ZERO;
+    }
+    output_dust_limit(script_pubkey.len(), script_pubkey.is_witness_program(), dust_relay_fee.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(FeeRate::from_sat_per_vb(1), FeeRate::MIN_RELAY);
+        assert_eq!(FeeRate::from_sat_per_vb(4).to_sat_per_kwu(), 1000);
+        assert_eq!(FeeRate::from_sat_per_vb_f64(1.5).to_sat_per_kwu(), 375);
+        assert_eq!(FeeRate::from_sat_per_vb_f64(-1.0), FeeRate::ZERO);
+        assert_eq!(FeeRate::from_sat_per_kwu(625).to_sat_per_vb(), 2.5);
+    }
+
+    #[test]
+    fn fees() {
+        let rate = FeeRate::from_sat_per_vb(2);
+        assert_eq!(rate.fee_for_weight(WeightUnits::no_discount(100)), Sats(200));
+        // 441 WU is 110.25 vbytes
+        assert_eq!(rate.fee_for_weight(WeightUnits::witness_discount(441)), Sats(221));
+        assert_eq!(
+            rate.fee_for_vbytes(VBytes::from(WeightUnits::witness_discount(441))),
+            Sats(222)
+        );
+        assert_eq!(
+            FeeRate::from_fee(Sats(221), WeightUnits::witness_discount(441)).to_sat_per_kwu(),
+            501
+        );
+        assert_eq!(FeeRate::from_fee(Sats(100), WeightUnits::witness_discount(0)), FeeRate::ZERO);
+    }
+
+    #[test]
+    fn display_from_str() {
+        assert_eq!(FeeRate::from_sat_per_vb(3).to_string(), "3 sat/vB");
+        assert_eq!(format!("{:.1}", FeeRate::from_sat_per_kwu(300)), "1.2 sat/vB");
+        assert_eq!(FeeRate::from_str("2.5 sat/vB"), Ok(FeeRate::from_sat_per_kwu(625)));
+        assert_eq!(FeeRate::from_str("10"), Ok(FeeRate::from_sat_per_vb(10)));
+        assert!(FeeRate::from_str("fast").is_err());
+        assert_eq!(FeeRate::from_str("0"), Ok(FeeRate::ZERO));
+        for invalid in ["-5", "NaN", "inf", "-inf", "1e20"] {
+            assert_eq!(
+                FeeRate::from_str(invalid),
+                Err(FeeRateParseError::OutOfRange(invalid.to_owned()))
+            );
+        }
+    }
+
+    #[test]
+    fn fee_overflow() {
+        let rate = FeeRate::from_sat_per_kwu(u64::MAX);
+        assert_eq!(
+            rate.fee_for_weight(WeightUnits::no_discount(100)),
+            Sats(u64::MAX.div_ceil(1000))
+        );
+        assert_eq!(
+            rate.fee_for_vbytes(VBytes::from(WeightUnits::no_discount(100))),
+            Sats(u64::MAX.div_ceil(250))
+        );
+        assert_eq!(FeeRate::from_sat_per_vb(u64::MAX), rate);
+        assert_eq!(
+            FeeRate::from_fee(Sats(u64::MAX), WeightUnits::no_discount(1)),
+            FeeRate(u64::MAX / 4)
+        );
+    }
+
+    #[test]
+    fn dust() {
+        use derive::{PubkeyHash, ScriptHash, WPubkeyHash, WScriptHash};
+        use descriptors::SpkClass;
+
+        for (script_pubkey, class) in [
+            (ScriptPubkey::p2pkh(PubkeyHash::from([1; 20])), SpkClass::P2pkh),
+            (ScriptPubkey::p2sh(ScriptHash::from([1; 20])), SpkClass::P2sh),
+            (ScriptPubkey::p2wpkh(WPubkeyHash::from([1; 20])), SpkClass::P2wpkh),
+            (ScriptPubkey::p2wsh(WScriptHash::from([1; 32])), SpkClass::P2wsh),
+            (ScriptPubkey::op_return(&[0; 10]), SpkClass::Bare),
+        ] {
+            assert_eq!(dust_limit(&script_pubkey, FeeRate::DUST_RELAY), class.dust_limit());
+        }
+        let p2wpkh = ScriptPubkey::p2wpkh(WPubkeyHash::from([1; 20]));
+        assert_eq!(dust_limit(&p2wpkh, FeeRate::from_sat_per_vb(1)), Sats(98));
+    }
+}
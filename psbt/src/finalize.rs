@@ -0,0 +1,353 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! PSBT finalizer and transaction extractor roles.
+
+use amplify::Wrapper;
+use derive::{
+    LeafVer, LegacyPk, LegacySig, PubkeyHash, RedeemScript, ScriptBytes, ScriptPubkey, SigScript,
+    Tx, TxIn, VarIntArray, Witness,
+};
+
+use crate::{Encode, Input, PrevoutError, Psbt};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum FinalizeError {
+    #[display(inner)]
+    #[from]
+    Prevout(PrevoutError),
+
+    /// input {0} spends P2SH output, but doesn't provide a redeem script.
+    NoRedeemScript(usize),
+
+    /// input {0} spends P2WSH output, but doesn't provide a witness script.
+    NoWitnessScript(usize),
+
+    /// input {0} doesn't have enough signatures to be finalized.
+    NotEnoughSigs(usize),
+
+    /// input {0} spends an output of a type which can't be finalized.
+    UnsupportedScript(usize),
+
+    /// input {0} spends P2WSH output with a witness script other than multisig, like the ones
+    /// of miniscript descriptors, which can't be satisfied by the finalizer; the final witness
+    /// must be constructed externally.
+    UnsupportedWitnessScript(usize),
+}
+
+/// PSBT can't be extracted into a transaction since the inputs {0:?} are not
+/// finalized.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub struct UnfinalizedInputs(pub Vec<usize>);
+
+impl Psbt {
+    /// Detects whether all PSBT inputs are finalized.
+    pub fn is_finalized(&self) -> bool { self.inputs().all(Input::is_finalized) }
+
+    /// Finalizes all inputs which have enough signatures for that. Inputs which can't be
+    /// finalized don't prevent finalization of the other inputs.
+    ///
+    /// # Returns
+    ///
+    /// Number of finalized inputs, including inputs which were finalized before the call, if all
+    /// the inputs got finalized; otherwise errors for each of the inputs which can't be
+    /// finalized.
+    pub fn finalize(&mut self) -> Result<usize, Vec<FinalizeError>> {
+        let errors =
+            self.inputs_mut().filter_map(|input| input.finalize().err()).collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(self.inputs.len())
+    }
+
+    /// Extracts fully-signed transaction from a finalized PSBT.
+    pub fn extract(&self) -> Result<Tx, UnfinalizedInputs> {
+        let unfinalized = self
+            .inputs()
+            .filter(|input| !input.is_finalized())
+            .map(Input::index)
+            .collect::<Vec<_>>();
+        if !unfinalized.is_empty() {
+            return Err(UnfinalizedInputs(unfinalized));
+        }
+
+        let unsigned_tx = self.to_unsigned_tx();
+        let inputs = self.inputs().map(|input| TxIn {
+            prev_output: input.previous_outpoint,
+            sig_script: input.final_script_sig.clone().unwrap_or_default(),
+            sequence: input.to_unsigned_txin().sequence,
+            witness: input.final_witness.clone().unwrap_or_default(),
+        });
+        Ok(Tx {
+            version: unsigned_tx.version,
+            inputs: VarIntArray::from_iter_unsafe(inputs),
+            outputs: unsigned_tx.outputs,
+            lock_time: unsigned_tx.lock_time,
+        })
+    }
+}
+
+impl Input {
+    /// Converts partial signatures into final `scriptSig` and witness.
+    ///
+    /// Supports P2PKH, P2WPKH, P2SH-P2WPKH, as well as P2SH, P2WSH and P2SH-P2WSH
+    /// multisig spendings, as well as P2TR key path and script path spendings; for the
+    /// script path only `pk` and `multi_a` tapscript leaves are supported,
+    /// selecting the leaf with the smallest witness. On success, removes all data which are not
+    /// required anymore (signatures, scripts and key derivations), leaving
+    /// only the spent output information. Does nothing with already finalized
+    /// inputs.
+    pub fn finalize(&mut self) -> Result<(), FinalizeError> {
+        if self.is_finalized() {
+            return Ok(());
+        }
+        let index = self.index();
+        let spk = self.prev_txout()?.script_pubkey.clone();
+        let (sig_script, witness) = if spk.is_p2tr() {
+            let witness = match self.tap_key_sig {
+                Some(sig) => Witness::from_consensus_stack([sig.to_vec()]),
+                None => self.tap_script_witness()?,
+            };
+            (None, Some(witness))
+        } else if spk.is_p2pkh() {
+            let (pk, sig) = self.pkh_sig(&spk[3..23])?;
+            let mut sig_script = ScriptBytes::default();
+            sig_script.push_slice(&sig.to_vec());
+            sig_script.push_slice(&pk.to_vec());
+            (Some(SigScript::from_unsafe(sig_script.into_vec())), None)
+        } else if spk.is_p2sh() {
+            let redeem_script =
+                self.redeem_script.clone().ok_or(FinalizeError::NoRedeemScript(index))?;
+            let redeem_spk = ScriptPubkey::from_unsafe(redeem_script.to_vec());
+            if redeem_spk.is_witness_program() {
+                let witness = self.segwit_witness(&redeem_spk)?;
+                (Some(redeem_script_sig(&redeem_script)), Some(witness))
+            } else {
+                let mut sig_script = ScriptBytes::default();
+                for item in self.multisig_stack(redeem_script.as_slice())? {
+                    sig_script.push_slice(&item);
+                }
+                (Some(SigScript::from_unsafe(sig_script.into_vec())), None)
+            }
+        } else {
+            (None, Some(self.segwit_witness(&spk)?))
+        };
+
+        self.final_script_sig = sig_script;
+        self.final_witness = witness;
+        self.partial_sigs.clear();
+        self.sighash_type = None;
+        self.redeem_script = None;
+        self.witness_script = None;
+        self.bip32_derivation.clear();
+        self.ripemd160.clear();
+        self.sha256.clear();
+        self.hash160.clear();
+        self.hash256.clear();
+        self.tap_key_sig = None;
+        self.tap_script_sig.clear();
+        self.tap_leaf_script.clear();
+        self.tap_bip32_derivation.clear();
+        self.tap_internal_key = None;
+        self.tap_merkle_root = None;
+        Ok(())
+    }
+
+    fn pkh_sig(&self, hash: &[u8]) -> Result<(LegacyPk, LegacySig), FinalizeError> {
+        self.partial_sigs
+            .iter()
+            .find(|(pk, _)| &PubkeyHash::from(**pk)[..] == hash)
+            .map(|(pk, sig)| (*pk, *sig))
+            .ok_or(FinalizeError::NotEnoughSigs(self.index()))
+    }
+
+    fn segwit_witness(&self, spk: &ScriptPubkey) -> Result<Witness, FinalizeError> {
+        let index = self.index();
+        if spk.is_p2wpkh() {
+            let (pk, sig) = self.pkh_sig(&spk[2..22])?;
+            return Ok(Witness::from_consensus_stack([sig.to_vec(), pk.to_vec()]));
+        }
+        if !spk.is_p2wsh() {
+            return Err(FinalizeError::UnsupportedScript(index));
+        }
+
+        let witness_script =
+            self.witness_script.as_ref().ok_or(FinalizeError::NoWitnessScript(index))?;
+        if parse_multisig(witness_script.as_slice()).is_none() {
+            return Err(FinalizeError::UnsupportedWitnessScript(index));
+        }
+        self.multisig_stack(witness_script.as_slice()).map(Witness::from_consensus_stack)
+    }
+
+    /// Constructs stack satisfying `OP_CHECKMULTISIG` script, followed by the script itself.
+    fn multisig_stack(&self, script: &[u8]) -> Result<Vec<Vec<u8>>, FinalizeError> {
+        let index = self.index();
+        let (threshold, keys) =
+            parse_multisig(script).ok_or(FinalizeError::UnsupportedScript(index))?;
+        let sigs = keys
+            .iter()
+            .filter_map(|pk| {
+                self.partial_sigs
+                    .iter()
+                    .find(|(key, _)| key.to_vec() == *pk)
+                    .map(|(_, sig)| sig.to_vec())
+            })
+            .take(threshold)
+            .collect::<Vec<_>>();
+        if sigs.len() < threshold {
+            return Err(FinalizeError::NotEnoughSigs(index));
+        }
+
+        // Dummy element consumed by OP_CHECKMULTISIG bug
+        let mut stack = vec![vec![]];
+        stack.extend(sigs);
+        stack.push(script.to_vec());
+        Ok(stack)
+    }
+
+    fn tap_script_witness(&self) -> Result<Witness, FinalizeError> {
+        let mut best: Option<Vec<Vec<u8>>> = None;
+        for (control_block, leaf_script) in &self.tap_leaf_script {
+            if leaf_script.version != LeafVer::TapScript {
+                continue;
+            }
+            let leaf_hash = leaf_script.tap_leaf_hash().into_inner();
+            let Some((threshold, keys)) = parse_multi_a(leaf_script.as_script_bytes()) else {
+                continue;
+            };
+
+            let mut sig_count = 0usize;
+            let mut stack = Vec::with_capacity(keys.len() + 2);
+            // The first key in the script consumes the topmost stack element
+            for pk in keys.iter().rev() {
+                let sig = self
+                    .tap_script_sig
+                    .iter()
+                    .find(|((key, hash), _)| key.to_byte_array() == *pk && *hash == leaf_hash);
+                match sig {
+                    Some((_, sig)) if sig_count < threshold => {
+                        sig_count += 1;
+                        stack.push(sig.to_vec())
+                    }
+                    _ => stack.push(vec![]),
+                }
+            }
+            if sig_count < threshold {
+                continue;
+            }
+            stack.push(leaf_script.script.to_vec());
+            let mut control_block_data = vec![];
+            control_block.encode(&mut control_block_data).expect("in-memory encoding");
+            stack.push(control_block_data);
+
+            let size = |stack: &Vec<Vec<u8>>| stack.iter().map(Vec::len).sum::<usize>();
+            if best.as_ref().map(|best| size(best) > size(&stack)).unwrap_or(true) {
+                best = Some(stack);
+            }
+        }
+        best.map(Witness::from_consensus_stack).ok_or(FinalizeError::NotEnoughSigs(self.index()))
+    }
+}
+
+fn redeem_script_sig(redeem_script: &RedeemScript) -> SigScript {
+    let mut sig_script = ScriptBytes::default();
+    sig_script.push_slice(redeem_script.as_slice());
+    SigScript::from_unsafe(sig_script.into_vec())
+}
+
+/// Parses bare `k <pk>... n OP_CHECKMULTISIG` script returning threshold and
+/// serialized public keys, which may be both compressed and uncompressed.
+fn parse_multisig(script: &[u8]) -> Option<(usize, Vec<&[u8]>)> {
+    const OP_PUSHNUM_1: u8 = 0x51;
+    const OP_PUSHNUM_16: u8 = 0x60;
+    const OP_CHECKMULTISIG: u8 = 0xae;
+
+    let (&first, rest) = script.split_first()?;
+    let (&last, rest) = rest.split_last()?;
+    let (&count, mut rest) = rest.split_last()?;
+    if !(OP_PUSHNUM_1..=OP_PUSHNUM_16).contains(&first)
+        || !(OP_PUSHNUM_1..=OP_PUSHNUM_16).contains(&count)
+        || last != OP_CHECKMULTISIG
+    {
+        return None;
+    }
+    let threshold = (first - OP_PUSHNUM_1 + 1) as usize;
+    let count = (count - OP_PUSHNUM_1 + 1) as usize;
+
+    let mut keys = Vec::with_capacity(count);
+    while let Some((&len, data)) = rest.split_first() {
+        if !matches!(len, 33 | 65) || data.len() < len as usize {
+            return None;
+        }
+        let (key, remains) = data.split_at(len as usize);
+        keys.push(key);
+        rest = remains;
+    }
+    if !rest.is_empty() || keys.len() != count || threshold > count {
+        return None;
+    }
+    Some((threshold, keys))
+}
+
+/// Parses `pk` (`<pk> OP_CHECKSIG`) and `multi_a` (`<pk> OP_CHECKSIG <pk>
+/// OP_CHECKSIGADD ... k OP_NUMEQUAL`) tapscripts returning threshold and
+/// x-only public keys.
+fn parse_multi_a(script: &ScriptBytes) -> Option<(usize, Vec<[u8; 32]>)> {
+    const OP_PUSHBYTES_32: u8 = 0x20;
+    const OP_PUSHNUM_1: u8 = 0x51;
+    const OP_PUSHNUM_16: u8 = 0x60;
+    const OP_NUMEQUAL: u8 = 0x9c;
+    const OP_CHECKSIG: u8 = 0xac;
+    const OP_CHECKSIGADD: u8 = 0xba;
+
+    let mut rest = script.as_slice();
+    let mut keys = vec![];
+    while let [OP_PUSHBYTES_32, data @ ..] = rest {
+        if data.len() < 33 {
+            return None;
+        }
+        let expected = if keys.is_empty() { OP_CHECKSIG } else { OP_CHECKSIGADD };
+        if data[32] != expected {
+            return None;
+        }
+        let mut pk = [0u8; 32];
+        pk.copy_from_slice(&data[..32]);
+        keys.push(pk);
+        rest = &data[33..];
+    }
+
+    let threshold = match rest {
+        [] if keys.len() == 1 => 1,
+        [op @ OP_PUSHNUM_1..=OP_PUSHNUM_16, OP_NUMEQUAL] => (op - OP_PUSHNUM_1 + 1) as usize,
+        [len @ 1..=2, data @ .., OP_NUMEQUAL] if data.len() == *len as usize => {
+            data.iter().rev().fold(0usize, |num, byte| (num << 8) | *byte as usize)
+        }
+        _ => return None,
+    };
+    if keys.is_empty() || threshold == 0 || threshold > keys.len() {
+        return None;
+    }
+    Some((threshold, keys))
+}
@@ -30,25 +30,50 @@ extern crate strict_encoding;
 extern crate serde_crate as serde;
 
 mod data;
+mod fee;
 mod keys;
 mod maps;
+mod prop;
 mod coders;
 #[cfg(feature = "client-side-validation")]
 mod csval;
 pub mod constructor;
+mod sign;
+mod mock;
+mod finalize;
+mod combine;
+mod reserves;
+mod payjoin;
+mod coinjoin;
+mod policy;
+mod report;
 
 pub use coders::{Decode, DecodeError, Encode, PsbtError};
+pub use coinjoin::CoinjoinError;
+pub use combine::TxMismatch;
 pub use constructor::{
-    Beneficiary, BeneficiaryParseError, ConstructionError, Payment, PsbtConstructor, PsbtMeta,
-    TxParams, Utxo,
+    Beneficiary, BeneficiaryParseError, ConsolidationParams, ConsolidationPlan, ConstructionError,
+    ForeignInput, Payment, PsbtConstructor, PsbtMeta, TxExtras, TxParams, Utxo,
 };
 #[cfg(feature = "client-side-validation")]
 pub use csval::*;
 pub use data::{
-    Input, ModifiableFlags, Output, Prevout, Psbt, PsbtParseError, UnsignedTx, UnsignedTxIn,
+    Input, ModifiableFlags, OpReturnError, Output, Prevout, PrevoutError, Psbt, PsbtParseError,
+    UnsignedTx, UnsignedTxIn, MAX_OP_RETURN_DATA,
 };
+pub use fee::{dust_limit, FeeRate, FeeRateParseError};
+pub use finalize::{FinalizeError, UnfinalizedInputs};
 pub use keys::{GlobalKey, InputKey, KeyPair, KeyType, OutputKey, PropKey};
 pub use maps::{KeyAlreadyPresent, KeyData, KeyMap, Map, MapName, ValueData};
+pub use mock::{EstimateError, MockSigner};
+pub use payjoin::{PayjoinError, PayjoinParams};
+pub use policy::{PolicyAction, PolicyViolation, SigningPolicy};
+pub use prop::PropField;
+pub use report::{AnalysisError, OutputReport, PsbtReport};
+pub use reserves::{
+    por_challenge, por_script_pubkey, verify_reserves, ReservesError, POR_MESSAGE_PREFIX,
+};
+pub use sign::{SighashPolicy, Sign, SignError};
 
 #[cfg(feature = "strict_encoding")]
 pub const LIB_NAME_PSBT: &str = "Psbt";
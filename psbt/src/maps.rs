@@ -36,7 +36,7 @@ use crate::coders::RawBytes;
 use crate::keys::KeyValue;
 use crate::{
     Decode, DecodeError, Encode, GlobalKey, Input, InputKey, KeyPair, KeyType, ModifiableFlags,
-    Output, OutputKey, PropKey, Psbt, PsbtError, PsbtVer, UnsignedTx,
+    Output, OutputKey, PropField, PropKey, Psbt, PsbtError, PsbtVer, UnsignedTx,
 };
 
 pub type KeyData = ByteStr;
@@ -164,7 +164,7 @@ pub trait KeyMap: Sized {
                 // We need this hack since Rust borrower checker can't see that the
                 // reference actually doesn't escape the scope
                 ::core::mem::transmute::<
-                    _,
+                    Vec<KeyPair<Self::Keys, Box<dyn Encode + '_>, Box<dyn Encode + '_>>>,
                     Vec<KeyPair<Self::Keys, Box<dyn Encode + 'static>, Box<dyn Encode + 'static>>>,
                 >(self.retrieve_key_pair(version, *key_type))
             }
@@ -243,6 +243,46 @@ pub trait KeyMap: Sized {
         self._proprietary_map_mut().shift_remove(key)
     }
 
+    /// Reads value of the typed proprietary field `F` under the given key data.
+    fn prop_field<F: PropField>(&self, key: &F::KeyData) -> Result<Option<F::Value>, PsbtError> {
+        self.proprietary(&F::prop_key(key)).map(F::decode_value).transpose()
+    }
+    /// Iterates over all key data and values of the typed proprietary field
+    /// `F`.
+    fn prop_fields<F: PropField>(
+        &self,
+    ) -> impl Iterator<Item = Result<(F::KeyData, F::Value), PsbtError>> + '_ {
+        self._proprietary_map()
+            .iter()
+            .filter(|(key, _)| F::matches(key))
+            .map(|(key, value)| Ok((F::KeyData::deserialize(&key.data)?, F::decode_value(value)?)))
+    }
+    /// Adds the typed proprietary field `F`, failing if the key is already
+    /// present.
+    fn push_prop_field<F: PropField>(
+        &mut self,
+        key: &F::KeyData,
+        value: &F::Value,
+    ) -> Result<(), KeyAlreadyPresent> {
+        self.push_proprietary(F::prop_key(key), F::encode_value(value))
+    }
+    /// Sets the typed proprietary field `F`, returning the previous raw value,
+    /// if any.
+    fn set_prop_field<F: PropField>(
+        &mut self,
+        key: &F::KeyData,
+        value: &F::Value,
+    ) -> Option<ValueData> {
+        self._proprietary_map_mut().insert(F::prop_key(key), F::encode_value(value))
+    }
+    /// Removes the typed proprietary field `F`, returning its decoded value.
+    fn remove_prop_field<F: PropField>(
+        &mut self,
+        key: &F::KeyData,
+    ) -> Result<Option<F::Value>, PsbtError> {
+        self.remove_proprietary(&F::prop_key(key)).as_ref().map(F::decode_value).transpose()
+    }
+
     #[doc(hidden)]
     fn _unknown_map(&self) -> &IndexMap<u8, IndexMap<KeyData, ValueData>>;
     #[doc(hidden)]
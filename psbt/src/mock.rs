@@ -0,0 +1,134 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dummy signer producing placeholder signatures of the correct size, used for estimating the
+//! weight of a transaction before it gets signed.
+
+use derive::secp256k1::{ecdsa, schnorr};
+use derive::{
+    CompressedPk, InternalPk, KeyOrigin, Sighash, TapNodeHash, Tx, TxStats, Weight, WeightUnits,
+    XOnlyPk,
+};
+
+use crate::{Psbt, SighashPolicy, Sign, SignError, UnfinalizedInputs};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum EstimateError {
+    #[display(inner)]
+    #[from]
+    Sign(SignError),
+
+    #[display(inner)]
+    #[from]
+    Unfinalized(UnfinalizedInputs),
+}
+
+/// Signer which "signs" with any key present in the PSBT derivation information, producing
+/// deterministic placeholder signatures which are not valid.
+///
+/// ECDSA placeholders have the maximal size of a low-S DER signature (72 bytes together with the
+/// sighash type), such that the estimated transaction weight is never below the weight of the
+/// transaction signed with real keys.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct MockSigner;
+
+impl MockSigner {
+    fn ecdsa() -> ecdsa::Signature {
+        let mut compact = [0x7Fu8; 64];
+        // High bit of `r` requires an extra zero byte in DER encoding
+        compact[..32].copy_from_slice(&[0x80; 32]);
+        ecdsa::Signature::from_compact(&compact).expect("valid placeholder signature")
+    }
+
+    fn bip340() -> schnorr::Signature {
+        schnorr::Signature::from_slice(&[0x01; 64]).expect("valid placeholder signature")
+    }
+}
+
+impl Sign for MockSigner {
+    fn sign_ecdsa(
+        &self,
+        _sighash: Sighash,
+        _pk: CompressedPk,
+        _origin: &KeyOrigin,
+    ) -> Option<ecdsa::Signature> {
+        Some(Self::ecdsa())
+    }
+
+    fn sign_bip340_key_only(
+        &self,
+        _sighash: Sighash,
+        _internal_pk: InternalPk,
+        _origin: &KeyOrigin,
+        _merkle_root: Option<TapNodeHash>,
+    ) -> Option<schnorr::Signature> {
+        Some(Self::bip340())
+    }
+
+    fn sign_bip340_script_path(
+        &self,
+        _sighash: Sighash,
+        _pk: XOnlyPk,
+        _origin: &KeyOrigin,
+    ) -> Option<schnorr::Signature> {
+        Some(Self::bip340())
+    }
+}
+
+impl Psbt {
+    /// Produces a transaction with all inputs signed and finalized by the [`MockSigner`], keeping
+    /// the PSBT itself intact. The transaction is not valid, but has the same size as it will
+    /// have once signed, up to the ECDSA signature size variation.
+    ///
+    /// Inputs which are already finalized are kept as is. Taproot inputs are finalized with the
+    /// key path whenever the internal key derivation is known.
+    pub fn mock_signed_tx(&self) -> Result<Tx, EstimateError> {
+        let mut psbt = self.clone();
+        psbt.sign_with_policy(&MockSigner, &SighashPolicy::any())?;
+        // Inputs which can't be finalized are reported by the extraction
+        let _ = psbt.finalize();
+        Ok(psbt.extract()?)
+    }
+
+    /// Estimates the weight of the transaction after it gets signed; see
+    /// [`Psbt::mock_signed_tx`] for the details.
+    pub fn estimate_weight(&self) -> Result<WeightUnits, EstimateError> {
+        self.mock_signed_tx().map(|tx| tx.weight_units())
+    }
+
+    /// Estimates the signature operation cost of the transaction after it gets signed, including
+    /// the P2SH and witness signature operations; see [`Psbt::mock_signed_tx`] for the details.
+    pub fn estimate_sigop_cost(&self) -> Result<u32, EstimateError> {
+        let tx = self.mock_signed_tx()?;
+        let prevouts = self.inputs().filter_map(|input| input.prev_txout().ok()).cloned();
+        let prevouts = prevouts.collect::<Vec<_>>();
+        Ok(tx.sigop_cost(&prevouts).expect("signed transaction has the same inputs"))
+    }
+
+    /// Estimates the weight of the signed transaction, falling back to the weight of the
+    /// unsigned transaction when the estimation is not possible, like for inputs which can't be
+    /// signed with the key derivation information present in the PSBT.
+    pub(crate) fn estimate_weight_or_unsigned(&self) -> WeightUnits {
+        self.estimate_weight().unwrap_or_else(|_| Tx::from(self.to_unsigned_tx()).weight_units())
+    }
+}
@@ -0,0 +1,319 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Payjoin (BIP-78) transactions.
+//!
+//! The sender constructs, signs and finalizes an "original" PSBT paying the receiver and sends
+//! it to the receiver. The receiver adds inputs spending its own coins, increasing the amount
+//! of its output by their value (see [`PsbtConstructor::construct_payjoin_proposal`]), signs
+//! and finalizes its inputs and returns the resulting "proposal" PSBT to the sender. The sender
+//! validates the proposal against the original PSBT (see [`Psbt::process_payjoin_proposal`]),
+//! signs it once again and broadcasts it.
+//!
+//! [`PsbtConstructor::construct_payjoin_proposal`]: crate::PsbtConstructor::construct_payjoin_proposal
+
+use std::collections::HashMap;
+
+use derive::{Outpoint, Sats, ScriptPubkey, Vout, Weight};
+use descriptors::{SpkClass, SpkTemplate};
+
+use crate::{ConstructionError, FeeRate, Input, ModifiableFlags, Output, PrevoutError, Psbt};
+
+#[derive(Clone, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PayjoinError {
+    #[display(inner)]
+    #[from]
+    Construction(ConstructionError),
+
+    /// the original payjoin PSBT has no inputs.
+    NoInputs,
+
+    #[display(inner)]
+    #[from]
+    Prevout(PrevoutError),
+
+    /// input {0} of the original payjoin PSBT is not finalized.
+    OriginalNotFinalized(usize),
+
+    /// the original payjoin PSBT spends more than present in its inputs.
+    NegativeFee,
+
+    /// payjoin inputs spend outputs of different types.
+    MixedInputTypes,
+
+    /// receiver output {0} is not present in the original payjoin PSBT.
+    InvalidReceiverOutput(Vout),
+
+    /// additional fee output {0} is not present in the original payjoin PSBT, matches the
+    /// receiver output or has not enough funds to pay the fee.
+    InvalidFeeOutput(Vout),
+
+    /// payjoin proposal changes the transaction version or lock time.
+    TxMismatch,
+
+    /// payjoin proposal doesn't spend sender output {0}.
+    MissingSenderInput(Outpoint),
+
+    /// payjoin proposal input spending {0} has a sequence number different from the sender
+    /// inputs.
+    SequenceMismatch(Outpoint),
+
+    /// receiver input {0} of the payjoin proposal is not finalized.
+    ReceiverNotFinalized(usize),
+
+    /// payjoin proposal removes or decreases output {0} of the original transaction.
+    OutputChanged(Vout),
+
+    /// payjoin proposal takes {0} sats of the additional fee from the sender, which exceeds the
+    /// allowed maximum.
+    ExcessiveFee(Sats),
+
+    /// payjoin proposal pays less fee than the original transaction.
+    FeeDecreased,
+
+    /// payjoin proposal takes {0} sats from the additional fee output, which exceeds the increase
+    /// of the transaction fee.
+    FeeContributionExceeded(Sats),
+
+    /// payjoin proposal fee rate {0} is below the fee rate of the original transaction.
+    FeeRateDecreased(FeeRate),
+}
+
+/// Optional parameters of the payjoin protocol, which the sender provides to the receiver
+/// together with the original PSBT.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct PayjoinParams {
+    /// Sender output from which the receiver may take the fee for its inputs.
+    pub additional_fee_output: Option<Vout>,
+    /// Maximal amount of the fee which the receiver may take from the additional fee output.
+    pub max_additional_fee: Sats,
+    /// Whether the receiver must not substitute its output with a different script.
+    pub disable_output_substitution: bool,
+}
+
+fn input_class(input: &Input) -> Result<SpkClass, PayjoinError> {
+    Ok(SpkTemplate::lift(&input.prev_txout()?.script_pubkey).class())
+}
+
+fn uniform_class<'a>(
+    inputs: impl IntoIterator<Item = &'a Input>,
+) -> Result<Option<SpkClass>, PayjoinError> {
+    let mut class = None;
+    for input in inputs {
+        let next = input_class(input)?;
+        if class.is_some_and(|class| class != next) {
+            return Err(PayjoinError::MixedInputTypes);
+        }
+        class = Some(next);
+    }
+    Ok(class)
+}
+
+fn find_output<'a>(psbt: &'a Psbt, script: &ScriptPubkey) -> Option<&'a Output> {
+    psbt.outputs().find(|output| output.script == *script)
+}
+
+impl Psbt {
+    /// Receiver-side validation of the original payjoin PSBT, which must be fully finalized and
+    /// spend outputs of a single type. Returns the fee rate of the original transaction.
+    pub fn check_payjoin_original(&self) -> Result<FeeRate, PayjoinError> {
+        if self.inputs().count() == 0 {
+            return Err(PayjoinError::NoInputs);
+        }
+        uniform_class(self.inputs())?;
+        let tx = self
+            .extract()
+            .map_err(|unfinalized| PayjoinError::OriginalNotFinalized(unfinalized.0[0]))?;
+        let fee = self.fee()?.ok_or(PayjoinError::NegativeFee)?;
+        Ok(FeeRate::from_fee(fee, tx.weight_units()))
+    }
+
+    /// Starts the payjoin proposal from the original PSBT, removing sender signatures and key
+    /// derivation information. The receiver inputs are added with the sequence number of the
+    /// sender inputs.
+    pub(crate) fn payjoin_proposal_base(&self) -> Psbt {
+        let mut proposal = self.clone();
+        proposal.xpubs.clear();
+        proposal.tx_modifiable = Some(ModifiableFlags::modifiable());
+        for input in proposal.inputs_mut() {
+            input.final_script_sig = None;
+            input.final_witness = None;
+            input.partial_sigs.clear();
+            input.tap_key_sig = None;
+            input.tap_script_sig.clear();
+            input.bip32_derivation.clear();
+            input.tap_bip32_derivation.clear();
+        }
+        for output in proposal.outputs_mut() {
+            output.bip32_derivation.clear();
+            output.tap_bip32_derivation.clear();
+        }
+        proposal
+    }
+
+    /// Moves the value of the receiver inputs to the receiver output, deducting the `fee` for
+    /// them. Up to [`PayjoinParams::max_additional_fee`] of the fee is paid from the additional
+    /// fee output of the sender, and the rest is paid by the receiver.
+    pub(crate) fn apply_payjoin_contribution(
+        &mut self,
+        receiver_vout: Vout,
+        contribution: Sats,
+        fee: Sats,
+        params: &PayjoinParams,
+    ) -> Result<(), PayjoinError> {
+        let mut receiver_fee = fee;
+        if let Some(vout) = params.additional_fee_output {
+            let sender_fee = fee.min(params.max_additional_fee);
+            let output = self
+                .outputs_mut()
+                .nth(vout.into_usize())
+                .filter(|_| vout != receiver_vout)
+                .ok_or(PayjoinError::InvalidFeeOutput(vout))?;
+            output.amount = output
+                .amount
+                .checked_sub(sender_fee)
+                .ok_or(PayjoinError::InvalidFeeOutput(vout))?;
+            receiver_fee -= sender_fee;
+        }
+        let output = self
+            .outputs_mut()
+            .nth(receiver_vout.into_usize())
+            .ok_or(PayjoinError::InvalidReceiverOutput(receiver_vout))?;
+        let value = output
+            .amount
+            .checked_add(contribution)
+            .ok_or(ConstructionError::Overflow(output.amount))?;
+        output.amount =
+            value.checked_sub(receiver_fee).ok_or(ConstructionError::NoFundsForFee {
+                input_value: contribution,
+                output_value: output.amount,
+                fee: receiver_fee,
+            })?;
+        Ok(())
+    }
+
+    /// Sender-side validation of the payjoin `proposal` returned by the receiver paying to the
+    /// `payee` script pubkey. Must be called on the original PSBT as it was before being
+    /// finalized, such that the sender inputs still contain key derivation information.
+    ///
+    /// Checks that the proposal spends all the sender inputs, keeps all the sender outputs, takes
+    /// no more than [`PayjoinParams::max_additional_fee`] from the additional fee output and
+    /// no more than the fee increase, doesn't decrease the fee rate, and that all the receiver
+    /// inputs are finalized and have the same type as the sender inputs.
+    ///
+    /// Returns the proposal with the sender inputs and outputs restored from the original PSBT,
+    /// ready to be signed by the sender.
+    pub fn process_payjoin_proposal(
+        &self,
+        mut proposal: Psbt,
+        payee: &ScriptPubkey,
+        params: &PayjoinParams,
+    ) -> Result<Psbt, PayjoinError> {
+        if proposal.tx_version != self.tx_version || proposal.lock_time() != self.lock_time() {
+            return Err(PayjoinError::TxMismatch);
+        }
+
+        let sender_inputs =
+            self.inputs().map(|input| (input.previous_outpoint, input)).collect::<HashMap<_, _>>();
+        let class = uniform_class(self.inputs())?;
+        let sequence =
+            self.inputs().next().ok_or(PayjoinError::NoInputs)?.to_unsigned_txin().sequence;
+        for outpoint in sender_inputs.keys() {
+            if !proposal.inputs().any(|input| input.previous_outpoint == *outpoint) {
+                return Err(PayjoinError::MissingSenderInput(*outpoint));
+            }
+        }
+        for input in proposal.inputs() {
+            if input.to_unsigned_txin().sequence != sequence {
+                return Err(PayjoinError::SequenceMismatch(input.previous_outpoint));
+            }
+            if sender_inputs.contains_key(&input.previous_outpoint) {
+                continue;
+            }
+            if !input.is_finalized() {
+                return Err(PayjoinError::ReceiverNotFinalized(input.index()));
+            }
+            let input_class = input_class(input)?;
+            if class.is_some_and(|class| class != input_class) {
+                return Err(PayjoinError::MixedInputTypes);
+            }
+        }
+
+        let mut taken = Sats::ZERO;
+        for (vout, output) in self.outputs().enumerate() {
+            let vout = Vout::from_u32(vout as u32);
+            let new = find_output(&proposal, &output.script).map(Output::value);
+            if output.script == *payee {
+                if params.disable_output_substitution && !new.is_some_and(|v| v >= output.amount) {
+                    return Err(PayjoinError::OutputChanged(vout));
+                }
+                continue;
+            }
+            let new = new.ok_or(PayjoinError::OutputChanged(vout))?;
+            if Some(vout) == params.additional_fee_output && new <= output.amount {
+                taken = output.amount - new;
+                if taken > params.max_additional_fee {
+                    return Err(PayjoinError::ExcessiveFee(taken));
+                }
+            } else if new != output.amount {
+                return Err(PayjoinError::OutputChanged(vout));
+            }
+        }
+
+        let (original_fee, fee) = match (self.fee()?, proposal.fee()?) {
+            (Some(original), Some(fee)) if fee >= original => (original, fee),
+            _ => return Err(PayjoinError::FeeDecreased),
+        };
+        if taken > fee - original_fee {
+            return Err(PayjoinError::FeeContributionExceeded(taken));
+        }
+
+        proposal.xpubs = self.xpubs.clone();
+        for input in proposal.inputs_mut() {
+            if let Some(original) = sender_inputs.get(&input.previous_outpoint) {
+                let index = input.index();
+                *input = (*original).clone();
+                input.index = index;
+            }
+        }
+        for output in proposal.outputs_mut() {
+            if let Some(original) = find_output(self, &output.script) {
+                output.redeem_script = original.redeem_script.clone();
+                output.witness_script = original.witness_script.clone();
+                output.bip32_derivation = original.bip32_derivation.clone();
+                output.tap_internal_key = original.tap_internal_key;
+                output.tap_tree = original.tap_tree.clone();
+                output.tap_bip32_derivation = original.tap_bip32_derivation.clone();
+            }
+        }
+
+        // Sender inputs are estimated in the same way for both transactions, such that the
+        // difference in their signature sizes doesn't affect the comparison
+        let original_rate = FeeRate::from_fee(original_fee, self.estimate_weight_or_unsigned());
+        let fee_rate = FeeRate::from_fee(fee, proposal.estimate_weight_or_unsigned());
+        if fee_rate < original_rate {
+            return Err(PayjoinError::FeeRateDecreased(fee_rate));
+        }
+        Ok(proposal)
+    }
+}
@@ -0,0 +1,201 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Risk analysis of PSBTs performed by signers before producing signatures.
+
+use std::collections::BTreeSet;
+
+use derive::{Sats, SighashType};
+use descriptors::{Descriptor, SpkClass, SpkTemplate};
+
+use crate::report::claimed_terminals;
+use crate::{FeeRate, Output, PrevoutError, Psbt};
+
+/// Action taken by [`SigningPolicy`] when a PSBT violates one of its checks.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum PolicyAction {
+    /// The violation is not reported.
+    Ignore,
+    /// The violation is reported as a warning, which doesn't prevent signing.
+    Warn,
+    /// The PSBT must not be signed.
+    Reject,
+}
+
+/// Violation of a [`SigningPolicy`] check.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PolicyViolation {
+    #[display(inner)]
+    #[from]
+    Prevout(PrevoutError),
+
+    /// transaction spends more than present in its inputs.
+    NegativeFee,
+
+    /// transaction pays absurdly high fee of {0} sats.
+    AbsurdFee(Sats),
+
+    /// transaction pays absurdly high fee rate of {0}.
+    AbsurdFeeRate(FeeRate),
+
+    /// output {0} claims to be a wallet change, but doesn't match the wallet descriptor.
+    UnknownChange(usize),
+
+    /// transaction inputs use different sighash types.
+    MixedSighash,
+
+    /// output {0} pays to a script pubkey spent by one of the transaction inputs.
+    AddressReuse(usize),
+
+    /// output {0} has a script pubkey of an unexpected type.
+    UnexpectedOutput(usize),
+}
+
+/// Policy which signers apply to a PSBT before signing it, detecting transactions which are
+/// invalid, wasteful or may result from a compromised wallet.
+///
+/// PSBTs which don't provide the spent outputs or spend more than present in their inputs are
+/// always rejected; the actions for the rest of the checks are configurable.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SigningPolicy {
+    /// Maximal fee which is not considered absurd.
+    pub max_fee: Sats,
+    /// Maximal fee rate which is not considered absurd.
+    pub max_fee_rate: FeeRate,
+    /// Types of the expected output script pubkeys. `OP_RETURN` outputs are always expected.
+    pub output_classes: BTreeSet<SpkClass>,
+
+    /// Action for the fee or fee rate exceeding the maximum.
+    pub absurd_fee: PolicyAction,
+    /// Action for the outputs which have derivation information of the wallet keys, but are
+    /// not derived from the wallet descriptor.
+    pub unknown_change: PolicyAction,
+    /// Action for the inputs signed with different sighash types.
+    pub mixed_sighash: PolicyAction,
+    /// Action for the outputs paying to the script pubkeys spent by the transaction.
+    pub address_reuse: PolicyAction,
+    /// Action for the outputs of unexpected types.
+    pub unexpected_output: PolicyAction,
+}
+
+impl Default for SigningPolicy {
+    fn default() -> Self {
+        SigningPolicy {
+            max_fee: Sats::from_sats(10_000_000u32),
+            max_fee_rate: FeeRate::from_sat_per_vb(1000),
+            output_classes: bset![
+                SpkClass::P2pkh,
+                SpkClass::P2sh,
+                SpkClass::P2wpkh,
+                SpkClass::P2wsh,
+                SpkClass::P2tr
+            ],
+            absurd_fee: PolicyAction::Reject,
+            unknown_change: PolicyAction::Reject,
+            mixed_sighash: PolicyAction::Warn,
+            address_reuse: PolicyAction::Warn,
+            unexpected_output: PolicyAction::Warn,
+        }
+    }
+}
+
+impl SigningPolicy {
+    /// Checks the PSBT against the policy before signing it with the keys of the wallet
+    /// `descriptor`.
+    ///
+    /// The fee rate is computed for the estimated weight of the signed transaction (see
+    /// [`Psbt::estimate_weight`]); if the estimation is not possible, the weight of the unsigned
+    /// transaction is used, overestimating the fee rate.
+    ///
+    /// # Returns
+    ///
+    /// Warnings on success; the first violation with [`PolicyAction::Reject`] action on error.
+    pub fn check<K, D: Descriptor<K>>(
+        &self,
+        psbt: &Psbt,
+        descriptor: &D,
+    ) -> Result<Vec<PolicyViolation>, PolicyViolation> {
+        let mut warnings = Vec::new();
+        let mut report = |action: PolicyAction, violation: PolicyViolation| match action {
+            PolicyAction::Ignore => Ok(()),
+            PolicyAction::Warn => {
+                warnings.push(violation);
+                Ok(())
+            }
+            PolicyAction::Reject => Err(violation),
+        };
+
+        let fee = psbt.fee()?.ok_or(PolicyViolation::NegativeFee)?;
+        if fee > self.max_fee {
+            report(self.absurd_fee, PolicyViolation::AbsurdFee(fee))?;
+        }
+        let fee_rate = FeeRate::from_fee(fee, psbt.estimate_weight_or_unsigned());
+        if fee_rate > self.max_fee_rate {
+            report(self.absurd_fee, PolicyViolation::AbsurdFeeRate(fee_rate))?;
+        }
+
+        let sighash_types = psbt
+            .inputs()
+            .map(|input| input.sighash_type.unwrap_or(SighashType::all()).into_consensus_u8())
+            .collect::<BTreeSet<_>>();
+        if sighash_types.len() > 1 {
+            report(self.mixed_sighash, PolicyViolation::MixedSighash)?;
+        }
+
+        for output in psbt.outputs() {
+            let index = output.index();
+            if is_unknown_change(output, descriptor) {
+                report(self.unknown_change, PolicyViolation::UnknownChange(index))?;
+            }
+            if psbt.inputs().any(|input| {
+                input.prev_txout().is_ok_and(|txout| txout.script_pubkey == output.script)
+            }) {
+                report(self.address_reuse, PolicyViolation::AddressReuse(index))?;
+            }
+            let class = SpkTemplate::lift(&output.script).class();
+            let expected = match class {
+                SpkClass::Bare => output.script.is_op_return(),
+                class => self.output_classes.contains(&class),
+            };
+            if !expected {
+                report(self.unexpected_output, PolicyViolation::UnexpectedOutput(index))?;
+            }
+        }
+
+        Ok(warnings)
+    }
+}
+
+/// Detects outputs having derivation information of the descriptor keys, which don't match
+/// the script pubkey derived by the descriptor for the terminal of the derivation.
+fn is_unknown_change<K, D: Descriptor<K>>(output: &Output, descriptor: &D) -> bool {
+    let fingerprints = descriptor.xpubs().map(|spec| spec.origin().master_fp()).collect::<Vec<_>>();
+    let unknown = claimed_terminals(output, &fingerprints).any(|terminal| match terminal {
+        Some(terminal) => {
+            descriptor.derive(terminal.keychain, terminal.index).to_script_pubkey() != output.script
+        }
+        None => true,
+    });
+    unknown
+}
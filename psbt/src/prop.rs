@@ -0,0 +1,168 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed access to proprietary PSBT keys.
+//!
+//! Downstream protocols register their proprietary keys by implementing
+//! [`PropField`] for a marker type, providing the prefix identifier, key
+//! subtype and the types of the key data and the value. The fields can be read
+//! and written with the typed methods of [`KeyMap`] on any of the PSBT maps.
+//!
+//! [`KeyMap`]: crate::KeyMap
+
+use crate::{Decode, Encode, PropKey, PsbtError, ValueData};
+
+/// Proprietary PSBT key registered by a downstream protocol.
+///
+/// Keys which do not carry key data should use `()` as [`Self::KeyData`].
+pub trait PropField {
+    /// Prefix identifier of the protocol.
+    const IDENTIFIER: &'static str;
+    /// Key subtype within the protocol prefix.
+    const SUBTYPE: u64;
+
+    /// Type of the key data.
+    type KeyData: Encode + Decode;
+    /// Type of the value.
+    type Value: Encode + Decode;
+
+    /// Constructs proprietary key for the field with the given key data.
+    fn prop_key(key_data: &Self::KeyData) -> PropKey {
+        PropKey {
+            identifier: Self::IDENTIFIER.to_owned(),
+            subtype: Self::SUBTYPE,
+            data: encode(key_data).into(),
+        }
+    }
+
+    /// Detects whether a proprietary key belongs to the field.
+    fn matches(key: &PropKey) -> bool {
+        key.identifier == Self::IDENTIFIER && key.subtype == Self::SUBTYPE
+    }
+
+    /// Encodes the field value.
+    fn encode_value(value: &Self::Value) -> ValueData { encode(value).into() }
+
+    /// Decodes the field value.
+    fn decode_value(value: &ValueData) -> Result<Self::Value, PsbtError> {
+        Self::Value::deserialize(value)
+    }
+}
+
+impl PropKey {
+    /// Constructs proprietary key for the typed field `F`.
+    pub fn typed<F: PropField>(key_data: &F::KeyData) -> PropKey { F::prop_key(key_data) }
+
+    /// Detects whether the key belongs to the typed field `F`.
+    pub fn is_typed<F: PropField>(&self) -> bool { F::matches(self) }
+
+    /// Decodes key data of the typed field `F`, if the key belongs to it.
+    pub fn typed_data<F: PropField>(&self) -> Option<Result<F::KeyData, PsbtError>> {
+        if !F::matches(self) {
+            return None;
+        }
+        Some(F::KeyData::deserialize(&self.data))
+    }
+}
+
+fn encode(data: &impl Encode) -> Vec<u8> {
+    let mut vec = Vec::new();
+    data.encode(&mut vec).expect("in-memory encoding can't error");
+    vec
+}
+
+#[cfg(test)]
+mod test {
+    use derive::{Sats, Vout};
+
+    use super::*;
+    use crate::{KeyAlreadyPresent, KeyMap, Psbt, PsbtVer};
+
+    struct Flag;
+    impl PropField for Flag {
+        const IDENTIFIER: &'static str = "TEST";
+        const SUBTYPE: u64 = 0x00;
+        type KeyData = ();
+        type Value = u8;
+    }
+
+    struct Amount;
+    impl PropField for Amount {
+        const IDENTIFIER: &'static str = "TEST";
+        const SUBTYPE: u64 = 0x01;
+        type KeyData = Vout;
+        type Value = Sats;
+    }
+
+    #[test]
+    fn key_construction() {
+        let key = PropKey::typed::<Amount>(&Vout::from_u32(2));
+        assert_eq!(key.identifier, "TEST");
+        assert_eq!(key.subtype, 0x01);
+        assert_eq!(key.data.as_slice(), &[2, 0, 0, 0]);
+        assert!(key.is_typed::<Amount>());
+        assert!(!key.is_typed::<Flag>());
+        assert_eq!(key.typed_data::<Amount>(), Some(Ok(Vout::from_u32(2))));
+        assert_eq!(key.typed_data::<Flag>(), None);
+        assert!(PropKey::typed::<Flag>(&()).data.is_empty());
+    }
+
+    #[test]
+    fn read_write() {
+        let mut psbt = Psbt::create(PsbtVer::V0);
+        assert_eq!(psbt.prop_field::<Flag>(&()), Ok(None));
+
+        psbt.push_prop_field::<Flag>(&(), &1).unwrap();
+        assert_eq!(
+            psbt.push_prop_field::<Flag>(&(), &2),
+            Err(KeyAlreadyPresent(PropKey::typed::<Flag>(&())))
+        );
+        assert_eq!(psbt.prop_field::<Flag>(&()), Ok(Some(1)));
+        psbt.set_prop_field::<Flag>(&(), &3);
+        assert_eq!(psbt.prop_field::<Flag>(&()), Ok(Some(3)));
+
+        psbt.set_prop_field::<Amount>(&Vout::from_u32(0), &Sats::from(1000u64));
+        psbt.set_prop_field::<Amount>(&Vout::from_u32(1), &Sats::from(2000u64));
+        let fields = psbt.prop_fields::<Amount>().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(fields, vec![
+            (Vout::from_u32(0), Sats::from(1000u64)),
+            (Vout::from_u32(1), Sats::from(2000u64))
+        ]);
+
+        let psbt = Psbt::deserialize(psbt.serialize(PsbtVer::V0)).unwrap();
+        assert_eq!(psbt.prop_field::<Flag>(&()), Ok(Some(3)));
+        assert_eq!(psbt.prop_fields::<Amount>().count(), 2);
+
+        let mut psbt = psbt;
+        assert_eq!(psbt.remove_prop_field::<Amount>(&Vout::from_u32(0)), Ok(Some(1000u64.into())));
+        assert_eq!(psbt.remove_prop_field::<Amount>(&Vout::from_u32(0)), Ok(None));
+        assert_eq!(psbt.prop_fields::<Amount>().count(), 1);
+    }
+
+    #[test]
+    fn invalid_value() {
+        let mut psbt = Psbt::create(PsbtVer::V0);
+        psbt.insert_proprietary(PropKey::typed::<Amount>(&Vout::from_u32(0)), vec![1, 2].into());
+        assert!(psbt.prop_field::<Amount>(&Vout::from_u32(0)).is_err());
+        assert!(psbt.prop_fields::<Amount>().next().unwrap().is_err());
+    }
+}
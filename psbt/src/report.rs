@@ -0,0 +1,99 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reports on the PSBTs presented to the user for the confirmation before signing.
+
+use derive::{Address, KeyOrigin, Sats, Terminal, Vout, WeightUnits, XpubFp};
+
+use crate::{FeeRate, Output, PrevoutError};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum AnalysisError {
+    #[display(inner)]
+    #[from]
+    Prevout(PrevoutError),
+
+    /// transaction spends more than present in its inputs.
+    NegativeFee,
+
+    /// output {0} claims to be a wallet change, but doesn't match the wallet descriptor.
+    InvalidChange(usize),
+}
+
+/// Output of the analyzed PSBT.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OutputReport {
+    pub vout: Vout,
+    pub value: Sats,
+    /// Address of the output, if the script pubkey can be represented as an address.
+    pub address: Option<Address>,
+    /// Terminal of the wallet change output, verified against the wallet descriptor.
+    pub change: Option<Terminal>,
+}
+
+impl OutputReport {
+    /// Detects whether the output is the wallet change.
+    #[inline]
+    pub fn is_change(&self) -> bool { self.change.is_some() }
+}
+
+/// Summary of the PSBT effects on the wallet, produced by
+/// [`PsbtConstructor::analyze_psbt`].
+///
+/// [`PsbtConstructor::analyze_psbt`]: crate::PsbtConstructor::analyze_psbt
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PsbtReport {
+    /// Value of the inputs spending the wallet coins.
+    pub wallet_inputs: Sats,
+    /// Value of the inputs spending coins of other parties.
+    pub foreign_inputs: Sats,
+    pub outputs: Vec<OutputReport>,
+    /// Value returned to the wallet as change.
+    pub change: Sats,
+    /// Value sent to the outputs which are not the wallet change.
+    pub sent: Sats,
+    pub fee: Sats,
+    /// Estimated weight of the signed transaction.
+    pub weight: WeightUnits,
+    pub fee_rate: FeeRate,
+}
+
+impl PsbtReport {
+    /// Value leaving the wallet, including the fee. May be negative if other parties
+    /// contribute more than the wallet receives.
+    pub fn spent(&self) -> Option<Sats> { self.wallet_inputs.checked_sub(self.change) }
+}
+
+/// Terminals of the key derivations of the output which belong to the keys with the provided
+/// master fingerprints. Derivations not ending with a terminal are returned as `None`.
+pub(crate) fn claimed_terminals<'a>(
+    output: &'a Output,
+    fingerprints: &'a [XpubFp],
+) -> impl Iterator<Item = Option<Terminal>> + 'a {
+    output
+        .bip32_derivation
+        .values()
+        .chain(output.tap_bip32_derivation.values().map(|derivation| &derivation.origin))
+        .filter(|origin| fingerprints.contains(&origin.master_fp()))
+        .map(|origin: &KeyOrigin| origin.derivation().terminal())
+}
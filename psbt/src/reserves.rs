@@ -0,0 +1,167 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proof-of-reserves transactions (BIP-127).
+//!
+//! A proof of reserves is a transaction which can't be mined: its first input spends a
+//! non-existing "challenge" output, derived from the message the proof commits to. All other
+//! inputs spend the UTXOs being proven, and must be signed with `SIGHASH_ALL`, committing the
+//! signatures to the challenge.
+
+use std::collections::HashSet;
+
+use bitcoin_hashes::{sha256d, Hash};
+use derive::secp256k1::SECP256K1;
+use derive::{
+    AddressPayload, Bip340Sig, CompressedPk, LegacySig, Outpoint, PubkeyHash, Sats, ScriptPubkey,
+    SighashCache, SighashType, Tx, TxOut, Txid, WPubkeyHash,
+};
+
+/// Prefix prepended to the message before it is hashed into the challenge txid.
+pub const POR_MESSAGE_PREFIX: &str = "Proof-of-Reserves: ";
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ReservesError {
+    /// proof-of-reserves transaction must contain a challenge input followed by at least one
+    /// input spending the reserves.
+    NoInputs,
+
+    /// the first input of the proof doesn't commit to the provided message.
+    InvalidChallenge,
+
+    /// proof-of-reserves transaction must contain exactly one output.
+    InvalidOutputs,
+
+    /// output {0} is spent by the proof more than once.
+    DuplicateInput(Outpoint),
+
+    /// output {0} spent by the proof is unknown or is already spent.
+    UnknownPrevout(Outpoint),
+
+    /// input {0} is not signed with SIGHASH_ALL and doesn't commit to the challenge.
+    SighashType(usize),
+
+    /// input {0} spends an output of a type for which signature verification is not supported.
+    UnsupportedInput(usize),
+
+    /// input {0} has an invalid signature.
+    InvalidSignature(usize),
+}
+
+/// Returns the outpoint spent by the challenge input of the proof of reserves committing to
+/// the `message`.
+pub fn por_challenge(message: &str) -> Outpoint {
+    let msg = format!("{POR_MESSAGE_PREFIX}{message}");
+    let txid = sha256d::Hash::hash(msg.as_bytes()).to_byte_array();
+    Outpoint::new(Txid::from(txid), 0u32)
+}
+
+/// Script pubkey of the single unspendable output of the proof of reserves.
+pub fn por_script_pubkey() -> ScriptPubkey { ScriptPubkey::op_return(&[]) }
+
+/// Verifies finalized proof-of-reserves transaction for the given `message`, returning the
+/// total amount of proven reserves.
+///
+/// The `prevout` resolver must return outputs only if they are present in the current UTXO set.
+/// The challenge input is expected to spend a zero-value output with the `challenge` script
+/// pubkey, which is provided by the prover together with the proof.
+///
+/// Only P2WPKH and P2TR key-path inputs can be verified; proofs spending other outputs are
+/// rejected with [`ReservesError::UnsupportedInput`].
+pub fn verify_reserves(
+    tx: &Tx,
+    message: &str,
+    challenge: &ScriptPubkey,
+    prevout: impl Fn(Outpoint) -> Option<TxOut>,
+) -> Result<Sats, ReservesError> {
+    if tx.inputs.len() < 2 {
+        return Err(ReservesError::NoInputs);
+    }
+    if tx.inputs[0].prev_output != por_challenge(message) {
+        return Err(ReservesError::InvalidChallenge);
+    }
+    if tx.outputs.len() != 1 {
+        return Err(ReservesError::InvalidOutputs);
+    }
+
+    let mut seen = HashSet::with_capacity(tx.inputs.len());
+    let mut prevouts = Vec::with_capacity(tx.inputs.len());
+    prevouts.push(TxOut::new(challenge.clone(), Sats::ZERO));
+    let mut reserves = Sats::ZERO;
+    for input in tx.inputs.iter().skip(1) {
+        let outpoint = input.prev_output;
+        if !seen.insert(outpoint) {
+            return Err(ReservesError::DuplicateInput(outpoint));
+        }
+        let txout = prevout(outpoint).ok_or(ReservesError::UnknownPrevout(outpoint))?;
+        reserves.saturating_add_assign(txout.value);
+        prevouts.push(txout);
+    }
+
+    let cache = SighashCache::new(tx.clone(), prevouts.clone()).expect("prevouts match tx inputs");
+    for (index, (input, txout)) in tx.inputs.iter().zip(&prevouts).enumerate() {
+        let witness = input.witness.elements().collect::<Vec<_>>();
+        match (AddressPayload::from_script(&txout.script_pubkey), witness.as_slice()) {
+            (Ok(AddressPayload::Wpkh(hash)), [sig, pk]) => {
+                let sig = LegacySig::from_bytes(sig)
+                    .map_err(|_| ReservesError::InvalidSignature(index))?;
+                if sig.sighash_type != SighashType::all() {
+                    return Err(ReservesError::SighashType(index));
+                }
+                let pk = CompressedPk::from_bytes(pk)
+                    .map_err(|_| ReservesError::InvalidSignature(index))?;
+                if hash != WPubkeyHash::from(pk) {
+                    return Err(ReservesError::InvalidSignature(index));
+                }
+                let script_code = ScriptPubkey::p2pkh(PubkeyHash::from(pk));
+                let sighash = cache
+                    .segwit_sighash(index, script_code.as_script_bytes(), sig.sighash_type)
+                    .map_err(|_| ReservesError::InvalidSignature(index))?;
+                let mut ecdsa = sig.sig;
+                ecdsa.normalize_s();
+                SECP256K1
+                    .verify_ecdsa(&sighash.into(), &ecdsa, &pk)
+                    .map_err(|_| ReservesError::InvalidSignature(index))?;
+            }
+            (Ok(AddressPayload::Tr(output_pk)), [sig]) => {
+                let sig = Bip340Sig::from_bytes(sig)
+                    .map_err(|_| ReservesError::InvalidSignature(index))?;
+                if sig.sighash_type.is_some_and(|ty| ty != SighashType::all()) {
+                    return Err(ReservesError::SighashType(index));
+                }
+                let sighash = cache
+                    .tap_sighash_key(index, sig.sighash_type)
+                    .map_err(|_| ReservesError::InvalidSignature(index))?;
+                SECP256K1
+                    .verify_schnorr(&sig.sig, &sighash.into(), &output_pk)
+                    .map_err(|_| ReservesError::InvalidSignature(index))?;
+            }
+            (Ok(AddressPayload::Wpkh(_) | AddressPayload::Tr(_)), _) => {
+                return Err(ReservesError::InvalidSignature(index))
+            }
+            _ => return Err(ReservesError::UnsupportedInput(index)),
+        }
+    }
+
+    Ok(reserves)
+}
@@ -0,0 +1,443 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! PSBT signer role.
+
+use amplify::Wrapper;
+use derive::secp256k1::{ecdsa, schnorr, Message, SECP256K1};
+use derive::{
+    script_pushes, verify_control_block, Bip340Sig, CompressedPk, InternalPk, KeyOrigin, LegacyPk,
+    LegacySig, OutputPk, PubkeyHash, ScriptHash, ScriptPubkey, Sighash, SighashCache, SighashError,
+    SighashType, TapNodeHash, Tx, WScriptHash, XOnlyPk, XprivAccount,
+};
+
+use crate::{Input, PrevoutError, Psbt};
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum SignError {
+    #[display(inner)]
+    #[from]
+    Prevout(PrevoutError),
+
+    /// input {0} spends P2SH output, but doesn't provide a redeem script.
+    NoRedeemScript(usize),
+
+    /// input {0} spends P2WSH output, but doesn't provide a witness script.
+    NoWitnessScript(usize),
+
+    /// redeem script of input {0} doesn't match the script pubkey of the spent output.
+    RedeemScriptMismatch(usize),
+
+    /// witness script of input {0} doesn't match the script pubkey of the spent output.
+    WitnessScriptMismatch(usize),
+
+    /// input {0} requires signature with sighash type {1:?}, which is not allowed by the signing
+    /// policy.
+    SighashNotAllowed(usize, SighashType),
+
+    #[display(inner)]
+    #[from]
+    Sighash(SighashError),
+}
+
+/// Policy restricting sighash types with which the signer produces signatures.
+///
+/// Taproot inputs without `PSBT_IN_SIGHASH_TYPE` are signed with `SIGHASH_DEFAULT`, which is
+/// treated by the policy as `SIGHASH_ALL`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SighashPolicy(u8);
+
+impl Default for SighashPolicy {
+    fn default() -> Self { SighashPolicy::all_only() }
+}
+
+impl SighashPolicy {
+    const ALL_TYPES: [SighashType; 6] = [
+        SighashType::all(),
+        SighashType::none(),
+        SighashType::single(),
+        SighashType::all_anyone_can_pay(),
+        SighashType::none_anyone_can_pay(),
+        SighashType::single_anyone_can_pay(),
+    ];
+
+    fn bit(sighash_type: SighashType) -> u8 {
+        let pos = sighash_type.flag as u8 - 1 + if sighash_type.anyone_can_pay { 3 } else { 0 };
+        1 << pos
+    }
+
+    /// Policy allowing only `SIGHASH_ALL` signatures.
+    pub fn all_only() -> Self { SighashPolicy::with([SighashType::all()]) }
+
+    /// Policy allowing signatures with any sighash type.
+    pub fn any() -> Self { SighashPolicy::with(Self::ALL_TYPES) }
+
+    /// Policy allowing signatures with the listed sighash types.
+    pub fn with(allowed: impl IntoIterator<Item = SighashType>) -> Self {
+        let mut policy = SighashPolicy(0);
+        for sighash_type in allowed {
+            policy.allow(sighash_type);
+        }
+        policy
+    }
+
+    /// Adds sighash type to the list of allowed types.
+    pub fn allow(&mut self, sighash_type: SighashType) { self.0 |= Self::bit(sighash_type); }
+
+    /// Removes sighash type from the list of allowed types.
+    pub fn deny(&mut self, sighash_type: SighashType) { self.0 &= !Self::bit(sighash_type); }
+
+    /// Checks whether the signatures with the sighash type are allowed.
+    pub fn is_allowed(&self, sighash_type: SighashType) -> bool {
+        self.0 & Self::bit(sighash_type) != 0
+    }
+
+    /// Lists the allowed sighash types.
+    pub fn allowed(&self) -> impl Iterator<Item = SighashType> + '_ {
+        Self::ALL_TYPES.into_iter().filter(|sighash_type| self.is_allowed(*sighash_type))
+    }
+
+    fn check(&self, index: usize, sighash_type: SighashType) -> Result<(), SignError> {
+        if !self.is_allowed(sighash_type) {
+            return Err(SignError::SighashNotAllowed(index, sighash_type));
+        }
+        Ok(())
+    }
+}
+
+/// Source of private keys used by PSBT signer role.
+///
+/// Implementations are expected to return `None` if they do not control the
+/// private key for the provided public key and its origin.
+pub trait Sign {
+    /// Produces ECDSA signature for a legacy or segwit v0 input.
+    fn sign_ecdsa(
+        &self,
+        sighash: Sighash,
+        pk: CompressedPk,
+        origin: &KeyOrigin,
+    ) -> Option<ecdsa::Signature>;
+
+    /// Produces BIP340 signature for a taproot key path spending, tweaking the
+    /// internal key with an optional script tree merkle root.
+    fn sign_bip340_key_only(
+        &self,
+        sighash: Sighash,
+        internal_pk: InternalPk,
+        origin: &KeyOrigin,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Option<schnorr::Signature>;
+
+    /// Produces BIP340 signature for a taproot script path spending.
+    fn sign_bip340_script_path(
+        &self,
+        sighash: Sighash,
+        pk: XOnlyPk,
+        origin: &KeyOrigin,
+    ) -> Option<schnorr::Signature>;
+}
+
+impl Sign for XprivAccount {
+    fn sign_ecdsa(
+        &self,
+        sighash: Sighash,
+        pk: CompressedPk,
+        origin: &KeyOrigin,
+    ) -> Option<ecdsa::Signature> {
+        let xpriv = self.derive_origin(origin)?;
+        if xpriv.to_compr_pub() != pk {
+            return None;
+        }
+        let mut sk = xpriv.to_private_ecdsa();
+        let sig = SECP256K1.sign_ecdsa(&Message::from(sighash), &sk);
+        sk.non_secure_erase();
+        Some(sig)
+    }
+
+    fn sign_bip340_key_only(
+        &self,
+        sighash: Sighash,
+        internal_pk: InternalPk,
+        origin: &KeyOrigin,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Option<schnorr::Signature> {
+        let xpriv = self.derive_origin(origin)?;
+        if xpriv.to_xonly_pub().to_byte_array() != internal_pk.to_byte_array() {
+            return None;
+        }
+        let keypair = xpriv.to_keypair_bip341(merkle_root);
+        Some(SECP256K1.sign_schnorr_no_aux_rand(&Message::from(sighash), &keypair))
+    }
+
+    fn sign_bip340_script_path(
+        &self,
+        sighash: Sighash,
+        pk: XOnlyPk,
+        origin: &KeyOrigin,
+    ) -> Option<schnorr::Signature> {
+        let xpriv = self.derive_origin(origin)?;
+        if xpriv.to_xonly_pub() != pk {
+            return None;
+        }
+        let keypair = xpriv.to_keypair_bip340();
+        Some(SECP256K1.sign_schnorr_no_aux_rand(&Message::from(sighash), &keypair))
+    }
+}
+
+impl<T: Sign> Sign for [T] {
+    fn sign_ecdsa(
+        &self,
+        sighash: Sighash,
+        pk: CompressedPk,
+        origin: &KeyOrigin,
+    ) -> Option<ecdsa::Signature> {
+        self.iter().find_map(|signer| signer.sign_ecdsa(sighash, pk, origin))
+    }
+
+    fn sign_bip340_key_only(
+        &self,
+        sighash: Sighash,
+        internal_pk: InternalPk,
+        origin: &KeyOrigin,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Option<schnorr::Signature> {
+        self.iter().find_map(|signer| {
+            signer.sign_bip340_key_only(sighash, internal_pk, origin, merkle_root)
+        })
+    }
+
+    fn sign_bip340_script_path(
+        &self,
+        sighash: Sighash,
+        pk: XOnlyPk,
+        origin: &KeyOrigin,
+    ) -> Option<schnorr::Signature> {
+        self.iter().find_map(|signer| signer.sign_bip340_script_path(sighash, pk, origin))
+    }
+}
+
+impl Psbt {
+    /// Signs all non-finalized inputs with keys provided by the `signer`,
+    /// selecting the keys using BIP32 derivation information of each input.
+    ///
+    /// Uses `PSBT_IN_SIGHASH_TYPE` of the input when present, defaulting to
+    /// `SIGHASH_ALL` for ECDSA and `SIGHASH_DEFAULT` for BIP340 signatures. Only `SIGHASH_ALL`
+    /// is allowed, as with the default [`SighashPolicy`]; other sighash types can be allowed with
+    /// [`Psbt::sign_with_policy`].
+    ///
+    /// # Returns
+    ///
+    /// Number of produced signatures.
+    pub fn sign(&mut self, signer: &(impl Sign + ?Sized)) -> Result<usize, SignError> {
+        self.sign_filtered(signer, &SighashPolicy::default(), |_| true)
+    }
+
+    /// Signs like [`Psbt::sign`], failing with [`SignError::SighashNotAllowed`] if any of the
+    /// inputs having key derivation information requires a sighash type not allowed by the
+    /// `policy`. The policy is checked before signing, such that no input gets signed on failure.
+    pub fn sign_with_policy(
+        &mut self,
+        signer: &(impl Sign + ?Sized),
+        policy: &SighashPolicy,
+    ) -> Result<usize, SignError> {
+        self.sign_filtered(signer, policy, |_| true)
+    }
+
+    /// Signs like [`Psbt::sign`], but only the inputs with the given indexes, leaving the other
+    /// inputs intact. Used for signing transactions constructed together with other parties.
+    pub fn sign_inputs(
+        &mut self,
+        signer: &(impl Sign + ?Sized),
+        inputs: &[usize],
+    ) -> Result<usize, SignError> {
+        self.sign_filtered(signer, &SighashPolicy::default(), |index| inputs.contains(&index))
+    }
+
+    fn sign_filtered(
+        &mut self,
+        signer: &(impl Sign + ?Sized),
+        policy: &SighashPolicy,
+        filter: impl Fn(usize) -> bool,
+    ) -> Result<usize, SignError> {
+        let mut prevouts = Vec::with_capacity(self.inputs.len());
+        for input in self.inputs() {
+            prevouts.push(input.prev_txout()?.clone());
+        }
+        let cache = SighashCache::new(Tx::from(self.to_unsigned_tx()), prevouts)?;
+
+        let selected = |input: &Input| !input.is_finalized() && filter(input.index());
+        for input in self.inputs().filter(|input| selected(input)) {
+            if input.bip32_derivation.is_empty() && input.tap_bip32_derivation.is_empty() {
+                continue;
+            }
+            policy.check(input.index(), input.sighash_type.unwrap_or(SighashType::all()))?;
+        }
+
+        let mut sig_count = 0usize;
+        for input in self.inputs_mut() {
+            if !selected(input) {
+                continue;
+            }
+            sig_count += if input.prev_txout()?.script_pubkey.is_p2tr() {
+                input.sign_bip340(&cache, signer)?
+            } else {
+                input.sign_ecdsa(&cache, signer)?
+            };
+        }
+        Ok(sig_count)
+    }
+}
+
+/// Detects whether the script code commits to the public key, either with its hash (for P2PKH
+/// script codes, including the ones of P2WPKH outputs) or by pushing the key itself.
+fn script_has_key(script_code: &ScriptPubkey, pk: CompressedPk) -> bool {
+    if script_code.is_p2pkh() {
+        return *script_code == ScriptPubkey::p2pkh(PubkeyHash::from(pk));
+    }
+    let pk = pk.serialize();
+    script_pushes(script_code.as_slice()).any(|data| data == pk)
+}
+
+impl Input {
+    /// Detects whether the input has a final `scriptSig` or witness.
+    pub fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some() || self.final_witness.is_some()
+    }
+
+    fn sign_ecdsa(
+        &mut self,
+        cache: &SighashCache,
+        signer: &(impl Sign + ?Sized),
+    ) -> Result<usize, SignError> {
+        let index = self.index();
+        let sighash_type = self.sighash_type.unwrap_or(SighashType::all());
+
+        let mut spk = self.prev_txout()?.script_pubkey.clone();
+        let mut segwit = false;
+        if spk.is_p2sh() {
+            let redeem_script =
+                self.redeem_script.as_ref().ok_or(SignError::NoRedeemScript(index))?;
+            if spk != ScriptPubkey::p2sh(ScriptHash::from(redeem_script)) {
+                return Err(SignError::RedeemScriptMismatch(index));
+            }
+            spk = ScriptPubkey::from_unsafe(redeem_script.to_vec());
+        }
+        let script_code = if spk.is_p2wpkh() {
+            segwit = true;
+            let mut hash = [0u8; 20];
+            hash.copy_from_slice(&spk[2..22]);
+            ScriptPubkey::p2pkh(hash)
+        } else if spk.is_p2wsh() {
+            segwit = true;
+            let witness_script =
+                self.witness_script.as_ref().ok_or(SignError::NoWitnessScript(index))?;
+            if spk != ScriptPubkey::p2wsh(WScriptHash::from(witness_script)) {
+                return Err(SignError::WitnessScriptMismatch(index));
+            }
+            ScriptPubkey::from_unsafe(witness_script.to_vec())
+        } else {
+            spk
+        };
+        let sighash = if segwit {
+            cache.segwit_sighash(index, script_code.as_script_bytes(), sighash_type)?
+        } else {
+            cache.legacy_sighash(
+                index,
+                script_code.as_script_bytes(),
+                sighash_type.into_consensus_u32(),
+            )?
+        };
+
+        let mut sig_count = 0usize;
+        for (pk, origin) in &self.bip32_derivation {
+            let legacy_pk = LegacyPk::compressed(**pk);
+            if self.partial_sigs.contains_key(&legacy_pk) || !script_has_key(&script_code, *pk) {
+                continue;
+            }
+            if let Some(sig) = signer.sign_ecdsa(sighash, *pk, origin) {
+                self.partial_sigs.insert(legacy_pk, LegacySig { sig, sighash_type });
+                sig_count += 1;
+            }
+        }
+        Ok(sig_count)
+    }
+
+    fn sign_bip340(
+        &mut self,
+        cache: &SighashCache,
+        signer: &(impl Sign + ?Sized),
+    ) -> Result<usize, SignError> {
+        let index = self.index();
+        let sighash_type = self.sighash_type;
+        let mut sig_count = 0usize;
+
+        if let (Some(internal_pk), None) = (self.tap_internal_key, self.tap_key_sig) {
+            let derivation = self
+                .tap_bip32_derivation
+                .iter()
+                .find(|(pk, _)| pk.to_byte_array() == internal_pk.to_byte_array())
+                .map(|(_, derivation)| derivation);
+            if let Some(derivation) = derivation {
+                let sighash = cache.tap_sighash_key(index, sighash_type)?;
+                if let Some(sig) = signer.sign_bip340_key_only(
+                    sighash,
+                    internal_pk,
+                    &derivation.origin,
+                    self.tap_merkle_root,
+                ) {
+                    self.tap_key_sig = Some(Bip340Sig { sig, sighash_type });
+                    sig_count += 1;
+                }
+            }
+        }
+
+        let output_pk = self.prev_txout()?.script_pubkey[2..]
+            .try_into()
+            .ok()
+            .and_then(|bytes| OutputPk::from_byte_array(bytes).ok());
+        let leaf_hashes = self
+            .tap_leaf_script
+            .iter()
+            .filter(|(control_block, leaf_script)| {
+                output_pk.is_some_and(|pk| verify_control_block(control_block, leaf_script, pk))
+            })
+            .map(|(_, leaf_script)| leaf_script.tap_leaf_hash())
+            .collect::<Vec<_>>();
+        for (pk, derivation) in &self.tap_bip32_derivation {
+            for leaf_hash in &derivation.leaf_hashes {
+                let key = (InternalPk::from_unchecked(*pk), leaf_hash.into_inner());
+                if self.tap_script_sig.contains_key(&key) || !leaf_hashes.contains(leaf_hash) {
+                    continue;
+                }
+                let sighash = cache.tap_sighash_script(index, *leaf_hash, sighash_type)?;
+                if let Some(sig) = signer.sign_bip340_script_path(sighash, *pk, &derivation.origin)
+                {
+                    self.tap_script_sig.insert(key, Bip340Sig { sig, sighash_type });
+                    sig_count += 1;
+                }
+            }
+        }
+
+        Ok(sig_count)
+    }
+}
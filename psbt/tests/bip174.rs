@@ -22,7 +22,7 @@
 
 use std::str::FromStr;
 
-use psbt::Psbt;
+use psbt::{MapName, Psbt, PsbtError, PsbtVer};
 
 fn parse_roundtrip(s: &str) {
     let psbt = Psbt::from_str(s).unwrap();
@@ -75,3 +75,27 @@ fn no_inputs_outputs() { parse_roundtrip(include_str!("valid.v0/no_inputs_output
 /// Case: PSBT with 0 inputs
 #[test]
 fn no_inputs() { parse_roundtrip(include_str!("valid.v0/no_inputs.psbt")); }
+
+/// Case: PSBT with the global unsigned tx key repeated twice.
+#[test]
+fn repeated_global_key() {
+    let mut data = Psbt::from_str(include_str!("valid.v0/no_inputs_outputs.psbt"))
+        .unwrap()
+        .serialize(PsbtVer::V0);
+    let pair = data[5..18].to_vec();
+    data.splice(5..5, pair);
+    assert!(matches!(Psbt::deserialize(data), Err(PsbtError::RepeatedKey(MapName::Global, 0))));
+}
+
+/// Case: PSBT with the global unsigned tx key having non-empty key data.
+#[test]
+fn non_empty_key_data() {
+    let mut data = Psbt::from_str(include_str!("valid.v0/no_inputs_outputs.psbt"))
+        .unwrap()
+        .serialize(PsbtVer::V0);
+    data.splice(5..7, [2, 0, 0xFF]);
+    assert!(matches!(
+        Psbt::deserialize(data),
+        Err(PsbtError::NonEmptyKeyData(MapName::Global, 0, _))
+    ));
+}
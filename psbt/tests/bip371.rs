@@ -26,7 +26,7 @@ use psbt::Psbt;
 
 fn parse_roundtrip(s: &str) {
     let psbt = Psbt::from_str(s).unwrap();
-    Psbt::from_str(&psbt.to_string()).unwrap();
+    assert_eq!(Psbt::from_str(&psbt.to_string()).unwrap(), psbt);
 }
 
 /// Case: PSBT with one P2TR key only input with internal key and its derivation path
@@ -0,0 +1,105 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use common::TestWallet;
+use derive::{Keychain, NormalIndex, Outpoint, Sats, SeqNo, SighashType, Terminal, TxOut, Txid};
+use psbt::{CoinjoinError, FinalizeError, Psbt, PsbtConstructor, PsbtVer};
+
+fn participant(seed: u8) -> TestWallet {
+    let coin = Outpoint::new(Txid::from([seed; 32]), 0u32);
+    let terminal = Terminal::new(Keychain::OUTER, NormalIndex::from(0u8));
+    TestWallet::wpkh(seed).with_coin(coin, 100_000, terminal)
+}
+
+fn coinjoin(wallet: &TestWallet, other: &TestWallet, prevout_value: u32) -> Psbt {
+    let seq_no = SeqNo::from_consensus_u32(0xFFFF_FFFF);
+    let mut psbt = Psbt::create(PsbtVer::V2);
+    let other_prevout = TxOut::new(other.script_pubkey(0), Sats::from_sats(120_000u32));
+    psbt.construct_foreign_input(other.coin(), other_prevout, seq_no).unwrap();
+    let prevout = TxOut::new(wallet.script_pubkey(0), Sats::from_sats(prevout_value));
+    psbt.construct_foreign_input(wallet.coin(), prevout, seq_no).unwrap();
+    for (script_pubkey, value) in [
+        (other.script_pubkey(1), 90_000u32),
+        (wallet.script_pubkey(1), 90_000),
+        (other.script_pubkey(2), 29_500),
+        (wallet.script_pubkey(2), 9_500),
+    ] {
+        psbt.construct_output_expect(script_pubkey, Sats::from_sats(value));
+    }
+    psbt
+}
+
+#[test]
+fn contribute_coinjoin() {
+    let wallet = participant(0xA5);
+    let other = participant(0x5A);
+    let mut psbt = coinjoin(&wallet, &other, 100_000);
+    let expected = [
+        TxOut::new(wallet.script_pubkey(1), Sats::from_sats(90_000u32)),
+        TxOut::new(wallet.script_pubkey(2), Sats::from_sats(9_500u32)),
+    ];
+
+    let inputs = wallet.contribute_coinjoin(&mut psbt, &expected).unwrap();
+    assert_eq!(inputs, vec![1]);
+    let input = psbt.inputs().nth(1).unwrap();
+    assert_eq!(input.sighash_type, Some(SighashType::all()));
+    assert_eq!(input.bip32_derivation.len(), 1);
+    assert!(psbt.inputs().next().unwrap().bip32_derivation.is_empty());
+
+    assert_eq!(psbt.sign_inputs(&wallet.account, &inputs).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Err(vec![FinalizeError::NotEnoughSigs(0)]));
+    assert!(psbt.inputs().nth(1).unwrap().is_finalized());
+    assert!(!psbt.inputs().next().unwrap().is_finalized());
+}
+
+#[test]
+fn contribute_coinjoin_invalid() {
+    let wallet = participant(0xA5);
+    let other = participant(0x5A);
+    let expected = [TxOut::new(wallet.script_pubkey(1), Sats::from_sats(90_000u32))];
+
+    let mut psbt = coinjoin(&wallet, &other, 100_000);
+    let tampered = [TxOut::new(wallet.script_pubkey(1), Sats::from_sats(91_000u32))];
+    assert!(matches!(
+        wallet.contribute_coinjoin(&mut psbt, &tampered),
+        Err(CoinjoinError::MissingOutput(0))
+    ));
+    let duplicated = [expected[0].clone(), expected[0].clone()];
+    assert!(matches!(
+        wallet.contribute_coinjoin(&mut psbt, &duplicated),
+        Err(CoinjoinError::MissingOutput(1))
+    ));
+
+    let mut psbt = coinjoin(&wallet, &other, 99_000);
+    assert!(matches!(
+        wallet.contribute_coinjoin(&mut psbt, &expected),
+        Err(CoinjoinError::PrevoutMismatch(1))
+    ));
+
+    let mut psbt = coinjoin(&other, &other, 100_000);
+    assert!(matches!(
+        wallet.contribute_coinjoin(&mut psbt, &[]),
+        Err(CoinjoinError::NoWalletInputs)
+    ));
+}
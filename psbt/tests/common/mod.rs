@@ -0,0 +1,113 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wallet fixture shared by the PSBT integration tests.
+
+#![allow(dead_code)]
+
+use derive::{
+    Derive, HardenedIndex, Idx, Keychain, Network, NormalIndex, Outpoint, Sats, ScriptPubkey,
+    Terminal, TxOut, Xpriv, XprivAccount, XpubDerivable,
+};
+use descriptors::{StdDescr, Wpkh};
+use psbt::{PsbtConstructor, Utxo};
+
+/// Testnet account `m/{purpose}h/1h/0h` of the wallet with the master key generated from the
+/// `seed` byte.
+pub fn account(seed: u8, purpose: u16) -> XprivAccount {
+    XprivAccount::new_master(Xpriv::new_master(true, &[seed; 32])).derive([
+        HardenedIndex::hardened(purpose),
+        HardenedIndex::hardened(1),
+        HardenedIndex::hardened(0),
+    ])
+}
+
+/// P2WPKH descriptor of the BIP-84 account generated from the `seed` byte.
+pub fn wpkh(seed: u8) -> Wpkh<XpubDerivable> {
+    Wpkh::from(XpubDerivable::from(account(seed, 84).to_xpub_spec()))
+}
+
+pub struct TestWallet {
+    pub account: XprivAccount,
+    pub descr: StdDescr,
+    pub utxos: Vec<Utxo>,
+}
+
+impl TestWallet {
+    pub fn new(account: XprivAccount, descr: impl Into<StdDescr>) -> Self {
+        TestWallet {
+            account,
+            descr: descr.into(),
+            utxos: vec![],
+        }
+    }
+
+    /// P2WPKH wallet of the BIP-84 account generated from the `seed` byte.
+    pub fn wpkh(seed: u8) -> Self { Self::new(account(seed, 84), wpkh(seed)) }
+
+    pub fn with_coin(mut self, outpoint: Outpoint, value: u32, terminal: Terminal) -> Self {
+        self.utxos.push(Utxo {
+            outpoint,
+            value: Sats::from_sats(value),
+            terminal,
+        });
+        self
+    }
+
+    pub fn coin(&self) -> Outpoint { self.utxos[0].outpoint }
+
+    pub fn coins(&self) -> Vec<Outpoint> { self.utxos.iter().map(|utxo| utxo.outpoint).collect() }
+
+    /// Script pubkey of the outer keychain at the `index`.
+    pub fn script_pubkey(&self, index: u8) -> ScriptPubkey {
+        self.descr.derive(Keychain::OUTER, index).to_script_pubkey()
+    }
+
+    pub fn txout(&self, outpoint: Outpoint) -> Option<TxOut> {
+        let utxo = self.utxo(outpoint)?;
+        let scripts = self.descr.derive(utxo.terminal.keychain, utxo.terminal.index);
+        Some(TxOut::new(scripts.to_script_pubkey(), utxo.value))
+    }
+}
+
+impl PsbtConstructor for TestWallet {
+    type Key = XpubDerivable;
+    type Descr = StdDescr;
+
+    fn descriptor(&self) -> &Self::Descr { &self.descr }
+
+    fn utxo(&self, outpoint: Outpoint) -> Option<Utxo> {
+        self.utxos.iter().find(|utxo| utxo.outpoint == outpoint).copied()
+    }
+
+    fn network(&self) -> Network { Network::Testnet3 }
+
+    /// Index following the last one used by the wallet coins.
+    fn next_derivation_index(
+        &mut self,
+        _keychain: impl Into<Keychain>,
+        _shift: bool,
+    ) -> NormalIndex {
+        let last = self.utxos.iter().map(|utxo| utxo.terminal.index).max();
+        last.map_or(NormalIndex::ZERO, |index| index.checked_inc().expect("test index"))
+    }
+}
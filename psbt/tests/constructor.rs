@@ -0,0 +1,409 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use derive::{
+    Address, AddressNetwork, Derive, FutureProgram, Keychain, LockTime, Network, NormalIndex,
+    Outpoint, Sats, ScriptPubkey, SeqNo, SigScript, Terminal, Tx, TxIn, TxOut, TxVer, Txid,
+    VarIntArray, Vout, Weight, WeightUnits, Witness, WitnessVer, XpubDerivable,
+};
+use descriptors::{Pkh, StdDescr, TrKey, Wpkh};
+use psbt::{
+    dust_limit, AnalysisError, Beneficiary, ConsolidationParams, ConstructionError, FeeRate,
+    ForeignInput, PsbtConstructor, TxExtras, TxParams, Utxo,
+};
+
+const XPUB: &str = "[643a7adc/84h/1h/0h]tpubDCNiWHaiSkgnQjuhsg9kjwaUzaxQjUcmhagvYzqQ3TYJTgFGJstVaqnu4yhtFktBhCVFmBNLQ5sN53qKzZbMksm3XEyGJsEhQPfVZdWmTE2/<0;1>/*";
+
+const XPUB_TR: &str = "[643a7adc/86h/1h/1h]tpubDEKaia7F7YbecaURTkY1eRKw7WpGFccEDtNtDy3Ggd6Y2TtFghgxxVvH1a5ngpAGCNHo1ZSLromAnAzyqvsC3EJMRa2B7bG9SYTCdT9UsnC/<0;1>/*";
+
+struct TestWallet {
+    descr: StdDescr,
+    change_descr: Option<StdDescr>,
+    prev_tx: Option<Tx>,
+}
+
+impl TestWallet {
+    fn new(descr: impl Into<StdDescr>) -> Self {
+        TestWallet {
+            descr: descr.into(),
+            change_descr: None,
+            prev_tx: None,
+        }
+    }
+
+    fn with_prev_tx(mut self) -> Self {
+        let script_pubkey = self.descr.derive(0, 0u8).to_script_pubkey();
+        self.prev_tx = Some(Tx {
+            version: TxVer::V2,
+            inputs: VarIntArray::from_iter_unsafe([TxIn {
+                prev_output: Outpoint::new(Txid::from([0xFF; 32]), 0u32),
+                sig_script: SigScript::new(),
+                sequence: SeqNo::from_consensus_u32(0),
+                witness: Witness::new(),
+            }]),
+            outputs: VarIntArray::from_iter_unsafe([TxOut::new(
+                script_pubkey,
+                Sats::from_sats(100_000u32),
+            )]),
+            lock_time: LockTime::ZERO,
+        });
+        self
+    }
+
+    fn coin(&self) -> Outpoint {
+        Outpoint::new(self.prev_tx.as_ref().map(Tx::txid).unwrap_or(Txid::from([1; 32])), 0u32)
+    }
+}
+
+impl PsbtConstructor for TestWallet {
+    type Key = XpubDerivable;
+    type Descr = StdDescr;
+
+    fn descriptor(&self) -> &Self::Descr { &self.descr }
+
+    fn keychain_descriptor(&self, keychain: Keychain) -> &Self::Descr {
+        match &self.change_descr {
+            Some(descr) if keychain == Keychain::INNER => descr,
+            _ => &self.descr,
+        }
+    }
+
+    fn utxo(&self, outpoint: Outpoint) -> Option<Utxo> {
+        // Coins of this transaction are used to test handling of coins unknown to the wallet.
+        if outpoint.txid == Txid::from([0xFF; 32]) {
+            return None;
+        }
+        Some(Utxo {
+            outpoint,
+            value: Sats::from_sats(100_000u32),
+            terminal: Terminal::new(Keychain::OUTER, NormalIndex::from(0u8)),
+        })
+    }
+
+    fn network(&self) -> Network { Network::Testnet3 }
+
+    fn next_derivation_index(
+        &mut self,
+        _keychain: impl Into<Keychain>,
+        _shift: bool,
+    ) -> NormalIndex {
+        NormalIndex::from(1u8)
+    }
+
+    fn prev_tx(&self, txid: Txid) -> Option<Tx> {
+        self.prev_tx.clone().filter(|tx| tx.txid() == txid)
+    }
+}
+
+fn beneficiary() -> Beneficiary {
+    let xpub = XpubDerivable::from_str(XPUB).unwrap();
+    let script_pubkey = Wpkh::from(xpub).derive(0, 5u8).to_script_pubkey();
+    let address = Address::with(&script_pubkey, Network::Testnet3).unwrap();
+    Beneficiary::new(address, Sats::from_sats(10_000u32))
+}
+
+#[test]
+fn construct_wpkh() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let coin = wallet.coin();
+    let (psbt, meta) = wallet
+        .construct_psbt([coin], [&beneficiary()], TxParams::with(Sats::from_sats(500u32)))
+        .unwrap();
+    let input = psbt.inputs().next().unwrap();
+    assert!(input.witness_utxo.is_some());
+    assert!(input.non_witness_tx.is_none());
+    assert_eq!(input.bip32_derivation.len(), 1);
+    assert_eq!(meta.change_vout, Some(Vout::from_u32(1)));
+    let change = psbt.outputs().nth(1).unwrap();
+    assert_eq!(change.bip32_derivation.len(), 1);
+    assert_eq!(change.value(), Sats::from_sats(100_000u32 - 10_000 - 500));
+}
+
+#[test]
+fn construct_pkh() {
+    let descr = Pkh::from(XpubDerivable::from_str(XPUB).unwrap());
+
+    let mut wallet = TestWallet::new(descr.clone());
+    let coin = wallet.coin();
+    assert!(matches!(
+        wallet.construct_psbt([coin], [&beneficiary()], TxParams::with(Sats::from_sats(500u32))),
+        Err(ConstructionError::NoPrevTx(outpoint)) if outpoint == coin
+    ));
+
+    let mut wallet = TestWallet::new(descr).with_prev_tx();
+    let coin = wallet.coin();
+    let (psbt, _) = wallet
+        .construct_psbt([coin], [&beneficiary()], TxParams::with(Sats::from_sats(500u32)))
+        .unwrap();
+    let input = psbt.inputs().next().unwrap();
+    assert!(input.witness_utxo.is_none());
+    assert!(input.non_witness_tx.is_some());
+    assert_eq!(input.value(), Ok(Sats::from_sats(100_000u32)));
+    assert_eq!(input.bip32_derivation.len(), 1);
+}
+
+#[test]
+fn construct_drain() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let coins =
+        [Outpoint::new(Txid::from([1; 32]), 0u32), Outpoint::new(Txid::from([2; 32]), 1u32)];
+    let destination = beneficiary().address;
+    let psbt = wallet
+        .construct_drain_psbt(coins, destination, TxParams::with(Sats::from_sats(700u32)))
+        .unwrap();
+    assert_eq!(psbt.inputs().count(), 2);
+    assert_eq!(psbt.outputs().count(), 1);
+    let output = psbt.outputs().next().unwrap();
+    assert_eq!(output.script, destination.script_pubkey());
+    assert_eq!(output.value(), Sats::from_sats(200_000u32 - 700));
+    assert_eq!(psbt.fee(), Ok(Some(Sats::from_sats(700u32))));
+
+    assert!(matches!(
+        wallet.construct_drain_psbt([], destination, TxParams::with(Sats::from_sats(700u32))),
+        Err(ConstructionError::NoInputs)
+    ));
+}
+
+#[test]
+fn construct_consolidation() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let coins = (1u8..=5).map(|i| Outpoint::new(Txid::from([i; 32]), 0u32)).collect::<Vec<_>>();
+    let params =
+        ConsolidationParams::with(FeeRate::from_sat_per_vb(1), FeeRate::from_sat_per_vb(20));
+
+    let plan = wallet.plan_consolidation(coins.clone(), params).unwrap().unwrap();
+    assert_eq!(plan.coins, coins);
+    assert_eq!(plan.fee, Sats::from_sats(383u32));
+    assert_eq!(plan.savings, Sats::from_sats(5077u32));
+    assert_eq!(plan.psbt.inputs().count(), 5);
+    assert_eq!(plan.psbt.outputs().count(), 1);
+    assert_eq!(plan.psbt.fee(), Ok(Some(plan.fee)));
+    let output = plan.psbt.outputs().next().unwrap();
+    assert_eq!(output.script, wallet.descr.derive(1, 1u8).to_script_pubkey());
+    assert!(plan.psbt.estimate_weight().unwrap().to_u32() <= 1531);
+
+    let mut expensive = params;
+    expensive.future_fee_rate = FeeRate::from_sat_per_vb(1);
+    assert_eq!(wallet.plan_consolidation(coins.clone(), expensive).unwrap(), None);
+    let mut small = params;
+    small.threshold = Sats::from_sats(99_999u32);
+    assert_eq!(wallet.plan_consolidation(coins.clone(), small).unwrap(), None);
+    let mut few = params;
+    few.min_coins = 6;
+    assert_eq!(wallet.plan_consolidation(coins.clone(), few).unwrap(), None);
+
+    let unknown_txid = Txid::from([0xFF; 32]);
+    let unknown = [Outpoint::new(unknown_txid, 0u32), Outpoint::new(unknown_txid, 1u32)];
+    assert!(matches!(
+        wallet.plan_consolidation(coins.into_iter().chain(unknown), params),
+        Err(ConstructionError::UnknownCoins(outpoints)) if outpoints == unknown
+    ));
+}
+
+#[test]
+fn construct_dust() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let coin = wallet.coin();
+    let address = beneficiary().address;
+    assert_eq!(dust_limit(&address.script_pubkey(), FeeRate::DUST_RELAY), Sats::from_sats(294u32));
+
+    let dust = Beneficiary::new(address, Sats::from_sats(293u32));
+    assert!(matches!(
+        wallet.construct_psbt([coin], [&dust], TxParams::with(Sats::from_sats(500u32))),
+        Err(ConstructionError::DustOutput { amount, dust_limit, .. })
+            if amount == Sats::from_sats(293u32) && dust_limit == Sats::from_sats(294u32)
+    ));
+
+    let max = Beneficiary::with_max(address);
+    let fee = TxParams::with(Sats::from_sats(100_000u32 - 200));
+    assert!(matches!(
+        wallet.construct_psbt([coin], [&max], fee),
+        Err(ConstructionError::DustOutput { amount, .. }) if amount == Sats::from_sats(200u32)
+    ));
+
+    let limit = Beneficiary::new(address, Sats::from_sats(294u32));
+    assert!(wallet
+        .construct_psbt([coin], [&limit], TxParams::with(Sats::from_sats(500u32)))
+        .is_ok());
+
+    // Change below the dust limit goes to the fee, while the change equal to it is kept
+    let payment = beneficiary();
+    let fee = Sats::from_sats(100_000u32 - 10_000 - 293);
+    let (psbt, meta) = wallet.construct_psbt([coin], [&payment], TxParams::with(fee)).unwrap();
+    assert_eq!(meta.change_vout, None);
+    assert_eq!(psbt.outputs().count(), 1);
+    let fee = Sats::from_sats(100_000u32 - 10_000 - 294);
+    let (psbt, meta) = wallet.construct_psbt([coin], [&payment], TxParams::with(fee)).unwrap();
+    assert_eq!(meta.change_vout, Some(Vout::from_u32(1)));
+    assert_eq!(psbt.outputs().nth(1).unwrap().value(), Sats::from_sats(294u32));
+}
+
+#[test]
+fn construct_batch() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let coin = wallet.coin();
+    let a = beneficiary();
+    let xpub = XpubDerivable::from_str(XPUB).unwrap();
+    let script_pubkey = Wpkh::from(xpub).derive(0, 6u8).to_script_pubkey();
+    let b = Beneficiary::new(
+        Address::with(&script_pubkey, Network::Testnet3).unwrap(),
+        Sats::from_sats(20_000u32),
+    );
+    let batch = [a, b, a, a];
+
+    let params = TxParams::with(Sats::from_sats(500u32));
+    let (psbt, _) = wallet.construct_psbt([coin], &batch, params).unwrap();
+    assert_eq!(psbt.outputs().count(), 5);
+
+    let params = params.with_merged_outputs(true);
+    let (psbt, meta) = wallet.construct_psbt([coin], &batch, params).unwrap();
+    let outputs = psbt.outputs().map(|out| (out.script.clone(), out.value())).collect::<Vec<_>>();
+    assert_eq!(outputs[..2], [
+        (a.script_pubkey(), Sats::from_sats(30_000u32)),
+        (b.script_pubkey(), Sats::from_sats(20_000u32))
+    ]);
+    assert_eq!(meta.change_vout, Some(Vout::from_u32(2)));
+    assert_eq!(outputs[2].1, Sats::from_sats(100_000u32 - 50_000 - 500));
+
+    let max = Beneficiary::with_max(a.address);
+    let (psbt, meta) = wallet.construct_psbt([coin], [&a, &b, &max], params).unwrap();
+    assert_eq!(psbt.outputs().count(), 2);
+    assert_eq!(meta.change_vout, None);
+    assert_eq!(psbt.outputs().next().unwrap().value(), Sats::from_sats(100_000u32 - 20_000 - 500));
+}
+
+#[test]
+fn construct_future_segwit() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let coin = wallet.coin();
+    let program = FutureProgram::new(WitnessVer::V2, &[0x75; 16]).unwrap();
+    let address = Address::new(program.into(), AddressNetwork::Testnet);
+    let future = Beneficiary::new(address, Sats::from_sats(10_000u32));
+
+    let params = TxParams::with(Sats::from_sats(500u32));
+    assert!(matches!(
+        wallet.construct_psbt([coin], [&future], params),
+        Err(ConstructionError::FutureSegwit(addr)) if addr == address
+    ));
+    let params = params.with_future_segwit(true);
+    let (psbt, _) = wallet.construct_psbt([coin], [&future], params).unwrap();
+    assert_eq!(psbt.outputs().next().unwrap().script, address.script_pubkey());
+}
+
+#[test]
+fn construct_extras() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let coin = wallet.coin();
+    let foreign = ForeignInput {
+        outpoint: Outpoint::new(Txid::from([2; 32]), 1u32),
+        txout: TxOut::new(beneficiary().script_pubkey(), Sats::from_sats(50_000u32)),
+        satisfaction_weight: WeightUnits::witness_discount(108),
+    };
+    let commitment = TxOut::new(ScriptPubkey::op_return(b"anchor"), Sats::ZERO);
+    let payout = TxOut::new(beneficiary().script_pubkey(), Sats::from_sats(60_000u32));
+    let extras = TxExtras {
+        inputs: vec![foreign.clone()],
+        outputs: vec![commitment.clone(), payout.clone()],
+    };
+    assert_eq!(
+        extras.weight(),
+        WeightUnits::no_discount(40)
+            + foreign.satisfaction_weight
+            + commitment.weight_units()
+            + payout.weight_units()
+    );
+
+    let params = TxParams::with(Sats::from_sats(500u32));
+    let (psbt, meta) =
+        wallet.construct_psbt_with([coin], &extras, [&beneficiary()], params).unwrap();
+    let inputs = psbt.inputs().collect::<Vec<_>>();
+    assert_eq!(inputs.len(), 2);
+    assert_eq!(inputs[1].previous_outpoint, foreign.outpoint);
+    assert_eq!(inputs[1].witness_utxo, Some(foreign.txout));
+    assert!(inputs[1].bip32_derivation.is_empty());
+
+    let outputs = psbt.outputs().map(|out| (out.script.clone(), out.value())).collect::<Vec<_>>();
+    assert_eq!(outputs[1..3], [
+        (commitment.script_pubkey, commitment.value),
+        (payout.script_pubkey, payout.value)
+    ]);
+    assert_eq!(meta.change_vout, Some(Vout::from_u32(3)));
+    assert_eq!(outputs[3].1, Sats::from_sats(150_000u32 - 10_000 - 60_000 - 500));
+}
+
+#[test]
+fn construct_keychain_descriptors() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let change_descr = StdDescr::from(TrKey::from(XpubDerivable::from_str(XPUB_TR).unwrap()));
+    wallet.change_descr = Some(change_descr.clone());
+    let coin = wallet.coin();
+
+    let (psbt, meta) = wallet
+        .construct_psbt([coin], [&beneficiary()], TxParams::with(Sats::from_sats(500u32)))
+        .unwrap();
+    let input = psbt.inputs().next().unwrap();
+    assert_eq!(input.bip32_derivation.len(), 1);
+    assert!(input.tap_bip32_derivation.is_empty());
+    let change = psbt.outputs().nth(meta.change_vout.unwrap().to_usize()).unwrap();
+    assert!(change.script.is_p2tr());
+    assert_eq!(change.script, change_descr.derive(1, 1u8).to_script_pubkey());
+    assert_eq!(change.tap_bip32_derivation.len(), 1);
+    assert_eq!(psbt.xpubs.len(), 2);
+}
+
+#[test]
+fn analyze_psbt() {
+    let mut wallet = TestWallet::new(Wpkh::from(XpubDerivable::from_str(XPUB).unwrap()));
+    let change_descr = StdDescr::from(TrKey::from(XpubDerivable::from_str(XPUB_TR).unwrap()));
+    wallet.change_descr = Some(change_descr.clone());
+    let coin = wallet.coin();
+    let (mut psbt, _) = wallet
+        .construct_psbt([coin], [&beneficiary()], TxParams::with(Sats::from_sats(500u32)))
+        .unwrap();
+
+    let report = wallet.analyze_psbt(&psbt).unwrap();
+    assert_eq!(report.wallet_inputs, Sats::from_sats(100_000u32));
+    assert_eq!(report.foreign_inputs, Sats::ZERO);
+    assert_eq!(report.sent, Sats::from_sats(10_000u32));
+    assert_eq!(report.change, Sats::from_sats(100_000u32 - 10_000 - 500));
+    assert_eq!(report.fee, Sats::from_sats(500u32));
+    assert_eq!(report.spent(), Some(Sats::from_sats(10_500u32)));
+    assert_eq!(report.fee_rate, FeeRate::from_fee(report.fee, report.weight));
+
+    assert_eq!(report.outputs.len(), 2);
+    assert_eq!(report.outputs[0].address, Some(beneficiary().address));
+    assert!(!report.outputs[0].is_change());
+    assert_eq!(
+        report.outputs[1].change,
+        Some(Terminal::new(Keychain::INNER, NormalIndex::from(1u8)))
+    );
+    let change_spk = change_descr.derive(1, 1u8).to_script_pubkey();
+    assert_eq!(
+        report.outputs[1].address,
+        Some(Address::with(&change_spk, Network::Testnet3).unwrap())
+    );
+
+    psbt.output_mut(1).unwrap().script = beneficiary().script_pubkey();
+    assert_eq!(wallet.analyze_psbt(&psbt), Err(AnalysisError::InvalidChange(1)));
+}
@@ -0,0 +1,174 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use common::TestWallet;
+use derive::{Address, Keychain, Network, NormalIndex, Outpoint, Sats, Terminal, Txid, Vout};
+use descriptors::Descriptor;
+use psbt::{
+    Beneficiary, FinalizeError, PayjoinError, PayjoinParams, Psbt, PsbtConstructor, TxParams,
+};
+
+fn wallet(seed: u8) -> TestWallet {
+    let coin = Outpoint::new(Txid::from([seed; 32]), 0u32);
+    let terminal = Terminal::new(Keychain::OUTER, NormalIndex::from(1u8));
+    TestWallet::wpkh(seed).with_coin(coin, 100_000, terminal)
+}
+
+struct Session {
+    sender: TestWallet,
+    receiver: TestWallet,
+    unsigned: Psbt,
+    original: Psbt,
+    params: PayjoinParams,
+}
+
+fn session() -> Session {
+    let mut sender = wallet(0xA5);
+    let receiver = wallet(0x5A);
+    let payee = Address::with(&receiver.script_pubkey(0), Network::Testnet3).unwrap();
+    let beneficiary = Beneficiary::new(payee, Sats::from_sats(30_000u32));
+    let (unsigned, meta) = sender
+        .construct_psbt([sender.coin()], [&beneficiary], TxParams::with(Sats::from_sats(500u32)))
+        .unwrap();
+    let mut original = unsigned.clone();
+    assert_eq!(original.sign(&sender.account).unwrap(), 1);
+    assert_eq!(original.finalize(), Ok(1));
+    let params = PayjoinParams {
+        additional_fee_output: meta.change_vout,
+        max_additional_fee: Sats::from_sats(200u32),
+        disable_output_substitution: true,
+    };
+    Session {
+        sender,
+        receiver,
+        unsigned,
+        original,
+        params,
+    }
+}
+
+fn propose(session: &Session) -> Psbt {
+    let mut proposal = session
+        .receiver
+        .construct_payjoin_proposal(
+            &session.original,
+            [session.receiver.coin()],
+            Vout::from_u32(0),
+            &session.params,
+        )
+        .unwrap();
+    assert_eq!(proposal.sign(&session.receiver.account).unwrap(), 1);
+    assert_eq!(proposal.finalize(), Err(vec![FinalizeError::NotEnoughSigs(0)]));
+    proposal
+}
+
+#[test]
+fn payjoin() {
+    let session = session();
+    let fee_rate = session.original.check_payjoin_original().unwrap();
+    let proposal = propose(&session);
+    assert_eq!(proposal.inputs().count(), 2);
+    assert!(proposal.xpubs().next().is_none());
+
+    let fee = fee_rate.fee_for_weight(session.receiver.descr.max_input_weight());
+    assert!(fee > session.params.max_additional_fee);
+    let outputs = proposal.outputs().map(|out| out.value()).collect::<Vec<_>>();
+    assert_eq!(outputs, vec![
+        Sats::from_sats(130_000u32 - fee.sats() as u32 + 200),
+        Sats::from_sats(100_000u32 - 30_000 - 500 - 200)
+    ]);
+    assert_eq!(proposal.fee(), Ok(Some(fee + Sats::from_sats(500u32))));
+
+    let mut psbt = session
+        .unsigned
+        .process_payjoin_proposal(proposal, &session.receiver.script_pubkey(0), &session.params)
+        .unwrap();
+    assert_eq!(psbt.sign(&session.sender.account).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Ok(2));
+    let tx = psbt.extract().unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+}
+
+#[test]
+fn payjoin_invalid_proposal() {
+    let session = session();
+    let payee = session.receiver.script_pubkey(0);
+    let proposal = propose(&session);
+
+    let mut params = session.params;
+    params.max_additional_fee = Sats::from_sats(100u32);
+    assert!(matches!(
+        session.unsigned.process_payjoin_proposal(proposal.clone(), &payee, &params),
+        Err(PayjoinError::ExcessiveFee(fee)) if fee == Sats::from_sats(200u32)
+    ));
+
+    let unfinalized = session
+        .receiver
+        .construct_payjoin_proposal(
+            &session.original,
+            [session.receiver.coin()],
+            Vout::from_u32(0),
+            &session.params,
+        )
+        .unwrap();
+    assert!(matches!(
+        session.unsigned.process_payjoin_proposal(unfinalized, &payee, &session.params),
+        Err(PayjoinError::ReceiverNotFinalized(1))
+    ));
+
+    let other = session.sender.script_pubkey(0);
+    assert!(matches!(
+        session.unsigned.process_payjoin_proposal(proposal, &other, &session.params),
+        Err(PayjoinError::OutputChanged(vout)) if vout == Vout::from_u32(0)
+    ));
+
+    assert!(matches!(
+        session.unsigned.check_payjoin_original(),
+        Err(PayjoinError::OriginalNotFinalized(0))
+    ));
+}
+
+#[test]
+fn payjoin_pocketed_fee() {
+    let session = session();
+    let payee = session.receiver.script_pubkey(0);
+    let proposal = propose(&session);
+    let fee = proposal.fee().unwrap().unwrap() - session.original.fee().unwrap().unwrap();
+
+    // Receiver keeps the fee taken from the sender, leaving the fee unchanged
+    let mut pocketed = proposal.clone();
+    pocketed.output_mut(0).unwrap().amount += fee;
+    assert!(matches!(
+        session.unsigned.process_payjoin_proposal(pocketed, &payee, &session.params),
+        Err(PayjoinError::FeeContributionExceeded(taken)) if taken == Sats::from_sats(200u32)
+    ));
+
+    // Receiver pays for its input only the fee taken from the sender
+    let mut underpaid = proposal;
+    underpaid.output_mut(0).unwrap().amount += fee - Sats::from_sats(200u32);
+    assert!(matches!(
+        session.unsigned.process_payjoin_proposal(underpaid, &payee, &session.params),
+        Err(PayjoinError::FeeRateDecreased(_))
+    ));
+}
@@ -0,0 +1,116 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use common::wpkh;
+use derive::{
+    Keychain, NormalIndex, Outpoint, Sats, ScriptPubkey, SeqNo, SighashType, Terminal, Txid,
+    XpubDerivable,
+};
+use descriptors::{SpkClass, Wpkh};
+use psbt::{FeeRate, PolicyAction, PolicyViolation, Prevout, Psbt, PsbtVer, SigningPolicy};
+
+fn psbt(descr: &Wpkh<XpubDerivable>, fee: u32) -> Psbt {
+    let mut psbt = Psbt::create(PsbtVer::V2);
+    for index in 0..2u8 {
+        psbt.construct_input_expect(
+            Prevout::new(
+                Outpoint::new(Txid::from([1; 32]), index as u32),
+                Sats::from_sats(100_000u32),
+            ),
+            descr,
+            Terminal::new(Keychain::OUTER, NormalIndex::from(index)),
+            SeqNo::from_consensus_u32(0xFFFF_FFFD),
+        );
+    }
+    psbt.construct_output_expect(ScriptPubkey::p2wpkh([7u8; 20]), Sats::from_sats(150_000u32));
+    let change = Terminal::new(Keychain::INNER, NormalIndex::from(0u8));
+    psbt.construct_change_expect(descr, change, Sats::from_sats(50_000u32 - fee));
+    psbt
+}
+
+#[test]
+fn policy_pass() {
+    let descr = wpkh(0xA5);
+    let policy = SigningPolicy::default();
+    assert_eq!(policy.check(&psbt(&descr, 500), &descr), Ok(vec![]));
+}
+
+#[test]
+fn policy_absurd_fee() {
+    let descr = wpkh(0xA5);
+    let mut policy = SigningPolicy::default();
+    let psbt = psbt(&descr, 40_000);
+    assert_eq!(policy.check(&psbt, &descr), Ok(vec![]));
+    policy.max_fee_rate = FeeRate::from_sat_per_vb(100);
+    assert!(matches!(policy.check(&psbt, &descr), Err(PolicyViolation::AbsurdFeeRate(_))));
+
+    policy.max_fee = Sats::from_sats(30_000u32);
+    assert_eq!(
+        policy.check(&psbt, &descr),
+        Err(PolicyViolation::AbsurdFee(Sats::from_sats(40_000u32)))
+    );
+
+    policy.absurd_fee = PolicyAction::Ignore;
+    assert_eq!(policy.check(&psbt, &descr), Ok(vec![]));
+}
+
+#[test]
+fn policy_unknown_change() {
+    let descr = wpkh(0xA5);
+    let policy = SigningPolicy::default();
+
+    // Change derived by the descriptor, but put into a different script pubkey
+    let mut psbt = psbt(&descr, 500);
+    psbt.output_mut(1).unwrap().script = ScriptPubkey::p2wpkh([8u8; 20]);
+    assert_eq!(policy.check(&psbt, &descr), Err(PolicyViolation::UnknownChange(1)));
+
+    // Change of another wallet is not checked
+    let other = wpkh(0x5A);
+    assert_eq!(policy.check(&psbt, &other), Ok(vec![]));
+}
+
+#[test]
+fn policy_warnings() {
+    let descr = wpkh(0xA5);
+    let mut policy = SigningPolicy::default();
+    let mut psbt = psbt(&descr, 500);
+    psbt.input_mut(1).unwrap().sighash_type = Some(SighashType::single_anyone_can_pay());
+    let reused = psbt.inputs().next().unwrap().prev_txout().unwrap().script_pubkey.clone();
+    psbt.output_mut(0).unwrap().script = reused;
+    psbt.construct_output_expect(ScriptPubkey::op_return(b"data"), Sats::ZERO);
+    psbt.construct_output_expect(ScriptPubkey::p2pkh([9u8; 20]), Sats::ZERO);
+    policy.output_classes.remove(&SpkClass::P2pkh);
+
+    assert_eq!(
+        policy.check(&psbt, &descr),
+        Ok(vec![
+            PolicyViolation::MixedSighash,
+            PolicyViolation::AddressReuse(0),
+            PolicyViolation::UnexpectedOutput(3)
+        ])
+    );
+
+    policy.address_reuse = PolicyAction::Reject;
+    assert_eq!(policy.check(&psbt, &descr), Err(PolicyViolation::AddressReuse(0)));
+}
@@ -0,0 +1,122 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use common::{account, TestWallet};
+use derive::{
+    Derive, Keychain, NormalIndex, Outpoint, Sats, ScriptPubkey, Terminal, Txid, XprivAccount,
+    XpubDerivable,
+};
+use descriptors::{StdDescr, TrKey, Wpkh};
+use psbt::{por_challenge, verify_reserves, ConstructionError, PsbtConstructor, ReservesError};
+
+fn wallet(account: XprivAccount, descr: impl Into<StdDescr>) -> TestWallet {
+    let terminal = |index: u8| Terminal::new(Keychain::OUTER, NormalIndex::from(index));
+    TestWallet::new(account, descr)
+        .with_coin(Outpoint::new(Txid::from([1; 32]), 0u32), 50_000, terminal(0))
+        .with_coin(Outpoint::new(Txid::from([2; 32]), 1u32), 100_000, terminal(1))
+}
+
+fn challenge() -> Terminal { Terminal::new(Keychain::INNER, NormalIndex::from(7u8)) }
+
+fn challenge_script(wallet: &TestWallet) -> ScriptPubkey {
+    wallet.descr.derive(challenge().keychain, challenge().index).to_script_pubkey()
+}
+
+fn prove(wallet: &TestWallet, message: &str) -> derive::Tx {
+    let mut psbt = wallet.construct_reserves_psbt(message, challenge(), wallet.coins()).unwrap();
+    assert_eq!(psbt.inputs().count(), 3);
+    assert_eq!(psbt.outputs().count(), 1);
+    let challenge = psbt.inputs().next().unwrap();
+    assert_eq!(challenge.previous_outpoint, por_challenge(message));
+    assert_eq!(challenge.proof_of_reserves.as_deref(), Some(message));
+    assert_eq!(challenge.prev_txout().unwrap().script_pubkey, challenge_script(wallet));
+
+    assert_eq!(psbt.sign(&wallet.account).unwrap(), 3);
+    assert_eq!(psbt.finalize(), Ok(3));
+    psbt.extract().unwrap()
+}
+
+#[test]
+fn reserves_wpkh() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let wallet = wallet(account, descr);
+    let tx = prove(&wallet, "Reserves of 2024-01-01");
+    let challenge = challenge_script(&wallet);
+    let reserves =
+        verify_reserves(&tx, "Reserves of 2024-01-01", &challenge, |o| wallet.txout(o)).unwrap();
+    assert_eq!(reserves, Sats::from_sats(150_000u32));
+
+    assert_eq!(
+        verify_reserves(&tx, "Reserves of 2024-01-02", &challenge, |o| wallet.txout(o)),
+        Err(ReservesError::InvalidChallenge)
+    );
+    let coin_script = wallet.txout(wallet.coins()[0]).unwrap().script_pubkey;
+    assert_eq!(
+        verify_reserves(&tx, "Reserves of 2024-01-01", &coin_script, |o| wallet.txout(o)),
+        Err(ReservesError::InvalidSignature(0))
+    );
+    let spent = wallet.coins()[1];
+    assert_eq!(
+        verify_reserves(&tx, "Reserves of 2024-01-01", &challenge, |o| {
+            wallet.txout(o).filter(|_| o != spent)
+        }),
+        Err(ReservesError::UnknownPrevout(spent))
+    );
+}
+
+#[test]
+fn reserves_tr() {
+    let account = account(0xA5, 86);
+    let descr = TrKey::from(XpubDerivable::from(account.to_xpub_spec()));
+    let wallet = wallet(account, descr);
+    let mut tx = prove(&wallet, "Proof");
+    let challenge = challenge_script(&wallet);
+    assert_eq!(
+        verify_reserves(&tx, "Proof", &challenge, |o| wallet.txout(o)),
+        Ok(Sats::from_sats(150_000u32))
+    );
+
+    tx.outputs[0].value = Sats::ZERO;
+    assert_eq!(
+        verify_reserves(&tx, "Proof", &challenge, |o| wallet.txout(o)),
+        Err(ReservesError::InvalidSignature(0))
+    );
+}
+
+#[test]
+fn reserves_no_coins() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let wallet = wallet(account, descr);
+    assert!(matches!(
+        wallet.construct_reserves_psbt("Proof", challenge(), []),
+        Err(ConstructionError::NoInputs)
+    ));
+    let unknown = Outpoint::new(Txid::from([3; 32]), 0u32);
+    assert!(matches!(
+        wallet.construct_reserves_psbt("Proof", challenge(), [unknown]),
+        Err(ConstructionError::UnknownCoins(coins)) if coins == [unknown]
+    ));
+}
@@ -0,0 +1,639 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use std::str::FromStr;
+
+use common::account;
+use derive::opcodes::{OP_CHECKMULTISIG, OP_PUSHNUM_2};
+use derive::secp256k1::{Message, SecretKey, SECP256K1};
+use derive::{
+    Derive, InternalPk, Keychain, LegacyPk, LockTime, NormalIndex, Outpoint, RedeemScript, Sats,
+    ScriptPubkey, SeqNo, SighashCache, SighashType, Terminal, Tx, TxStats, TxVer, Txid,
+    VarIntArray, Vout, Weight, XprivAccount, XpubDerivable,
+};
+use descriptors::{Descriptor, Pkh, ShMulti, ShWpkh, TrKey, TrScript, Wpkh, Wsh, WshMulti};
+use psbt::{
+    FinalizeError, MockSigner, Prevout, PrevoutError, Psbt, PsbtVer, SighashPolicy, Sign,
+    SignError, TxMismatch, UnfinalizedInputs,
+};
+
+fn psbt<D: Descriptor<XpubDerivable>>(descr: &D) -> Psbt {
+    let mut psbt = Psbt::create(PsbtVer::V2);
+    let terminal = Terminal::new(Keychain::OUTER, NormalIndex::from(3u8));
+    psbt.construct_input_expect(
+        Prevout::new(Outpoint::new(Txid::from([1; 32]), 0u32), Sats::from_sats(100_000u32)),
+        descr,
+        terminal,
+        SeqNo::from_consensus_u32(0xFFFF_FFFD),
+    );
+    psbt.construct_output_expect(ScriptPubkey::p2pkh([7u8; 20]), Sats::from_sats(99_000u32));
+    psbt
+}
+
+fn sighash_cache(psbt: &Psbt) -> SighashCache {
+    let prevouts = psbt.inputs().map(|input| input.prev_txout().unwrap().clone()).collect();
+    SighashCache::new(Tx::from(psbt.to_unsigned_tx()), prevouts).unwrap()
+}
+
+fn check_ecdsa(mut psbt: Psbt, signer: &impl Sign) {
+    assert_eq!(psbt.sign(signer).unwrap(), 1);
+    // Already signed inputs are not signed twice
+    assert_eq!(psbt.sign(signer).unwrap(), 0);
+
+    let input = psbt.inputs().next().unwrap();
+    let (pk, _) = input.bip32_derivation.first().unwrap();
+    let sig = input.partial_sigs[&LegacyPk::compressed(**pk)];
+    assert_eq!(sig.sighash_type, SighashType::all());
+
+    let mut hash = [0u8; 20];
+    hash.copy_from_slice(&derive::WPubkeyHash::from(*pk)[..]);
+    let script_code = ScriptPubkey::p2pkh(hash);
+    let sighash = sighash_cache(&psbt)
+        .segwit_sighash(0, script_code.as_script_bytes(), SighashType::all())
+        .unwrap();
+    SECP256K1.verify_ecdsa(&Message::from(sighash), &sig.sig, pk).unwrap();
+}
+
+#[test]
+fn sign_wpkh() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    check_ecdsa(psbt(&descr), &account);
+}
+
+#[test]
+fn sign_sh_wpkh() {
+    let account = account(0xA5, 49);
+    let descr = ShWpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    check_ecdsa(psbt(&descr), &account);
+}
+
+#[test]
+fn sign_tr_key_only() {
+    let account = account(0xA5, 86);
+    let descr = TrKey::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    assert_eq!(psbt.sign(&account).unwrap(), 1);
+
+    let input = psbt.inputs().next().unwrap();
+    let sig = input.tap_key_sig.unwrap();
+    assert_eq!(sig.sighash_type, None);
+
+    let internal_pk: InternalPk = input.tap_internal_key.unwrap();
+    let (output_pk, _) = internal_pk.to_output_pk(None::<derive::TapNodeHash>);
+    let sighash = sighash_cache(&psbt).tap_sighash_key(0, None).unwrap();
+    let output_pk =
+        derive::secp256k1::XOnlyPublicKey::from_slice(&output_pk.to_byte_array()).unwrap();
+    SECP256K1.verify_schnorr(&sig.sig, &Message::from(sighash), &output_pk).unwrap();
+}
+
+#[test]
+fn sign_foreign() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    assert_eq!(psbt.sign(&self::account(0x5A, 84)).unwrap(), 0);
+    assert_eq!(psbt.sign([self::account(0x5A, 84), account].as_slice()).unwrap(), 1);
+}
+
+#[test]
+fn sign_invalid_prev_tx() {
+    let account = account(0xA5, 44);
+    let descr = Pkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    let input = psbt.input_mut(0).unwrap();
+    let txout = input.witness_utxo.take().unwrap();
+    let prev_tx = Tx {
+        version: TxVer::V2,
+        inputs: VarIntArray::new(),
+        outputs: VarIntArray::from_iter_unsafe([txout]),
+        lock_time: LockTime::ZERO,
+    };
+    let txid = prev_tx.txid();
+    input.non_witness_tx = Some(prev_tx);
+
+    // Spent output id is kept from the PSBT construction and doesn't match the transaction
+    assert_eq!(psbt.sign(&account), Err(SignError::Prevout(PrevoutError::TxidMismatch(0))));
+    assert_eq!(psbt.inputs().next().unwrap().value(), Err(PrevoutError::TxidMismatch(0)));
+
+    psbt.input_mut(0).unwrap().previous_outpoint = Outpoint::new(txid, 3u32);
+    assert_eq!(
+        psbt.sign(&account),
+        Err(SignError::Prevout(PrevoutError::NoOutput(0, Vout::from_u32(3))))
+    );
+
+    psbt.input_mut(0).unwrap().previous_outpoint = Outpoint::new(txid, 0u32);
+    assert_eq!(psbt.sign(&account), Ok(1));
+}
+
+#[test]
+fn sign_witness_utxo_mismatch() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    let input = psbt.input_mut(0).unwrap();
+    let mut txout = input.witness_utxo.clone().unwrap();
+    let prev_tx = Tx {
+        version: TxVer::V2,
+        inputs: VarIntArray::new(),
+        outputs: VarIntArray::from_iter_unsafe([txout.clone()]),
+        lock_time: LockTime::ZERO,
+    };
+    input.previous_outpoint = Outpoint::new(prev_tx.txid(), 0u32);
+    input.non_witness_tx = Some(prev_tx);
+    let fee = psbt.fee().unwrap().unwrap();
+
+    // Witness UTXO overstating the spent value must not be trusted
+    txout.value += Sats::from_sats(10_000u32);
+    psbt.input_mut(0).unwrap().witness_utxo = Some(txout);
+    assert_eq!(psbt.fee(), Err(PrevoutError::WitnessUtxoMismatch(0)));
+    assert_eq!(psbt.sign(&account), Err(SignError::Prevout(PrevoutError::WitnessUtxoMismatch(0))));
+
+    psbt.input_mut(0).unwrap().witness_utxo = None;
+    assert_eq!(psbt.fee(), Ok(Some(fee)));
+    assert_eq!(psbt.sign(&account), Ok(1));
+}
+
+#[test]
+fn sign_script_mismatch() {
+    let account1 = account(0xA5, 48);
+    let account2 = account(0x5A, 48);
+    let keys = format!(
+        "multi(2,{},{})",
+        XpubDerivable::from(account1.to_xpub_spec()),
+        XpubDerivable::from(account2.to_xpub_spec())
+    );
+
+    let descr = ShMulti::from_str(&format!("sh({keys})")).unwrap();
+    let mut psbt = psbt(&descr);
+    let other = descr.derive(0, 4u8).to_redeem_script();
+    psbt.input_mut(0).unwrap().redeem_script = other;
+    assert_eq!(psbt.sign(&account1), Err(SignError::RedeemScriptMismatch(0)));
+
+    let descr = WshMulti::from_str(&format!("wsh({keys})")).unwrap();
+    let mut psbt = self::psbt(&descr);
+    let other = descr.derive(0, 4u8).to_witness_script();
+    psbt.input_mut(0).unwrap().witness_script = other;
+    assert_eq!(psbt.sign(&account1), Err(SignError::WitnessScriptMismatch(0)));
+}
+
+#[test]
+fn sign_only_script_keys() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = self::psbt(&descr);
+    let mut other = Psbt::create(PsbtVer::V2);
+    other.construct_input_expect(
+        Prevout::new(Outpoint::new(Txid::from([2; 32]), 0u32), Sats::from_sats(100_000u32)),
+        &descr,
+        Terminal::new(Keychain::OUTER, NormalIndex::from(4u8)),
+        SeqNo::from_consensus_u32(0xFFFF_FFFD),
+    );
+    let (pk, origin) = other.inputs().next().unwrap().bip32_derivation.first().unwrap();
+    // Key of the wallet which is not a part of the spent script must not be signed with
+    let input = psbt.input_mut(0).unwrap();
+    input.bip32_derivation.insert(*pk, origin.clone());
+    assert_eq!(psbt.sign(&account), Ok(1));
+    let input = psbt.inputs().next().unwrap();
+    assert_eq!(input.partial_sigs.len(), 1);
+    assert!(!input.partial_sigs.contains_key(&LegacyPk::compressed(**pk)));
+}
+
+#[test]
+fn sign_sighash_policy() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let single_acp = SighashType::single_anyone_can_pay();
+    let mut psbt = psbt(&descr);
+    psbt.input_mut(0).unwrap().sighash_type = Some(single_acp);
+
+    let policy = SighashPolicy::default();
+    assert_eq!(policy.allowed().collect::<Vec<_>>(), vec![SighashType::all()]);
+    assert_eq!(
+        psbt.sign_with_policy(&account, &policy),
+        Err(SignError::SighashNotAllowed(0, single_acp))
+    );
+    assert!(psbt.inputs().next().unwrap().partial_sigs.is_empty());
+    // Policy is checked before signing, whether the signer controls the input keys or not
+    assert_eq!(
+        psbt.sign_with_policy(&self::account(0x5A, 84), &policy),
+        Err(SignError::SighashNotAllowed(0, single_acp))
+    );
+    assert_eq!(psbt.sign(&account), Err(SignError::SighashNotAllowed(0, single_acp)));
+
+    let mut policy = SighashPolicy::with([SighashType::all(), single_acp]);
+    assert!(!policy.is_allowed(SighashType::single()));
+    assert_eq!(psbt.sign_with_policy(&account, &policy), Ok(1));
+    policy.deny(single_acp);
+    assert_eq!(policy, SighashPolicy::all_only());
+
+    let input = psbt.inputs().next().unwrap();
+    let (pk, _) = input.bip32_derivation.first().unwrap();
+    let sig = input.partial_sigs[&LegacyPk::compressed(**pk)];
+    assert_eq!(sig.sighash_type, single_acp);
+    let mut hash = [0u8; 20];
+    hash.copy_from_slice(&derive::WPubkeyHash::from(*pk)[..]);
+    let script_code = ScriptPubkey::p2pkh(hash);
+    let sighash =
+        sighash_cache(&psbt).segwit_sighash(0, script_code.as_script_bytes(), single_acp).unwrap();
+    SECP256K1.verify_ecdsa(&Message::from(sighash), &sig.sig, pk).unwrap();
+
+    assert_eq!(psbt.finalize(), Ok(1));
+    let tx = psbt.extract().unwrap();
+    assert_eq!(tx.inputs[0].witness.iter().next().unwrap().last(), Some(&0x83));
+}
+
+#[test]
+fn sign_tr_sighash_policy() {
+    let account = account(0xA5, 86);
+    let descr = TrKey::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    assert_eq!(psbt.sign_with_policy(&account, &SighashPolicy::all_only()), Ok(1));
+
+    let mut psbt = self::psbt(&descr);
+    psbt.input_mut(0).unwrap().sighash_type = Some(SighashType::none());
+    assert_eq!(
+        psbt.sign_with_policy(&account, &SighashPolicy::all_only()),
+        Err(SignError::SighashNotAllowed(0, SighashType::none()))
+    );
+    assert_eq!(psbt.sign_with_policy(&account, &SighashPolicy::any()), Ok(1));
+    let sig = psbt.inputs().next().unwrap().tap_key_sig.unwrap();
+    assert_eq!(sig.sighash_type, Some(SighashType::none()));
+}
+
+#[test]
+fn finalize_wpkh() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    assert_eq!(psbt.extract(), Err(UnfinalizedInputs(vec![0])));
+    assert_eq!(psbt.input_mut(0).unwrap().finalize(), Err(FinalizeError::NotEnoughSigs(0)));
+
+    psbt.sign(&account).unwrap();
+    assert_eq!(psbt.finalize(), Ok(1));
+    assert!(psbt.is_finalized());
+    let input = psbt.inputs().next().unwrap();
+    assert!(input.partial_sigs.is_empty());
+    assert!(input.bip32_derivation.is_empty());
+    assert!(input.witness_utxo.is_some());
+
+    let tx = psbt.extract().unwrap();
+    assert_eq!(tx.txid(), psbt.txid());
+    assert!(tx.inputs[0].sig_script.is_empty());
+    assert_eq!(tx.inputs[0].witness.len(), 2);
+}
+
+#[test]
+fn finalize_sh_wpkh() {
+    let account = account(0xA5, 49);
+    let descr = ShWpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    psbt.sign(&account).unwrap();
+    assert_eq!(psbt.finalize(), Ok(1));
+
+    let tx = psbt.extract().unwrap();
+    // Single push of the P2WPKH redeem script
+    assert_eq!(tx.inputs[0].sig_script.len(), 23);
+    assert_eq!(tx.inputs[0].witness.len(), 2);
+}
+
+#[test]
+fn finalize_pkh() {
+    let account = account(0xA5, 44);
+    let descr = Pkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    psbt.sign(&account).unwrap();
+    assert_eq!(psbt.finalize(), Ok(1));
+
+    let tx = psbt.extract().unwrap();
+    assert!(!tx.inputs[0].sig_script.is_empty());
+    assert!(tx.inputs[0].witness.is_empty());
+}
+
+#[test]
+fn finalize_tr_key_only() {
+    let account = account(0xA5, 86);
+    let descr = TrKey::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt = psbt(&descr);
+    psbt.sign(&account).unwrap();
+    assert_eq!(psbt.finalize(), Ok(1));
+
+    let tx = psbt.extract().unwrap();
+    assert_eq!(tx.inputs[0].witness.len(), 1);
+    assert_eq!(tx.inputs[0].witness.elements().next().unwrap().len(), 64);
+}
+
+#[test]
+fn finalize_wsh_multi() {
+    let account1 = account(0xA5, 48);
+    let account2 = account(0x5A, 48);
+    let descr = WshMulti::from_str(&format!(
+        "wsh(multi(2,{},{}))",
+        XpubDerivable::from(account1.to_xpub_spec()),
+        XpubDerivable::from(account2.to_xpub_spec())
+    ))
+    .unwrap();
+    let mut psbt = psbt(&descr);
+
+    assert_eq!(psbt.sign(&account1).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Err(vec![FinalizeError::NotEnoughSigs(0)]));
+    assert_eq!(psbt.input_mut(0).unwrap().finalize(), Err(FinalizeError::NotEnoughSigs(0)));
+
+    assert_eq!(psbt.sign(&account2).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Ok(1));
+    let tx = psbt.extract().unwrap();
+    let witness = tx.inputs[0].witness.elements().collect::<Vec<_>>();
+    assert_eq!(witness.len(), 4);
+    assert!(witness[0].is_empty());
+}
+
+#[test]
+fn finalize_wsh_miniscript() {
+    let account = account(0xA5, 84);
+    let key = XpubDerivable::from(account.to_xpub_spec());
+    let descr = Wsh::from_str(&format!("wsh(and_v(v:pk({key}),older(144)))")).unwrap();
+    let mut psbt = psbt(&descr);
+    assert_eq!(psbt.sign(&account).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Err(vec![FinalizeError::UnsupportedWitnessScript(0)]));
+    assert!(!psbt.is_finalized());
+}
+
+#[test]
+fn finalize_sh_multi() {
+    let account1 = account(0xA5, 45);
+    let account2 = account(0x5A, 45);
+    let descr = ShMulti::from_str(&format!(
+        "sh(multi(2,{},{}))",
+        XpubDerivable::from(account1.to_xpub_spec()),
+        XpubDerivable::from(account2.to_xpub_spec())
+    ))
+    .unwrap();
+    let mut psbt = psbt(&descr);
+    assert!(psbt.inputs().next().unwrap().redeem_script.is_some());
+
+    assert_eq!(psbt.sign(&account1).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Err(vec![FinalizeError::NotEnoughSigs(0)]));
+    assert_eq!(psbt.sign(&account2).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Ok(1));
+
+    let tx = psbt.extract().unwrap();
+    assert!(tx.inputs[0].witness.is_empty());
+    let sig_script = tx.inputs[0].sig_script.as_slice();
+    // Dummy element consumed by OP_CHECKMULTISIG, followed by the signature pushes
+    assert_eq!(sig_script[0], 0);
+    let redeem_script = descr.derive(0, 3u8).to_redeem_script().unwrap();
+    assert!(sig_script.ends_with(redeem_script.as_slice()));
+}
+
+#[test]
+fn finalize_sh_multi_uncompressed() {
+    let sks = [[0x11u8; 32], [0x22; 32]].map(|sk| SecretKey::from_slice(&sk).unwrap());
+    let pks = [
+        LegacyPk::uncompressed(sks[0].public_key(SECP256K1)),
+        LegacyPk::compressed(sks[1].public_key(SECP256K1)),
+    ];
+    let mut script = vec![OP_PUSHNUM_2];
+    for pk in pks {
+        let pk = pk.to_vec();
+        script.push(pk.len() as u8);
+        script.extend(pk);
+    }
+    script.extend([OP_PUSHNUM_2, OP_CHECKMULTISIG]);
+    let redeem_script = RedeemScript::from_unsafe(script);
+
+    let account = account(0xA5, 44);
+    let mut psbt = psbt(&Pkh::from(XpubDerivable::from(account.to_xpub_spec())));
+    let input = psbt.input_mut(0).unwrap();
+    input.bip32_derivation.clear();
+    input.witness_utxo.as_mut().unwrap().script_pubkey = redeem_script.to_script_pubkey();
+    input.redeem_script = Some(redeem_script.clone());
+
+    let cache = sighash_cache(&psbt);
+    for (sk, pk) in sks.iter().zip(pks) {
+        let sig =
+            cache.sign_legacy(0, redeem_script.as_script_bytes(), SighashType::all(), sk).unwrap();
+        psbt.input_mut(0).unwrap().partial_sigs.insert(pk, sig);
+    }
+    assert_eq!(psbt.finalize(), Ok(1));
+
+    let tx = psbt.extract().unwrap();
+    let sig_script = tx.inputs[0].sig_script.as_slice();
+    assert_eq!(sig_script[0], 0);
+    assert!(sig_script.ends_with(redeem_script.as_slice()));
+}
+
+#[test]
+fn combine_wsh_multi() {
+    let account1 = account(0xA5, 48);
+    let account2 = account(0x5A, 48);
+    let descr = WshMulti::from_str(&format!(
+        "wsh(multi(2,{},{}))",
+        XpubDerivable::from(account1.to_xpub_spec()),
+        XpubDerivable::from(account2.to_xpub_spec())
+    ))
+    .unwrap();
+    let mut psbt1 = psbt(&descr);
+    let mut psbt2 = psbt1.clone();
+    assert_eq!(psbt1.sign(&account1).unwrap(), 1);
+    assert_eq!(psbt2.sign(&account2).unwrap(), 1);
+
+    psbt1.combine(psbt2).unwrap();
+    assert_eq!(psbt1.inputs().next().unwrap().partial_sigs.len(), 2);
+    assert_eq!(psbt1.finalize(), Ok(1));
+    assert!(psbt1.extract().is_ok());
+}
+
+#[test]
+fn combine_mismatch() {
+    let account = account(0xA5, 84);
+    let descr = Wpkh::from(XpubDerivable::from(account.to_xpub_spec()));
+    let mut psbt1 = psbt(&descr);
+    let mut psbt2 = psbt1.clone();
+    psbt2.construct_output_expect(ScriptPubkey::p2pkh([8u8; 20]), Sats::from_sats(500u32));
+
+    let expected = psbt1.txid();
+    let found = psbt2.txid();
+    assert_eq!(psbt1.combine(psbt2), Err(TxMismatch { expected, found }));
+}
+
+fn tr_script(tree: &str) -> (TrScript, [XprivAccount; 2]) {
+    let internal = account(0x33, 86);
+    let account1 = account(0xA5, 86);
+    let account2 = account(0x5A, 86);
+    let tree = tree
+        .replace("@A", &XpubDerivable::from(account1.to_xpub_spec()).to_string())
+        .replace("@B", &XpubDerivable::from(account2.to_xpub_spec()).to_string());
+    let descr =
+        TrScript::from_str(&format!("tr({},{tree})", XpubDerivable::from(internal.to_xpub_spec())))
+            .unwrap();
+    (descr, [account1, account2])
+}
+
+#[test]
+fn sign_tr_script_path() {
+    let (descr, [account1, account2]) = tr_script("multi_a(2,@A,@B)");
+    let mut psbt = psbt(&descr);
+    assert_eq!(psbt.sign(&account1).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Err(vec![FinalizeError::NotEnoughSigs(0)]));
+    assert_eq!(psbt.sign(&account2).unwrap(), 1);
+
+    let input = psbt.inputs().next().unwrap();
+    assert!(input.tap_key_sig.is_none());
+    let cache = sighash_cache(&psbt);
+    for ((pk, leaf_hash), sig) in &input.tap_script_sig {
+        let sighash = cache.tap_sighash_script(0, *leaf_hash, None).unwrap();
+        let pk = derive::secp256k1::XOnlyPublicKey::from_slice(&pk.to_byte_array()).unwrap();
+        SECP256K1.verify_schnorr(&sig.sig, &Message::from(sighash), &pk).unwrap();
+    }
+
+    assert_eq!(psbt.finalize(), Ok(1));
+    let tx = psbt.extract().unwrap();
+    let witness = tx.inputs[0].witness.elements().collect::<Vec<_>>();
+    assert_eq!(witness.len(), 4);
+    assert_eq!(witness[0].len(), 64);
+    assert_eq!(witness[1].len(), 64);
+    // Single leaf tree has no merkle path
+    assert_eq!(witness[3].len(), 33);
+    assert_eq!(witness[3][0] & 0xFE, 0xC0);
+}
+
+#[test]
+fn sign_tr_script_unknown_leaf() {
+    let (descr, [account1, _]) = tr_script("multi_a(2,@A,@B)");
+    let mut psbt = psbt(&descr);
+    let (control_block, leaf_script) = psbt.input_mut(0).unwrap().tap_leaf_script.pop().unwrap();
+
+    // Leaf hash claimed by the key derivation without the leaf script is not signed
+    assert_eq!(psbt.sign(&account1), Ok(0));
+
+    // Control block not committing to the output key is not signed
+    let mut forged = control_block.clone();
+    forged.internal_pk = InternalPk::from_unchecked(account1.xpriv().to_xonly_pub());
+    let input = psbt.input_mut(0).unwrap();
+    input.tap_leaf_script.insert(forged.clone(), leaf_script.clone());
+    assert_eq!(psbt.sign(&account1), Ok(0));
+
+    let input = psbt.input_mut(0).unwrap();
+    input.tap_leaf_script.shift_remove(&forged);
+    input.tap_leaf_script.insert(control_block, leaf_script);
+    assert_eq!(psbt.sign(&account1), Ok(1));
+}
+
+#[test]
+fn finalize_tr_script_leaf_selection() {
+    let (descr, [_, account2]) = tr_script("{pk(@A),pk(@B)}");
+    let mut psbt = psbt(&descr);
+    assert_eq!(psbt.sign(&account2).unwrap(), 1);
+    assert_eq!(psbt.finalize(), Ok(1));
+
+    let tx = psbt.extract().unwrap();
+    let witness = tx.inputs[0].witness.elements().collect::<Vec<_>>();
+    assert_eq!(witness.len(), 3);
+    assert_eq!(witness[1].len(), 34);
+    assert_eq!(witness[2].len(), 65);
+}
+
+fn check_estimate(psbt: Psbt, signers: &[XprivAccount]) {
+    let estimate = psbt.estimate_weight().unwrap();
+    let mock = psbt.mock_signed_tx().unwrap();
+    assert_eq!(mock.outputs, psbt.to_unsigned_tx().outputs);
+    // The PSBT itself is not modified
+    assert!(!psbt.is_finalized());
+
+    let mut signed = psbt.clone();
+    assert!(signed.sign(signers).unwrap() > 0);
+    assert_eq!(signed.finalize(), Ok(1));
+    let weight = signed.extract().unwrap().weight_units().to_u32();
+    // Real ECDSA signatures may be a byte shorter than placeholders
+    assert!(estimate.to_u32() >= weight);
+    assert!(estimate.to_u32() - weight <= 4 * signers.len() as u32);
+}
+
+#[test]
+fn estimate_weight() {
+    let account44 = account(0xA5, 44);
+    check_estimate(psbt(&Pkh::from(XpubDerivable::from(account44.to_xpub_spec()))), &[account44]);
+    let account84 = account(0xA5, 84);
+    check_estimate(psbt(&Wpkh::from(XpubDerivable::from(account84.to_xpub_spec()))), &[account84]);
+    let account86 = account(0xA5, 86);
+    check_estimate(psbt(&TrKey::from(XpubDerivable::from(account86.to_xpub_spec()))), &[account86]);
+
+    let account1 = account(0xA5, 48);
+    let account2 = account(0x5A, 48);
+    let descr = WshMulti::from_str(&format!(
+        "wsh(multi(2,{},{}))",
+        XpubDerivable::from(account1.to_xpub_spec()),
+        XpubDerivable::from(account2.to_xpub_spec())
+    ))
+    .unwrap();
+    check_estimate(psbt(&descr), &[account1.clone(), account2.clone()]);
+
+    let descr = ShMulti::from_str(&format!(
+        "sh(multi(2,{},{}))",
+        XpubDerivable::from(account1.to_xpub_spec()),
+        XpubDerivable::from(account2.to_xpub_spec())
+    ))
+    .unwrap();
+    check_estimate(psbt(&descr), &[account1, account2]);
+}
+
+#[test]
+fn estimate_sigops() {
+    let xpub = |seed, purpose| XpubDerivable::from(account(seed, purpose).to_xpub_spec());
+    // P2PKH output of the transaction accounts for 4 units
+    let pkh = psbt(&Pkh::from(xpub(0xA5, 44)));
+    assert_eq!(pkh.estimate_sigop_cost(), Ok(4));
+    let tx = pkh.mock_signed_tx().unwrap();
+    assert_eq!(tx.legacy_sigops(), 1);
+    assert_eq!(tx.total_size(), tx.stripped_size());
+    assert!(tx.is_standard_size(4));
+
+    let wpkh = psbt(&Wpkh::from(xpub(0xA5, 84)));
+    assert_eq!(wpkh.estimate_sigop_cost(), Ok(5));
+    let tx = wpkh.mock_signed_tx().unwrap();
+    assert!(tx.total_size() > tx.stripped_size());
+    assert_eq!(tx.weight_units().to_u32() as usize, tx.stripped_size() * 3 + tx.total_size());
+    assert_eq!(psbt(&TrKey::from(xpub(0xA5, 86))).estimate_sigop_cost(), Ok(4));
+
+    let keys = format!("multi(2,{},{})", xpub(0xA5, 48), xpub(0x5A, 48));
+    let descr = WshMulti::from_str(&format!("wsh({keys})")).unwrap();
+    assert_eq!(psbt(&descr).estimate_sigop_cost(), Ok(4 + 2));
+    let descr = ShMulti::from_str(&format!("sh({keys})")).unwrap();
+    assert_eq!(psbt(&descr).estimate_sigop_cost(), Ok(4 + 2 * 4));
+}
+
+#[test]
+fn estimate_tr_script_path() {
+    let (descr, [_, account2]) = tr_script("{pk(@A),pk(@B)}");
+    let mut psbt = psbt(&descr);
+    // Without the internal key derivation the inputs can be signed with script path only
+    let input = psbt.input_mut(0).unwrap();
+    let internal_pk = input.tap_internal_key.unwrap();
+    input.tap_bip32_derivation.retain(|pk, _| pk.to_byte_array() != internal_pk.to_byte_array());
+
+    let mut signed = psbt.clone();
+    assert_eq!(signed.sign(&MockSigner).unwrap(), 2);
+    assert!(signed.inputs().next().unwrap().tap_key_sig.is_none());
+    check_estimate(psbt, &[account2]);
+}
@@ -0,0 +1,227 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wallet labels export format (BIP-329).
+//!
+//! Labels are stored as JSON Lines, where each line is a JSON object describing a single label.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use derive::{Address, LegacyPk, Outpoint, Txid, Xpub};
+use serde_crate::{Deserialize, Serialize};
+
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum LabelError {
+    /// malformed label record - {0}
+    #[from]
+    Json(serde_json::Error),
+
+    /// unknown label type '{0}'.
+    UnknownType(String),
+
+    /// invalid reference '{1}' for label of type '{0}'.
+    InvalidRef(String, String),
+
+    /// invalid label record at line {line} - {error}
+    Line { line: usize, error: Box<LabelError> },
+}
+
+/// Object to which a label is attached.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LabelRef {
+    Tx(Txid),
+    Addr(Address),
+    Pubkey(LegacyPk),
+    Input(Outpoint),
+    Output(Outpoint),
+    Xpub(Xpub),
+}
+
+impl LabelRef {
+    /// Name of the label type used in the `type` field of the record.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LabelRef::Tx(_) => "tx",
+            LabelRef::Addr(_) => "addr",
+            LabelRef::Pubkey(_) => "pubkey",
+            LabelRef::Input(_) => "input",
+            LabelRef::Output(_) => "output",
+            LabelRef::Xpub(_) => "xpub",
+        }
+    }
+
+    fn parse(ty: &str, reference: &str) -> Result<Self, LabelError> {
+        let invalid = || LabelError::InvalidRef(ty.to_owned(), reference.to_owned());
+        Ok(match ty {
+            "tx" => LabelRef::Tx(Txid::from_str(reference).map_err(|_| invalid())?),
+            "addr" => LabelRef::Addr(Address::from_str(reference).map_err(|_| invalid())?),
+            "pubkey" => LabelRef::Pubkey(LegacyPk::from_str(reference).map_err(|_| invalid())?),
+            "input" => LabelRef::Input(Outpoint::from_str(reference).map_err(|_| invalid())?),
+            "output" => LabelRef::Output(Outpoint::from_str(reference).map_err(|_| invalid())?),
+            "xpub" => LabelRef::Xpub(Xpub::from_str(reference).map_err(|_| invalid())?),
+            _ => return Err(LabelError::UnknownType(ty.to_owned())),
+        })
+    }
+}
+
+impl Display for LabelRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LabelRef::Tx(txid) => Display::fmt(txid, f),
+            LabelRef::Addr(addr) => Display::fmt(addr, f),
+            LabelRef::Pubkey(pk) => Display::fmt(pk, f),
+            LabelRef::Input(outpoint) | LabelRef::Output(outpoint) => Display::fmt(outpoint, f),
+            LabelRef::Xpub(xpub) => Display::fmt(xpub, f),
+        }
+    }
+}
+
+/// Single BIP-329 label record.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Label {
+    pub reference: LabelRef,
+    pub label: Option<String>,
+    /// Key origin of the descriptor the object belongs to, like `wpkh([d34db33f/84'/0'/0'])`.
+    pub origin: Option<String>,
+    /// Whether the output can be spent; used only with [`LabelRef::Output`].
+    pub spendable: Option<bool>,
+}
+
+impl Label {
+    pub fn new(reference: LabelRef, label: impl Into<String>) -> Self {
+        Label {
+            reference,
+            label: Some(label.into()),
+            origin: None,
+            spendable: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Record {
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(rename = "ref")]
+    reference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spendable: Option<bool>,
+}
+
+impl FromStr for Label {
+    type Err = LabelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let record: Record = serde_json::from_str(s)?;
+        Ok(Label {
+            reference: LabelRef::parse(&record.ty, &record.reference)?,
+            label: record.label,
+            origin: record.origin,
+            spendable: record.spendable,
+        })
+    }
+}
+
+/// Displays label as a single-line JSON object.
+impl Display for Label {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let record = Record {
+            ty: self.reference.type_name().to_owned(),
+            reference: self.reference.to_string(),
+            label: self.label.clone(),
+            origin: self.origin.clone(),
+            spendable: self.spendable,
+        };
+        let json = serde_json::to_string(&record).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+/// Reads labels from JSON Lines data, skipping empty lines and records of unknown types.
+pub fn read_labels(jsonl: &str) -> Result<Vec<Label>, LabelError> {
+    let mut labels = vec![];
+    for (no, line) in jsonl.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Label::from_str(line) {
+            Ok(label) => labels.push(label),
+            Err(LabelError::UnknownType(_)) => {}
+            Err(error) => {
+                return Err(LabelError::Line {
+                    line: no + 1,
+                    error: Box::new(error),
+                })
+            }
+        }
+    }
+    Ok(labels)
+}
+
+/// Writes labels as JSON Lines data.
+pub fn write_labels<'a>(labels: impl IntoIterator<Item = &'a Label>) -> String {
+    labels.into_iter().map(|label| format!("{label}\n")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Test vectors from BIP-329
+    const LABELS: &str = r#"{"type":"tx","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd","label":"Transaction","origin":"wpkh([d34db33f/84'/0'/0'])"}
+{"type":"addr","ref":"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c","label":"Address"}
+{"type":"pubkey","ref":"0283409659355b6d1cc3c32decd5d561abaac86c37a353b52895a5e6c196d6f448","label":"Public Key"}
+{"type":"input","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0","label":"Input"}
+{"type":"output","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1","label":"Output","spendable":false}
+{"type":"xpub","ref":"xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8","label":"Extended Public Key"}
+"#;
+
+    #[test]
+    fn roundtrip() {
+        let labels = read_labels(LABELS).unwrap();
+        assert_eq!(labels.len(), 6);
+        assert_eq!(labels[0].origin.as_deref(), Some("wpkh([d34db33f/84'/0'/0'])"));
+        assert!(matches!(labels[1].reference, LabelRef::Addr(_)));
+        assert_eq!(labels[4].spendable, Some(false));
+        assert_eq!(write_labels(&labels), LABELS);
+    }
+
+    #[test]
+    fn unknown_and_invalid() {
+        let data = "{\"type\":\"block\",\"ref\":\"00\",\"label\":\"Block\"}\n\n{\"type\":\"tx\",\"\
+                    ref\":\"zz\"}";
+        match read_labels(data) {
+            Err(LabelError::Line { line: 3, error }) => {
+                assert!(matches!(*error, LabelError::InvalidRef(..)))
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(read_labels("{\"type\":\"block\",\"ref\":\"00\"}").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+// Modern, minimalistic & standard-compliant cold wallet library.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2020-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2020-2024 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2020-2024 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hardware wallet interface (HWI) integration.
+//!
+//! Devices are accessed by running the [HWI] command-line tool, which must be installed
+//! separately, and parsing its JSON responses.
+//!
+//! [HWI]: https://github.com/bitcoin-core/HWI
+
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+
+use derive::{
+    DerivationPath, HardenedIndex, Network, Xpub, XpubFp, XpubOrigin, XpubParseError, XpubSpec,
+};
+use psbt::{Psbt, PsbtParseError, PsbtVer, TxMismatch};
+use serde_crate::de::DeserializeOwned;
+use serde_crate::Deserialize;
+
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum HwiError {
+    /// unable to run HWI - {0}
+    #[from]
+    Io(io::Error),
+
+    /// HWI returned malformed response - {0}
+    #[from]
+    Json(serde_json::Error),
+
+    /// HWI error {code}: {message}
+    Device { code: i64, message: String },
+
+    /// device doesn't report its master key fingerprint.
+    NoFingerprint,
+
+    /// invalid device fingerprint '{0}'.
+    InvalidFingerprint(String),
+
+    /// device returned invalid extended public key - {0}
+    #[from]
+    Xpub(XpubParseError),
+
+    /// device returned invalid PSBT - {0}
+    #[from]
+    Psbt(PsbtParseError),
+
+    /// device signed a different transaction - {0}
+    #[from]
+    TxMismatch(TxMismatch),
+}
+
+/// Hardware signing device detected by HWI.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct HwiDevice {
+    /// Device type, like `trezor`, `ledger` or `coldcard`.
+    pub device_type: String,
+    pub model: String,
+    /// Device path used by HWI to access the device.
+    pub path: String,
+    /// Fingerprint of the device master key, identifying the device in HWI commands.
+    pub fingerprint: XpubFp,
+    /// Device is locked and requires PIN to be entered before it can be used.
+    pub needs_pin_sent: bool,
+    /// Device requires passphrase to be provided to derive the keys.
+    pub needs_passphrase_sent: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct DeviceInfo {
+    #[serde(rename = "type")]
+    device_type: String,
+    model: String,
+    path: String,
+    fingerprint: Option<String>,
+    #[serde(default)]
+    needs_pin_sent: bool,
+    #[serde(default)]
+    needs_passphrase_sent: bool,
+    error: Option<String>,
+    code: Option<i64>,
+}
+
+impl TryFrom<DeviceInfo> for HwiDevice {
+    type Error = HwiError;
+
+    fn try_from(info: DeviceInfo) -> Result<Self, Self::Error> {
+        if let Some(message) = info.error {
+            return Err(HwiError::Device {
+                code: info.code.unwrap_or_default(),
+                message,
+            });
+        }
+        let fingerprint = info.fingerprint.ok_or(HwiError::NoFingerprint)?;
+        let fingerprint = XpubFp::from_str(&fingerprint)
+            .map_err(|_| HwiError::InvalidFingerprint(fingerprint))?;
+        Ok(HwiDevice {
+            device_type: info.device_type,
+            model: info.model,
+            path: info.path,
+            fingerprint,
+            needs_pin_sent: info.needs_pin_sent,
+            needs_passphrase_sent: info.needs_passphrase_sent,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct XpubResponse {
+    xpub: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct SignResponse {
+    psbt: String,
+    #[serde(default)]
+    signed: bool,
+}
+
+/// Runner of HWI commands for a specific network.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Hwi {
+    command: PathBuf,
+    network: Network,
+}
+
+impl Hwi {
+    /// Constructs HWI runner using `hwi` executable from the `PATH`.
+    pub fn new(network: Network) -> Self { Hwi::with_command("hwi", network) }
+
+    /// Constructs HWI runner using a custom HWI executable.
+    pub fn with_command(command: impl Into<PathBuf>, network: Network) -> Self {
+        Hwi {
+            command: command.into(),
+            network,
+        }
+    }
+
+    fn chain(&self) -> &'static str {
+        match self.network {
+            Network::Mainnet => "main",
+            Network::Testnet3 => "test",
+            Network::Testnet4 => "testnet4",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    fn run<T: DeserializeOwned>(
+        &self,
+        device: Option<&HwiDevice>,
+        args: &[&str],
+    ) -> Result<T, HwiError> {
+        let mut cmd = Command::new(&self.command);
+        cmd.args(["--chain", self.chain()]);
+        if let Some(device) = device {
+            cmd.args(["--fingerprint", &device.fingerprint.to_string()]);
+        }
+        let output = cmd.args(args).output()?;
+        parse_response(&output.stdout)
+    }
+
+    /// Lists connected devices.
+    ///
+    /// Devices which can't be accessed (for instance, locked devices which don't report their
+    /// fingerprint) are skipped.
+    pub fn enumerate(&self) -> Result<Vec<HwiDevice>, HwiError> {
+        let devices: Vec<DeviceInfo> = self.run(None, &["enumerate"])?;
+        Ok(devices.into_iter().filter_map(|info| HwiDevice::try_from(info).ok()).collect())
+    }
+
+    /// Retrieves extended public key for the account at the given derivation path, returning it
+    /// together with the key origin information.
+    pub fn xpub(
+        &self,
+        device: &HwiDevice,
+        derivation: &DerivationPath<HardenedIndex>,
+    ) -> Result<XpubSpec, HwiError> {
+        let path = format!("m{derivation}");
+        let resp: XpubResponse = self.run(Some(device), &["getxpub", &path])?;
+        let xpub = Xpub::from_str(&resp.xpub)?;
+        Ok(XpubSpec::new(xpub, XpubOrigin::new(device.fingerprint, derivation.clone())))
+    }
+
+    /// Sends PSBT to the device for signing, adding the produced signatures to the PSBT.
+    ///
+    /// # Returns
+    ///
+    /// Whether the device has produced any signatures.
+    pub fn sign(&self, device: &HwiDevice, psbt: &mut Psbt) -> Result<bool, HwiError> {
+        // Many devices support only PSBT v0
+        let data = psbt.to_base64_ver(PsbtVer::V0);
+        let resp: SignResponse = self.run(Some(device), &["signtx", &data])?;
+        if !resp.signed {
+            return Ok(false);
+        }
+        psbt.combine(Psbt::from_base64(&resp.psbt)?)?;
+        Ok(true)
+    }
+}
+
+fn parse_response<T: DeserializeOwned>(data: &[u8]) -> Result<T, HwiError> {
+    let value: serde_json::Value = serde_json::from_slice(data)?;
+    if let Some(message) = value.get("error").filter(|_| value.is_object()) {
+        return Err(HwiError::Device {
+            code: value.get("code").and_then(serde_json::Value::as_i64).unwrap_or_default(),
+            message: message.as_str().map(str::to_owned).unwrap_or_else(|| message.to_string()),
+        });
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn enumerate() {
+        let resp = br#"[
+            {"type": "coldcard", "model": "coldcard", "label": null, "path": "0001:0005:00",
+             "fingerprint": "0f056943", "needs_pin_sent": false, "needs_passphrase_sent": false},
+            {"type": "trezor", "model": "trezor_t", "path": "webusb:001:4",
+             "needs_pin_sent": true, "needs_passphrase_sent": false,
+             "error": "Could not open client or get fingerprint information", "code": -12}
+        ]"#;
+        let devices: Vec<DeviceInfo> = parse_response(resp).unwrap();
+        let mut devices = devices.into_iter().map(HwiDevice::try_from);
+        let coldcard = devices.next().unwrap().unwrap();
+        assert_eq!(coldcard.device_type, "coldcard");
+        assert_eq!(coldcard.fingerprint, XpubFp::from_str("0f056943").unwrap());
+        assert!(!coldcard.needs_pin_sent);
+        assert!(matches!(devices.next().unwrap(), Err(HwiError::Device { code: -12, .. })));
+    }
+
+    #[test]
+    fn chain() {
+        assert_eq!(Hwi::new(Network::Mainnet).chain(), "main");
+        assert_eq!(Hwi::new(Network::Testnet3).chain(), "test");
+        assert_eq!(Hwi::new(Network::Testnet4).chain(), "testnet4");
+        assert_eq!(Hwi::new(Network::Signet).chain(), "signet");
+        assert_eq!(Hwi::new(Network::Regtest).chain(), "regtest");
+    }
+
+    #[test]
+    fn error_response() {
+        let resp = br#"{"error": "No device path specified", "code": -7}"#;
+        let err = parse_response::<XpubResponse>(resp).unwrap_err();
+        assert_eq!(err.to_string(), "HWI error -7: No device path specified");
+    }
+
+    #[test]
+    fn xpub_response() {
+        let xpub = derive::Xpriv::new_master(true, &[0xA5; 32]).to_xpub();
+        let resp = format!(r#"{{"xpub": "{xpub}"}}"#);
+        let resp: XpubResponse = parse_response(resp.as_bytes()).unwrap();
+        assert_eq!(Xpub::from_str(&resp.xpub).unwrap(), xpub);
+    }
+}
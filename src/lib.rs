@@ -20,6 +20,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(any(feature = "hwi", feature = "bip329"))]
+#[macro_use]
+extern crate amplify;
+
+#[cfg(feature = "bip329")]
+pub mod bip329;
+#[cfg(feature = "hwi")]
+pub mod hwi;
+
 #[cfg(feature = "client-side-validation")]
 pub use ::bp::{dbc, seals};
 pub use bc::{secp256k1, *};